@@ -1,12 +1,32 @@
 let wasmModule = null
 let workerInstance = null
 
+// The threaded build (`npm run build:wasm:threads`) needs SharedArrayBuffer,
+// which only a cross-origin isolated page has; elsewhere, or when it was not
+// built, the single-threaded build is loaded instead.
+async function loadThreadedInkWasm() {
+  if (!globalThis.crossOriginIsolated) return null
+  try {
+    const url = new URL('../wasm/ink_engine/pkg-threads/ink_engine.js', import.meta.url).href
+    const mod = await import(/* @vite-ignore */ url)
+    await mod.default()
+    await mod.initThreadPool(navigator.hardwareConcurrency || 4)
+    return mod
+  } catch (err) {
+    console.warn('[WASM] threaded ink_engine not available:', err?.message || err)
+    return null
+  }
+}
+
 export async function loadInkWasm() {
   if (wasmModule) return wasmModule
   try {
-    const mod = await import('../wasm/ink_engine/pkg/ink_engine.js')
-    if (typeof mod.default === 'function') {
-      await mod.default()
+    let mod = await loadThreadedInkWasm()
+    if (!mod) {
+      mod = await import('../wasm/ink_engine/pkg/ink_engine.js')
+      if (typeof mod.default === 'function') {
+        await mod.default()
+      }
     }
     wasmModule = mod
     console.log('[WASM] ink_engine loaded')
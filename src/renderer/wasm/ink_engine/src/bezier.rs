@@ -0,0 +1,250 @@
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{dot, length, point_at, sub, Point};
+use crate::mesh::Mesh;
+use crate::tessellate::{self, MeshOptions};
+
+/// Floats per fitted curve: start point, two control points, end point.
+const FLOATS_PER_CURVE: usize = 8;
+
+/// Piecewise cubic Bézier path. Consecutive curves share their end points;
+/// `knot_widths` holds the stroke width at each curve end (curve count + 1).
+#[wasm_bindgen]
+pub struct BezierPath {
+    curves: Vec<f32>,
+    knot_widths: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl BezierPath {
+    /// `[x0, y0, c1x, c1y, c2x, c2y, x3, y3]` per curve.
+    pub fn curves(&self) -> Vec<f32> {
+        self.curves.clone()
+    }
+
+    pub fn knot_widths(&self) -> Vec<f32> {
+        self.knot_widths.clone()
+    }
+
+    pub fn curve_count(&self) -> usize {
+        self.curves.len() / FLOATS_PER_CURVE
+    }
+}
+
+/// Least-squares cubic fitting after Schneider ("An Algorithm for
+/// Automatically Fitting Digitized Curves", Graphics Gems, 1990). Every
+/// input sample stays within `tolerance` of the fitted path.
+#[wasm_bindgen]
+pub fn fit_beziers(points: &[f32], widths: &[f32], tolerance: f32) -> BezierPath {
+    let n = points.len() / 2;
+    let pts: Vec<Point> = (0..n).map(|i| point_at(points, i)).collect();
+    let width = |i: usize| widths.get(i).copied().unwrap_or(1.0);
+    let mut path = BezierPath {
+        curves: Vec::new(),
+        knot_widths: Vec::new(),
+    };
+    if n < 2 {
+        return path;
+    }
+    path.knot_widths.push(width(0));
+    let mut fitter = Fitter {
+        pts: &pts,
+        tolerance_sq: tolerance.max(1e-3) * tolerance.max(1e-3),
+        out: Vec::new(),
+    };
+    let t1 = normalize(sub(pts[1], pts[0]));
+    let t2 = normalize(sub(pts[n - 2], pts[n - 1]));
+    fitter.fit(0, n - 1, t1, t2);
+    for (curve, last) in fitter.out {
+        for p in curve {
+            path.curves.push(p.0);
+            path.curves.push(p.1);
+        }
+        path.knot_widths.push(width(last));
+    }
+    path
+}
+
+/// Flattens fitted curves to within `flatness` and tessellates the result
+/// with the usual options. `knot_widths` are interpolated along each curve.
+#[wasm_bindgen]
+pub fn build_mesh_beziers(curves: &[f32], knot_widths: &[f32], color: &[f32], options: &MeshOptions, flatness: f32) -> Vec<f32> {
+    let (points, widths) = flatten(curves, knot_widths, flatness);
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(&points, &widths, crate::rgba(color), options, &mut mesh);
+    mesh.triangle_soup()
+}
+
+pub(crate) fn flatten(curves: &[f32], knot_widths: &[f32], flatness: f32) -> (Vec<f32>, Vec<f32>) {
+    let width = |i: usize| knot_widths.get(i).copied().unwrap_or(1.0);
+    let tol = flatness.max(1e-3);
+    let mut points = Vec::new();
+    let mut widths = Vec::new();
+    for (c, curve) in curves.chunks_exact(FLOATS_PER_CURVE).enumerate() {
+        let p = [point_at(curve, 0), point_at(curve, 1), point_at(curve, 2), point_at(curve, 3)];
+        if c == 0 {
+            points.extend_from_slice(&[p[0].0, p[0].1]);
+            widths.push(width(0));
+        }
+        // Wang's formula for the segment count that keeps the chord error under `tol`.
+        let dd = |a: Point, b: Point, c: Point| length((a.0 - 2.0 * b.0 + c.0, a.1 - 2.0 * b.1 + c.1));
+        let m = dd(p[0], p[1], p[2]).max(dd(p[1], p[2], p[3]));
+        let steps = ((0.75 * m / tol).sqrt().ceil() as usize).clamp(1, 256);
+        for s in 1..=steps {
+            let t = s as f32 / steps as f32;
+            let q = eval(&p, t);
+            points.extend_from_slice(&[q.0, q.1]);
+            widths.push(width(c) + (width(c + 1) - width(c)) * t);
+        }
+    }
+    (points, widths)
+}
+
+struct Fitter<'a> {
+    pts: &'a [Point],
+    tolerance_sq: f32,
+    /// Fitted curves with the sample index each one ends on.
+    out: Vec<([Point; 4], usize)>,
+}
+
+impl Fitter<'_> {
+    fn fit(&mut self, first: usize, last: usize, t1: Point, t2: Point) {
+        let pts = &self.pts[first..=last];
+        if pts.len() == 2 {
+            let dist = length(sub(pts[1], pts[0])) / 3.0;
+            let curve = [pts[0], add_scaled(pts[0], t1, dist), add_scaled(pts[1], t2, dist), pts[1]];
+            self.out.push((curve, last));
+            return;
+        }
+
+        let mut u = chord_params(pts);
+        let mut curve = generate(pts, &u, t1, t2);
+        let (mut err, mut split) = max_error(pts, &curve, &u);
+        if err < self.tolerance_sq {
+            self.out.push((curve, last));
+            return;
+        }
+        // Close misses are worth a few Newton-Raphson reparameterisations.
+        if err < self.tolerance_sq * 4.0 {
+            for _ in 0..4 {
+                u = reparameterize(pts, &curve, &u);
+                curve = generate(pts, &u, t1, t2);
+                (err, split) = max_error(pts, &curve, &u);
+                if err < self.tolerance_sq {
+                    self.out.push((curve, last));
+                    return;
+                }
+            }
+        }
+
+        let split = split.clamp(1, pts.len() - 2);
+        let center = normalize(sub(pts[split - 1], pts[split + 1]));
+        self.fit(first, first + split, t1, center);
+        self.fit(first + split, last, (-center.0, -center.1), t2);
+    }
+}
+
+fn normalize(v: Point) -> Point {
+    let len = length(v);
+    if len < 1e-9 {
+        (0.0, 0.0)
+    } else {
+        (v.0 / len, v.1 / len)
+    }
+}
+
+fn add_scaled(p: Point, d: Point, s: f32) -> Point {
+    (p.0 + d.0 * s, p.1 + d.1 * s)
+}
+
+fn eval(c: &[Point; 4], t: f32) -> Point {
+    let mt = 1.0 - t;
+    let (b0, b1, b2, b3) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+    (
+        c[0].0 * b0 + c[1].0 * b1 + c[2].0 * b2 + c[3].0 * b3,
+        c[0].1 * b0 + c[1].1 * b1 + c[2].1 * b2 + c[3].1 * b3,
+    )
+}
+
+fn chord_params(pts: &[Point]) -> Vec<f32> {
+    let mut u = Vec::with_capacity(pts.len());
+    u.push(0.0);
+    for i in 1..pts.len() {
+        u.push(u[i - 1] + length(sub(pts[i], pts[i - 1])));
+    }
+    let total = u[pts.len() - 1].max(1e-9);
+    u.iter().map(|v| v / total).collect()
+}
+
+/// Least-squares placement of the two inner control points along the given
+/// end tangents, falling back to the one-third heuristic when ill-conditioned.
+fn generate(pts: &[Point], u: &[f32], t1: Point, t2: Point) -> [Point; 4] {
+    let (first, last) = (pts[0], pts[pts.len() - 1]);
+    let mut c = [[0.0f32; 2]; 2];
+    let mut x = [0.0f32; 2];
+    for (p, &t) in pts.iter().zip(u) {
+        let mt = 1.0 - t;
+        let a1 = (t1.0 * 3.0 * mt * mt * t, t1.1 * 3.0 * mt * mt * t);
+        let a2 = (t2.0 * 3.0 * mt * t * t, t2.1 * 3.0 * mt * t * t);
+        c[0][0] += dot(a1, a1);
+        c[0][1] += dot(a1, a2);
+        c[1][1] += dot(a2, a2);
+        let base = eval(&[first, first, last, last], t);
+        let tmp = sub(*p, base);
+        x[0] += dot(a1, tmp);
+        x[1] += dot(a2, tmp);
+    }
+    c[1][0] = c[0][1];
+    let det = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let (mut alpha_l, mut alpha_r) = (0.0, 0.0);
+    if det.abs() > 1e-12 {
+        alpha_l = (x[0] * c[1][1] - x[1] * c[0][1]) / det;
+        alpha_r = (c[0][0] * x[1] - c[1][0] * x[0]) / det;
+    }
+    let seg = length(sub(last, first));
+    let eps = 1e-6 * seg;
+    if alpha_l < eps || alpha_r < eps {
+        alpha_l = seg / 3.0;
+        alpha_r = seg / 3.0;
+    }
+    [first, add_scaled(first, t1, alpha_l), add_scaled(last, t2, alpha_r), last]
+}
+
+fn max_error(pts: &[Point], curve: &[Point; 4], u: &[f32]) -> (f32, usize) {
+    let mut worst = 0.0;
+    let mut split = pts.len() / 2;
+    for i in 1..pts.len() - 1 {
+        let d = sub(eval(curve, u[i]), pts[i]);
+        let e = dot(d, d);
+        if e >= worst {
+            worst = e;
+            split = i;
+        }
+    }
+    (worst, split)
+}
+
+fn reparameterize(pts: &[Point], curve: &[Point; 4], u: &[f32]) -> Vec<f32> {
+    let d1: [Point; 3] = [0, 1, 2].map(|i| ((curve[i + 1].0 - curve[i].0) * 3.0, (curve[i + 1].1 - curve[i].1) * 3.0));
+    let d2: [Point; 2] = [0, 1].map(|i| ((d1[i + 1].0 - d1[i].0) * 2.0, (d1[i + 1].1 - d1[i].1) * 2.0));
+    pts.iter()
+        .zip(u)
+        .map(|(p, &t)| {
+            let q = eval(curve, t);
+            let mt = 1.0 - t;
+            let q1 = (
+                d1[0].0 * mt * mt + d1[1].0 * 2.0 * mt * t + d1[2].0 * t * t,
+                d1[0].1 * mt * mt + d1[1].1 * 2.0 * mt * t + d1[2].1 * t * t,
+            );
+            let q2 = (d2[0].0 * mt + d2[1].0 * t, d2[0].1 * mt + d2[1].1 * t);
+            let diff = sub(q, *p);
+            let num = dot(diff, q1);
+            let den = dot(q1, q1) + dot(diff, q2);
+            if den.abs() < 1e-12 {
+                t
+            } else {
+                (t - num / den).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,791 @@
+use wasm_bindgen::prelude::*;
+
+use crate::curve::{speed_falloff, Easing};
+use crate::geom::{length, point_at, sub, Point};
+use crate::mesh::{Mesh, Topology, FLOATS_PER_VERTEX};
+use crate::nib::{self, NibOptions};
+use crate::noise::{value_noise, Rng};
+use crate::tessellate::{self, CapStyle, JoinStyle, MeshOptions, Shading};
+
+/// How the renderer should composite a brush's mesh onto the layer below.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BlendMode {
+    /// Source-over.
+    #[default]
+    Normal,
+    /// Darkens what is underneath, so text stays legible under a highlighter.
+    Multiply,
+    /// Clears what is underneath by the mesh's alpha (canvas
+    /// `destination-out`), for erasers.
+    DestinationOut,
+}
+
+/// Brush output: triangle soup as from `build_mesh`, plus how to blend it.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct BrushMesh {
+    vertices: Vec<f32>,
+    blend: BlendMode,
+    floats_per_vertex: usize,
+    premultiplied: bool,
+}
+
+#[wasm_bindgen]
+impl BrushMesh {
+    pub fn vertices(&self) -> Vec<f32> {
+        self.vertices.clone()
+    }
+
+    pub fn blend(&self) -> BlendMode {
+        self.blend
+    }
+
+    /// 6 for `[x, y, r, g, b, a]` triangle soup, 8 for textured stamps with
+    /// `[x, y, u, v, r, g, b, a]`.
+    pub fn floats_per_vertex(&self) -> usize {
+        self.floats_per_vertex
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.floats_per_vertex
+    }
+
+    /// Whether colors have red, green and blue multiplied by alpha, as the
+    /// brush's mesh options asked.
+    pub fn premultiplied(&self) -> bool {
+        self.premultiplied
+    }
+}
+
+impl BrushMesh {
+    pub(crate) fn new(vertices: Vec<f32>, blend: BlendMode, floats_per_vertex: usize) -> Self {
+        BrushMesh {
+            vertices,
+            blend,
+            floats_per_vertex,
+            premultiplied: false,
+        }
+    }
+
+    /// The same kind of mesh with other vertices, e.g. moved ones.
+    pub(crate) fn with_vertices(&self, vertices: Vec<f32>) -> Self {
+        BrushMesh { vertices, ..*self }
+    }
+
+    /// Multiplies each vertex's red, green and blue, its last four floats
+    /// but one, by its alpha, once.
+    pub(crate) fn premultiply(&mut self) {
+        if self.premultiplied {
+            return;
+        }
+        for v in self.vertices.chunks_exact_mut(self.floats_per_vertex) {
+            let color = &mut v[self.floats_per_vertex - 4..];
+            let a = color[3];
+            color[..3].iter_mut().for_each(|c| *c *= a);
+        }
+        self.premultiplied = true;
+    }
+
+    pub(crate) fn soup(&self) -> &[f32] {
+        &self.vertices
+    }
+}
+
+/// Highlighter ink is always this translucent, whatever the color's alpha.
+const HIGHLIGHTER_ALPHA: f32 = 0.35;
+
+/// Highlighter preset: a chisel tip held at a fixed near-vertical angle, so
+/// horizontal strokes over text get the full `width` and vertical ones about
+/// 40% of it. Pressure is ignored, caps are square and the stroke never
+/// darkens where it overlaps itself. Draw it with `BlendMode::Multiply`.
+#[wasm_bindgen]
+pub fn build_mesh_highlighter(points: &[f32], width: f32, color: &[f32]) -> BrushMesh {
+    highlighter(points, width, crate::rgba(color))
+}
+
+pub(crate) fn highlighter(points: &[f32], width: f32, color: [f32; 4]) -> BrushMesh {
+    let tip = NibOptions {
+        angle: 75f32.to_radians(),
+        thin_ratio: 0.4,
+        follow_azimuth: false,
+    };
+    let widths = vec![width; points.len() / 2];
+    let widths = nib::nib_widths(points, &widths, &[], &tip);
+    let options = MeshOptions {
+        join: JoinStyle::Miter,
+        cap: CapStyle::Square,
+        overlap_free: true,
+        ..MeshOptions::default()
+    };
+    let mut mesh = Mesh::default();
+    tessellate::tessellate(points, &widths, [color[0], color[1], color[2], HIGHLIGHTER_ALPHA], &options, &mut mesh);
+    BrushMesh::new(mesh.triangle_soup(), BlendMode::Multiply, FLOATS_PER_VERTEX)
+}
+
+/// Two concentric ribbons in one mesh: the full-width stroke in `edge_color`
+/// and, drawn over it, the same stroke at `core_ratio` of the width in
+/// `core_color`. The core covers the edge ribbon's middle, so translucent
+/// colors blend there; use opaque ones for a crisp outline.
+#[wasm_bindgen]
+pub fn build_mesh_dual_tone(points: &[f32], widths: &[f32], core_color: &[f32], edge_color: &[f32], core_ratio: f32, options: &MeshOptions) -> Vec<f32> {
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(points, widths, crate::rgba(edge_color), options, &mut mesh);
+    let n = points.len() / 2;
+    let ratio = core_ratio.clamp(0.0, 1.0);
+    let core: Vec<f32> = (0..n).map(|i| widths.get(i).copied().unwrap_or(1.0) * ratio).collect();
+    tessellate::tessellate(points, &core, crate::rgba(core_color), options, &mut mesh);
+    mesh.triangle_soup()
+}
+
+/// Eraser geometry: the same widths, smoothing, tapers and caps as ink drawn
+/// with `options`, so erasing feels identical to drawing, in opaque white.
+/// Draw it with `BlendMode::DestinationOut`, which only uses the alpha.
+#[wasm_bindgen]
+pub fn build_mesh_eraser(points: &[f32], widths: &[f32], options: &MeshOptions) -> BrushMesh {
+    eraser(points, widths, options)
+}
+
+pub(crate) fn eraser(points: &[f32], widths: &[f32], options: &MeshOptions) -> BrushMesh {
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(points, widths, [1.0; 4], options, &mut mesh);
+    BrushMesh::new(mesh.triangle_soup(), BlendMode::DestinationOut, FLOATS_PER_VERTEX)
+}
+
+/// Pencil look without textures: the outline wobbles with procedural noise and
+/// the alpha varies with a simulated graphite grain. Both are seeded, so a
+/// stroke looks the same every time it is rebuilt.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct PencilOptions {
+    /// Maximum edge displacement as a fraction of the mean stroke width.
+    pub roughness: f32,
+    /// Maximum alpha reduction from the grain, as a fraction of the color's alpha.
+    pub grain: f32,
+    /// Size of a grain feature in stroke units. The grain is fixed to the
+    /// page like paper tooth, so it does not stretch with the stroke.
+    pub grain_size: f32,
+    pub seed: u32,
+}
+
+#[wasm_bindgen]
+impl PencilOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> PencilOptions {
+        PencilOptions::default()
+    }
+}
+
+impl Default for PencilOptions {
+    fn default() -> Self {
+        PencilOptions {
+            roughness: 0.15,
+            grain: 0.6,
+            grain_size: 1.5,
+            seed: 0,
+        }
+    }
+}
+
+/// Pencil preset. Returns triangle soup as from `build_mesh`.
+#[wasm_bindgen]
+pub fn build_mesh_pencil(points: &[f32], widths: &[f32], color: &[f32], options: &PencilOptions) -> Vec<f32> {
+    pencil(points, widths, crate::rgba(color), &MeshOptions::default(), options)
+}
+
+pub(crate) fn pencil(points: &[f32], widths: &[f32], color: [f32; 4], mesh_options: &MeshOptions, options: &PencilOptions) -> Vec<f32> {
+    let n = points.len() / 2;
+    let width = |i: usize| widths.get(i).copied().unwrap_or(1.0);
+    let mean_width = ((0..n).map(width).sum::<f32>() / n.max(1) as f32).max(1e-3);
+    // The outline can only wobble between vertices, so give it some.
+    let (points, widths) = densify(points, widths, mean_width * 0.25);
+    let mut mesh = Mesh::with_topology(mesh_options.topology, false);
+    tessellate::tessellate(&points, &widths, color, mesh_options, &mut mesh);
+
+    let seed = options.seed.wrapping_mul(4);
+    let amplitude = options.roughness * mean_width;
+    let grain_size = options.grain_size.max(1e-3);
+    let grain = options.grain.clamp(0.0, 1.0);
+    for v in &mut mesh.vertices {
+        // Displacement and grain depend only on position, so vertices shared
+        // between pieces stay welded.
+        let (x, y) = (v.pos.0 / mean_width, v.pos.1 / mean_width);
+        let dx = value_noise(x, y, seed) * 2.0 - 1.0;
+        let dy = value_noise(x, y, seed + 1) * 2.0 - 1.0;
+        let (gx, gy) = (v.pos.0 / grain_size, v.pos.1 / grain_size);
+        // Two octaves: coarse paper tooth plus fine speckle.
+        let tooth = 0.65 * value_noise(gx, gy, seed + 2) + 0.35 * value_noise(gx * 3.1, gy * 3.1, seed + 3);
+        v.pos = (v.pos.0 + dx * amplitude, v.pos.1 + dy * amplitude);
+        v.color[3] *= 1.0 - grain * tooth;
+    }
+    mesh.triangle_soup()
+}
+
+/// Splits segments longer than `spacing`, keeping every original point.
+fn densify(points: &[f32], widths: &[f32], spacing: f32) -> (Vec<f32>, Vec<f32>) {
+    let n = points.len() / 2;
+    let width = |i: usize| widths.get(i).copied().unwrap_or(1.0);
+    let mut out_points = Vec::with_capacity(points.len());
+    let mut out_widths = Vec::with_capacity(n);
+    for i in 0..n {
+        let b = point_at(points, i);
+        if i > 0 {
+            let a = point_at(points, i - 1);
+            let pieces = (length(sub(b, a)) / spacing).ceil().min(256.0) as usize;
+            for k in 1..pieces {
+                let t = k as f32 / pieces as f32;
+                out_points.extend_from_slice(&[a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t]);
+                out_widths.push(width(i - 1) + (width(i) - width(i - 1)) * t);
+            }
+        }
+        out_points.extend_from_slice(&[b.0, b.1]);
+        out_widths.push(width(i));
+    }
+    (out_points, out_widths)
+}
+
+/// Soft spray: overlapping round dabs whose alpha falls off from the centre to
+/// zero at the rim. Each dab deposits ink for the time the pen spent reaching
+/// it, so slow movement and pauses build up density.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct AirbrushOptions {
+    /// Distance between dabs as a fraction of the local width.
+    pub spacing: f32,
+    /// Fraction of the remaining coverage sprayed per millisecond of dwell.
+    pub flow: f32,
+    /// Dwell per dab when there are no timestamps.
+    pub dwell_ms: f32,
+    /// Fraction of the radius sprayed at full density before the falloff starts.
+    pub hardness: f32,
+}
+
+#[wasm_bindgen]
+impl AirbrushOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> AirbrushOptions {
+        AirbrushOptions::default()
+    }
+}
+
+impl Default for AirbrushOptions {
+    fn default() -> Self {
+        AirbrushOptions {
+            spacing: 0.2,
+            flow: 0.02,
+            dwell_ms: 4.0,
+            hardness: 0.0,
+        }
+    }
+}
+
+/// Rings per dab between the hard core and the rim.
+const AIRBRUSH_RINGS: usize = 4;
+const AIRBRUSH_SEGMENTS: usize = 16;
+/// Dabs closer than this (in stroke units) are never placed, whatever the width.
+const MIN_DAB_SPACING: f32 = 0.05;
+
+/// Airbrush preset. `timestamps` are in milliseconds, one per point, and may
+/// be empty to spray every dab for `dwell_ms`. Returns triangle soup as from
+/// `build_mesh`.
+#[wasm_bindgen]
+pub fn build_mesh_airbrush(points: &[f32], widths: &[f32], timestamps: &[f64], color: &[f32], options: &AirbrushOptions) -> Vec<f32> {
+    airbrush(points, widths, timestamps, crate::rgba(color), options)
+}
+
+pub(crate) fn airbrush(points: &[f32], widths: &[f32], timestamps: &[f64], color: [f32; 4], options: &AirbrushOptions) -> Vec<f32> {
+    let n = points.len() / 2;
+    let mut mesh = Mesh::default();
+    if n == 0 {
+        return Vec::new();
+    }
+    let width = |i: usize| widths.get(i).copied().unwrap_or(1.0);
+    let timed = timestamps.len() >= n && timestamps[..n].iter().all(|t| t.is_finite());
+    let time = |i: usize| if timed { timestamps[i] } else { 0.0 };
+    let deposit = |dwell: f64| {
+        let dwell = if timed { dwell.max(0.0) as f32 } else { options.dwell_ms };
+        color[3] * (1.0 - (1.0 - options.flow.clamp(0.0, 1.0)).powf(dwell))
+    };
+
+    // The first dab sprays as if the pen had already dwelt there for one dab.
+    let mut last_time = time(0);
+    dab(&mut mesh, point_at(points, 0), width(0) * 0.5, color, deposit(options.dwell_ms as f64), options.hardness);
+    let mut need = (options.spacing * width(0)).max(MIN_DAB_SPACING);
+    for i in 0..n - 1 {
+        let (a, b) = (point_at(points, i), point_at(points, i + 1));
+        let seg = length(sub(b, a));
+        if seg < 1e-6 {
+            // Time spent in place is picked up by the next dab.
+            continue;
+        }
+        let mut at = need;
+        while at <= seg {
+            let t = at / seg;
+            let w = width(i) + (width(i + 1) - width(i)) * t;
+            let now = time(i) + (time(i + 1) - time(i)) * t as f64;
+            let pos = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            dab(&mut mesh, pos, w * 0.5, color, deposit(now - last_time), options.hardness);
+            last_time = now;
+            at += (options.spacing * w).max(MIN_DAB_SPACING);
+        }
+        need = at - seg;
+    }
+    // Ink sprayed since the last dab, including any pause at the end.
+    if timed && n > 1 && time(n - 1) > last_time {
+        dab(&mut mesh, point_at(points, n - 1), width(n - 1) * 0.5, color, deposit(time(n - 1) - last_time), options.hardness);
+    }
+    mesh.triangle_soup()
+}
+
+/// One dab: a solid core out to `hardness * radius`, then rings easing the
+/// alpha down to zero at `radius`.
+fn dab(mesh: &mut Mesh, center: Point, radius: f32, color: [f32; 4], alpha: f32, hardness: f32) {
+    if radius <= 0.0 || alpha <= 0.0 {
+        return;
+    }
+    let hardness = hardness.clamp(0.0, 0.99);
+    let with_alpha = |a: f32| [color[0], color[1], color[2], a];
+    let hub = mesh.push_vertex(center, with_alpha(alpha));
+    let mut inner: Option<Vec<u32>> = None;
+    for ring in 0..=AIRBRUSH_RINGS {
+        // Skip a zero-radius core ring; the hub stands in for it.
+        if ring == 0 && hardness == 0.0 {
+            continue;
+        }
+        let t = ring as f32 / AIRBRUSH_RINGS as f32;
+        let r = radius * (hardness + (1.0 - hardness) * t);
+        let falloff = (1.0 - t * t) * (1.0 - t * t);
+        let ids: Vec<u32> = (0..AIRBRUSH_SEGMENTS)
+            .map(|s| {
+                let angle = s as f32 / AIRBRUSH_SEGMENTS as f32 * std::f32::consts::TAU;
+                mesh.push_vertex((center.0 + angle.cos() * r, center.1 + angle.sin() * r), with_alpha(alpha * falloff))
+            })
+            .collect();
+        for s in 0..AIRBRUSH_SEGMENTS {
+            let next = (s + 1) % AIRBRUSH_SEGMENTS;
+            match &inner {
+                None => mesh.push_tri(hub, ids[s], ids[next]),
+                Some(prev) => {
+                    mesh.push_tri(prev[s], ids[s], ids[next]);
+                    mesh.push_tri(prev[s], ids[next], prev[next]);
+                }
+            }
+        }
+        inner = Some(ids);
+    }
+}
+
+/// Color that cycles through the hues along the stroke, ignoring the ink color.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct RainbowOptions {
+    /// Advance the hue with time instead of arc length.
+    pub by_time: bool,
+    /// Arc length in stroke units, or milliseconds with `by_time`, per full cycle.
+    pub period: f32,
+    /// Hue at the start of the stroke, in turns (0 is red).
+    pub hue_offset: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+    pub alpha: f32,
+}
+
+#[wasm_bindgen]
+impl RainbowOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> RainbowOptions {
+        RainbowOptions::default()
+    }
+}
+
+impl Default for RainbowOptions {
+    fn default() -> Self {
+        RainbowOptions {
+            by_time: false,
+            period: 300.0,
+            hue_offset: 0.0,
+            saturation: 1.0,
+            lightness: 0.5,
+            alpha: 1.0,
+        }
+    }
+}
+
+/// Rainbow preset: per-vertex colors whose hue advances with arc length, or
+/// with `timestamps` (one per point, in milliseconds) when `by_time` is set.
+/// Returns triangle soup as from `build_mesh`.
+#[wasm_bindgen]
+pub fn build_mesh_rainbow(points: &[f32], widths: &[f32], timestamps: &[f64], options: &MeshOptions, rainbow: &RainbowOptions) -> Vec<f32> {
+    rainbow_mesh(points, widths, timestamps, options, rainbow)
+}
+
+pub(crate) fn rainbow_mesh(points: &[f32], widths: &[f32], timestamps: &[f64], options: &MeshOptions, rainbow: &RainbowOptions) -> Vec<f32> {
+    let period = if rainbow.period > 0.0 { rainbow.period } else { 1.0 };
+    // Colors blend in RGB between points, so keep points close enough in hue.
+    let (points, widths) = if rainbow.by_time { (points.to_vec(), widths.to_vec()) } else { densify(points, widths, period / 24.0) };
+    let n = points.len() / 2;
+    let mut along = 0.0;
+    let colors: Vec<[f32; 4]> = (0..n)
+        .map(|i| {
+            let phase = if rainbow.by_time {
+                let t0 = timestamps.first().copied().unwrap_or(0.0);
+                (timestamps.get(i).map_or(0.0, |&t| t - t0) as f32) / period
+            } else {
+                if i > 0 {
+                    along += length(sub(point_at(&points, i), point_at(&points, i - 1)));
+                }
+                along / period
+            };
+            let [r, g, b] = hsl((rainbow.hue_offset + phase).rem_euclid(1.0), rainbow.saturation, rainbow.lightness);
+            [r, g, b, rainbow.alpha]
+        })
+        .collect();
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate_colored(&points, &widths, colors.first().copied().unwrap_or_default(), &colors, options, &mut mesh);
+    mesh.triangle_soup()
+}
+
+/// RGB from hue in turns, saturation and lightness in 0..1.
+fn hsl(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
+    let (s, l) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let channel = |k: f32| {
+        let h = (k + hue * 12.0).rem_euclid(12.0);
+        l - c * 0.5 * (h - 3.0).min(9.0 - h).clamp(-1.0, 1.0)
+    };
+    [channel(0.0), channel(8.0), channel(4.0)]
+}
+
+/// Ink pooling: wet ink dries darker along the stroke's edges and where the
+/// pen lingered.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct WetInkOptions {
+    /// How much lighter the middle of the stroke is than its edges, 0..1.
+    pub edge_darkening: f32,
+    /// Extra alpha, as a fraction of the color's, where the pen dwelt longest.
+    pub dwell_boost: f32,
+    /// Time in milliseconds spent within half a width of a point that gives
+    /// the full boost.
+    pub dwell_ms: f32,
+}
+
+#[wasm_bindgen]
+impl WetInkOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WetInkOptions {
+        WetInkOptions::default()
+    }
+}
+
+impl Default for WetInkOptions {
+    fn default() -> Self {
+        WetInkOptions {
+            edge_darkening: 0.35,
+            dwell_boost: 0.6,
+            dwell_ms: 300.0,
+        }
+    }
+}
+
+/// Wet-ink preset: darker edges, and darker pools where `timestamps` (one per
+/// point, in milliseconds; may be empty) show the pen moving slowly or
+/// resting. Returns triangle soup as from `build_mesh`; overlap-free and
+/// strip options are ignored so the shading survives.
+#[wasm_bindgen]
+pub fn build_mesh_wet_ink(points: &[f32], widths: &[f32], timestamps: &[f64], color: &[f32], options: &MeshOptions, wet: &WetInkOptions) -> Vec<f32> {
+    wet_ink(points, widths, timestamps, crate::rgba(color), options, wet)
+}
+
+pub(crate) fn wet_ink(points: &[f32], widths: &[f32], timestamps: &[f64], color: [f32; 4], options: &MeshOptions, wet: &WetInkOptions) -> Vec<f32> {
+    let n = points.len() / 2;
+    let colors: Vec<[f32; 4]> = dwell_times(points, widths, timestamps)
+        .iter()
+        .map(|&dwell| {
+            let pooled = (dwell / wet.dwell_ms.max(1e-3)).min(1.0);
+            [color[0], color[1], color[2], (color[3] * (1.0 + wet.dwell_boost.max(0.0) * pooled)).min(1.0)]
+        })
+        .collect();
+    let options = MeshOptions {
+        overlap_free: false,
+        topology: Topology::TriangleList,
+        ..*options
+    };
+    let mut mesh = Mesh::default();
+    let center_alpha = 1.0 - wet.edge_darkening.clamp(0.0, 1.0);
+    let colors = if colors.len() == n { &colors[..] } else { &[] };
+    let shading = Shading {
+        colors,
+        center_alpha,
+        ..Shading::default()
+    };
+    tessellate::tessellate_shaded(points, widths, color, &shading, &options, &mut mesh);
+    mesh.triangle_soup()
+}
+
+/// Milliseconds the pen spent within half a width of each point, or empty
+/// without a timestamp per point.
+fn dwell_times(points: &[f32], widths: &[f32], timestamps: &[f64]) -> Vec<f32> {
+    let n = points.len() / 2;
+    if timestamps.len() < n || n == 0 {
+        return Vec::new();
+    }
+    (0..n)
+        .map(|i| {
+            let p = point_at(points, i);
+            let reach = tessellate::radius_at(widths, i);
+            let near = |j: &usize| length(sub(point_at(points, *j), p)) <= reach;
+            let first = (0..i).rev().take_while(near).last().unwrap_or(i);
+            let last = (i + 1..n).take_while(near).last().unwrap_or(i);
+            (timestamps[last] - timestamps[first]).max(0.0) as f32
+        })
+        .collect()
+}
+
+/// Fountain pen: a fixed italic nib plus thinning with speed, as the ink
+/// flow cannot keep up, and short tapers where the nib lands and lifts. Works
+/// from positions and timestamps alone, without tilt.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct FountainPenOptions {
+    /// Nib edge angle in radians, counter-clockwise from the x axis.
+    pub nib_angle: f32,
+    /// Width when moving along the nib edge, as a fraction of the full width.
+    pub thin_ratio: f32,
+    /// Fraction of the width lost at `full_speed` and above.
+    pub speed_thinning: f32,
+    /// Speed in pixels per millisecond that gives the full thinning.
+    pub full_speed: f32,
+    /// Taper length at each end as a multiple of the mean width; 0 disables it.
+    pub taper: f32,
+}
+
+#[wasm_bindgen]
+impl FountainPenOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> FountainPenOptions {
+        FountainPenOptions::default()
+    }
+}
+
+impl Default for FountainPenOptions {
+    fn default() -> Self {
+        FountainPenOptions {
+            nib_angle: 30f32.to_radians(),
+            thin_ratio: 0.35,
+            speed_thinning: 0.4,
+            full_speed: 2.0,
+            taper: 1.5,
+        }
+    }
+}
+
+/// Fountain-pen preset. `timestamps` hold one per point in milliseconds and
+/// may be empty to skip the speed thinning. The options' own tapers are
+/// replaced by the pen's. Returns triangle soup as from `build_mesh`.
+#[wasm_bindgen]
+pub fn build_mesh_fountain_pen(points: &[f32], widths: &[f32], timestamps: &[f64], color: &[f32], options: &MeshOptions, pen: &FountainPenOptions) -> Vec<f32> {
+    fountain_pen(points, widths, timestamps, crate::rgba(color), options, pen)
+}
+
+pub(crate) fn fountain_pen(points: &[f32], widths: &[f32], timestamps: &[f64], color: [f32; 4], options: &MeshOptions, pen: &FountainPenOptions) -> Vec<f32> {
+    let n = points.len() / 2;
+    let tip = NibOptions {
+        angle: pen.nib_angle,
+        thin_ratio: pen.thin_ratio,
+        follow_azimuth: false,
+    };
+    let shaped = nib::nib_widths(points, widths, &[], &tip);
+    let flow = speed_falloff(points, timestamps, 1.0 - pen.speed_thinning, pen.full_speed);
+    // Speed from sample timing is jittery; a 3-tap average keeps the width calm.
+    let widths: Vec<f32> = (0..n)
+        .map(|i| {
+            let (a, b) = (i.saturating_sub(1), (i + 1).min(n - 1));
+            shaped[i] * (flow[a] + flow[i] + flow[b]) / 3.0
+        })
+        .collect();
+    let mean_width = widths.iter().sum::<f32>() / n.max(1) as f32;
+    let taper = pen.taper.max(0.0) * mean_width;
+    let options = MeshOptions {
+        start_taper: taper,
+        start_taper_easing: Easing::EaseOut,
+        end_taper: taper,
+        end_taper_easing: Easing::EaseOut,
+        ..*options
+    };
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(points, &widths, color, &options, &mut mesh);
+    mesh.triangle_soup()
+}
+
+/// A companion mesh drawn under the stroke: offset for a drop shadow, or
+/// centred and widened with soft edges for a glow.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowOptions {
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// Extra width on each side of the stroke, in stroke units.
+    pub spread: f32,
+    /// Width of the fade to transparent at the shadow's edge; 0 for a hard edge.
+    pub softness: f32,
+    /// Merge overlapping parts so a translucent shadow stays even. Slower.
+    pub overlap_free: bool,
+}
+
+#[wasm_bindgen]
+impl ShadowOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ShadowOptions {
+        ShadowOptions::default()
+    }
+}
+
+impl Default for ShadowOptions {
+    fn default() -> Self {
+        ShadowOptions {
+            offset_x: 2.0,
+            offset_y: 2.0,
+            spread: 1.0,
+            softness: 2.0,
+            overlap_free: true,
+        }
+    }
+}
+
+/// A stroke and its shadow, both triangle soup as from `build_mesh`. Draw the
+/// shadow first.
+#[wasm_bindgen]
+pub struct ShadowedMesh {
+    vertices: Vec<f32>,
+    shadow: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl ShadowedMesh {
+    pub fn vertices(&self) -> Vec<f32> {
+        self.vertices.clone()
+    }
+
+    pub fn shadow_vertices(&self) -> Vec<f32> {
+        self.shadow.clone()
+    }
+}
+
+/// Tessellates the stroke plus a shadow or glow mesh in `shadow_color`, with
+/// the same smoothing and tapers so the two line up.
+#[wasm_bindgen]
+pub fn build_mesh_with_shadow(points: &[f32], widths: &[f32], color: &[f32], shadow_color: &[f32], options: &MeshOptions, shadow: &ShadowOptions) -> ShadowedMesh {
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(points, widths, crate::rgba(color), options, &mut mesh);
+
+    let n = points.len() / 2;
+    let moved: Vec<f32> = points.chunks_exact(2).flat_map(|p| [p[0] + shadow.offset_x, p[1] + shadow.offset_y]).collect();
+    let spread = shadow.spread.max(0.0) * 2.0;
+    let widened: Vec<f32> = (0..n).map(|i| widths.get(i).copied().unwrap_or(1.0) + spread).collect();
+    let shadow_options = MeshOptions {
+        fringe: shadow.softness.max(0.0),
+        overlap_free: shadow.overlap_free,
+        ..*options
+    };
+    let mut shadow_mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(&moved, &widened, crate::rgba(shadow_color), &shadow_options, &mut shadow_mesh);
+    ShadowedMesh {
+        vertices: mesh.triangle_soup(),
+        shadow: shadow_mesh.triangle_soup(),
+    }
+}
+
+/// Hand-drawn roughs: the stroke is drawn several times, each pass wobbling
+/// off the centreline and slightly missing the ends, as when sketching a line
+/// with a few quick strokes. Seeded, so rebuilding gives the same passes.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct SketchOptions {
+    pub passes: u32,
+    /// Maximum sideways wobble in stroke units.
+    pub jitter: f32,
+    /// Arc length over which the wobble changes direction, in stroke units.
+    pub wavelength: f32,
+    /// Maximum distance each pass falls short of or overshoots either end.
+    pub end_jitter: f32,
+    /// Width of each pass as a fraction of the stroke's.
+    pub pass_width: f32,
+    pub seed: u32,
+}
+
+#[wasm_bindgen]
+impl SketchOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SketchOptions {
+        SketchOptions::default()
+    }
+}
+
+impl Default for SketchOptions {
+    fn default() -> Self {
+        SketchOptions {
+            passes: 2,
+            jitter: 1.5,
+            wavelength: 40.0,
+            end_jitter: 3.0,
+            pass_width: 0.7,
+            seed: 0,
+        }
+    }
+}
+
+/// Passes are capped here; more is indistinguishable from a thick smudge.
+const MAX_SKETCH_PASSES: u32 = 8;
+
+/// Sketch preset: all passes merged into one triangle soup as from
+/// `build_mesh`, using `options` for each pass.
+#[wasm_bindgen]
+pub fn build_mesh_sketch(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions, sketch: &SketchOptions) -> Vec<f32> {
+    sketch_mesh(points, widths, crate::rgba(color), options, sketch)
+}
+
+pub(crate) fn sketch_mesh(points: &[f32], widths: &[f32], color: [f32; 4], options: &MeshOptions, sketch: &SketchOptions) -> Vec<f32> {
+    let n = points.len() / 2;
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    if n < 2 {
+        tessellate::tessellate(points, widths, color, options, &mut mesh);
+        return mesh.triangle_soup();
+    }
+    let wavelength = sketch.wavelength.max(1e-3);
+    let (points, widths) = densify(points, widths, wavelength / 8.0);
+    let n = points.len() / 2;
+    let pass_widths: Vec<f32> = widths.iter().map(|w| w * sketch.pass_width.max(0.0)).collect();
+    let mut rng = Rng::new(sketch.seed);
+    for pass in 0..sketch.passes.clamp(1, MAX_SKETCH_PASSES) {
+        let noise_seed = sketch.seed.wrapping_add(pass.wrapping_mul(0x9E37));
+        // Each pass starts at a random point of the noise field so the wobbles differ.
+        let phase = rng.unit() * 1000.0;
+        let (start_shift, end_shift) = (rng.signed() * sketch.end_jitter, rng.signed() * sketch.end_jitter);
+        let mut along = 0.0;
+        let mut moved = Vec::with_capacity(points.len());
+        for i in 0..n {
+            let p = point_at(&points, i);
+            if i > 0 {
+                along += length(sub(p, point_at(&points, i - 1)));
+            }
+            let a = point_at(&points, i.saturating_sub(1));
+            let b = point_at(&points, (i + 1).min(n - 1));
+            let chord = sub(b, a);
+            let len = length(chord).max(1e-6);
+            let (dx, dy) = (chord.0 / len, chord.1 / len);
+            let side = (value_noise(along / wavelength + phase, 0.5, noise_seed) * 2.0 - 1.0) * sketch.jitter;
+            // Slide the end points along the stroke to fall short or overshoot.
+            let slide = if i == 0 {
+                -start_shift
+            } else if i == n - 1 {
+                end_shift
+            } else {
+                0.0
+            };
+            moved.extend_from_slice(&[p.0 - dy * side + dx * slide, p.1 + dx * side + dy * slide]);
+        }
+        tessellate::tessellate(&moved, &pass_widths, color, options, &mut mesh);
+    }
+    mesh.triangle_soup()
+}
@@ -0,0 +1,43 @@
+use wasm_bindgen::prelude::*;
+
+use crate::mesh::Mesh;
+use crate::tessellate::{self, MeshOptions};
+
+/// Reserves `len` floats of linear memory for `build_mesh_into`. JS can keep
+/// a `Float32Array` view over it (recreated whenever `memory.buffer` grows)
+/// and reuse it for every stroke. Release it with `free_f32_buffer`.
+#[wasm_bindgen]
+pub fn alloc_f32_buffer(len: usize) -> *mut f32 {
+    let mut buf = vec![0.0f32; len].into_boxed_slice();
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// # Safety
+/// `ptr` and `len` must come from one `alloc_f32_buffer` call, freed once.
+#[wasm_bindgen]
+pub unsafe fn free_f32_buffer(ptr: *mut f32, len: usize) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+    }
+}
+
+/// Tessellates like `build_mesh_with_options` but writes the vertex data
+/// straight into `capacity` floats at `out` instead of returning a copy.
+/// Returns the number of floats written. When the mesh needs more than
+/// `capacity`, nothing is written and the required length is returned, so a
+/// result above `capacity` means "grow the buffer and call again".
+///
+/// # Safety
+/// `out` must point to `capacity` writable floats, e.g. from `alloc_f32_buffer`.
+#[wasm_bindgen]
+pub unsafe fn build_mesh_into(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions, out: *mut f32, capacity: usize) -> usize {
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(points, widths, crate::rgba(color), options, &mut mesh);
+    let len = mesh.soup_len();
+    if len <= capacity && !out.is_null() {
+        mesh.write_triangle_soup(std::slice::from_raw_parts_mut(out, len));
+    }
+    len
+}
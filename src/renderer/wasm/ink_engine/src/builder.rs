@@ -0,0 +1,356 @@
+use wasm_bindgen::prelude::*;
+
+use crate::curve::PressureCurve;
+use crate::filter::OneEuroFilter;
+use crate::input::{self, InputOptions};
+use crate::mesh::{self, Mesh, Topology, FLOATS_PER_VERTEX};
+use crate::nib::NibOptions;
+use crate::predict;
+use crate::samples::Sample;
+use crate::tessellate::{self, MeshOptions};
+
+/// What an update changed in the stroke's vertex buffer: drop the last
+/// `invalidated` vertices uploaded so far, then write `vertices` starting at
+/// vertex `first_vertex`. Nothing before `first_vertex` ever changes.
+#[wasm_bindgen]
+pub struct MeshDelta {
+    first_vertex: u32,
+    invalidated: u32,
+    vertices: Vec<f32>,
+    predicted: Vec<f32>,
+    dirty: Option<[f32; 4]>,
+}
+
+#[wasm_bindgen]
+impl MeshDelta {
+    pub fn first_vertex(&self) -> u32 {
+        self.first_vertex
+    }
+
+    pub fn invalidated(&self) -> u32 {
+        self.invalidated
+    }
+
+    pub fn vertices(&self) -> Vec<f32> {
+        self.vertices.clone()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / FLOATS_PER_VERTEX
+    }
+
+    /// Where `vertices` go in a buffer holding the stroke's f32 vertex data.
+    pub fn byte_offset(&self) -> usize {
+        self.first_vertex as usize * FLOATS_PER_VERTEX * 4
+    }
+
+    pub fn byte_length(&self) -> usize {
+        self.vertices.len() * 4
+    }
+
+    /// Geometry from the last real point to the predicted pen position, when
+    /// prediction is on. It is never part of `vertices`: keep it in its own
+    /// buffer or draw range, e.g. at reduced alpha, and replace it whole on
+    /// every update.
+    pub fn predicted_vertices(&self) -> Vec<f32> {
+        self.predicted.clone()
+    }
+
+    /// `[min_x, min_y, max_x, max_y]` covering both the replaced and the new
+    /// geometry, in stroke units; empty when nothing changed. Repainting this
+    /// rectangle is enough to show the update.
+    pub fn dirty_rect(&self) -> Vec<f32> {
+        self.dirty.map_or_else(Vec::new, |r| r.to_vec())
+    }
+}
+
+/// Arc tolerance in screen pixels for wet ink when the options ask for less.
+const WET_TOLERANCE: f32 = 0.5;
+/// Real points handed to the predictor; it only looks at the last 60 ms.
+const PREDICTION_HISTORY: usize = 16;
+const PREDICTION_STEPS: usize = 3;
+
+/// A stroke in two stages. While the pen is down the stroke is "wet": it is
+/// tessellated incrementally with cheap settings, and only the geometry that
+/// new points can still change is rebuilt on each update. `finish` dries it,
+/// running the full options (smoothing, tapers, fringe, overlap removal) once
+/// over the whole stroke. The wet layer can be composited over the committed
+/// dry layer and cleared when the dry mesh lands.
+///
+/// Wet output is a triangle-list soup like `build_mesh`. It keeps the join
+/// and miter settings, coarsens round joins and caps to `WET_TOLERANCE`,
+/// and leaves out everything that needs the whole stroke.
+#[wasm_bindgen]
+pub struct StrokeBuilder {
+    /// Options for the dry mesh, as given.
+    options: MeshOptions,
+    wet: MeshOptions,
+    input: InputOptions,
+    /// Present while `input.filter` is on; restarted by `begin`.
+    filter: Option<OneEuroFilter>,
+    /// Replaces the linear `input` width mapping when set.
+    pressure_curve: Option<PressureCurve>,
+    /// Shapes pointer-event widths with a broad nib when set.
+    nib: Option<NibOptions>,
+    color: [f32; 4],
+    points: Vec<f32>,
+    widths: Vec<f32>,
+    /// Sample times in milliseconds; zero for points added without one.
+    times: Vec<f64>,
+    /// Soup of the pieces no later point can change.
+    committed: Vec<f32>,
+    /// End offset in `committed` of each piece it holds, see `tessellate::Span`.
+    piece_ends: Vec<usize>,
+    /// Soup of the last segment and end cap, rebuilt on every update.
+    tail: Vec<f32>,
+    /// Soup of the predicted segment, kept apart from the stroke.
+    predicted: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl StrokeBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(options: &MeshOptions) -> StrokeBuilder {
+        StrokeBuilder {
+            options: *options,
+            wet: MeshOptions {
+                closed: false,
+                topology: Topology::TriangleList,
+                primitive_restart: false,
+                overlap_free: false,
+                fringe: 0.0,
+                smoothing: 0,
+                start_taper: 0.0,
+                end_taper: 0.0,
+                tolerance: options.tolerance.max(WET_TOLERANCE),
+                ..*options
+            },
+            input: InputOptions::default(),
+            filter: None,
+            pressure_curve: None,
+            nib: None,
+            color: [0.0, 0.0, 0.0, 1.0],
+            points: Vec::new(),
+            widths: Vec::new(),
+            times: Vec::new(),
+            committed: Vec::new(),
+            piece_ends: Vec::new(),
+            tail: Vec::new(),
+            predicted: Vec::new(),
+        }
+    }
+
+    /// Pressure-to-width mapping and filtering for `add_pointer_events`.
+    pub fn set_input_options(&mut self, input: &InputOptions) {
+        self.input = *input;
+        self.filter = input.filter.then(|| OneEuroFilter::new(input.min_cutoff, input.beta, input.d_cutoff));
+    }
+
+    /// Maps pressure through `curve` instead of `InputOptions::base_width`.
+    pub fn set_pressure_curve(&mut self, curve: &PressureCurve) {
+        self.pressure_curve = Some(curve.clone());
+    }
+
+    pub fn clear_pressure_curve(&mut self) {
+        self.pressure_curve = None;
+    }
+
+    /// Shapes `add_pointer_events` widths with a broad nib, oriented by the
+    /// pen tilt where reported. Each width uses the direction from the
+    /// previous point, as the next one is not known yet.
+    pub fn set_nib(&mut self, nib: &NibOptions) {
+        self.nib = Some(*nib);
+    }
+
+    pub fn clear_nib(&mut self) {
+        self.nib = None;
+    }
+
+    /// Starts a new stroke, discarding any previous one.
+    pub fn begin(&mut self, color: &[f32]) {
+        if let Some(filter) = &mut self.filter {
+            filter.reset();
+        }
+        self.color = crate::rgba(color);
+        self.points.clear();
+        self.widths.clear();
+        self.times.clear();
+        self.committed.clear();
+        self.piece_ends.clear();
+        self.tail.clear();
+        self.predicted.clear();
+    }
+
+    /// Appends points with their widths (missing widths default to 1) and
+    /// tessellates the new segments. Repeated positions are skipped. Returns
+    /// the vertices to upload: the previous tail is always replaced.
+    pub fn add_points(&mut self, points: &[f32], widths: &[f32]) -> MeshDelta {
+        self.splice(0, points, widths, &[])
+    }
+
+    /// Ingests one frame of pointer samples packed as eight values each:
+    /// `x, y, pressure, tiltX, tiltY, twist, timeStamp, coalesced` (1 for
+    /// entries of `getCoalescedEvents()`, 0 for the dispatched event). Filters
+    /// and maps pressure to width per the input options, then tessellates, all
+    /// in one call per frame. With `predict_ms` set, the delta also carries
+    /// the predicted tip.
+    pub fn add_pointer_events(&mut self, packed: &[f64]) -> MeshDelta {
+        let events = input::unpack(packed);
+        let mut points = Vec::with_capacity(events.len() * 2);
+        let mut widths = Vec::with_capacity(events.len());
+        let times: Vec<f64> = events.iter().map(|e| e.time).collect();
+        for e in events {
+            let (mut x, mut y, mut pressure) = (e.x, e.y, e.pressure);
+            if let Some(filter) = &mut self.filter {
+                let [fx, fy, fp] = filter.step([x as f64, y as f64, pressure as f64], e.time);
+                (x, y, pressure) = (fx as f32, fy as f32, fp as f32);
+            }
+            let mut width = match &self.pressure_curve {
+                Some(curve) => curve.width(pressure),
+                None => self.input.width(pressure),
+            };
+            if let Some(nib) = &self.nib {
+                let prev = if points.is_empty() { &self.points[..] } else { &points[..] };
+                if let [.., px, py] = *prev {
+                    width = nib.width(width, (x - px, y - py), e.azimuth);
+                }
+            }
+            points.extend_from_slice(&[x, y]);
+            widths.push(width);
+        }
+        self.splice(0, &points, &widths, &times)
+    }
+
+    /// Drops the last `count` points and appends `points` in their place, for
+    /// smoothers that revise recent samples. Only the geometry that depends on
+    /// the dropped points is regenerated: the returned delta starts at the
+    /// earliest piece they touched, at most three segments back.
+    pub fn replace_last_points(&mut self, count: u32, points: &[f32], widths: &[f32]) -> MeshDelta {
+        self.splice(count as usize, points, widths, &[])
+    }
+
+    /// Dries the stroke: tessellates all points with the full options and
+    /// returns the final mesh, exactly as `build_mesh_with_options` would.
+    /// The wet mesh stays available until the next `begin`.
+    pub fn finish(&mut self) -> Vec<f32> {
+        let mut mesh = Mesh::with_topology(self.options.topology, false);
+        tessellate::tessellate(&self.points, &self.widths, self.color, &self.options, &mut mesh);
+        mesh.triangle_soup()
+    }
+
+    /// The complete wet mesh so far, end cap included.
+    pub fn mesh(&self) -> Vec<f32> {
+        [&self.committed[..], &self.tail[..]].concat()
+    }
+
+    pub fn predicted_mesh(&self) -> Vec<f32> {
+        self.predicted.clone()
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.widths.len()
+    }
+}
+
+impl StrokeBuilder {
+    fn splice(&mut self, count: usize, points: &[f32], widths: &[f32], times: &[f64]) -> MeshDelta {
+        let old_len = self.widths.len();
+        let keep = old_len.saturating_sub(count);
+        self.points.truncate(keep * 2);
+        self.widths.truncate(keep);
+        self.times.truncate(keep);
+        for i in 0..points.len() / 2 {
+            let p = (points[i * 2], points[i * 2 + 1]);
+            if !(p.0.is_finite() && p.1.is_finite()) || self.points.ends_with(&[p.0, p.1]) {
+                continue;
+            }
+            self.points.extend_from_slice(&[p.0, p.1]);
+            self.widths.push(widths.get(i).copied().unwrap_or(1.0));
+            self.times.push(times.get(i).copied().unwrap_or(0.0));
+        }
+        if keep == old_len && self.widths.len() == old_len {
+            return MeshDelta {
+                first_vertex: ((self.committed.len() + self.tail.len()) / FLOATS_PER_VERTEX) as u32,
+                invalidated: 0,
+                vertices: Vec::new(),
+                predicted: self.predicted.clone(),
+                dirty: None,
+            };
+        }
+        let old_prediction = mesh::bounds(&self.predicted);
+        let mut delta = self.update(keep);
+        self.predict();
+        delta.predicted = self.predicted.clone();
+        delta.dirty = mesh::union_bounds(delta.dirty, mesh::union_bounds(old_prediction, mesh::bounds(&self.predicted)));
+        delta
+    }
+
+    /// Rebuilds everything that depends on points from index `unchanged` on.
+    fn update(&mut self, unchanged: usize) -> MeshDelta {
+        // Piece k reads points k - 2 ..= k + 1, so only pieces before
+        // `unchanged - 1` survive, and never more than were committed.
+        let valid = unchanged.saturating_sub(1).min(self.piece_ends.len());
+        self.piece_ends.truncate(valid);
+        let first = self.piece_ends.last().copied().unwrap_or(0);
+        let old_total = self.committed.len() + self.tail.len();
+        let removed = mesh::union_bounds(mesh::bounds(&self.committed[first..]), mesh::bounds(&self.tail));
+        self.committed.truncate(first);
+        self.tail.clear();
+
+        let n = self.widths.len();
+        // The join at point k needs point k + 1, so pieces up to n - 2 are final.
+        let settled = n.saturating_sub(1);
+        for piece in valid..settled {
+            let soup = self.emit(piece, piece, false);
+            self.committed.extend_from_slice(&soup);
+            self.piece_ends.push(self.committed.len());
+        }
+        if n > 0 {
+            self.tail = self.emit(settled, settled, true);
+        }
+        let vertices = [&self.committed[first..], &self.tail[..]].concat();
+        MeshDelta {
+            first_vertex: (first / FLOATS_PER_VERTEX) as u32,
+            invalidated: ((old_total - first) / FLOATS_PER_VERTEX) as u32,
+            dirty: mesh::union_bounds(removed, mesh::bounds(&vertices)),
+            vertices,
+            predicted: Vec::new(),
+        }
+    }
+
+    /// Extends the last few real points with the predicted tip and emits the
+    /// pieces past the last real point, end cap included.
+    fn predict(&mut self) {
+        self.predicted.clear();
+        let n = self.widths.len();
+        if self.input.predict_ms <= 0.0 || n == 0 {
+            return;
+        }
+        let from = n.saturating_sub(PREDICTION_HISTORY);
+        let recent: Vec<Sample> = (from..n)
+            .map(|i| Sample {
+                x: self.points[i * 2],
+                y: self.points[i * 2 + 1],
+                width: self.widths[i],
+                time: self.times[i],
+            })
+            .collect();
+        let tail = predict::predict(&recent, self.input.predict_ms as f64, PREDICTION_STEPS);
+        if tail.is_empty() {
+            return;
+        }
+        let lo = n.saturating_sub(3);
+        let mut points = self.points[lo * 2..].to_vec();
+        let mut widths = self.widths[lo..].to_vec();
+        for s in &tail {
+            points.extend_from_slice(&[s.x, s.y]);
+            widths.push(s.width);
+        }
+        let last_real = n - 1 - lo;
+        self.predicted = tessellate::emit_pieces(&points, &widths, self.color, &self.wet, last_real + 1, widths.len() - 1, true);
+    }
+
+    fn emit(&self, first: usize, last: usize, end_cap: bool) -> Vec<f32> {
+        tessellate::emit_pieces(&self.points, &self.widths, self.color, &self.wet, first, last, end_cap)
+    }
+}
@@ -0,0 +1,182 @@
+use wasm_bindgen::prelude::*;
+
+use crate::codec::{self, Reader, Writer};
+use crate::document::InkDocument;
+use crate::erase::placed_rings;
+use crate::geom::Point;
+use crate::transform;
+
+const MAGIC: &[u8; 4] = b"INKC";
+const VERSION: u8 = 2;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Serializes `ids` into a self-contained blob for the system
+    /// clipboard: geometry in document coordinates with transforms baked
+    /// in, colors, and brushes by name, so another window or document can
+    /// paste it. Unknown ids are skipped; strokes keep their drawing order.
+    pub fn copy_strokes(&self, ids: &[u32]) -> Vec<u8> {
+        let strokes: Vec<_> = self.ordered().map(|i| &self.strokes[i]).filter(|s| ids.contains(&s.id)).collect();
+        let mut w = Writer::default();
+        w.buf.extend_from_slice(MAGIC);
+        w.u8(VERSION);
+        w.varint(strokes.len() as u64);
+        for s in strokes {
+            w.str(&self.brushes.name(s.brush).unwrap_or_default());
+            s.color.iter().for_each(|&c| w.f32(c));
+            let scale = transform::scale_factor(&s.transform);
+            let points: Vec<f32> = s
+                .points
+                .chunks_exact(2)
+                .flat_map(|p| {
+                    let (x, y) = transform::apply(&s.transform, (p[0], p[1]));
+                    [x, y]
+                })
+                .collect();
+            let widths: Vec<f32> = s.widths.iter().map(|w| w * scale).collect();
+            codec::write_samples(&mut w, &points, &widths, &s.timestamps);
+            w.u8(s.fill.is_some() as u8);
+            if let Some(rings) = &s.fill {
+                codec::write_rings(&mut w, &placed_rings(&s.transform, rings));
+            }
+        }
+        w.buf
+    }
+
+    /// Inserts the strokes of a `copy_strokes` blob on top of the active
+    /// layer, shifted by `offset_x, offset_y`, and returns their new ids.
+    /// Brushes are matched by name, falling back to brush 0. A blob that is
+    /// not from `copy_strokes` or is cut short pastes nothing.
+    pub fn paste_strokes(&mut self, bytes: &[u8], offset_x: f32, offset_y: f32) -> Vec<u32> {
+        let Some(strokes) = read_clipboard(bytes) else {
+            return Vec::new();
+        };
+        let mut ids = Vec::with_capacity(strokes.len());
+        for (brush, color, mut points, widths, timestamps, fill) in strokes {
+            for p in points.chunks_exact_mut(2) {
+                p[0] += offset_x;
+                p[1] += offset_y;
+            }
+            let brush = self.brushes.id(&brush).unwrap_or(0);
+            let id = self.add_stroke(&points, &widths, &timestamps, &color, brush).id;
+            if let Some(mut rings) = fill {
+                rings.iter_mut().flatten().for_each(|p| *p = (p.0 + offset_x, p.1 + offset_y));
+                let i = self.index(id).unwrap();
+                let s = &mut self.strokes[i];
+                s.fill = Some(rings);
+                s.invalidate();
+                self.record_stroke(i);
+            }
+            ids.push(id);
+        }
+        ids
+    }
+}
+
+type ClipboardStroke = (String, [f32; 4], Vec<f32>, Vec<f32>, Vec<f64>, Option<Vec<Vec<Point>>>);
+
+fn read_clipboard(bytes: &[u8]) -> Option<Vec<ClipboardStroke>> {
+    let mut r = Reader::new(bytes);
+    if r.raw(MAGIC.len())? != MAGIC || r.u8()? != VERSION {
+        return None;
+    }
+    let n = r.count()?;
+    let mut out = Vec::with_capacity(n);
+    for _ in 0..n {
+        let brush = r.str()?;
+        let color = [r.f32()?, r.f32()?, r.f32()?, r.f32()?];
+        let (points, widths, timestamps) = codec::read_samples(&mut r)?;
+        let fill = match r.u8()? {
+            0 => None,
+            _ => Some(codec::read_rings(&mut r)?),
+        };
+        out.push((brush, color, points, widths, timestamps, fill));
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::{Brush, BrushStyle};
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    #[test]
+    fn copies_transforms_baked_in() {
+        let mut doc = InkDocument::new();
+        let id = doc.add_stroke(&[0.0, 0.0, 10.0, 0.0], &[2.0, 3.0], &[5.0, 9.0], &BLACK, 0).id;
+        doc.set_stroke_transform(id, &[2.0, 0.0, 0.0, 2.0, 100.0, 50.0]);
+        let mut target = InkDocument::new();
+        let pasted = target.paste_strokes(&doc.copy_strokes(&[id]), 1.0, -1.0);
+        assert_eq!(target.stroke_points(pasted[0]), [101.0, 49.0, 121.0, 49.0]);
+        assert_eq!(target.stroke_widths(pasted[0]), [4.0, 6.0]);
+        assert_eq!(target.stroke_transform(pasted[0]), transform::IDENTITY);
+        assert_eq!(target.strokes[0].timestamps, [5.0, 9.0]);
+    }
+
+    #[test]
+    fn fills_move_with_the_paste() {
+        let mut doc = InkDocument::new();
+        let id = doc.add_stroke(&[0.0, 0.0, 10.0, 0.0], &[2.0; 2], &[], &BLACK, 0).id;
+        doc.strokes[0].fill = Some(vec![vec![(0.0, 0.0), (4.0, 0.0), (0.0, 4.0)]]);
+        doc.set_stroke_transform(id, &[1.0, 0.0, 0.0, 1.0, 10.0, 0.0]);
+        let mut target = InkDocument::new();
+        let pasted = target.paste_strokes(&doc.copy_strokes(&[id]), 0.0, 5.0);
+        assert!(target.stroke_is_filled(pasted[0]));
+        assert_eq!(target.strokes[0].fill, Some(vec![vec![(10.0, 5.0), (14.0, 5.0), (10.0, 9.0)]]));
+    }
+
+    #[test]
+    fn brushes_go_by_name() {
+        let mut doc = InkDocument::new();
+        let marker = doc.register_brush("marker", &Brush::new(BrushStyle::Highlighter));
+        let pencil = doc.register_brush("pencil", &Brush::new(BrushStyle::Pencil));
+        let a = doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, marker).id;
+        let b = doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, pencil).id;
+        let mut target = InkDocument::new();
+        target.register_brush("spare", &Brush::new(BrushStyle::Ink));
+        let marker_here = target.register_brush("marker", &Brush::new(BrushStyle::Highlighter));
+        let pasted = target.paste_strokes(&doc.copy_strokes(&[a, b]), 0.0, 0.0);
+        assert_eq!(target.stroke_brush(pasted[0]), Some(marker_here));
+        assert_eq!(target.stroke_brush(pasted[1]), Some(0));
+    }
+
+    #[test]
+    fn keeps_drawing_order_and_skips_unknown_ids() {
+        let mut doc = InkDocument::new();
+        let upper = doc.add_layer("Upper");
+        doc.set_active_layer(upper);
+        let top = doc.add_stroke(&[0.0, 0.0, 1.0, 0.0], &[1.0; 2], &[], &BLACK, 0).id;
+        doc.set_active_layer(0);
+        let bottom = doc.add_stroke(&[5.0, 0.0, 6.0, 0.0], &[1.0; 2], &[], &BLACK, 0).id;
+        let mut target = InkDocument::new();
+        let pasted = target.paste_strokes(&doc.copy_strokes(&[top, 999, bottom]), 0.0, 0.0);
+        assert_eq!(pasted.len(), 2);
+        assert_eq!(target.stroke_points(pasted[0]), doc.stroke_points(bottom));
+        assert_eq!(target.stroke_points(pasted[1]), doc.stroke_points(top));
+    }
+
+    #[test]
+    fn a_damaged_blob_pastes_nothing() {
+        let mut doc = InkDocument::new();
+        let a = doc.add_stroke(&[0.0, 0.0, 1.0, 0.0], &[1.0; 2], &[], &BLACK, 0).id;
+        let b = doc.add_stroke(&[0.0, 5.0, 1.0, 5.0], &[1.0; 2], &[], &BLACK, 0).id;
+        let bytes = doc.copy_strokes(&[a, b]);
+        // Cut inside the second stroke, the first one is not pasted alone.
+        let mut target = InkDocument::new();
+        assert!(target.paste_strokes(&bytes[..bytes.len() - 3], 0.0, 0.0).is_empty());
+        assert_eq!(target.stroke_count(), 0);
+
+        let mut other = bytes.clone();
+        other[MAGIC.len()] = VERSION + 1;
+        assert!(target.paste_strokes(&other, 0.0, 0.0).is_empty());
+
+        // A stroke count past what the blob could hold.
+        let mut w = Writer::default();
+        w.buf.extend_from_slice(MAGIC);
+        w.u8(VERSION);
+        w.varint(1 << 40);
+        assert!(target.paste_strokes(&w.buf, 0.0, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use crate::geom::Point;
+
+/// Little-endian byte writer with LEB128 varints, shared by the clipboard,
+/// history and file formats.
+#[derive(Default)]
+pub(crate) struct Writer {
+    pub buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn varint(&mut self, mut v: u64) {
+        while v >= 0x80 {
+            self.buf.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        self.buf.push(v as u8);
+    }
+
+    /// Zigzag-encoded, so small negative numbers stay short.
+    pub fn sint(&mut self, v: i64) {
+        self.varint(((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    pub fn f32(&mut self, v: f32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn f64(&mut self, v: f64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.varint(v.len() as u64);
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn str(&mut self, v: &str) {
+        self.bytes(v.as_bytes());
+    }
+}
+
+/// Reads what `Writer` wrote. Every read returns `None` past the end or on
+/// malformed data, so truncated input is detected rather than misread.
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Reader { buf, pos: 0 }
+    }
+
+    /// Bytes read so far.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// The next `n` bytes as they are.
+    pub fn raw(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let out = self.buf.get(self.pos..end)?;
+        self.pos = end;
+        Some(out)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.raw(1).map(|b| b[0])
+    }
+
+    pub fn varint(&mut self) -> Option<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.u8()?;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Some(v);
+            }
+        }
+        None
+    }
+
+    pub fn sint(&mut self) -> Option<i64> {
+        let v = self.varint()?;
+        Some((v >> 1) as i64 ^ -((v & 1) as i64))
+    }
+
+    /// A varint used as a count or length, bounded by the bytes left so a
+    /// corrupt length cannot trigger a huge allocation.
+    pub fn count(&mut self) -> Option<usize> {
+        let n = self.varint()?;
+        (n <= (self.buf.len() - self.pos) as u64).then_some(n as usize)
+    }
+
+    pub fn f32(&mut self) -> Option<f32> {
+        self.raw(4).map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn f64(&mut self) -> Option<f64> {
+        self.raw(8).map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self) -> Option<&'a [u8]> {
+        let n = self.count()?;
+        self.raw(n)
+    }
+
+    pub fn str(&mut self) -> Option<String> {
+        self.bytes().and_then(|b| String::from_utf8(b.to_vec()).ok())
+    }
+}
+
+/// FNV-1a, enough to catch a damaged or mismatched blob.
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Geometry is stored in steps of 1/`QUANTUM` units: far below a pixel at
+/// any sensible zoom, and small enough deltas to fit a byte or two.
+const QUANTUM: f32 = 64.0;
+/// Timestamps are stored in microseconds.
+const TIME_QUANTUM: f64 = 1000.0;
+
+fn quantize(v: f32) -> i64 {
+    (v * QUANTUM).round() as i64
+}
+
+/// Writes points, widths and optional timestamps as quantized deltas.
+pub(crate) fn write_samples(w: &mut Writer, points: &[f32], widths: &[f32], timestamps: &[f64]) {
+    let n = points.len() / 2;
+    w.varint(n as u64);
+    let (mut x, mut y, mut width) = (0, 0, 0);
+    for i in 0..n {
+        let (qx, qy) = (quantize(points[i * 2]), quantize(points[i * 2 + 1]));
+        let qw = quantize(widths.get(i).copied().unwrap_or(1.0));
+        w.sint(qx - x);
+        w.sint(qy - y);
+        w.sint(qw - width);
+        (x, y, width) = (qx, qy, qw);
+    }
+    let timed = timestamps.len() == n && n > 0;
+    w.u8(timed as u8);
+    if timed {
+        w.f64(timestamps[0]);
+        let mut t = (timestamps[0] * TIME_QUANTUM).round() as i64;
+        for &ts in &timestamps[1..] {
+            let q = (ts * TIME_QUANTUM).round() as i64;
+            w.sint(q - t);
+            t = q;
+        }
+    }
+}
+
+/// Reads what `write_samples` wrote: points, widths and timestamps, the
+/// last empty when the stroke had none.
+pub(crate) fn read_samples(r: &mut Reader) -> Option<(Vec<f32>, Vec<f32>, Vec<f64>)> {
+    let n = r.count()?;
+    let (mut points, mut widths) = (Vec::with_capacity(n * 2), Vec::with_capacity(n));
+    let (mut x, mut y, mut width) = (0i64, 0i64, 0i64);
+    for _ in 0..n {
+        x = x.checked_add(r.sint()?)?;
+        y = y.checked_add(r.sint()?)?;
+        width = width.checked_add(r.sint()?)?;
+        points.push(x as f32 / QUANTUM);
+        points.push(y as f32 / QUANTUM);
+        widths.push(width as f32 / QUANTUM);
+    }
+    let mut timestamps = Vec::new();
+    if r.u8()? != 0 {
+        let first = r.f64()?;
+        timestamps.push(first);
+        let mut t = (first * TIME_QUANTUM).round() as i64;
+        for _ in 1..n {
+            t = t.checked_add(r.sint()?)?;
+            timestamps.push(t as f64 / TIME_QUANTUM);
+        }
+    }
+    Some((points, widths, timestamps))
+}
+
+/// Writes outline rings as quantized deltas running across all of them.
+pub(crate) fn write_rings(w: &mut Writer, rings: &[Vec<Point>]) {
+    w.varint(rings.len() as u64);
+    let (mut x, mut y) = (0, 0);
+    for ring in rings {
+        w.varint(ring.len() as u64);
+        for p in ring {
+            let (qx, qy) = (quantize(p.0), quantize(p.1));
+            w.sint(qx - x);
+            w.sint(qy - y);
+            (x, y) = (qx, qy);
+        }
+    }
+}
+
+/// Reads what `write_rings` wrote.
+pub(crate) fn read_rings(r: &mut Reader) -> Option<Vec<Vec<Point>>> {
+    let n = r.count()?;
+    let mut rings = Vec::with_capacity(n);
+    let (mut x, mut y) = (0i64, 0i64);
+    for _ in 0..n {
+        let len = r.count()?;
+        let mut ring = Vec::with_capacity(len);
+        for _ in 0..len {
+            x = x.checked_add(r.sint()?)?;
+            y = y.checked_add(r.sint()?)?;
+            ring.push((x as f32 / QUANTUM, y as f32 / QUANTUM));
+        }
+        rings.push(ring);
+    }
+    Some(rings)
+}
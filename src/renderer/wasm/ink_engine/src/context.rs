@@ -0,0 +1,100 @@
+use wasm_bindgen::prelude::*;
+
+use crate::mesh::{Mesh, VertexLayout};
+use crate::tessellate::{self, MeshOptions, Scratch, Shading};
+
+/// Tessellates stroke after stroke into buffers it keeps between calls, for
+/// hosts that rebuild meshes every frame. Once the buffers have grown to
+/// the longest stroke, a build allocates nothing: not the segment frames,
+/// edge, join and fan buffers, the mesh or the vertex data. Smoothing,
+/// tapers and overlap removal still allocate their intermediate paths.
+///
+/// A build's vertex data stays in the context until the next one. Read it
+/// with `vertices`, which copies it into a fresh JS array, or without a
+/// copy through a view: `new Float32Array(memory.buffer, ctx.vertices_ptr(),
+/// ctx.vertices_len())` can go straight to `bufferData`. Alternatively
+/// `copy_into` writes it to a buffer from `alloc_f32_buffer`.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct MeshContext {
+    mesh: Mesh,
+    scratch: Scratch,
+    layout: VertexLayout,
+    vertices: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl MeshContext {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> MeshContext {
+        MeshContext::default()
+    }
+
+    /// Tessellates like `build_mesh_with_options`. Returns the number of
+    /// floats of vertex data.
+    pub fn build(&mut self, points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions) -> usize {
+        self.build_with_layout(points, widths, color, options, VertexLayout::PositionColor)
+    }
+
+    /// Like `build` with the vertex attributes chosen by `layout`, as
+    /// `build_mesh_with_layout`.
+    pub fn build_with_layout(&mut self, points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions, layout: VertexLayout) -> usize {
+        // Restart indices have no meaning once the strip is expanded into vertices.
+        self.mesh.reset(options.topology, false);
+        tessellate::tessellate_with(points, widths, crate::rgba(color), &Shading::for_layout(layout), options, &mut self.mesh, &mut self.scratch);
+        self.layout = layout;
+        self.vertices.clear();
+        self.vertices.resize(self.mesh.indices.len() * layout.floats(), 0.0);
+        self.mesh.write_triangle_soup_with(&mut self.vertices, layout);
+        self.vertices.len()
+    }
+
+    /// The last build's vertex data.
+    pub fn vertices(&self) -> Vec<f32> {
+        self.vertices.clone()
+    }
+
+    /// Where the last build's vertex data starts in linear memory, for a
+    /// `Float32Array` of `vertices_len` floats over `memory.buffer`. The view
+    /// is only valid until the next `build`, `build_with_layout` or
+    /// `release`, which may move the data, and until `memory.buffer` grows,
+    /// which any call into the engine may do and which detaches every view.
+    /// Make a new view after any of them, and never write through one. With
+    /// no vertex data the pointer is dangling but aligned.
+    pub fn vertices_ptr(&self) -> *const f32 {
+        self.vertices.as_ptr()
+    }
+
+    /// Floats of vertex data at `vertices_ptr`.
+    pub fn vertices_len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.layout.floats()
+    }
+
+    pub fn layout(&self) -> VertexLayout {
+        self.layout
+    }
+
+    /// Copies the last build's vertex data to `capacity` floats at `out`
+    /// and returns its length. When longer than `capacity`, nothing is
+    /// written, as with `build_mesh_into`.
+    ///
+    /// # Safety
+    /// `out` must point to `capacity` writable floats, e.g. from `alloc_f32_buffer`.
+    pub unsafe fn copy_into(&self, out: *mut f32, capacity: usize) -> usize {
+        let len = self.vertices.len();
+        if len <= capacity && !out.is_null() {
+            std::slice::from_raw_parts_mut(out, len).copy_from_slice(&self.vertices);
+        }
+        len
+    }
+
+    /// Frees the buffers, e.g. after an unusually long stroke; the next
+    /// build grows them again.
+    pub fn release(&mut self) {
+        *self = MeshContext::default();
+    }
+}
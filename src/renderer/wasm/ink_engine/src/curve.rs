@@ -0,0 +1,169 @@
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{length, point_at, sub};
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Quadratic, slow at the start.
+    EaseIn,
+    /// Quadratic, slow at the end.
+    EaseOut,
+    /// Smoothstep.
+    EaseInOut,
+}
+
+impl Easing {
+    pub(crate) fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Maps raw pen pressure in 0..1 to a stroke width: pressure is shaped by
+/// the control-point curve when one is set (the easing preset otherwise),
+/// raised to `gamma`, then spread between `min_width` and `max_width`.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct PressureCurve {
+    pub min_width: f32,
+    pub max_width: f32,
+    /// Above 1 needs a firmer press to widen; below 1 widens early.
+    pub gamma: f32,
+    pub easing: Easing,
+    /// `(pressure, output)` knots sorted by pressure, with Hermite tangents.
+    knots: Vec<(f32, f32, f32)>,
+}
+
+#[wasm_bindgen]
+impl PressureCurve {
+    #[wasm_bindgen(constructor)]
+    pub fn new(min_width: f32, max_width: f32) -> PressureCurve {
+        PressureCurve {
+            min_width,
+            max_width,
+            gamma: 1.0,
+            easing: Easing::Linear,
+            knots: Vec::new(),
+        }
+    }
+
+    /// Replaces the preset with a curve through `[p0, out0, p1, out1, ...]`,
+    /// both in 0..1. The curve is monotone cubic (Fritsch–Carlson), so it
+    /// never overshoots between knots; an empty array removes it.
+    pub fn set_control_points(&mut self, knots: &[f32]) {
+        let mut pts: Vec<(f32, f32)> = knots
+            .chunks_exact(2)
+            .filter(|k| k[0].is_finite() && k[1].is_finite())
+            .map(|k| (k[0].clamp(0.0, 1.0), k[1].clamp(0.0, 1.0)))
+            .collect();
+        pts.sort_by(|a, b| a.0.total_cmp(&b.0));
+        pts.dedup_by(|b, a| b.0 - a.0 < 1e-6);
+        self.knots = monotone_tangents(&pts);
+    }
+
+    pub fn width(&self, pressure: f32) -> f32 {
+        let p = if pressure.is_finite() { pressure.clamp(0.0, 1.0) } else { 1.0 };
+        let shaped = if self.knots.is_empty() { self.easing.apply(p) } else { self.eval_knots(p) };
+        let t = shaped.clamp(0.0, 1.0).powf(self.gamma.max(1e-3));
+        self.min_width + (self.max_width - self.min_width) * t
+    }
+
+    /// Widths for a whole array of pressures.
+    pub fn map(&self, pressures: &[f32]) -> Vec<f32> {
+        pressures.iter().map(|&p| self.width(p)).collect()
+    }
+}
+
+/// Per-point opacity from drawing speed, for brush pens that run dry when
+/// moved quickly: 1 at rest, falling linearly to `min_alpha` at `full_speed`
+/// pixels per millisecond and above. Points without usable timestamps get 1.
+/// For pressure-driven opacity, map pressures through a `PressureCurve`
+/// spanning 0..1 instead.
+#[wasm_bindgen]
+pub fn alphas_from_speed(points: &[f32], timestamps: &[f64], min_alpha: f32, full_speed: f32) -> Vec<f32> {
+    speed_falloff(points, timestamps, min_alpha, full_speed)
+}
+
+/// Per point, 1 at rest falling linearly to `min` at `full_speed` px/ms.
+pub(crate) fn speed_falloff(points: &[f32], timestamps: &[f64], min: f32, full_speed: f32) -> Vec<f32> {
+    let n = points.len() / 2;
+    (0..n)
+        .map(|i| {
+            // Central difference, one-sided at the ends.
+            let (a, b) = (i.saturating_sub(1), (i + 1).min(n - 1));
+            let (Some(&ta), Some(&tb)) = (timestamps.get(a), timestamps.get(b)) else {
+                return 1.0;
+            };
+            let dt = (tb - ta) as f32;
+            if dt.is_nan() || dt <= 0.0 || full_speed <= 0.0 {
+                return 1.0;
+            }
+            let speed = length(sub(point_at(points, b), point_at(points, a))) / dt;
+            1.0 - (1.0 - min.clamp(0.0, 1.0)) * (speed / full_speed).min(1.0)
+        })
+        .collect()
+}
+
+impl PressureCurve {
+    fn eval_knots(&self, p: f32) -> f32 {
+        let k = &self.knots;
+        if k.len() == 1 || p <= k[0].0 {
+            return k[0].1;
+        }
+        let last = k[k.len() - 1];
+        if p >= last.0 {
+            return last.1;
+        }
+        let i = k.partition_point(|knot| knot.0 <= p) - 1;
+        let ((x0, y0, m0), (x1, y1, m1)) = (k[i], k[i + 1]);
+        let h = x1 - x0;
+        let t = (p - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0 + (t3 - 2.0 * t2 + t) * h * m0 + (-2.0 * t3 + 3.0 * t2) * y1 + (t3 - t2) * h * m1
+    }
+}
+
+fn monotone_tangents(pts: &[(f32, f32)]) -> Vec<(f32, f32, f32)> {
+    let n = pts.len();
+    if n < 2 {
+        return pts.iter().map(|&(x, y)| (x, y, 0.0)).collect();
+    }
+    let slopes: Vec<f32> = pts.windows(2).map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0)).collect();
+    let mut m: Vec<f32> = (0..n)
+        .map(|i| {
+            if i == 0 {
+                slopes[0]
+            } else if i == n - 1 {
+                slopes[n - 2]
+            } else if slopes[i - 1] * slopes[i] <= 0.0 {
+                0.0
+            } else {
+                (slopes[i - 1] + slopes[i]) * 0.5
+            }
+        })
+        .collect();
+    for i in 0..n - 1 {
+        if slopes[i] == 0.0 {
+            m[i] = 0.0;
+            m[i + 1] = 0.0;
+            continue;
+        }
+        let (a, b) = (m[i] / slopes[i], m[i + 1] / slopes[i]);
+        let s = a * a + b * b;
+        // Keeps the segment monotone (Fritsch & Carlson, 1980).
+        if s > 9.0 {
+            let tau = 3.0 / s.sqrt();
+            m[i] = tau * a * slopes[i];
+            m[i + 1] = tau * b * slopes[i];
+        }
+    }
+    pts.iter().zip(m).map(|(&(x, y), m)| (x, y, m)).collect()
+}
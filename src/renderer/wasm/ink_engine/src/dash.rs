@@ -0,0 +1,128 @@
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{length, point_at, sub};
+use crate::mesh::Mesh;
+use crate::tessellate::{self, Decoration, MeshOptions};
+
+/// Tessellates the stroke as dashes, like SVG `stroke-dasharray` and
+/// `stroke-dashoffset`. `pattern` alternates on and off lengths along the
+/// stroke (an odd count is repeated to make it even) and `offset` shifts it
+/// towards the start. Every dash gets the options' cap and joins, so a
+/// zero-length dash with round caps draws a dot. An empty or all-zero
+/// pattern draws the stroke solid.
+#[wasm_bindgen]
+pub fn build_mesh_dashed(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions, pattern: &[f32], offset: f32) -> Vec<f32> {
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate_dashed(points, widths, crate::rgba(color), options, pattern, offset, &mut mesh);
+    mesh.triangle_soup()
+}
+
+pub(crate) fn tessellate_dashed(points: &[f32], widths: &[f32], color: [f32; 4], options: &MeshOptions, pattern: &[f32], offset: f32, mesh: &mut Mesh) {
+    let mut pattern: Vec<f32> = pattern.iter().map(|&l| if l.is_finite() { l.max(0.0) } else { 0.0 }).collect();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..);
+    }
+    let total: f32 = pattern.iter().sum();
+    if total <= 1e-6 || points.len() < 4 {
+        tessellate::tessellate(points, widths, color, options, mesh);
+        return;
+    }
+
+    // Smoothing and tapers follow the whole stroke, not each dash.
+    let (points, widths) = tessellate::prepare(points, widths, options);
+    let (mut points, mut widths) = (points.into_owned(), widths.into_owned());
+    widths.resize(points.len() / 2, 1.0);
+    if options.closed {
+        points.extend_from_slice(&[points[0], points[1]]);
+        widths.push(widths[0]);
+    }
+    let (start_decoration, end_decoration) = if options.closed {
+        (Decoration::None, Decoration::None)
+    } else {
+        (options.start_decoration, options.end_decoration)
+    };
+    let dash_options = MeshOptions {
+        closed: false,
+        start_decoration: Decoration::None,
+        end_decoration: Decoration::None,
+        smoothing: 0,
+        start_taper: 0.0,
+        end_taper: 0.0,
+        ..*options
+    };
+
+    // Only the dashes at the stroke's own ends carry its decorations.
+    let dashes = dashes(&points, &widths, &pattern, offset.rem_euclid(total));
+    let (first, last) = (point_at(&points, 0), point_at(&points, points.len() / 2 - 1));
+    let near = |a, b| length(sub(a, b)) < 1e-6;
+    let count = dashes.len();
+    for (k, (dash_points, dash_widths)) in dashes.into_iter().enumerate() {
+        let starts = k == 0 && near(point_at(&dash_points, 0), first);
+        let ends = k + 1 == count && near(point_at(&dash_points, dash_points.len() / 2 - 1), last);
+        let options = MeshOptions {
+            start_decoration: if starts { start_decoration } else { Decoration::None },
+            end_decoration: if ends { end_decoration } else { Decoration::None },
+            ..dash_options
+        };
+        tessellate::tessellate(&dash_points, &dash_widths, color, &options, mesh);
+    }
+}
+
+/// Splits the polyline at the pattern's on/off boundaries, starting `phase`
+/// into the pattern. Each dash keeps the stroke's points that fall inside it.
+/// `widths` holds one width per point.
+fn dashes(points: &[f32], widths: &[f32], pattern: &[f32], phase: f32) -> Vec<(Vec<f32>, Vec<f32>)> {
+    let n = points.len() / 2;
+    let mut k = 0;
+    let mut remaining = phase;
+    while remaining > pattern[k] {
+        remaining -= pattern[k];
+        k = (k + 1) % pattern.len();
+    }
+    remaining = pattern[k] - remaining;
+
+    let mut out = Vec::new();
+    let mut current: Option<(Vec<f32>, Vec<f32>)> = (k % 2 == 0).then(Default::default);
+    let push = |dash: &mut (Vec<f32>, Vec<f32>), p: (f32, f32), w: f32| {
+        let m = dash.0.len();
+        if m >= 2 && length(sub(p, (dash.0[m - 2], dash.0[m - 1]))) < 1e-6 {
+            return;
+        }
+        dash.0.extend_from_slice(&[p.0, p.1]);
+        dash.1.push(w);
+    };
+    if let Some(dash) = current.as_mut() {
+        push(dash, point_at(points, 0), widths[0]);
+    }
+    for i in 0..n - 1 {
+        let (a, b) = (point_at(points, i), point_at(points, i + 1));
+        let (wa, wb) = (widths[i], widths[i + 1]);
+        let seg = length(sub(b, a));
+        let mut at = 0.0;
+        while seg - at >= remaining {
+            at += remaining;
+            let t = if seg > 0.0 { at / seg } else { 0.0 };
+            let p = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            let w = wa + (wb - wa) * t;
+            match current.take() {
+                Some(mut dash) => {
+                    push(&mut dash, p, w);
+                    out.push(dash);
+                }
+                None => {
+                    let mut dash = Default::default();
+                    push(&mut dash, p, w);
+                    current = Some(dash);
+                }
+            }
+            k = (k + 1) % pattern.len();
+            remaining = pattern[k];
+        }
+        remaining -= seg - at;
+        if let Some(dash) = current.as_mut() {
+            push(dash, b, wb);
+        }
+    }
+    out.extend(current);
+    out
+}
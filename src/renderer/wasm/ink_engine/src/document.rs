@@ -0,0 +1,931 @@
+use std::collections::HashSet;
+
+use wasm_bindgen::prelude::*;
+
+use crate::brush::{BlendMode, BrushMesh};
+use crate::geom::Point;
+use crate::group::Group;
+use crate::history::Op;
+use crate::mesh::{self, Mesh, MeshBatch, FLOATS_PER_VERTEX};
+use crate::polygon;
+use crate::preset::{Brush, BrushRegistry, BrushStyle};
+use crate::samples::{self, Sample};
+use crate::spatial::SpatialIndex;
+use crate::tile::TileCache;
+use crate::transform::{self, Affine};
+use crate::version::Version;
+
+/// `PointerEvent.pointerType` of the device that drew a stroke.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PointerType {
+    #[default]
+    Unknown,
+    Mouse,
+    Pen,
+    Touch,
+}
+
+/// Where a stroke came from. Ids are never reused within a document, so
+/// erasing, selection and sync can keep referring to a stroke by id alone.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct StrokeMetadata {
+    /// Creation time in the host's clock, typically `Date.now()`.
+    pub created: f64,
+    pub pointer: PointerType,
+    pub brush: u32,
+    author: String,
+}
+
+#[wasm_bindgen]
+impl StrokeMetadata {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StrokeMetadata {
+        StrokeMetadata::default()
+    }
+
+    /// Free-form tag for whoever drew the stroke, e.g. a user id in a shared
+    /// document.
+    pub fn author(&self) -> String {
+        self.author.clone()
+    }
+
+    pub fn set_author(&mut self, author: &str) {
+        self.author = author.to_owned();
+    }
+}
+
+/// One stroke as the document keeps it. `mesh` is built on first request,
+/// in the stroke's own coordinates, and dropped whenever something it
+/// depends on changes; `transform` places it in the document.
+#[derive(Clone)]
+pub(crate) struct Stroke {
+    pub id: u32,
+    pub points: Vec<f32>,
+    pub widths: Vec<f32>,
+    pub timestamps: Vec<f64>,
+    pub brush: u32,
+    pub color: [f32; 4],
+    pub layer: u32,
+    /// Innermost group holding the stroke.
+    pub group: Option<u32>,
+    pub transform: Affine,
+    pub created: f64,
+    pub pointer: PointerType,
+    pub author: String,
+    /// Outline rings, in the stroke's coordinates, of what the vector
+    /// eraser left of it. When set the stroke is drawn as a flat fill in
+    /// `color` instead of through its brush, and has no samples.
+    pub fill: Option<Vec<Vec<Point>>>,
+    /// Meshes by zoom bucket, most recently built last.
+    pub meshes: Vec<(i32, BrushMesh)>,
+    /// Whether the spatial index holds the current bounds.
+    pub indexed: bool,
+}
+
+impl Stroke {
+    pub(crate) fn samples(&self) -> Vec<Sample> {
+        samples::samples_from(&self.points, &self.widths, &self.timestamps)
+    }
+
+    /// A stroke with this one's brush, color, layer, group, transform and
+    /// metadata but other samples, e.g. a piece the eraser left.
+    pub(crate) fn with_samples(&self, id: u32, samples: &[Sample]) -> Stroke {
+        let (points, widths) = samples::split(samples);
+        Stroke {
+            id,
+            points,
+            widths,
+            timestamps: if self.timestamps.is_empty() { Vec::new() } else { samples.iter().map(|s| s.time).collect() },
+            brush: self.brush,
+            color: self.color,
+            layer: self.layer,
+            group: self.group,
+            transform: self.transform,
+            created: self.created,
+            pointer: self.pointer,
+            author: self.author.clone(),
+            fill: None,
+            meshes: Vec::new(),
+            indexed: false,
+        }
+    }
+
+    /// A stroke with this one's attributes drawn as the filled `rings`.
+    pub(crate) fn with_fill(&self, id: u32, rings: Vec<Vec<Point>>) -> Stroke {
+        Stroke {
+            fill: Some(rings),
+            ..self.with_samples(id, &[])
+        }
+    }
+
+    /// A copy without the cached meshes, for the op log.
+    pub(crate) fn record(&self) -> Stroke {
+        Stroke {
+            points: self.points.clone(),
+            widths: self.widths.clone(),
+            timestamps: self.timestamps.clone(),
+            fill: self.fill.clone(),
+            ..self.with_samples(self.id, &[])
+        }
+    }
+
+    pub(crate) fn invalidate(&mut self) {
+        self.meshes.clear();
+        self.indexed = false;
+    }
+
+    /// Tessellates the stroke for a zoom bucket, bypassing the cache.
+    pub(crate) fn build(&self, brushes: &BrushRegistry, bucket: i32) -> BrushMesh {
+        match (&self.fill, brushes.get(self.brush)) {
+            (Some(rings), brush) => {
+                let mut mesh = fill_mesh(rings, self.color);
+                if brush.is_some_and(Brush::premultiplied) {
+                    mesh.premultiply();
+                }
+                mesh
+            }
+            (None, Some(b)) => b.build_widths(&self.points, &self.widths, &self.timestamps, self.color, bucket_scale(bucket)),
+            (None, None) => BrushMesh::new(Vec::new(), Default::default(), FLOATS_PER_VERTEX),
+        }
+    }
+
+    /// The mesh for a zoom bucket, built on a miss. Only the last
+    /// `CACHED_BUCKETS` buckets stay cached.
+    pub(crate) fn mesh(&mut self, brushes: &BrushRegistry, bucket: i32) -> &BrushMesh {
+        let k = match self.meshes.iter().position(|(b, _)| *b == bucket) {
+            Some(k) => k,
+            None => {
+                let mesh = self.build(brushes, bucket);
+                self.cache(bucket, mesh)
+            }
+        };
+        &self.meshes[k].1
+    }
+
+    pub(crate) fn has_mesh(&self, bucket: i32) -> bool {
+        self.meshes.iter().any(|(b, _)| *b == bucket)
+    }
+
+    /// Keeps `mesh` as the one for `bucket`, which has none yet, and
+    /// returns its slot in `meshes`.
+    pub(crate) fn cache(&mut self, bucket: i32, mesh: BrushMesh) -> usize {
+        if self.meshes.len() >= CACHED_BUCKETS {
+            self.meshes.remove(0);
+        }
+        self.meshes.push((bucket, mesh));
+        self.meshes.len() - 1
+    }
+
+    /// Document-space extent of the stroke's geometry, caps, joins and brush
+    /// effects included, tessellating it if needed.
+    fn bounds(&mut self, brushes: &BrushRegistry, bucket: i32) -> Option<[f32; 4]> {
+        let transform = self.transform;
+        let mesh = self.mesh(brushes, bucket);
+        let stride = mesh.floats_per_vertex();
+        if transform == transform::IDENTITY {
+            return mesh::strided_bounds(mesh.soup(), stride);
+        }
+        mesh.soup().chunks_exact(stride).fold(None, |acc, v| {
+            let p = transform::apply(&transform, (v[0], v[1]));
+            mesh::union_bounds(acc, Some([p.0, p.1, p.0, p.1]))
+        })
+    }
+
+    /// The mesh with the transform applied to its positions.
+    pub(crate) fn placed_mesh(&mut self, brushes: &BrushRegistry, bucket: i32) -> BrushMesh {
+        let transform = self.transform;
+        let mesh = self.mesh(brushes, bucket);
+        if transform == transform::IDENTITY {
+            return mesh.clone();
+        }
+        mesh.with_vertices(transform::apply_vertices(&transform, mesh.soup(), mesh.floats_per_vertex()))
+    }
+}
+
+/// Result of an edit: the stroke it touched and the area to repaint.
+#[wasm_bindgen]
+pub struct StrokeChange {
+    pub(crate) id: u32,
+    pub(crate) dirty: Option<[f32; 4]>,
+}
+
+#[wasm_bindgen]
+impl StrokeChange {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// `[min_x, min_y, max_x, max_y]` covering the stroke's geometry before
+    /// and after the edit; empty when neither drew anything.
+    pub fn dirty_rect(&self) -> Vec<f32> {
+        self.dirty.map_or_else(Vec::new, |r| r.to_vec())
+    }
+}
+
+pub(crate) struct Layer {
+    pub id: u32,
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+}
+
+/// Every stroke of a drawing held in WASM memory, so the host sends a
+/// stroke's samples once and afterwards refers to it by id. Meshes are
+/// tessellated on request and cached until the stroke or its brush changes.
+#[wasm_bindgen]
+pub struct InkDocument {
+    pub(crate) brushes: BrushRegistry,
+    /// In drawing order within each layer, bottom first.
+    pub(crate) strokes: Vec<Stroke>,
+    pub(crate) next_id: u32,
+    /// Bottom first.
+    pub(crate) layers: Vec<Layer>,
+    pub(crate) active_layer: u32,
+    pub(crate) next_layer_id: u32,
+    pub(crate) index: SpatialIndex,
+    pub(crate) groups: Vec<Group>,
+    pub(crate) next_group_id: u32,
+    pub(crate) zoom_bucket: i32,
+    /// Every change so far, oldest first.
+    pub(crate) ops: Vec<Op>,
+    /// Ops that came before `ops[0]` but are no longer held, e.g. those
+    /// folded into a restored snapshot.
+    pub(crate) op_base: usize,
+    /// Snapshot of the state `ops[0]` applies to; empty for a new document.
+    pub(crate) history_base: Vec<u8>,
+    /// Ops kept by automatic compaction; 0 keeps every op.
+    pub(crate) history_limit: usize,
+    /// Set on the copy history compaction replays into: edits leave their
+    /// dirty rects out, so replaying tessellates nothing.
+    pub(crate) model_only: bool,
+    pub(crate) versions: Vec<Version>,
+    pub(crate) next_version_id: u32,
+    pub(crate) tiles: TileCache,
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// An empty document with one layer, id 0, and brush 0, a default
+    /// `BrushStyle::Ink` named "ink".
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> InkDocument {
+        let mut brushes = BrushRegistry::new();
+        brushes.register("ink", &Brush::new(BrushStyle::Ink));
+        InkDocument {
+            brushes,
+            strokes: Vec::new(),
+            next_id: 1,
+            layers: vec![Layer {
+                id: 0,
+                name: "Layer 1".to_owned(),
+                visible: true,
+                opacity: 1.0,
+            }],
+            active_layer: 0,
+            next_layer_id: 1,
+            index: SpatialIndex::default(),
+            groups: Vec::new(),
+            next_group_id: 1,
+            zoom_bucket: 0,
+            ops: Vec::new(),
+            op_base: 0,
+            history_base: Vec::new(),
+            history_limit: 0,
+            model_only: false,
+            versions: Vec::new(),
+            next_version_id: 1,
+            tiles: TileCache::default(),
+        }
+    }
+
+    /// Registers or replaces a brush, as `BrushRegistry::register`. Replacing
+    /// one drops the cached meshes of the strokes drawn with it.
+    pub fn register_brush(&mut self, name: &str, brush: &Brush) -> u32 {
+        let id = self.brushes.register(name, brush);
+        for s in self.strokes.iter_mut().filter(|s| s.brush == id) {
+            s.invalidate();
+        }
+        id
+    }
+
+    pub fn brush_id(&self, name: &str) -> Option<u32> {
+        self.brushes.id(name)
+    }
+
+    /// Adds a stroke on top of the active layer. Missing widths default to
+    /// 1; `timestamps` may be empty for brushes that do not need them. The
+    /// creation time starts as the first timestamp, or 0. The change carries
+    /// the new id and the stroke's extent, for which it is tessellated now.
+    pub fn add_stroke(&mut self, points: &[f32], widths: &[f32], timestamps: &[f64], color: &[f32], brush: u32) -> StrokeChange {
+        let (points, widths, timestamps) = samples(points, widths, timestamps);
+        let id = self.next_id;
+        let stroke = Stroke {
+            id,
+            points,
+            widths,
+            created: timestamps.first().copied().unwrap_or(0.0),
+            timestamps,
+            brush,
+            color: crate::rgba(color),
+            layer: self.active_layer,
+            group: None,
+            transform: transform::IDENTITY,
+            pointer: PointerType::Unknown,
+            author: String::new(),
+            fill: None,
+            meshes: Vec::new(),
+            indexed: false,
+        };
+        self.insert_stroke(self.strokes.len(), stroke);
+        let dirty = self.strokes.last_mut().unwrap().bounds(&self.brushes, self.zoom_bucket);
+        StrokeChange { id, dirty }
+    }
+
+    /// Deletes a stroke; `None` when there was no such stroke.
+    pub fn remove_stroke(&mut self, id: u32) -> Option<StrokeChange> {
+        let i = self.index(id)?;
+        let dirty = self.dirty_bounds(i);
+        self.strokes.remove(i);
+        self.index.remove(id);
+        self.prune_groups();
+        self.record(Op::RemoveStroke(id));
+        Some(StrokeChange { id, dirty })
+    }
+
+    /// Swaps in new samples for an existing stroke, e.g. after smoothing or
+    /// a host-side edit, keeping its id, brush, color, layer and metadata.
+    /// A filled stroke goes back to being drawn by its brush. Only this
+    /// stroke's mesh is rebuilt.
+    pub fn replace_stroke_points(&mut self, id: u32, points: &[f32], widths: &[f32], timestamps: &[f64]) -> Option<StrokeChange> {
+        let i = self.index(id)?;
+        let s = &mut self.strokes[i];
+        let old = s.bounds(&self.brushes, self.zoom_bucket);
+        (s.points, s.widths, s.timestamps) = samples(points, widths, timestamps);
+        s.fill = None;
+        s.invalidate();
+        let dirty = mesh::union_bounds(old, s.bounds(&self.brushes, self.zoom_bucket));
+        self.record_stroke(i);
+        Some(StrokeChange { id, dirty })
+    }
+
+    /// Removes every stroke and group; layers and brushes stay.
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.index.clear();
+        self.groups.clear();
+        self.record(Op::Clear);
+    }
+
+    pub fn stroke_count(&self) -> usize {
+        self.strokes.len()
+    }
+
+    /// Ids in drawing order, bottom first, across all layers.
+    pub fn stroke_ids(&self) -> Vec<u32> {
+        self.ordered().map(|i| self.strokes[i].id).collect()
+    }
+
+    /// The stroke's centreline; empty for a stroke the vector eraser turned
+    /// into a fill.
+    pub fn stroke_points(&self, id: u32) -> Vec<f32> {
+        self.stroke(id).map(|s| s.points.clone()).unwrap_or_default()
+    }
+
+    pub fn stroke_widths(&self, id: u32) -> Vec<f32> {
+        self.stroke(id).map(|s| s.widths.clone()).unwrap_or_default()
+    }
+
+    /// Whether `erase_outline` left the stroke as a filled outline.
+    pub fn stroke_is_filled(&self, id: u32) -> bool {
+        self.stroke(id).is_some_and(|s| s.fill.is_some())
+    }
+
+    pub fn stroke_brush(&self, id: u32) -> Option<u32> {
+        self.stroke(id).map(|s| s.brush)
+    }
+
+    pub fn stroke_color(&self, id: u32) -> Vec<f32> {
+        self.stroke(id).map(|s| s.color.to_vec()).unwrap_or_default()
+    }
+
+    pub fn set_stroke_color(&mut self, id: u32, color: &[f32]) {
+        if let Some(i) = self.index(id) {
+            let s = &mut self.strokes[i];
+            s.color = crate::rgba(color);
+            s.invalidate();
+            self.record_stroke(i);
+        }
+    }
+
+    pub fn set_stroke_brush(&mut self, id: u32, brush: u32) {
+        if let Some(i) = self.index(id) {
+            let s = &mut self.strokes[i];
+            s.brush = brush;
+            s.invalidate();
+            self.record_stroke(i);
+        }
+    }
+
+    pub fn stroke_metadata(&self, id: u32) -> Option<StrokeMetadata> {
+        self.stroke(id).map(|s| StrokeMetadata {
+            created: s.created,
+            pointer: s.pointer,
+            brush: s.brush,
+            author: s.author.clone(),
+        })
+    }
+
+    /// Replaces a stroke's metadata, including its brush.
+    pub fn set_stroke_metadata(&mut self, id: u32, metadata: &StrokeMetadata) {
+        if let Some(i) = self.index(id) {
+            let s = &mut self.strokes[i];
+            if s.brush != metadata.brush {
+                s.brush = metadata.brush;
+                s.invalidate();
+            }
+            s.created = metadata.created;
+            s.pointer = metadata.pointer;
+            s.author = metadata.author.clone();
+            self.record_stroke(i);
+        }
+    }
+
+    /// Ids of the strokes tagged with `author`, in drawing order.
+    pub fn strokes_by_author(&self, author: &str) -> Vec<u32> {
+        self.ordered().filter(|&i| self.strokes[i].author == author).map(|i| self.strokes[i].id).collect()
+    }
+
+    /// Ids of the strokes created in `[from, to)`, in drawing order.
+    pub fn strokes_created_between(&self, from: f64, to: f64) -> Vec<u32> {
+        self.ordered()
+            .filter(|&i| (from..to).contains(&self.strokes[i].created))
+            .map(|i| self.strokes[i].id)
+            .collect()
+    }
+
+    pub fn contains_stroke(&self, id: u32) -> bool {
+        self.index(id).is_some()
+    }
+
+    pub fn stroke_layer(&self, id: u32) -> Option<u32> {
+        self.stroke(id).map(|s| s.layer)
+    }
+
+    /// Moves a stroke to the top of another layer. Returns false when either
+    /// does not exist.
+    pub fn set_stroke_layer(&mut self, id: u32, layer: u32) -> bool {
+        if self.layer_index(layer).is_none() {
+            return false;
+        }
+        match self.index(id) {
+            Some(i) => {
+                let mut s = self.strokes.remove(i);
+                s.layer = layer;
+                self.strokes.push(s);
+                self.record(Op::SetStrokeLayer { id, layer });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds an empty layer on top and returns its id.
+    pub fn add_layer(&mut self, name: &str) -> u32 {
+        let id = self.next_layer_id;
+        self.insert_layer(id, name);
+        id
+    }
+
+    /// Deletes a layer with all its strokes. The last layer cannot be
+    /// deleted; if the active one goes, the layer below it (or the new
+    /// bottom) becomes active.
+    pub fn remove_layer(&mut self, layer: u32) -> bool {
+        let Some(i) = self.layer_index(layer) else {
+            return false;
+        };
+        if self.layers.len() == 1 {
+            return false;
+        }
+        self.layers.remove(i);
+        for s in self.strokes.iter().filter(|s| s.layer == layer) {
+            self.index.remove(s.id);
+        }
+        self.strokes.retain(|s| s.layer != layer);
+        self.prune_groups();
+        if self.active_layer == layer {
+            self.active_layer = self.layers[i.saturating_sub(1)].id;
+        }
+        self.record(Op::RemoveLayer(layer));
+        true
+    }
+
+    /// Moves a layer to position `index` counted from the bottom, clamped
+    /// to the layer count.
+    pub fn move_layer(&mut self, layer: u32, index: usize) -> bool {
+        let Some(i) = self.layer_index(layer) else {
+            return false;
+        };
+        let l = self.layers.remove(i);
+        let index = index.min(self.layers.len());
+        self.layers.insert(index, l);
+        self.record(Op::MoveLayer { id: layer, index: index as u32 });
+        true
+    }
+
+    /// Layer ids bottom first.
+    pub fn layer_ids(&self) -> Vec<u32> {
+        self.layers.iter().map(|l| l.id).collect()
+    }
+
+    pub fn layer_name(&self, layer: u32) -> Option<String> {
+        self.layer(layer).map(|l| l.name.clone())
+    }
+
+    pub fn set_layer_name(&mut self, layer: u32, name: &str) {
+        if let Some(l) = self.layer_mut(layer) {
+            l.name = name.to_owned();
+            self.record(Op::SetLayerName { id: layer, name: name.to_owned() });
+        }
+    }
+
+    pub fn layer_visible(&self, layer: u32) -> bool {
+        self.layer(layer).is_some_and(|l| l.visible)
+    }
+
+    pub fn set_layer_visible(&mut self, layer: u32, visible: bool) {
+        if let Some(l) = self.layer_mut(layer) {
+            l.visible = visible;
+            self.record(Op::SetLayerVisible { id: layer, visible });
+        }
+    }
+
+    /// Opacity the renderer composites the layer with, 0–1. Not baked into
+    /// the meshes: overlapping strokes on a half-transparent layer should
+    /// not show through each other.
+    pub fn layer_opacity(&self, layer: u32) -> f32 {
+        self.layer(layer).map_or(0.0, |l| l.opacity)
+    }
+
+    pub fn set_layer_opacity(&mut self, layer: u32, opacity: f32) {
+        if let Some(l) = self.layer_mut(layer) {
+            l.opacity = opacity.clamp(0.0, 1.0);
+            let opacity = l.opacity;
+            self.record(Op::SetLayerOpacity { id: layer, opacity });
+        }
+    }
+
+    /// Layer that `add_stroke` draws on.
+    pub fn active_layer(&self) -> u32 {
+        self.active_layer
+    }
+
+    pub fn set_active_layer(&mut self, layer: u32) -> bool {
+        if self.layer_index(layer).is_none() {
+            return false;
+        }
+        self.active_layer = layer;
+        self.record(Op::SetActiveLayer(layer));
+        true
+    }
+
+    /// Ids of one layer's strokes, bottom first.
+    pub fn layer_stroke_ids(&self, layer: u32) -> Vec<u32> {
+        self.strokes.iter().filter(|s| s.layer == layer).map(|s| s.id).collect()
+    }
+
+    /// The stroke's `[a, b, c, d, e, f]` transform, identity by default.
+    pub fn stroke_transform(&self, id: u32) -> Vec<f32> {
+        self.stroke(id).map(|s| s.transform.to_vec()).unwrap_or_default()
+    }
+
+    /// Replaces a stroke's transform without retessellating it. `matrix` is
+    /// `[a, b, c, d, e, f]` as canvas `setTransform`; anything shorter resets
+    /// it to the identity.
+    pub fn set_stroke_transform(&mut self, id: u32, matrix: &[f32]) -> Option<StrokeChange> {
+        let m = transform::from_slice(matrix);
+        self.edit_transform(id, |_| m)
+    }
+
+    /// Applies `matrix` on top of the stroke's current transform.
+    pub fn transform_stroke(&mut self, id: u32, matrix: &[f32]) -> Option<StrokeChange> {
+        let m = transform::from_slice(matrix);
+        self.edit_transform(id, |t| transform::compose(&m, t))
+    }
+
+    /// Folds the transform into the points, scaling widths by the
+    /// transform's average scale, and resets it to the identity. The stroke
+    /// is retessellated, so brush texture and noise follow the new shape.
+    pub fn bake_stroke_transform(&mut self, id: u32) -> Option<StrokeChange> {
+        let i = self.index(id)?;
+        let s = &mut self.strokes[i];
+        let old = s.bounds(&self.brushes, self.zoom_bucket);
+        let m = std::mem::replace(&mut s.transform, transform::IDENTITY);
+        s.indexed = false;
+        for p in s.points.chunks_exact_mut(2) {
+            (p[0], p[1]) = transform::apply(&m, (p[0], p[1]));
+        }
+        for p in s.fill.iter_mut().flatten().flatten() {
+            *p = transform::apply(&m, *p);
+        }
+        let scale = transform::scale_factor(&m);
+        s.widths.iter_mut().for_each(|w| *w *= scale);
+        s.invalidate();
+        let dirty = mesh::union_bounds(old, s.bounds(&self.brushes, self.zoom_bucket));
+        self.record_stroke(i);
+        Some(StrokeChange { id, dirty })
+    }
+
+    /// `[min_x, min_y, max_x, max_y]` of the stroke's geometry in document
+    /// coordinates, including caps, joins and its transform; empty for an
+    /// unknown id or a stroke that draws nothing.
+    pub fn stroke_bounds(&mut self, id: u32) -> Vec<f32> {
+        self.refresh_index();
+        self.index.bounds(id).map_or_else(Vec::new, |b| b.to_vec())
+    }
+
+    /// Box around all of `ids`, e.g. a selection; unknown ids are skipped.
+    pub fn selection_bounds(&mut self, ids: &[u32]) -> Vec<f32> {
+        self.refresh_index();
+        let bounds = ids.iter().fold(None, |acc, &id| mesh::union_bounds(acc, self.index.bounds(id)));
+        bounds.map_or_else(Vec::new, |b| b.to_vec())
+    }
+
+    /// Box around every stroke, or only those on visible layers, for
+    /// fit-to-content zoom and export cropping. Empty for a blank document.
+    pub fn document_bounds(&mut self, visible_only: bool) -> Vec<f32> {
+        self.refresh_index();
+        let bounds = self
+            .strokes
+            .iter()
+            .filter(|s| !visible_only || self.layer_visible(s.layer))
+            .fold(None, |acc, s| mesh::union_bounds(acc, self.index.bounds(s.id)));
+        bounds.map_or_else(Vec::new, |b| b.to_vec())
+    }
+
+    /// Ids of the strokes on visible layers whose geometry may show in the
+    /// view `x, y, width, height` (document units), in drawing order.
+    /// `zoom` is screen pixels per document unit; the view is grown by one
+    /// screen pixel so antialiased edges just outside it are kept.
+    pub fn visible_strokes(&mut self, x: f32, y: f32, width: f32, height: f32, zoom: f32) -> Vec<u32> {
+        self.refresh_index();
+        let pad = if zoom > 0.0 { 1.0 / zoom } else { 0.0 };
+        let hits: HashSet<u32> = self.index.query(&[x - pad, y - pad, x + width + pad, y + height + pad]).into_iter().collect();
+        self.ordered()
+            .map(|i| &self.strokes[i])
+            .filter(|s| hits.contains(&s.id) && self.layer_visible(s.layer))
+            .map(|s| s.id)
+            .collect()
+    }
+
+    /// As `meshes`, limited to `visible_strokes`, so off-screen ink in a
+    /// large document is neither tessellated nor uploaded.
+    /// The meshes are tessellated for `zoom`'s bucket, as after `set_zoom`.
+    pub fn visible_meshes(&mut self, x: f32, y: f32, width: f32, height: f32, zoom: f32) -> MeshBatch {
+        let ids = self.visible_strokes(x, y, width, height, zoom);
+        let order: Vec<usize> = ids.iter().filter_map(|&id| self.index(id)).collect();
+        self.batch(&order, zoom_bucket(zoom))
+    }
+
+    /// Zoom (screen pixels per document unit, device pixel ratio included)
+    /// that meshes are tessellated for. Zooms are grouped into power-of-two
+    /// buckets, so round joins and caps stay smooth without rebuilding on
+    /// every zoom step; each stroke caches its meshes for the last few
+    /// buckets.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom_bucket = zoom_bucket(zoom);
+    }
+
+    /// The stroke's mesh in its own coordinates for the current zoom. It is
+    /// only tessellated if no cached mesh exists, i.e. after an edit, a
+    /// brush change or a move to a new zoom bucket. Draw it with
+    /// `stroke_transform` as the model matrix.
+    pub fn get_mesh(&mut self, id: u32) -> Option<BrushMesh> {
+        let i = self.index(id)?;
+        Some(self.strokes[i].mesh(&self.brushes, self.zoom_bucket).clone())
+    }
+
+    /// Whether `get_mesh` would return without tessellating.
+    pub fn is_mesh_cached(&self, id: u32) -> bool {
+        self.stroke(id).is_some_and(|s| s.has_mesh(self.zoom_bucket))
+    }
+
+    /// Drops every cached mesh, e.g. to free memory for an off-screen page.
+    pub fn clear_mesh_cache(&mut self) {
+        for s in &mut self.strokes {
+            s.meshes.clear();
+        }
+    }
+
+    /// The stroke's mesh in document coordinates. Moving a stroke only
+    /// re-applies the transform to the cached vertices.
+    pub fn placed_mesh(&mut self, id: u32) -> Option<BrushMesh> {
+        let i = self.index(id)?;
+        Some(self.strokes[i].placed_mesh(&self.brushes, self.zoom_bucket))
+    }
+
+    /// Every visible stroke's `[x, y, r, g, b, a]` soup in drawing order,
+    /// layer by layer, in document coordinates. Stamp strokes need a
+    /// textured pass and are left out; fetch them with `get_mesh`.
+    pub fn meshes(&mut self) -> MeshBatch {
+        let order: Vec<usize> = self.ordered().filter(|&i| self.layer_visible(self.strokes[i].layer)).collect();
+        self.batch(&order, self.zoom_bucket)
+    }
+
+    /// As `meshes` for one layer, visible or not, so the renderer can draw
+    /// each layer into its own target and composite it with its opacity.
+    pub fn layer_meshes(&mut self, layer: u32) -> MeshBatch {
+        let order: Vec<usize> = (0..self.strokes.len()).filter(|&i| self.strokes[i].layer == layer).collect();
+        self.batch(&order, self.zoom_bucket)
+    }
+}
+
+/// Zoom buckets each stroke keeps a mesh for.
+const CACHED_BUCKETS: usize = 2;
+
+/// Power-of-two bucket a zoom falls in: 0 covers about 0.7–1.4×.
+pub(crate) fn zoom_bucket(zoom: f32) -> i32 {
+    if zoom > 0.0 {
+        (zoom.log2().round() as i32).clamp(-8, 8)
+    } else {
+        0
+    }
+}
+
+fn bucket_scale(bucket: i32) -> f32 {
+    2f32.powi(bucket)
+}
+
+/// Flat fill of outline rings in `color`, ignoring the brush.
+fn fill_mesh(rings: &[Vec<Point>], color: [f32; 4]) -> BrushMesh {
+    let (verts, tris) = polygon::triangulate(rings);
+    let mut mesh = Mesh::default();
+    for &v in &verts {
+        mesh.push_vertex(v, color);
+    }
+    for t in &tris {
+        mesh.push_tri(t[0], t[1], t[2]);
+    }
+    BrushMesh::new(mesh.triangle_soup(), BlendMode::Normal, FLOATS_PER_VERTEX)
+}
+
+/// Whole points only, one width per point (1 when missing) and at most one
+/// timestamp per point.
+fn samples(points: &[f32], widths: &[f32], timestamps: &[f64]) -> (Vec<f32>, Vec<f32>, Vec<f64>) {
+    let n = points.len() / 2;
+    (
+        points[..n * 2].to_vec(),
+        (0..n).map(|i| widths.get(i).copied().unwrap_or(1.0)).collect(),
+        timestamps.iter().take(n).copied().collect(),
+    )
+}
+
+impl Default for InkDocument {
+    fn default() -> Self {
+        InkDocument::new()
+    }
+}
+
+impl InkDocument {
+    pub(crate) fn index(&self, id: u32) -> Option<usize> {
+        self.strokes.iter().position(|s| s.id == id)
+    }
+
+    pub(crate) fn stroke(&self, id: u32) -> Option<&Stroke> {
+        self.strokes.iter().find(|s| s.id == id)
+    }
+
+    /// Brings the spatial index up to date with every stroke edited since
+    /// the last query, tessellating those whose mesh was dropped.
+    pub(crate) fn refresh_index(&mut self) {
+        let stale: Vec<usize> = (0..self.strokes.len()).filter(|&i| !self.strokes[i].indexed).collect();
+        self.build_missing(&stale, self.zoom_bucket);
+        for s in self.strokes.iter_mut().filter(|s| !s.indexed) {
+            self.index.insert(s.id, s.bounds(&self.brushes, self.zoom_bucket));
+            s.indexed = true;
+        }
+    }
+
+    /// Replaces stroke `i` by `pieces` of it, which take its place in the
+    /// drawing order. Returns the new ids and the area to repaint, which the
+    /// old geometry covers since the pieces lie within it.
+    pub(crate) fn split_at(&mut self, i: usize, pieces: &[Vec<Sample>]) -> (Vec<u32>, Option<[f32; 4]>) {
+        self.replace_at(i, pieces.len(), |old, id, k| old.with_samples(id, &pieces[k]))
+    }
+
+    /// As `split_at` with the pieces drawn as filled `regions`, each an
+    /// outer ring and its holes.
+    pub(crate) fn fill_at(&mut self, i: usize, regions: &[Vec<Vec<Point>>]) -> (Vec<u32>, Option<[f32; 4]>) {
+        self.replace_at(i, regions.len(), |old, id, k| old.with_fill(id, regions[k].clone()))
+    }
+
+    fn replace_at(&mut self, i: usize, count: usize, piece: impl Fn(&Stroke, u32, usize) -> Stroke) -> (Vec<u32>, Option<[f32; 4]>) {
+        let pieces: Vec<Stroke> = (0..count).map(|k| piece(&self.strokes[i], self.next_id + k as u32, k)).collect();
+        let ids = pieces.iter().map(|p| p.id).collect();
+        (ids, self.replace_with(i, pieces))
+    }
+
+    /// Puts `pieces`, ids already assigned, in place of stroke `i`.
+    /// Returns the old stroke's extent.
+    pub(crate) fn replace_with(&mut self, i: usize, pieces: Vec<Stroke>) -> Option<[f32; 4]> {
+        let dirty = self.dirty_bounds(i);
+        let old = self.strokes.remove(i);
+        self.index.remove(old.id);
+        let record = pieces.iter().map(Stroke::record).collect();
+        for (k, piece) in pieces.into_iter().enumerate() {
+            self.next_id = self.next_id.max(piece.id + 1);
+            self.strokes.insert(i + k, piece);
+        }
+        self.prune_groups();
+        self.record(Op::ReplaceStroke { id: old.id, pieces: record });
+        dirty
+    }
+
+    /// Puts `stroke` at `index` in the stroke list, clamped to its length.
+    pub(crate) fn insert_stroke(&mut self, index: usize, stroke: Stroke) {
+        let index = index.min(self.strokes.len());
+        self.next_id = self.next_id.max(stroke.id + 1);
+        let record = stroke.record();
+        self.strokes.insert(index, stroke);
+        self.record(Op::InsertStroke { index: index as u32, stroke: record });
+    }
+
+    pub(crate) fn record_stroke(&mut self, i: usize) {
+        let record = self.strokes[i].record();
+        self.record(Op::UpdateStroke(record));
+    }
+
+    pub(crate) fn insert_layer(&mut self, id: u32, name: &str) {
+        if self.layer_index(id).is_some() {
+            return;
+        }
+        self.next_layer_id = self.next_layer_id.max(id + 1);
+        self.layers.push(Layer {
+            id,
+            name: name.to_owned(),
+            visible: true,
+            opacity: 1.0,
+        });
+        self.record(Op::AddLayer { id, name: name.to_owned() });
+    }
+
+    pub(crate) fn edit_transform(&mut self, id: u32, f: impl FnOnce(&Affine) -> Affine) -> Option<StrokeChange> {
+        let i = self.index(id)?;
+        let old = self.dirty_bounds(i);
+        let s = &mut self.strokes[i];
+        s.transform = f(&s.transform);
+        s.indexed = false;
+        let transform = s.transform;
+        let dirty = mesh::union_bounds(old, self.dirty_bounds(i));
+        self.record(Op::SetTransform { id, transform });
+        Some(StrokeChange { id, dirty })
+    }
+
+    /// Extent of stroke `i` for a change's dirty rect, `None` without
+    /// tessellating in a `model_only` document.
+    fn dirty_bounds(&mut self, i: usize) -> Option<[f32; 4]> {
+        if self.model_only {
+            return None;
+        }
+        self.strokes[i].bounds(&self.brushes, self.zoom_bucket)
+    }
+
+    fn layer_index(&self, layer: u32) -> Option<usize> {
+        self.layers.iter().position(|l| l.id == layer)
+    }
+
+    fn layer(&self, layer: u32) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.id == layer)
+    }
+
+    fn layer_mut(&mut self, layer: u32) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|l| l.id == layer)
+    }
+
+    /// Stroke indices in drawing order: by layer, then by position.
+    pub(crate) fn ordered(&self) -> impl Iterator<Item = usize> + '_ {
+        self.layers
+            .iter()
+            .flat_map(move |l| (0..self.strokes.len()).filter(move |&i| self.strokes[i].layer == l.id))
+    }
+
+    pub(crate) fn batch(&mut self, order: &[usize], bucket: i32) -> MeshBatch {
+        self.build_missing(order, bucket);
+        let mut batch = MeshBatch::default();
+        for &i in order {
+            let s = &mut self.strokes[i];
+            let (id, transform) = (s.id, s.transform);
+            let mesh = s.mesh(&self.brushes, bucket);
+            if mesh.floats_per_vertex() != FLOATS_PER_VERTEX {
+                continue;
+            }
+            if transform == transform::IDENTITY {
+                batch.push(id, mesh.soup());
+            } else {
+                batch.push(id, &transform::apply_vertices(&transform, mesh.soup(), FLOATS_PER_VERTEX));
+            }
+        }
+        batch
+    }
+}
@@ -0,0 +1,119 @@
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::geom::{length, sub};
+use crate::samples::Sample;
+use crate::smooth::moving_average;
+use crate::transform;
+
+/// Samples on either side of a joint that `join_strokes` smooths.
+const SEAM_REACH: usize = 3;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Cuts a stroke in two at `t`, a fraction of its arc length, with the
+    /// point, width and timestamp interpolated at the cut. The halves take
+    /// the stroke's place and return as `[first, second]`; empty when the
+    /// id is unknown or the cut would leave an empty half.
+    pub fn split_stroke(&mut self, id: u32, t: f32) -> Vec<u32> {
+        let Some(i) = self.index(id) else {
+            return Vec::new();
+        };
+        let samples = self.strokes[i].samples();
+        match split_samples(&samples, t) {
+            Some((a, b)) => self.split_at(i, &[a, b]).0,
+            None => Vec::new(),
+        }
+    }
+
+    /// Heals two strokes whose ends lie within `tolerance` document units,
+    /// e.g. halves of a stroke broken by `pointercancel`. Whichever pair of
+    /// ends is closest is joined, reversing a stroke if needed. The joined
+    /// stroke takes the first stroke's place and attributes; the second is
+    /// removed. `smooth_seam` evens out the few samples around the joint.
+    /// Returns the new id, or `None` when the ends are too far apart.
+    pub fn join_strokes(&mut self, id_a: u32, id_b: u32, tolerance: f32, smooth_seam: bool) -> Option<u32> {
+        let (ia, ib) = (self.index(id_a)?, self.index(id_b)?);
+        if ia == ib {
+            return None;
+        }
+        let (sa, sb) = (&self.strokes[ia], &self.strokes[ib]);
+        // Bring b into a's coordinates.
+        let to_a = transform::compose(&transform::invert(&sa.transform)?, &sb.transform);
+        let width_scale = transform::scale_factor(&to_a);
+        let mut a = sa.samples();
+        let mut b: Vec<Sample> = sb
+            .samples()
+            .iter()
+            .map(|s| {
+                let (x, y) = transform::apply(&to_a, s.pos());
+                Sample {
+                    x,
+                    y,
+                    width: s.width * width_scale,
+                    ..*s
+                }
+            })
+            .collect();
+        let (a_start, a_end) = (a.first()?.pos(), a.last()?.pos());
+        let (b_start, b_end) = (b.first()?.pos(), b.last()?.pos());
+        // Joining a's end to b's start after flipping either as needed.
+        let gap = |p: (f32, f32), q: (f32, f32)| length(sub(p, q));
+        let options = [
+            (gap(a_end, b_start), false, false),
+            (gap(a_end, b_end), false, true),
+            (gap(a_start, b_start), true, false),
+            (gap(a_start, b_end), true, true),
+        ];
+        let (distance, flip_a, flip_b) = options.into_iter().min_by(|x, y| x.0.total_cmp(&y.0))?;
+        // Compare in document units.
+        if distance * transform::scale_factor(&sa.transform) > tolerance {
+            return None;
+        }
+        if flip_a {
+            a.reverse();
+        }
+        if flip_b {
+            b.reverse();
+        }
+        let seam = a.len();
+        if a.last().map(|s| s.pos()) == b.first().map(|s| s.pos()) {
+            b.remove(0);
+        }
+        a.extend(b);
+        if smooth_seam {
+            let smoothed = moving_average(&a, 2, &[]);
+            let (lo, hi) = (seam.saturating_sub(SEAM_REACH), (seam + SEAM_REACH).min(a.len()));
+            a[lo..hi].copy_from_slice(&smoothed[lo..hi]);
+        }
+        let id = self.split_at(ia, &[a]).0[0];
+        self.remove_stroke(id_b);
+        Some(id)
+    }
+}
+
+/// Halves of `samples` at arc-length fraction `t`, sharing the cut sample.
+pub(crate) fn split_samples(samples: &[Sample], t: f32) -> Option<(Vec<Sample>, Vec<Sample>)> {
+    let seg_len = |k: usize| length(sub(samples[k + 1].pos(), samples[k].pos()));
+    let total: f32 = (0..samples.len().saturating_sub(1)).map(seg_len).sum();
+    if total.is_nan() || total <= 0.0 || t.is_nan() || t <= 0.0 || t >= 1.0 {
+        return None;
+    }
+    let target = t * total;
+    let mut walked = 0.0;
+    for k in 0..samples.len() - 1 {
+        let len = seg_len(k);
+        if walked + len >= target && len > 0.0 {
+            let cut = samples[k].lerp(&samples[k + 1], (target - walked) / len);
+            let mut first = samples[..=k].to_vec();
+            let mut second = vec![cut];
+            if cut.pos() != samples[k].pos() {
+                first.push(cut);
+            }
+            second.extend(samples[k + 1..].iter().filter(|s| s.pos() != cut.pos()));
+            return (first.len() > 1 && second.len() > 1).then_some((first, second));
+        }
+        walked += len;
+    }
+    None
+}
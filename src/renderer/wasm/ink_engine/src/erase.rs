@@ -0,0 +1,471 @@
+use wasm_bindgen::prelude::*;
+
+use std::f32::consts::{PI, TAU};
+
+use crate::brush::{BlendMode, BrushMesh};
+use crate::document::{InkDocument, Stroke};
+use crate::geom::{dot, length, point_at, segment_distance, segments_intersect, sub, Point};
+use crate::mesh::{self, FLOATS_PER_VERTEX};
+use crate::polygon::{self, point_in_ring};
+use crate::samples::Sample;
+use crate::spatial::overlaps;
+use crate::transform::{self, Affine};
+
+/// Bisection steps placing a cut between two samples on either side of the
+/// eraser's edge, each halving the error.
+const CUT_ITERATIONS: usize = 10;
+/// Furthest the polygons standing in for the eraser's round ends stray
+/// outside its circle, in document units.
+const OUTLINE_TOLERANCE: f32 = 0.25;
+/// Per-channel slack for a color filter: half a step of 8-bit color.
+const COLOR_TOLERANCE: f32 = 0.5 / 255.0;
+
+/// Which strokes an eraser may touch, so a "highlighter eraser" or "erase
+/// my ink only" tool is one call. Every criterion set must match; a new
+/// filter lets everything through.
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default)]
+pub struct EraseFilter {
+    layer: Option<u32>,
+    color: Option<[f32; 4]>,
+    brush: Option<u32>,
+    author: Option<String>,
+}
+
+#[wasm_bindgen]
+impl EraseFilter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> EraseFilter {
+        EraseFilter::default()
+    }
+
+    pub fn set_layer(&mut self, layer: u32) {
+        self.layer = Some(layer);
+    }
+
+    /// `[r, g, b, a]`, matched to within half a step of 8-bit color.
+    pub fn set_color(&mut self, color: &[f32]) {
+        self.color = Some(crate::rgba(color));
+    }
+
+    pub fn set_brush(&mut self, brush: u32) {
+        self.brush = Some(brush);
+    }
+
+    /// As `StrokeMetadata::author`.
+    pub fn set_author(&mut self, author: &str) {
+        self.author = Some(author.to_owned());
+    }
+}
+
+impl EraseFilter {
+    fn matches(&self, s: &Stroke) -> bool {
+        self.layer.is_none_or(|l| s.layer == l)
+            && self.color.is_none_or(|c| (0..4).all(|k| (s.color[k] - c[k]).abs() <= COLOR_TOLERANCE))
+            && self.brush.is_none_or(|b| s.brush == b)
+            && self.author.as_ref().is_none_or(|a| s.author == *a)
+    }
+}
+
+/// The area an eraser swept: a polyline in document units thickened by
+/// `radius`, and which strokes it may touch. A single point is a dab.
+pub(crate) struct Eraser {
+    path: Vec<Point>,
+    radius: f32,
+    filter: EraseFilter,
+}
+
+impl Eraser {
+    pub fn new(points: &[f32], radius: f32, filter: &EraseFilter) -> Option<Eraser> {
+        let path: Vec<Point> = (0..points.len() / 2).map(|i| point_at(points, i)).collect();
+        if path.is_empty() || radius.is_nan() || radius < 0.0 {
+            return None;
+        }
+        Some(Eraser {
+            path,
+            radius,
+            filter: filter.clone(),
+        })
+    }
+
+    /// Box that ink must overlap to be touched.
+    pub fn bounds(&self) -> [f32; 4] {
+        let r = self.radius;
+        self.path
+            .iter()
+            .fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |b, p| [b[0].min(p.0 - r), b[1].min(p.1 - r), b[2].max(p.0 + r), b[3].max(p.1 + r)])
+    }
+
+    fn distance(&self, p: Point) -> f32 {
+        if self.path.len() == 1 {
+            return length(sub(p, self.path[0]));
+        }
+        self.path.windows(2).map(|s| segment_distance(p, s[0], s[1])).fold(f32::MAX, f32::min)
+    }
+
+    /// Whether ink of `width` centred on `p` is touched.
+    pub fn touches(&self, p: Point, width: f32) -> bool {
+        self.distance(p) < self.radius + width * 0.5
+    }
+
+    /// Whether the ribbon from `a` to `b`, with the width interpolated
+    /// between the two, is touched anywhere.
+    fn touches_segment(&self, a: Point, wa: f32, b: Point, wb: f32) -> bool {
+        if self.touches(a, wa) || self.touches(b, wb) {
+            return true;
+        }
+        let ab = sub(b, a);
+        let len2 = dot(ab, ab);
+        // The closest approach is at an eraser vertex or a crossing.
+        let near_vertex = |p: Point| {
+            let t = if len2 > 0.0 { (dot(sub(p, a), ab) / len2).clamp(0.0, 1.0) } else { 0.0 };
+            let w = wa + (wb - wa) * t;
+            length(sub(p, (a.0 + ab.0 * t, a.1 + ab.1 * t))) < self.radius + w * 0.5
+        };
+        self.path.iter().any(|&p| near_vertex(p)) || self.path.windows(2).any(|e| segments_intersect(a, b, e[0], e[1]))
+    }
+
+    /// Whether any part of a filled outline, rings in document units, is
+    /// touched.
+    pub fn touches_fill(&self, rings: &[Vec<Point>]) -> bool {
+        let inside = |p: Point| rings.iter().filter(|r| point_in_ring(p, r)).count() % 2 == 1;
+        self.path.iter().any(|&p| inside(p))
+            || rings.iter().any(|r| (0..r.len()).any(|k| self.touches_segment(r[k], 0.0, r[(k + 1) % r.len()], 0.0)))
+    }
+
+    /// Convex pieces covering the swept area: a polygon just outside the
+    /// circle around each point of the path and a rectangle along each
+    /// segment.
+    pub fn pieces(&self) -> Vec<Vec<Point>> {
+        let r = self.radius;
+        let sides = (PI / (r / (r + OUTLINE_TOLERANCE)).acos()).ceil().clamp(8.0, 64.0) as usize;
+        let outer = r / (PI / sides as f32).cos();
+        let mut pieces: Vec<Vec<Point>> = self
+            .path
+            .iter()
+            .map(|c| {
+                (0..sides)
+                    .map(|k| {
+                        let a = k as f32 * TAU / sides as f32;
+                        (c.0 + outer * a.cos(), c.1 + outer * a.sin())
+                    })
+                    .collect()
+            })
+            .collect();
+        for s in self.path.windows(2) {
+            let d = sub(s[1], s[0]);
+            let len = length(d);
+            if len <= 0.0 {
+                continue;
+            }
+            let n = (-d.1 / len * r, d.0 / len * r);
+            pieces.push(vec![
+                (s[0].0 + n.0, s[0].1 + n.1),
+                (s[1].0 + n.0, s[1].1 + n.1),
+                (s[1].0 - n.0, s[1].1 - n.1),
+                (s[0].0 - n.0, s[0].1 - n.1),
+            ]);
+        }
+        pieces
+    }
+
+    /// Whether any part of the stroke's ribbon, in document units, is touched.
+    pub fn touches_stroke(&self, samples: &[(Point, f32)]) -> bool {
+        match samples {
+            [] => false,
+            [(p, w)] => self.touches(*p, *w),
+            _ => samples.windows(2).any(|s| self.touches_segment(s[0].0, s[0].1, s[1].0, s[1].1)),
+        }
+    }
+}
+
+/// What an erase did: strokes taken out, pieces put back and the area to
+/// repaint.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct EraseResult {
+    pub(crate) removed: Vec<u32>,
+    pub(crate) added: Vec<u32>,
+    pub(crate) dirty: Option<[f32; 4]>,
+}
+
+#[wasm_bindgen]
+impl EraseResult {
+    /// Ids of strokes that no longer exist, split ones included.
+    pub fn removed(&self) -> Vec<u32> {
+        self.removed.clone()
+    }
+
+    /// Ids of the surviving pieces of split strokes, in drawing order.
+    pub fn added(&self) -> Vec<u32> {
+        self.added.clone()
+    }
+
+    /// `[min_x, min_y, max_x, max_y]` covering all removed geometry; empty
+    /// when nothing was erased.
+    pub fn dirty_rect(&self) -> Vec<f32> {
+        self.dirty.map_or_else(Vec::new, |r| r.to_vec())
+    }
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Pixel eraser: cuts away every part of the strokes on visible layers
+    /// that the eraser path `points` (document units) with `radius`
+    /// touches, counting each stroke's width. Strokes keep their id when
+    /// untouched; touched ones are replaced by their surviving pieces, cut
+    /// exactly at the eraser's edge with interpolated widths and timestamps.
+    /// Strokes `erase_outline` already turned into fills lose area as there.
+    pub fn erase_path(&mut self, points: &[f32], radius: f32) -> EraseResult {
+        self.erase_path_filtered(points, radius, &EraseFilter::default())
+    }
+
+    /// `erase_path` sparing the strokes `filter` rejects.
+    pub fn erase_path_filtered(&mut self, points: &[f32], radius: f32, filter: &EraseFilter) -> EraseResult {
+        let Some(eraser) = Eraser::new(points, radius, filter) else {
+            return EraseResult::default();
+        };
+        let mut cutters = None;
+        let mut result = EraseResult::default();
+        for id in self.erase_candidates(&eraser) {
+            let Some(i) = self.index(id) else {
+                continue;
+            };
+            let cut = if self.strokes[i].fill.is_none() {
+                self.cut_centreline(i, &eraser)
+            } else if touched(&eraser, &self.strokes[i]) {
+                self.cut_outline(i, cutters.get_or_insert_with(|| eraser.pieces()))
+            } else {
+                None
+            };
+            if let Some((added, dirty)) = cut {
+                result.record(id, added, dirty);
+            }
+        }
+        result
+    }
+
+    /// Vector eraser: subtracts the area the eraser path `points` with
+    /// `radius` sweeps from the outlines of the strokes on visible layers
+    /// it touches. What is left of each stroke comes back as flat filled
+    /// regions in its color, one stroke per connected region, so erasing
+    /// part way through a wide stroke leaves its exact remaining shape.
+    /// Outlines are taken at the current zoom or finer. Stamp and
+    /// destination-out strokes have no plain outline and are cut along
+    /// their centreline as by `erase_path`.
+    pub fn erase_outline(&mut self, points: &[f32], radius: f32) -> EraseResult {
+        self.erase_outline_filtered(points, radius, &EraseFilter::default())
+    }
+
+    /// `erase_outline` sparing the strokes `filter` rejects.
+    pub fn erase_outline_filtered(&mut self, points: &[f32], radius: f32, filter: &EraseFilter) -> EraseResult {
+        let Some(eraser) = Eraser::new(points, radius, filter) else {
+            return EraseResult::default();
+        };
+        let cutters = eraser.pieces();
+        let mut result = EraseResult::default();
+        for id in self.hits(&eraser) {
+            let Some(i) = self.index(id) else {
+                continue;
+            };
+            let cut = self.cut_outline(i, &cutters).or_else(|| self.cut_centreline(i, &eraser));
+            if let Some((added, dirty)) = cut {
+                result.record(id, added, dirty);
+            }
+        }
+        result
+    }
+}
+
+impl EraseResult {
+    fn record(&mut self, id: u32, added: Vec<u32>, dirty: Option<[f32; 4]>) {
+        self.removed.push(id);
+        self.added.extend(added);
+        self.dirty = mesh::union_bounds(self.dirty, dirty);
+    }
+}
+
+type Cut = (Vec<u32>, Option<[f32; 4]>);
+
+impl InkDocument {
+    /// Replaces stroke `i` by the runs of its samples outside the eraser;
+    /// `None` when the eraser misses every sample.
+    fn cut_centreline(&mut self, i: usize, eraser: &Eraser) -> Option<Cut> {
+        let s = &self.strokes[i];
+        let (m, scale) = (s.transform, transform::scale_factor(&s.transform));
+        let inside = |p: &Sample| eraser.touches(transform::apply(&m, p.pos()), p.width * scale);
+        let step = (eraser.radius * 0.25).max(0.01) / scale.max(1e-6);
+        let pieces = surviving(&s.samples(), inside, step)?;
+        Some(self.split_at(i, &pieces))
+    }
+
+    /// Replaces stroke `i` by the filled regions of its outline outside
+    /// `cutters`; `None` when it has no plain triangle outline or none of
+    /// the cutters reach it.
+    fn cut_outline(&mut self, i: usize, cutters: &[Vec<Point>]) -> Option<Cut> {
+        let s = &mut self.strokes[i];
+        let mesh = s.placed_mesh(&self.brushes, self.zoom_bucket.max(0));
+        let pieces = outline_pieces(&mesh)?;
+        let inverse = transform::invert(&s.transform)?;
+        let extent = mesh::strided_bounds(mesh.soup(), FLOATS_PER_VERTEX)?;
+        let near: Vec<Vec<Point>> = cutters.iter().filter(|c| overlaps(&ring_bounds(c), &extent)).cloned().collect();
+        if near.is_empty() {
+            return None;
+        }
+        let rings = placed_rings(&inverse, &polygon::difference(&pieces, &near));
+        Some(self.fill_at(i, &polygon::regions(&rings)))
+    }
+}
+
+/// The triangles of a flat-colored mesh, for tracing its outline. `None`
+/// for textured or specially blended meshes, whose look an outline misses.
+pub(crate) fn outline_pieces(mesh: &BrushMesh) -> Option<Vec<Vec<Point>>> {
+    if mesh.blend() != BlendMode::Normal {
+        return None;
+    }
+    triangle_pieces(mesh)
+}
+
+/// The triangles of an untextured mesh, whatever its blend. `None` for
+/// stamp meshes.
+pub(crate) fn triangle_pieces(mesh: &BrushMesh) -> Option<Vec<Vec<Point>>> {
+    if mesh.floats_per_vertex() != FLOATS_PER_VERTEX {
+        return None;
+    }
+    // The zero-alpha anti-aliasing fringe is not part of the outline.
+    let pieces = mesh
+        .soup()
+        .chunks_exact(FLOATS_PER_VERTEX * 3)
+        .filter(|t| t.chunks_exact(FLOATS_PER_VERTEX).all(|v| v[5] > 0.0))
+        .map(|t| t.chunks_exact(FLOATS_PER_VERTEX).map(|v| (v[0], v[1])).collect())
+        .collect();
+    Some(pieces)
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Stroke-eraser hit test: ids of the strokes on visible layers whose
+    /// ribbon, not just bounding box, the eraser path `points` with
+    /// `radius` touches, in drawing order. A single point tests a dab.
+    pub fn hit_strokes(&mut self, points: &[f32], radius: f32) -> Vec<u32> {
+        self.hit_strokes_filtered(points, radius, &EraseFilter::default())
+    }
+
+    /// `hit_strokes` leaving out the strokes `filter` rejects.
+    pub fn hit_strokes_filtered(&mut self, points: &[f32], radius: f32, filter: &EraseFilter) -> Vec<u32> {
+        let Some(eraser) = Eraser::new(points, radius, filter) else {
+            return Vec::new();
+        };
+        self.hits(&eraser)
+    }
+
+    /// Deletes every stroke `hit_strokes` reports.
+    pub fn erase_strokes(&mut self, points: &[f32], radius: f32) -> EraseResult {
+        self.erase_strokes_filtered(points, radius, &EraseFilter::default())
+    }
+
+    /// Deletes every stroke `hit_strokes_filtered` reports.
+    pub fn erase_strokes_filtered(&mut self, points: &[f32], radius: f32, filter: &EraseFilter) -> EraseResult {
+        let mut result = EraseResult::default();
+        for id in self.hit_strokes_filtered(points, radius, filter) {
+            if let Some(change) = self.remove_stroke(id) {
+                result.removed.push(id);
+                result.dirty = mesh::union_bounds(result.dirty, change.dirty);
+            }
+        }
+        result
+    }
+}
+
+impl InkDocument {
+    pub(crate) fn hits(&mut self, eraser: &Eraser) -> Vec<u32> {
+        let candidates = self.erase_candidates(eraser);
+        candidates.into_iter().filter(|&id| touched(eraser, self.stroke(id).unwrap())).collect()
+    }
+
+    /// Strokes on visible layers that pass the eraser's filter and whose
+    /// bounds meet the eraser's, in drawing order.
+    pub(crate) fn erase_candidates(&mut self, eraser: &Eraser) -> Vec<u32> {
+        self.refresh_index();
+        let hits = self.index.query(&eraser.bounds());
+        self.ordered()
+            .map(|i| &self.strokes[i])
+            .filter(|s| hits.binary_search(&s.id).is_ok() && self.layer_visible(s.layer) && eraser.filter.matches(s))
+            .map(|s| s.id)
+            .collect()
+    }
+}
+
+/// Splits `samples` into the runs outside the erased region, looking for
+/// crossings every `step` units along each segment. `None` when nothing is
+/// erased; an empty list when everything is.
+pub(crate) fn surviving(samples: &[Sample], inside: impl Fn(&Sample) -> bool, step: f32) -> Option<Vec<Vec<Sample>>> {
+    let first = samples.first()?;
+    let mut was_inside = inside(first);
+    let mut erased = was_inside;
+    let mut pieces = Vec::new();
+    let mut piece = if was_inside { Vec::new() } else { vec![*first] };
+    for pair in samples.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let steps = (length(sub(b.pos(), a.pos())) / step).ceil().max(1.0) as usize;
+        let mut prev_t = 0.0;
+        for k in 1..=steps {
+            let t = k as f32 / steps as f32;
+            let now_inside = inside(&a.lerp(b, t));
+            if now_inside != was_inside {
+                // Bisect for the edge, keeping `lo` on the `was_inside` side.
+                let (mut lo, mut hi) = (prev_t, t);
+                for _ in 0..CUT_ITERATIONS {
+                    let mid = (lo + hi) * 0.5;
+                    if inside(&a.lerp(b, mid)) == was_inside {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                piece.push(a.lerp(b, if was_inside { hi } else { lo }));
+                if !was_inside {
+                    pieces.push(std::mem::take(&mut piece));
+                    erased = true;
+                }
+                was_inside = now_inside;
+            }
+            prev_t = t;
+        }
+        if !was_inside {
+            piece.push(*b);
+        }
+    }
+    if !erased {
+        return None;
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+    for p in &mut pieces {
+        p.dedup_by(|b, a| a.pos() == b.pos());
+    }
+    // A piece of one point is a sliver between two cuts; drop it.
+    pieces.retain(|p| p.len() > 1);
+    Some(pieces)
+}
+
+/// Whether the eraser touches the stroke's ribbon, or its outline once it
+/// is a fill.
+fn touched(eraser: &Eraser, s: &Stroke) -> bool {
+    if let Some(rings) = &s.fill {
+        return eraser.touches_fill(&placed_rings(&s.transform, rings));
+    }
+    let scale = transform::scale_factor(&s.transform);
+    let placed: Vec<(Point, f32)> = s.samples().iter().map(|p| (transform::apply(&s.transform, p.pos()), p.width * scale)).collect();
+    eraser.touches_stroke(&placed)
+}
+
+pub(crate) fn placed_rings(m: &Affine, rings: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    rings.iter().map(|r| r.iter().map(|&p| transform::apply(m, p)).collect()).collect()
+}
+
+fn ring_bounds(ring: &[Point]) -> [f32; 4] {
+    ring.iter()
+        .fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |b, p| [b[0].min(p.0), b[1].min(p.1), b[2].max(p.0), b[3].max(p.1)])
+}
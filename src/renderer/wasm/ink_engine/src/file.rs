@@ -0,0 +1,400 @@
+use wasm_bindgen::prelude::*;
+
+use crate::codec::{self, Reader, Writer};
+use crate::document::{InkDocument, Layer, Stroke};
+use crate::group::Group;
+use crate::history::read_pointer;
+use crate::snapshot::Snapshot;
+use crate::transform::{self, Affine};
+
+const MAGIC: &[u8; 4] = b"INKF";
+/// After the magic and version come tagged, length-prefixed sections.
+/// Readers skip sections with tags they do not know and ignore bytes past
+/// the fields they know at the end of a section or stroke record, so a
+/// minor version may add sections or append fields and stay readable by
+/// older code. Anything else bumps the major version, and readers refuse
+/// majors newer than their own.
+const MAJOR: u8 = 1;
+const MINOR: u8 = 0;
+
+const COUNTERS: u8 = 1;
+const BRUSHES: u8 = 2;
+const LAYERS: u8 = 3;
+const GROUPS: u8 = 4;
+const STROKES: u8 = 5;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// The document in the file format, for saving to disk. Much smaller
+    /// than `snapshot`, which keeps exact floats: points and widths are
+    /// rounded to 1/64 unit, colors to 8 bits per channel. Brushes go by
+    /// name, so the file opens wherever the same brushes are registered.
+    pub fn save(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.buf.extend_from_slice(MAGIC);
+        w.u8(MAJOR);
+        w.u8(MINOR);
+
+        let mut s = Writer::default();
+        [self.next_id, self.next_layer_id, self.next_group_id, self.active_layer].iter().for_each(|&v| s.varint(v as u64));
+        section(&mut w, COUNTERS, s);
+
+        // Brush ids here index this table, which lists only brushes in use.
+        let mut table: Vec<u32> = Vec::new();
+        for st in &self.strokes {
+            if !table.contains(&st.brush) {
+                table.push(st.brush);
+            }
+        }
+        let mut s = Writer::default();
+        s.varint(table.len() as u64);
+        table.iter().for_each(|&b| s.str(&self.brushes.name(b).unwrap_or_default()));
+        section(&mut w, BRUSHES, s);
+
+        let mut s = Writer::default();
+        s.varint(self.layers.len() as u64);
+        for l in &self.layers {
+            s.varint(l.id as u64);
+            s.str(&l.name);
+            s.u8(l.visible as u8);
+            s.f32(l.opacity);
+        }
+        section(&mut w, LAYERS, s);
+
+        let mut s = Writer::default();
+        s.varint(self.groups.len() as u64);
+        for g in &self.groups {
+            s.varint(g.id as u64);
+            s.varint(g.parent.map_or(0, |p| p as u64 + 1));
+            write_transform(&mut s, &g.transform);
+        }
+        section(&mut w, GROUPS, s);
+
+        let mut s = Writer::default();
+        s.varint(self.strokes.len() as u64);
+        for st in &self.strokes {
+            let mut r = Writer::default();
+            r.varint(st.id as u64);
+            r.varint(table.iter().position(|&b| b == st.brush).unwrap_or(0) as u64);
+            st.color.iter().for_each(|&c| r.u8((c.clamp(0.0, 1.0) * 255.0).round() as u8));
+            r.varint(st.layer as u64);
+            r.varint(st.group.map_or(0, |g| g as u64 + 1));
+            write_transform(&mut r, &st.transform);
+            r.f64(st.created);
+            r.u8(st.pointer as u8);
+            r.str(&st.author);
+            codec::write_samples(&mut r, &st.points, &st.widths, &st.timestamps);
+            match &st.fill {
+                Some(rings) => {
+                    r.u8(1);
+                    codec::write_rings(&mut r, rings);
+                }
+                None => r.u8(0),
+            }
+            s.bytes(&r.buf);
+        }
+        section(&mut w, STROKES, s);
+        w.buf
+    }
+
+    /// Opens a `save` file in place of the current document. Brushes are
+    /// matched by name, falling back to brush 0. The op log and saved
+    /// versions start over. Returns false, leaving the document untouched,
+    /// when the bytes are not a file this version can read.
+    pub fn load(&mut self, bytes: &[u8]) -> bool {
+        let Some(file) = read_file(bytes, &|name| self.brushes.id(name).unwrap_or(0)) else {
+            return false;
+        };
+        self.replace_model(file);
+        self.ops.clear();
+        self.op_base = 0;
+        self.history_base = self.snapshot();
+        self.versions.clear();
+        self.next_version_id = 1;
+        true
+    }
+}
+
+fn section(w: &mut Writer, tag: u8, body: Writer) {
+    w.u8(tag);
+    w.bytes(&body.buf);
+}
+
+/// A flag for the common identity, else the six coefficients.
+fn write_transform(w: &mut Writer, m: &Affine) {
+    if *m == transform::IDENTITY {
+        w.u8(0);
+    } else {
+        w.u8(1);
+        m.iter().for_each(|&v| w.f32(v));
+    }
+}
+
+fn read_transform(r: &mut Reader) -> Option<Affine> {
+    let mut m = transform::IDENTITY;
+    if r.u8()? != 0 {
+        for v in &mut m {
+            *v = r.f32()?;
+        }
+    }
+    Some(m)
+}
+
+fn read_id(r: &mut Reader) -> Option<u32> {
+    r.varint().and_then(|v| u32::try_from(v).ok())
+}
+
+/// An id stored plus one, with 0 for none.
+fn read_optional_id(r: &mut Reader) -> Option<Option<u32>> {
+    match r.varint()? {
+        0 => Some(None),
+        v => Some(Some(u32::try_from(v - 1).ok()?)),
+    }
+}
+
+fn read_file(bytes: &[u8], brush_id: &dyn Fn(&str) -> u32) -> Option<Snapshot> {
+    let mut r = Reader::new(bytes);
+    if r.raw(MAGIC.len())? != MAGIC || r.u8()? > MAJOR {
+        return None;
+    }
+    r.u8()?;
+    let (mut counters, mut layers) = (None, None);
+    let (mut brushes, mut groups, mut strokes) = (Vec::new(), Vec::new(), Vec::new());
+    // Strokes name brushes by table index, so they wait for the table.
+    let mut stroke_section = None;
+    while r.pos() < bytes.len() {
+        let tag = r.u8()?;
+        let mut s = Reader::new(r.bytes()?);
+        match tag {
+            COUNTERS => counters = Some([read_id(&mut s)?, read_id(&mut s)?, read_id(&mut s)?, read_id(&mut s)?]),
+            BRUSHES => {
+                let n = s.count()?;
+                brushes = (0..n).map(|_| s.str().map(|name| brush_id(&name))).collect::<Option<_>>()?;
+            }
+            LAYERS => {
+                let n = s.count()?;
+                let mut out = Vec::with_capacity(n);
+                for _ in 0..n {
+                    out.push(Layer {
+                        id: read_id(&mut s)?,
+                        name: s.str()?,
+                        visible: s.u8()? != 0,
+                        opacity: s.f32()?,
+                    });
+                }
+                layers = Some(out);
+            }
+            GROUPS => {
+                let n = s.count()?;
+                for _ in 0..n {
+                    groups.push(Group {
+                        id: read_id(&mut s)?,
+                        parent: read_optional_id(&mut s)?,
+                        transform: read_transform(&mut s)?,
+                    });
+                }
+            }
+            STROKES => stroke_section = Some(s),
+            _ => {}
+        }
+    }
+    if let Some(mut s) = stroke_section {
+        let n = s.count()?;
+        for _ in 0..n {
+            strokes.push(read_stroke(&mut Reader::new(s.bytes()?), &brushes)?);
+        }
+    }
+    let (counters, layers) = (counters?, layers?);
+    // A document always has a layer to draw on.
+    if !layers.iter().any(|l| l.id == counters[3]) {
+        return None;
+    }
+    Snapshot {
+        watermark: 0,
+        next_id: counters[0],
+        next_layer_id: counters[1],
+        next_group_id: counters[2],
+        active_layer: counters[3],
+        layers,
+        groups,
+        strokes,
+    }
+    .checked()
+}
+
+fn read_stroke(r: &mut Reader, brushes: &[u32]) -> Option<Stroke> {
+    let id = read_id(r)?;
+    let brush = brushes.get(r.varint()? as usize).copied().unwrap_or(0);
+    let color = [r.u8()?, r.u8()?, r.u8()?, r.u8()?].map(|c| c as f32 / 255.0);
+    let layer = read_id(r)?;
+    let group = read_optional_id(r)?;
+    let transform = read_transform(r)?;
+    let created = r.f64()?;
+    let pointer = read_pointer(r)?;
+    let author = r.str()?;
+    let (points, widths, timestamps) = codec::read_samples(r)?;
+    let fill = match r.u8()? {
+        0 => None,
+        _ => Some(codec::read_rings(r)?),
+    };
+    Some(Stroke {
+        id,
+        points,
+        widths,
+        timestamps,
+        brush,
+        color,
+        layer,
+        group,
+        transform,
+        created,
+        pointer,
+        author,
+        fill,
+        meshes: Vec::new(),
+        indexed: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::{Brush, BrushStyle};
+
+    /// A file of `sections`, each a tag and its body.
+    fn file(minor: u8, sections: Vec<(u8, Writer)>) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.buf.extend_from_slice(MAGIC);
+        w.u8(MAJOR);
+        w.u8(minor);
+        for (tag, body) in sections {
+            section(&mut w, tag, body);
+        }
+        w.buf
+    }
+
+    fn counters(next_id: u64) -> (u8, Writer) {
+        let mut s = Writer::default();
+        [next_id, 1, 1, 0].iter().for_each(|&v| s.varint(v));
+        (COUNTERS, s)
+    }
+
+    fn one_layer() -> (u8, Writer) {
+        let mut s = Writer::default();
+        s.varint(1);
+        s.varint(0);
+        s.str("Layer");
+        s.u8(1);
+        s.f32(1.0);
+        (LAYERS, s)
+    }
+
+    /// A strokes section of one record, `extra` appended to it.
+    fn one_stroke(id: u64, group: u64, extra: &[u8]) -> (u8, Writer) {
+        let mut r = Writer::default();
+        r.varint(id);
+        r.varint(0);
+        [0, 0, 0, 255].iter().for_each(|&c| r.u8(c));
+        r.varint(0);
+        r.varint(group);
+        write_transform(&mut r, &transform::IDENTITY);
+        r.f64(0.0);
+        r.u8(0);
+        r.str("");
+        codec::write_samples(&mut r, &[0.0, 0.0, 8.0, 4.0], &[2.0, 2.0], &[]);
+        r.u8(0);
+        r.buf.extend_from_slice(extra);
+        let mut s = Writer::default();
+        s.varint(1);
+        s.bytes(&r.buf);
+        (STROKES, s)
+    }
+
+    #[test]
+    fn rounds_samples_and_colors() {
+        let mut doc = InkDocument::new();
+        let id = doc.add_stroke(&[0.3, 10.0, 100.0 / 3.0, -7.77], &[1.1, 2.0], &[0.0, 8.5], &[0.5, 0.25, 1.0, 0.1], 0).id;
+        let mut loaded = InkDocument::new();
+        assert!(loaded.load(&doc.save()));
+        for (a, b) in loaded.stroke_points(id).iter().zip(doc.stroke_points(id)) {
+            assert!((a - b).abs() <= 1.0 / 128.0, "{a} {b}");
+            assert_eq!(a * 64.0, (a * 64.0).round());
+        }
+        for (a, b) in loaded.stroke_color(id).iter().zip(doc.stroke_color(id)) {
+            assert!((a - b).abs() <= 0.5 / 255.0 + 1e-6);
+        }
+        // What was rounded once saves the same again.
+        assert_eq!(loaded.save(), {
+            let mut again = InkDocument::new();
+            again.load(&loaded.save());
+            again.save()
+        });
+    }
+
+    #[test]
+    fn brushes_go_by_name() {
+        let mut doc = InkDocument::new();
+        let marker = doc.register_brush("marker", &Brush::new(BrushStyle::Highlighter));
+        let pencil = doc.register_brush("pencil", &Brush::new(BrushStyle::Pencil));
+        let a = doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &[0.0, 0.0, 0.0, 1.0], marker).id;
+        let b = doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &[0.0, 0.0, 0.0, 1.0], pencil).id;
+        let mut loaded = InkDocument::new();
+        loaded.register_brush("spare", &Brush::new(BrushStyle::Ink));
+        let marker_here = loaded.register_brush("marker", &Brush::new(BrushStyle::Highlighter));
+        assert!(loaded.load(&doc.save()));
+        assert_eq!(loaded.stroke_brush(a), Some(marker_here));
+        assert_eq!(loaded.stroke_brush(b), Some(0));
+    }
+
+    #[test]
+    fn reads_what_a_later_minor_adds() {
+        let mut unknown = Writer::default();
+        unknown.str("from the future");
+        let bytes = file(MINOR + 1, vec![(200, unknown), counters(2), one_layer(), one_stroke(1, 0, &[1, 2, 3])]);
+        let mut doc = InkDocument::new();
+        assert!(doc.load(&bytes));
+        assert_eq!(doc.stroke_points(1), [0.0, 0.0, 8.0, 4.0]);
+
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = MAJOR + 1;
+        assert!(!InkDocument::new().load(&newer));
+    }
+
+    #[test]
+    fn needs_counters_and_layers() {
+        assert!(InkDocument::new().load(&file(MINOR, vec![counters(1), one_layer()])));
+        assert!(!InkDocument::new().load(&file(MINOR, vec![one_layer()])));
+        assert!(!InkDocument::new().load(&file(MINOR, vec![counters(1)])));
+        // A section cut short is not taken for a shorter file.
+        let bytes = file(MINOR, vec![counters(2), one_layer(), one_stroke(1, 0, &[])]);
+        assert!(!InkDocument::new().load(&bytes[..bytes.len() - 1]));
+    }
+
+    #[test]
+    fn rejects_broken_models() {
+        assert!(!InkDocument::new().load(&file(MINOR, vec![counters(2), one_layer(), one_stroke(1, 4, &[])])));
+        assert!(!InkDocument::new().load(&file(MINOR, vec![counters(2), one_layer(), one_stroke(u32::MAX as u64, 0, &[])])));
+        let mut groups = Writer::default();
+        groups.varint(2);
+        for (id, parent) in [(1, 3), (2, 2)] {
+            groups.varint(id);
+            groups.varint(parent);
+            write_transform(&mut groups, &transform::IDENTITY);
+        }
+        assert!(!InkDocument::new().load(&file(MINOR, vec![counters(1), one_layer(), (GROUPS, groups)])));
+    }
+
+    #[test]
+    fn starts_history_over() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &[0.0, 0.0, 0.0, 1.0], 0);
+        doc.save_version("draft");
+        let mut loaded = InkDocument::new();
+        loaded.add_layer("Old");
+        loaded.save_version("old");
+        assert!(loaded.load(&file(MINOR, vec![counters(1), one_layer(), one_stroke(7, 0, &[])])));
+        assert_eq!(loaded.op_count(), 0);
+        assert!(loaded.version_ids().is_empty());
+        assert_eq!(loaded.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &[0.0, 0.0, 0.0, 1.0], 0).id, 8);
+    }
+}
@@ -0,0 +1,223 @@
+use std::f64::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::samples::{Sample, StrokeSamples};
+
+/// Fallback interval when timestamps repeat or go backwards (one 120 Hz frame).
+const DEFAULT_DT: f64 = 1.0 / 120.0;
+
+fn smoothing_factor(cutoff: f64, dt: f64) -> f64 {
+    let tau = 1.0 / (2.0 * PI * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
+
+/// The 1€ filter (Casiez et al., CHI 2012) over x, y and pressure. Position
+/// shares one speed estimate so strokes smooth the same in every direction.
+/// Keep one instance per pointer id and `reset` it on pointerdown.
+#[wasm_bindgen]
+pub struct OneEuroFilter {
+    min_cutoff: f64,
+    beta: f64,
+    d_cutoff: f64,
+    last: Option<(f64, [f64; 3])>,
+    /// Filtered derivatives: position speed and pressure rate.
+    deriv: [f64; 2],
+}
+
+#[wasm_bindgen]
+impl OneEuroFilter {
+    /// `min_cutoff` in Hz sets jitter removal at rest, `beta` how quickly the
+    /// cutoff rises with speed, `d_cutoff` in Hz smooths the speed estimate.
+    #[wasm_bindgen(constructor)]
+    pub fn new(min_cutoff: f64, beta: f64, d_cutoff: f64) -> OneEuroFilter {
+        OneEuroFilter {
+            min_cutoff: min_cutoff.max(1e-3),
+            beta: beta.max(0.0),
+            d_cutoff: d_cutoff.max(1e-3),
+            last: None,
+            deriv: [0.0; 2],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.deriv = [0.0; 2];
+    }
+
+    /// Filters one sample; `timestamp` in milliseconds. Returns `[x, y, pressure]`.
+    pub fn filter(&mut self, x: f32, y: f32, pressure: f32, timestamp: f64) -> Vec<f32> {
+        let [fx, fy, fp] = self.step([x as f64, y as f64, pressure as f64], timestamp);
+        vec![fx as f32, fy as f32, fp as f32]
+    }
+
+    /// Filters a batch such as one frame of coalesced events, continuing from
+    /// the current state. Pressures come back in the widths array.
+    pub fn filter_points(&mut self, points: &[f32], pressures: &[f32], timestamps: &[f64]) -> StrokeSamples {
+        let out: Vec<Sample> = (0..points.len() / 2)
+            .map(|i| {
+                let time = timestamps.get(i).copied().unwrap_or(0.0);
+                let raw = [points[i * 2] as f64, points[i * 2 + 1] as f64, pressures.get(i).copied().unwrap_or(1.0) as f64];
+                let [x, y, p] = self.step(raw, time);
+                Sample {
+                    x: x as f32,
+                    y: y as f32,
+                    width: p as f32,
+                    time,
+                }
+            })
+            .collect();
+        StrokeSamples::from_samples(&out, !timestamps.is_empty())
+    }
+}
+
+impl OneEuroFilter {
+    pub(crate) fn step(&mut self, raw: [f64; 3], timestamp: f64) -> [f64; 3] {
+        let Some((last_t, prev)) = self.last else {
+            self.last = Some((timestamp, raw));
+            return raw;
+        };
+        let mut dt = (timestamp - last_t) / 1000.0;
+        if dt <= 0.0 || !dt.is_finite() {
+            dt = DEFAULT_DT;
+        }
+
+        let speed = ((raw[0] - prev[0]).hypot(raw[1] - prev[1])) / dt;
+        let rate = (raw[2] - prev[2]) / dt;
+        let ad = smoothing_factor(self.d_cutoff, dt);
+        self.deriv[0] += ad * (speed - self.deriv[0]);
+        self.deriv[1] += ad * (rate - self.deriv[1]);
+
+        let a_pos = smoothing_factor(self.min_cutoff + self.beta * self.deriv[0].abs(), dt);
+        let a_p = smoothing_factor(self.min_cutoff + self.beta * self.deriv[1].abs(), dt);
+        let out = [
+            prev[0] + a_pos * (raw[0] - prev[0]),
+            prev[1] + a_pos * (raw[1] - prev[1]),
+            prev[2] + a_p * (raw[2] - prev[2]),
+        ];
+        self.last = Some((timestamp, out));
+        out
+    }
+}
+
+/// Position and velocity estimate for one axis with its 2x2 covariance.
+#[derive(Clone, Copy)]
+struct Axis {
+    pos: f64,
+    vel: f64,
+    p: [[f64; 2]; 2],
+}
+
+impl Axis {
+    fn new(pos: f64, r: f64) -> Axis {
+        Axis {
+            pos,
+            vel: 0.0,
+            p: [[r, 0.0], [0.0, 1e6]],
+        }
+    }
+
+    /// Constant-velocity predict with white-noise acceleration `q`, then
+    /// update with the measured position `z` of variance `r`.
+    fn step(&mut self, z: f64, dt: f64, q: f64, r: f64) -> f64 {
+        self.pos += self.vel * dt;
+        let [[p00, p01], [p10, p11]] = self.p;
+        let (dt2, dt3, dt4) = (dt * dt, dt * dt * dt, dt * dt * dt * dt);
+        let p00 = p00 + dt * (p10 + p01) + dt2 * p11 + q * dt4 / 4.0;
+        let p01 = p01 + dt * p11 + q * dt3 / 2.0;
+        let p10 = p10 + dt * p11 + q * dt3 / 2.0;
+        let p11 = p11 + q * dt2;
+
+        let s = p00 + r;
+        let (k0, k1) = (p00 / s, p10 / s);
+        let y = z - self.pos;
+        self.pos += k0 * y;
+        self.vel += k1 * y;
+        self.p = [[(1.0 - k0) * p00, (1.0 - k0) * p01], [p10 - k1 * p00, p11 - k1 * p01]];
+        self.pos
+    }
+}
+
+/// Kalman stabilizer for shaky input: a constant-velocity model per axis that
+/// trades lag for steadiness. Create one per stroke, or `reset` between strokes.
+#[wasm_bindgen]
+pub struct KalmanStabilizer {
+    /// Acceleration noise variance, (px/s²)².
+    q: f64,
+    /// Measurement noise variance, px².
+    r: f64,
+    state: Option<(f64, [Axis; 2])>,
+}
+
+#[wasm_bindgen]
+impl KalmanStabilizer {
+    /// `process_noise` is the expected hand acceleration in px/s² and
+    /// `measurement_noise` the expected tremor/jitter in px, both as standard deviations.
+    #[wasm_bindgen(constructor)]
+    pub fn new(process_noise: f64, measurement_noise: f64) -> KalmanStabilizer {
+        KalmanStabilizer {
+            q: process_noise.max(1e-3).powi(2),
+            r: measurement_noise.max(1e-3).powi(2),
+            state: None,
+        }
+    }
+
+    pub fn set_process_noise(&mut self, process_noise: f64) {
+        self.q = process_noise.max(1e-3).powi(2);
+    }
+
+    pub fn set_measurement_noise(&mut self, measurement_noise: f64) {
+        self.r = measurement_noise.max(1e-3).powi(2);
+    }
+
+    /// Maps a 0..1 "stabilization strength" slider onto the measurement
+    /// noise, from 0.5 px (barely any smoothing) to 32 px (heavy).
+    pub fn set_strength(&mut self, strength: f64) {
+        self.set_measurement_noise(0.5 * 2f64.powf(strength.clamp(0.0, 1.0) * 6.0));
+    }
+
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+
+    /// Filters one position; `timestamp` in milliseconds. Returns `[x, y]`.
+    pub fn filter(&mut self, x: f32, y: f32, timestamp: f64) -> Vec<f32> {
+        let (fx, fy) = self.step(x as f64, y as f64, timestamp);
+        vec![fx as f32, fy as f32]
+    }
+
+    /// Filters a batch of samples, continuing from the current state. Widths
+    /// pass through unchanged.
+    pub fn filter_points(&mut self, points: &[f32], widths: &[f32], timestamps: &[f64]) -> StrokeSamples {
+        let out: Vec<Sample> = (0..points.len() / 2)
+            .map(|i| {
+                let time = timestamps.get(i).copied().unwrap_or(0.0);
+                let (x, y) = self.step(points[i * 2] as f64, points[i * 2 + 1] as f64, time);
+                Sample {
+                    x: x as f32,
+                    y: y as f32,
+                    width: widths.get(i).copied().unwrap_or(1.0),
+                    time,
+                }
+            })
+            .collect();
+        StrokeSamples::from_samples(&out, !timestamps.is_empty())
+    }
+}
+
+impl KalmanStabilizer {
+    fn step(&mut self, x: f64, y: f64, timestamp: f64) -> (f64, f64) {
+        let Some((last_t, mut axes)) = self.state else {
+            self.state = Some((timestamp, [Axis::new(x, self.r), Axis::new(y, self.r)]));
+            return (x, y);
+        };
+        let mut dt = (timestamp - last_t) / 1000.0;
+        if dt <= 0.0 || !dt.is_finite() {
+            dt = DEFAULT_DT;
+        }
+        let fx = axes[0].step(x, dt, self.q, self.r);
+        let fy = axes[1].step(y, dt, self.q, self.r);
+        self.state = Some((timestamp, axes));
+        (fx, fy)
+    }
+}
@@ -0,0 +1,45 @@
+pub(crate) type Point = (f32, f32);
+
+pub(crate) fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+pub(crate) fn dot(a: Point, b: Point) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+pub(crate) fn cross(a: Point, b: Point) -> f32 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+pub(crate) fn length(a: Point) -> f32 {
+    (a.0 * a.0 + a.1 * a.1).sqrt()
+}
+
+pub(crate) fn offset(p: Point, dir: Point, dist: f32) -> Point {
+    (p.0 + dir.0 * dist, p.1 + dir.1 * dist)
+}
+
+pub(crate) fn point_at(points: &[f32], i: usize) -> Point {
+    (points[i * 2], points[i * 2 + 1])
+}
+
+/// Distance from `p` to the segment `a`–`b`, which may be a single point.
+pub(crate) fn segment_distance(p: Point, a: Point, b: Point) -> f32 {
+    let ab = sub(b, a);
+    let len2 = dot(ab, ab);
+    let t = if len2 > 0.0 { (dot(sub(p, a), ab) / len2).clamp(0.0, 1.0) } else { 0.0 };
+    length(sub(p, offset(a, ab, t)))
+}
+
+/// Whether segments `a`–`b` and `c`–`d` cross or touch.
+pub(crate) fn segments_intersect(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let side = |p: Point, q: Point, r: Point| cross(sub(q, p), sub(r, p));
+    let (d1, d2) = (side(c, d, a), side(c, d, b));
+    let (d3, d4) = (side(a, b, c), side(a, b, d));
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0)) && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0)) {
+        return true;
+    }
+    // Collinear or touching cases come down to an endpoint lying on the other segment.
+    segment_distance(a, c, d) == 0.0 || segment_distance(b, c, d) == 0.0 || segment_distance(c, a, b) == 0.0 || segment_distance(d, a, b) == 0.0
+}
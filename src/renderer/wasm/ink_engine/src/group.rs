@@ -0,0 +1,187 @@
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::history::Op;
+use crate::mesh;
+use crate::transform::{self, Affine};
+
+/// A set of strokes and other groups moved, selected and transformed as a
+/// unit. Membership lives on the children: a stroke's `group` and a
+/// group's `parent` name the innermost group holding them.
+pub(crate) struct Group {
+    pub id: u32,
+    pub parent: Option<u32>,
+    /// Everything `transform_group` applied so far, for handles that show
+    /// the group's rotation; member strokes already carry it.
+    pub transform: Affine,
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Groups the given strokes and groups and returns the new group's id,
+    /// or `None` when none of them exist. Members leave any group they were
+    /// in; when they all shared one, the new group is nested inside it.
+    pub fn group(&mut self, strokes: &[u32], groups: &[u32]) -> Option<u32> {
+        let id = self.next_group_id;
+        self.insert_group(id, strokes, groups).then_some(id)
+    }
+
+    /// Dissolves a group, handing its members to its parent group.
+    pub fn ungroup(&mut self, group: u32) -> bool {
+        let Some(i) = self.group_index(group) else {
+            return false;
+        };
+        let parent = self.groups.remove(i).parent;
+        for s in self.strokes.iter_mut().filter(|s| s.group == Some(group)) {
+            s.group = parent;
+        }
+        for g in self.groups.iter_mut().filter(|g| g.parent == Some(group)) {
+            g.parent = parent;
+        }
+        self.record(Op::Ungroup(group));
+        true
+    }
+
+    pub fn group_ids(&self) -> Vec<u32> {
+        self.groups.iter().map(|g| g.id).collect()
+    }
+
+    pub fn group_parent(&self, group: u32) -> Option<u32> {
+        self.group_index(group).and_then(|i| self.groups[i].parent)
+    }
+
+    /// Innermost group holding the stroke.
+    pub fn stroke_group(&self, id: u32) -> Option<u32> {
+        self.stroke(id).and_then(|s| s.group)
+    }
+
+    /// Outermost group holding the stroke, which is what a click on the
+    /// stroke selects in a vector editor.
+    pub fn top_group(&self, id: u32) -> Option<u32> {
+        let mut group = self.stroke_group(id)?;
+        while let Some(parent) = self.group_parent(group) {
+            group = parent;
+        }
+        Some(group)
+    }
+
+    /// Groups directly inside `group`.
+    pub fn group_children(&self, group: u32) -> Vec<u32> {
+        self.groups.iter().filter(|g| g.parent == Some(group)).map(|g| g.id).collect()
+    }
+
+    /// Every stroke in the group or its nested groups, in drawing order.
+    pub fn group_strokes(&self, group: u32) -> Vec<u32> {
+        self.ordered()
+            .map(|i| &self.strokes[i])
+            .filter(|s| s.group.is_some_and(|g| self.is_within(g, group)))
+            .map(|s| s.id)
+            .collect()
+    }
+
+    /// Box around every stroke in the group, as `selection_bounds`.
+    pub fn group_bounds(&mut self, group: u32) -> Vec<f32> {
+        let ids = self.group_strokes(group);
+        self.selection_bounds(&ids)
+    }
+
+    /// Accumulated `transform_group` matrices, `[a, b, c, d, e, f]`.
+    pub fn group_transform(&self, group: u32) -> Vec<f32> {
+        self.group_index(group).map(|i| self.groups[i].transform.to_vec()).unwrap_or_default()
+    }
+
+    /// Applies `matrix` on top of every member stroke's transform and the
+    /// transforms of nested groups, without retessellating. Returns the
+    /// rectangle to repaint, empty for an unknown or empty group.
+    pub fn transform_group(&mut self, group: u32, matrix: &[f32]) -> Vec<f32> {
+        let m = transform::from_slice(matrix);
+        let mut dirty = None;
+        for id in self.group_strokes(group) {
+            let change = self.edit_transform(id, |t| transform::compose(&m, t));
+            dirty = mesh::union_bounds(dirty, change.and_then(|c| c.dirty));
+        }
+        for i in 0..self.groups.len() {
+            let g = &self.groups[i];
+            if self.is_within(g.id, group) {
+                let (id, t) = (g.id, transform::compose(&m, &g.transform));
+                self.set_group_transform(id, t);
+            }
+        }
+        dirty.map_or_else(Vec::new, |r| r.to_vec())
+    }
+}
+
+impl InkDocument {
+    /// Creates group `id` around the strokes and groups that exist; false
+    /// when none do.
+    pub(crate) fn insert_group(&mut self, id: u32, strokes: &[u32], groups: &[u32]) -> bool {
+        if self.group_index(id).is_some() {
+            return false;
+        }
+        let strokes: Vec<u32> = strokes.iter().copied().filter(|&id| self.index(id).is_some()).collect();
+        let groups: Vec<u32> = groups.iter().copied().filter(|&g| self.group_index(g).is_some()).collect();
+        let mut parents = strokes
+            .iter()
+            .map(|&id| self.stroke(id).unwrap().group)
+            .chain(groups.iter().map(|&g| self.groups[self.group_index(g).unwrap()].parent));
+        let Some(first) = parents.next() else {
+            return false;
+        };
+        let parent = if parents.all(|p| p == first) { first } else { None };
+
+        self.next_group_id = self.next_group_id.max(id + 1);
+        self.groups.push(Group {
+            id,
+            parent,
+            transform: transform::IDENTITY,
+        });
+        for s in self.strokes.iter_mut().filter(|s| strokes.contains(&s.id)) {
+            s.group = Some(id);
+        }
+        for g in self.groups.iter_mut().filter(|g| groups.contains(&g.id)) {
+            g.parent = Some(id);
+        }
+        self.prune_groups();
+        self.record(Op::Group { id, strokes, groups });
+        true
+    }
+
+    pub(crate) fn set_group_transform(&mut self, group: u32, transform: Affine) {
+        if let Some(i) = self.group_index(group) {
+            self.groups[i].transform = transform;
+            self.record(Op::SetGroupTransform { id: group, transform });
+        }
+    }
+
+    fn group_index(&self, group: u32) -> Option<usize> {
+        self.groups.iter().position(|g| g.id == group)
+    }
+
+    /// Whether `group` is `ancestor` or nested somewhere inside it.
+    fn is_within(&self, group: u32, ancestor: u32) -> bool {
+        let mut at = Some(group);
+        while let Some(g) = at {
+            if g == ancestor {
+                return true;
+            }
+            at = self.group_parent(g);
+        }
+        false
+    }
+
+    /// Drops groups left without strokes or child groups, e.g. after
+    /// erasing, innermost first.
+    pub(crate) fn prune_groups(&mut self) {
+        loop {
+            let empty = self.groups.iter().position(|g| {
+                !self.strokes.iter().any(|s| s.group == Some(g.id)) && !self.groups.iter().any(|c| c.parent == Some(g.id))
+            });
+            match empty {
+                Some(i) => {
+                    self.groups.remove(i);
+                }
+                None => break,
+            }
+        }
+    }
+}
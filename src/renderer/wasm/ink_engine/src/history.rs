@@ -0,0 +1,795 @@
+use wasm_bindgen::prelude::*;
+
+use crate::codec::{checksum, Reader, Writer};
+use crate::document::{InkDocument, PointerType, Stroke};
+use crate::geom::Point;
+use crate::snapshot::{read_snapshot, write_snapshot};
+use crate::transform::{self, Affine};
+
+const MAGIC: &[u8; 4] = b"INKO";
+const VERSION: u8 = 2;
+
+/// One change to a document as its op log records it. Ops say what the
+/// document became rather than which call got it there, so replaying them
+/// does not depend on brushes, zoom or the eraser's arithmetic. Strokes are
+/// carried without their cached meshes.
+#[derive(Clone)]
+pub(crate) enum Op {
+    InsertStroke { index: u32, stroke: Stroke },
+    RemoveStroke(u32),
+    /// Every attribute of the stroke with this id replaced, its place kept.
+    UpdateStroke(Stroke),
+    /// A stroke swapped for pieces that take its place, e.g. by the eraser.
+    ReplaceStroke { id: u32, pieces: Vec<Stroke> },
+    SetTransform { id: u32, transform: Affine },
+    SetStrokeLayer { id: u32, layer: u32 },
+    Clear,
+    AddLayer { id: u32, name: String },
+    RemoveLayer(u32),
+    MoveLayer { id: u32, index: u32 },
+    SetLayerName { id: u32, name: String },
+    SetLayerVisible { id: u32, visible: bool },
+    SetLayerOpacity { id: u32, opacity: f32 },
+    SetActiveLayer(u32),
+    Group { id: u32, strokes: Vec<u32>, groups: Vec<u32> },
+    Ungroup(u32),
+    SetGroupTransform { id: u32, transform: Affine },
+    /// The whole model replaced by a `snapshot`, e.g. a restored version.
+    Load(Vec<u8>),
+}
+
+impl Op {
+    /// The op when every id it hands out leaves room for a next one, for
+    /// ops read from bytes that may be corrupt or crafted; counters move
+    /// past the ids ops bring in, as `Snapshot::checked` has them do for a
+    /// whole model.
+    pub(crate) fn checked(self) -> Option<Op> {
+        let room = |id: u32| id.checked_add(1).is_some();
+        let fits = match &self {
+            Op::InsertStroke { stroke, .. } | Op::UpdateStroke(stroke) => room(stroke.id),
+            Op::ReplaceStroke { pieces, .. } => pieces.iter().all(|s| room(s.id)),
+            Op::AddLayer { id, .. } | Op::Group { id, .. } => room(*id),
+            _ => true,
+        };
+        fits.then_some(self)
+    }
+}
+
+/// What `recover_journal` made of a journal.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct Recovery {
+    recovered: usize,
+    valid_bytes: usize,
+    total_bytes: usize,
+}
+
+#[wasm_bindgen]
+impl Recovery {
+    /// Ops read and applied.
+    pub fn recovered(&self) -> usize {
+        self.recovered
+    }
+
+    /// Length of the journal up to the end of the last op recovered.
+    pub fn valid_bytes(&self) -> usize {
+        self.valid_bytes
+    }
+
+    /// Whether the whole journal was read, i.e. nothing was lost.
+    pub fn complete(&self) -> bool {
+        self.valid_bytes == self.total_bytes
+    }
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Number of ops recorded since the document was created.
+    pub fn op_count(&self) -> usize {
+        self.op_base + self.ops.len()
+    }
+
+    /// Serializes the ops from index `from` on: every stroke, layer and
+    /// group change, but not brush registrations, which the host repeats
+    /// itself. An autosave journal appends each export and keeps
+    /// `op_count` as its cursor; sync sends the same stream to peers. Ops
+    /// before a restored snapshot's watermark are no longer held, so
+    /// exporting starts at the watermark at the earliest.
+    pub fn export_ops(&self, from: usize) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.buf.extend_from_slice(MAGIC);
+        w.u8(VERSION);
+        let ops = self.ops_from(from);
+        w.varint(ops.len() as u64);
+        for op in ops {
+            write_op(&mut w, op);
+        }
+        w.buf
+    }
+
+    /// Rebuilds what it can from an autosave journal, the `export_ops`
+    /// blobs appended one after another, e.g. after the app was killed
+    /// mid-write. Ops are applied in turn up to the first one that is cut
+    /// short, fails its checksum or does not decode; everything from there
+    /// on is dropped.
+    /// Rewrite the journal afterwards rather than appending to the broken
+    /// one.
+    pub fn recover_journal(&mut self, bytes: &[u8]) -> Recovery {
+        let mut r = Reader::new(bytes);
+        let mut out = Recovery {
+            total_bytes: bytes.len(),
+            ..Default::default()
+        };
+        'blobs: while r.pos() < bytes.len() {
+            let Some(n) = read_header(&mut r) else {
+                break;
+            };
+            for _ in 0..n {
+                let Some(op) = read_op(&mut r) else {
+                    break 'blobs;
+                };
+                self.apply(op);
+                out.recovered += 1;
+                out.valid_bytes = r.pos();
+            }
+            // A blob without ops is whole once its header is.
+            out.valid_bytes = r.pos();
+        }
+        out
+    }
+
+    /// Bounds the op log for long sessions: once it holds twice `limit`
+    /// ops, all but the newest `limit` are squashed into the base state.
+    /// Squashing replays the old ops, so doing it in batches keeps the cost
+    /// per op low. 0, the default, keeps every op.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        self.compact_if_over_limit();
+    }
+
+    /// Squashes all but the newest `keep` ops into the base state, which
+    /// `history_base` then returns. `op_count` and the kept ops are
+    /// unchanged, and squashed strokes keep their timestamps for replay.
+    /// The squashed ops are replayed on the model alone, so nothing is
+    /// tessellated. Returns how many ops were squashed.
+    pub fn compact_history(&mut self, keep: usize) -> usize {
+        let cut = self.ops.len().saturating_sub(keep);
+        if cut == 0 {
+            return 0;
+        }
+        let mut base = InkDocument::new();
+        base.model_only = true;
+        if !self.history_base.is_empty() {
+            base.restore(&self.history_base);
+        }
+        for op in self.ops.drain(..cut) {
+            base.apply(op);
+        }
+        self.op_base += cut;
+        // Ops that found nothing to change record nothing, so the base's
+        // own count can fall short of the ops squashed.
+        let counters = [base.next_id, base.next_layer_id, base.next_group_id, base.active_layer];
+        self.history_base = write_snapshot(self.op_base, counters, &base.layers, &base.groups, &base.strokes);
+        cut
+    }
+
+    /// The snapshot the held ops apply to: `restore` it and apply
+    /// `export_ops(0)` to rebuild the document. Empty while the log still
+    /// reaches back to a new document.
+    pub fn history_base(&self) -> Vec<u8> {
+        self.history_base.clone()
+    }
+
+    /// Replays an `export_ops` blob, recording the ops here in turn. Meant
+    /// for a document in the state the ops started from, e.g. a new one
+    /// for a full log; ops naming strokes, layers or groups that do not
+    /// exist are skipped. Returns false, applying nothing, when the blob is
+    /// not from `export_ops` or is cut short.
+    pub fn apply_ops(&mut self, bytes: &[u8]) -> bool {
+        let Some(ops) = read_ops(bytes) else {
+            return false;
+        };
+        for op in ops {
+            self.apply(op);
+        }
+        true
+    }
+}
+
+impl InkDocument {
+    /// The held ops from index `from` of the whole log on.
+    pub(crate) fn ops_from(&self, from: usize) -> &[Op] {
+        self.ops.get(from.saturating_sub(self.op_base)..).unwrap_or_default()
+    }
+
+    pub(crate) fn record(&mut self, op: Op) {
+        self.ops.push(op);
+        self.compact_if_over_limit();
+    }
+
+    fn compact_if_over_limit(&mut self) {
+        if self.history_limit > 0 && self.ops.len() >= self.history_limit.saturating_mul(2) {
+            self.compact_history(self.history_limit);
+        }
+    }
+
+    /// Performs `op` through the same paths that recorded it.
+    pub(crate) fn apply(&mut self, op: Op) {
+        match op {
+            Op::InsertStroke { index, stroke } => {
+                if self.index(stroke.id).is_none() && self.can_hold(&stroke) {
+                    self.insert_stroke(index as usize, stroke);
+                }
+            }
+            Op::RemoveStroke(id) => {
+                self.remove_stroke(id);
+            }
+            Op::UpdateStroke(stroke) => {
+                if let Some(i) = self.index(stroke.id).filter(|_| self.can_hold(&stroke)) {
+                    self.strokes[i] = stroke;
+                    self.record_stroke(i);
+                }
+            }
+            Op::ReplaceStroke { id, pieces } => {
+                // Pieces take new ids, but may keep the one they replace.
+                let mut ids: Vec<u32> = pieces.iter().map(|s| s.id).collect();
+                ids.sort_unstable();
+                ids.dedup();
+                let fresh = ids.len() == pieces.len() && ids.iter().all(|&p| p == id || self.index(p).is_none());
+                if let Some(i) = self.index(id).filter(|_| fresh && pieces.iter().all(|s| self.can_hold(s))) {
+                    self.replace_with(i, pieces);
+                }
+            }
+            Op::SetTransform { id, transform } => {
+                self.edit_transform(id, |_| transform);
+            }
+            Op::SetStrokeLayer { id, layer } => {
+                self.set_stroke_layer(id, layer);
+            }
+            Op::Clear => self.clear(),
+            Op::AddLayer { id, name } => self.insert_layer(id, &name),
+            Op::RemoveLayer(id) => {
+                self.remove_layer(id);
+            }
+            Op::MoveLayer { id, index } => {
+                self.move_layer(id, index as usize);
+            }
+            Op::SetLayerName { id, name } => self.set_layer_name(id, &name),
+            Op::SetLayerVisible { id, visible } => self.set_layer_visible(id, visible),
+            Op::SetLayerOpacity { id, opacity } => self.set_layer_opacity(id, opacity),
+            Op::SetActiveLayer(id) => {
+                self.set_active_layer(id);
+            }
+            Op::Group { id, strokes, groups } => {
+                self.insert_group(id, &strokes, &groups);
+            }
+            Op::Ungroup(id) => {
+                self.ungroup(id);
+            }
+            Op::SetGroupTransform { id, transform } => self.set_group_transform(id, transform),
+            Op::Load(snapshot) => {
+                self.load_snapshot(&snapshot);
+            }
+        }
+    }
+
+    /// Whether `stroke`'s layer and group exist, so a replayed stroke
+    /// cannot end up pointing at nothing.
+    fn can_hold(&self, stroke: &Stroke) -> bool {
+        self.layers.iter().any(|l| l.id == stroke.layer) && stroke.group.is_none_or(|g| self.groups.iter().any(|x| x.id == g))
+    }
+}
+
+fn write_op(w: &mut Writer, op: &Op) {
+    // Each op is tagged, length-prefixed so a reader can step over it, and
+    // checksummed so a journal damaged mid-write is caught.
+    let mut body = Writer::default();
+    let tag = match op {
+        Op::InsertStroke { index, stroke } => {
+            body.varint(*index as u64);
+            write_stroke(&mut body, stroke);
+            0
+        }
+        Op::RemoveStroke(id) => {
+            body.varint(*id as u64);
+            1
+        }
+        Op::UpdateStroke(stroke) => {
+            write_stroke(&mut body, stroke);
+            2
+        }
+        Op::ReplaceStroke { id, pieces } => {
+            body.varint(*id as u64);
+            body.varint(pieces.len() as u64);
+            pieces.iter().for_each(|s| write_stroke(&mut body, s));
+            3
+        }
+        Op::SetTransform { id, transform } => {
+            body.varint(*id as u64);
+            transform.iter().for_each(|&v| body.f32(v));
+            4
+        }
+        Op::SetStrokeLayer { id, layer } => {
+            body.varint(*id as u64);
+            body.varint(*layer as u64);
+            5
+        }
+        Op::Clear => 6,
+        Op::AddLayer { id, name } => {
+            body.varint(*id as u64);
+            body.str(name);
+            7
+        }
+        Op::RemoveLayer(id) => {
+            body.varint(*id as u64);
+            8
+        }
+        Op::MoveLayer { id, index } => {
+            body.varint(*id as u64);
+            body.varint(*index as u64);
+            9
+        }
+        Op::SetLayerName { id, name } => {
+            body.varint(*id as u64);
+            body.str(name);
+            10
+        }
+        Op::SetLayerVisible { id, visible } => {
+            body.varint(*id as u64);
+            body.u8(*visible as u8);
+            11
+        }
+        Op::SetLayerOpacity { id, opacity } => {
+            body.varint(*id as u64);
+            body.f32(*opacity);
+            12
+        }
+        Op::SetActiveLayer(id) => {
+            body.varint(*id as u64);
+            13
+        }
+        Op::Group { id, strokes, groups } => {
+            body.varint(*id as u64);
+            write_ids(&mut body, strokes);
+            write_ids(&mut body, groups);
+            14
+        }
+        Op::Ungroup(id) => {
+            body.varint(*id as u64);
+            15
+        }
+        Op::SetGroupTransform { id, transform } => {
+            body.varint(*id as u64);
+            transform.iter().for_each(|&v| body.f32(v));
+            16
+        }
+        Op::Load(snapshot) => {
+            body.bytes(snapshot);
+            17
+        }
+    };
+    w.u8(tag);
+    w.bytes(&body.buf);
+    w.varint(op_checksum(tag, &body.buf));
+}
+
+/// 32 bits of it, which is plenty per op and keeps small ops small.
+fn op_checksum(tag: u8, body: &[u8]) -> u64 {
+    (checksum(body) ^ tag as u64) & 0xffff_ffff
+}
+
+fn read_ops(bytes: &[u8]) -> Option<Vec<Op>> {
+    let mut r = Reader::new(bytes);
+    let n = read_header(&mut r)?;
+    let mut ops = Vec::with_capacity(n);
+    for _ in 0..n {
+        ops.push(read_op(&mut r)?);
+    }
+    Some(ops)
+}
+
+/// The magic and version of an `export_ops` blob, then its op count.
+fn read_header(r: &mut Reader) -> Option<usize> {
+    if r.raw(MAGIC.len())? != MAGIC || r.u8()? != VERSION {
+        return None;
+    }
+    r.count()
+}
+
+fn read_op(r: &mut Reader) -> Option<Op> {
+    let tag = r.u8()?;
+    let body = r.bytes()?;
+    if r.varint()? != op_checksum(tag, body) {
+        return None;
+    }
+    let mut b = Reader::new(body);
+    let op = match tag {
+        0 => Op::InsertStroke {
+            index: read_id(&mut b)?,
+            stroke: read_stroke(&mut b)?,
+        },
+        1 => Op::RemoveStroke(read_id(&mut b)?),
+        2 => Op::UpdateStroke(read_stroke(&mut b)?),
+        3 => {
+            let id = read_id(&mut b)?;
+            let n = b.count()?;
+            let pieces = (0..n).map(|_| read_stroke(&mut b)).collect::<Option<_>>()?;
+            Op::ReplaceStroke { id, pieces }
+        }
+        4 => Op::SetTransform {
+            id: read_id(&mut b)?,
+            transform: read_affine(&mut b)?,
+        },
+        5 => Op::SetStrokeLayer {
+            id: read_id(&mut b)?,
+            layer: read_id(&mut b)?,
+        },
+        6 => Op::Clear,
+        7 => Op::AddLayer {
+            id: read_id(&mut b)?,
+            name: b.str()?,
+        },
+        8 => Op::RemoveLayer(read_id(&mut b)?),
+        9 => Op::MoveLayer {
+            id: read_id(&mut b)?,
+            index: read_id(&mut b)?,
+        },
+        10 => Op::SetLayerName {
+            id: read_id(&mut b)?,
+            name: b.str()?,
+        },
+        11 => Op::SetLayerVisible {
+            id: read_id(&mut b)?,
+            visible: b.u8()? != 0,
+        },
+        12 => Op::SetLayerOpacity {
+            id: read_id(&mut b)?,
+            opacity: b.f32()?,
+        },
+        13 => Op::SetActiveLayer(read_id(&mut b)?),
+        14 => Op::Group {
+            id: read_id(&mut b)?,
+            strokes: read_ids(&mut b)?,
+            groups: read_ids(&mut b)?,
+        },
+        15 => Op::Ungroup(read_id(&mut b)?),
+        16 => Op::SetGroupTransform {
+            id: read_id(&mut b)?,
+            transform: read_affine(&mut b)?,
+        },
+        17 => {
+            let snapshot = b.bytes()?;
+            read_snapshot(snapshot)?;
+            Op::Load(snapshot.to_vec())
+        }
+        _ => return None,
+    };
+    op.checked()
+}
+
+fn write_ids(w: &mut Writer, ids: &[u32]) {
+    w.varint(ids.len() as u64);
+    ids.iter().for_each(|&id| w.varint(id as u64));
+}
+
+fn read_id(r: &mut Reader) -> Option<u32> {
+    r.varint().and_then(|v| u32::try_from(v).ok())
+}
+
+fn read_ids(r: &mut Reader) -> Option<Vec<u32>> {
+    let n = r.count()?;
+    (0..n).map(|_| read_id(r)).collect()
+}
+
+fn read_affine(r: &mut Reader) -> Option<Affine> {
+    let mut m = transform::IDENTITY;
+    for v in &mut m {
+        *v = r.f32()?;
+    }
+    Some(m)
+}
+
+fn write_f32s(w: &mut Writer, values: &[f32]) {
+    w.varint(values.len() as u64);
+    values.iter().for_each(|&v| w.f32(v));
+}
+
+fn read_f32s(r: &mut Reader) -> Option<Vec<f32>> {
+    let n = r.count()?;
+    (0..n).map(|_| r.f32()).collect()
+}
+
+/// Writes a stroke exactly, unlike the quantized clipboard format, so a
+/// replayed document matches the original bit for bit.
+pub(crate) fn write_stroke(w: &mut Writer, s: &Stroke) {
+    w.varint(s.id as u64);
+    write_f32s(w, &s.points);
+    write_f32s(w, &s.widths);
+    w.varint(s.timestamps.len() as u64);
+    s.timestamps.iter().for_each(|&t| w.f64(t));
+    w.varint(s.brush as u64);
+    s.color.iter().for_each(|&c| w.f32(c));
+    w.varint(s.layer as u64);
+    match s.group {
+        Some(g) => {
+            w.u8(1);
+            w.varint(g as u64);
+        }
+        None => w.u8(0),
+    }
+    s.transform.iter().for_each(|&v| w.f32(v));
+    w.f64(s.created);
+    w.u8(s.pointer as u8);
+    w.str(&s.author);
+    match &s.fill {
+        Some(rings) => {
+            w.u8(1);
+            w.varint(rings.len() as u64);
+            for ring in rings {
+                let flat: Vec<f32> = ring.iter().flat_map(|p| [p.0, p.1]).collect();
+                write_f32s(w, &flat);
+            }
+        }
+        None => w.u8(0),
+    }
+}
+
+/// A `PointerType` written as its `u8`; unknown values read as `Unknown`.
+pub(crate) fn read_pointer(r: &mut Reader) -> Option<PointerType> {
+    Some(match r.u8()? {
+        1 => PointerType::Mouse,
+        2 => PointerType::Pen,
+        3 => PointerType::Touch,
+        _ => PointerType::Unknown,
+    })
+}
+
+pub(crate) fn read_stroke(r: &mut Reader) -> Option<Stroke> {
+    let id = read_id(r)?;
+    let points = read_f32s(r)?;
+    let widths = read_f32s(r)?;
+    let n = r.count()?;
+    let timestamps = (0..n).map(|_| r.f64()).collect::<Option<Vec<f64>>>()?;
+    let brush = read_id(r)?;
+    let color = [r.f32()?, r.f32()?, r.f32()?, r.f32()?];
+    let layer = read_id(r)?;
+    let group = match r.u8()? {
+        0 => None,
+        _ => Some(read_id(r)?),
+    };
+    let transform = read_affine(r)?;
+    let created = r.f64()?;
+    let pointer = read_pointer(r)?;
+    let author = r.str()?;
+    let fill = match r.u8()? {
+        0 => None,
+        _ => {
+            let n = r.count()?;
+            let mut rings: Vec<Vec<Point>> = Vec::with_capacity(n);
+            for _ in 0..n {
+                rings.push(read_f32s(r)?.chunks_exact(2).map(|p| (p[0], p[1])).collect());
+            }
+            Some(rings)
+        }
+    };
+    Some(Stroke {
+        id,
+        points,
+        widths,
+        timestamps,
+        brush,
+        color,
+        layer,
+        group,
+        transform,
+        created,
+        pointer,
+        author,
+        fill,
+        meshes: Vec::new(),
+        indexed: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn blob(ops: &[Op]) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.buf.extend_from_slice(MAGIC);
+        w.u8(VERSION);
+        w.varint(ops.len() as u64);
+        ops.iter().for_each(|op| write_op(&mut w, op));
+        w.buf
+    }
+
+    /// A document with one stroke, and that stroke as the op log holds it.
+    fn one_stroke() -> (InkDocument, Stroke) {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 10.0, 5.0], &[2.0; 2], &[0.0, 16.0], &BLACK, 0);
+        let stroke = doc.strokes[0].record();
+        (doc, stroke)
+    }
+
+    #[test]
+    fn a_peer_catches_up_from_its_cursor() {
+        let mut doc = InkDocument::new();
+        let mut peer = InkDocument::new();
+        let a = doc.add_stroke(&[0.0, 0.0, 10.0, 0.0], &[2.0; 2], &[], &BLACK, 0).id;
+        let layer = doc.add_layer("Notes");
+        assert!(peer.apply_ops(&doc.export_ops(0)));
+        let cursor = doc.op_count();
+        doc.set_stroke_layer(a, layer);
+        doc.transform_stroke(a, &[1.0, 0.0, 0.0, 1.0, 4.0, 0.0]);
+        doc.group(&[a], &[]);
+        assert!(peer.apply_ops(&doc.export_ops(cursor)));
+        assert_eq!(peer.snapshot(), doc.snapshot());
+        assert_eq!(doc.export_ops(doc.op_count()), blob(&[]));
+    }
+
+    #[test]
+    fn exports_start_at_a_restored_watermark() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 10.0, 0.0], &[2.0; 2], &[], &BLACK, 0);
+        let mut restored = InkDocument::new();
+        assert!(restored.restore(&doc.snapshot()));
+        assert_eq!(restored.export_ops(0), blob(&[]));
+        restored.add_layer("Notes");
+        assert_eq!(read_ops(&restored.export_ops(0)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_damaged_op_rejects_the_whole_blob() {
+        let (_, stroke) = one_stroke();
+        let mut bytes = blob(&[Op::AddLayer { id: 5, name: "Notes".to_owned() }, Op::InsertStroke { index: 0, stroke }]);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let mut doc = InkDocument::new();
+        assert!(!doc.apply_ops(&bytes));
+        assert_eq!(doc.layer_ids(), [0]);
+        assert_eq!(doc.op_count(), 0);
+    }
+
+    #[test]
+    fn skips_strokes_placed_on_nothing() {
+        let (_, stroke) = one_stroke();
+        let mut doc = InkDocument::new();
+        let on_missing_layer = Stroke { layer: 7, ..stroke.record() };
+        let in_missing_group = Stroke { group: Some(3), ..stroke.record() };
+        assert!(doc.apply_ops(&blob(&[Op::InsertStroke { index: 0, stroke: on_missing_layer }, Op::InsertStroke { index: 0, stroke: in_missing_group }])));
+        assert_eq!(doc.stroke_count(), 0);
+
+        let (mut doc, stroke) = one_stroke();
+        let moved = Stroke { layer: 7, ..stroke.record() };
+        let pieces = vec![Stroke { id: 2, group: Some(3), ..stroke.record() }];
+        assert!(doc.apply_ops(&blob(&[Op::UpdateStroke(moved), Op::ReplaceStroke { id: stroke.id, pieces }])));
+        assert_eq!(doc.stroke_ids(), [stroke.id]);
+        assert_eq!(doc.stroke_layer(stroke.id), Some(0));
+
+        // Pieces may not take the id of another stroke.
+        let other = doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0).id;
+        let pieces = vec![Stroke { id: other, ..stroke.record() }];
+        assert!(doc.apply_ops(&blob(&[Op::ReplaceStroke { id: stroke.id, pieces }])));
+        assert_eq!(doc.stroke_ids(), [stroke.id, other]);
+    }
+
+    #[test]
+    fn rejects_ids_without_a_next_one() {
+        let (_, stroke) = one_stroke();
+        let last = Stroke { id: u32::MAX, ..stroke.record() };
+        let ops = [
+            Op::InsertStroke { index: 0, stroke: last.record() },
+            Op::UpdateStroke(last.record()),
+            Op::ReplaceStroke { id: stroke.id, pieces: vec![last.record()] },
+            Op::AddLayer { id: u32::MAX, name: String::new() },
+            Op::Group { id: u32::MAX, strokes: vec![stroke.id], groups: Vec::new() },
+        ];
+        for op in ops {
+            let (mut doc, _) = one_stroke();
+            assert!(!doc.apply_ops(&blob(&[op])));
+            let next = doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0).id;
+            assert_eq!(next, stroke.id + 1);
+        }
+        let mut doc = InkDocument::new();
+        let journal = [blob(&[Op::AddLayer { id: 3, name: String::new() }]), blob(&[Op::AddLayer { id: u32::MAX, name: String::new() }])].concat();
+        let recovery = doc.recover_journal(&journal);
+        assert_eq!(recovery.recovered(), 1);
+        assert!(!recovery.complete());
+    }
+
+    #[test]
+    fn compaction_keeps_the_op_count() {
+        let (mut doc, stroke) = one_stroke();
+        // Ops that found nothing to change are in the log all the same.
+        doc.record(Op::RemoveStroke(999));
+        doc.add_layer("Notes");
+        doc.transform_stroke(stroke.id, &[2.0, 0.0, 0.0, 2.0, 0.0, 0.0]);
+        let (count, state) = (doc.op_count(), doc.snapshot());
+        assert_eq!(doc.compact_history(1), count - 1);
+        assert_eq!(doc.op_count(), count);
+        let mut rebuilt = InkDocument::new();
+        assert!(rebuilt.restore(&doc.history_base()));
+        assert_eq!(rebuilt.op_count(), count - 1);
+        assert!(rebuilt.apply_ops(&doc.export_ops(0)));
+        assert_eq!(rebuilt.snapshot(), state);
+        assert_eq!(doc.compact_history(0), 1);
+        assert_eq!(doc.history_base(), state);
+    }
+
+    #[test]
+    fn compaction_tessellates_nothing() {
+        let (doc, stroke) = one_stroke();
+        let mut base = InkDocument::new();
+        base.model_only = true;
+        assert!(base.apply_ops(&doc.export_ops(0)));
+        base.transform_stroke(stroke.id, &[2.0, 0.0, 0.0, 2.0, 0.0, 0.0]);
+        assert!(base.strokes.iter().all(|s| s.meshes.is_empty()));
+        assert!(base.remove_stroke(stroke.id).unwrap().dirty.is_none());
+    }
+
+    #[test]
+    fn a_history_limit_compacts_in_batches() {
+        let mut doc = InkDocument::new();
+        doc.set_history_limit(2);
+        for k in 0..3 {
+            doc.add_layer(&k.to_string());
+        }
+        assert_eq!((doc.ops.len(), doc.op_base), (3, 0));
+        doc.add_layer("3");
+        assert_eq!((doc.ops.len(), doc.op_base), (2, 2));
+        assert_eq!(doc.op_count(), 4);
+    }
+
+    /// Three strokes drawn with the journal appended after each, as an
+    /// autosave writes it.
+    fn journal() -> (InkDocument, Vec<u8>, Vec<usize>) {
+        let mut doc = InkDocument::new();
+        let (mut bytes, mut ends) = (Vec::new(), Vec::new());
+        for k in 0..3 {
+            let cursor = doc.op_count();
+            doc.add_stroke(&[0.0, k as f32, 10.0, k as f32], &[2.0; 2], &[], &BLACK, 0);
+            bytes.extend(doc.export_ops(cursor));
+            ends.push(bytes.len());
+        }
+        (doc, bytes, ends)
+    }
+
+    #[test]
+    fn recovers_a_whole_journal() {
+        let (doc, bytes, _) = journal();
+        let mut recovered = InkDocument::new();
+        let recovery = recovered.recover_journal(&bytes);
+        assert!(recovery.complete());
+        assert_eq!(recovery.recovered(), 3);
+        assert_eq!(recovered.snapshot(), doc.snapshot());
+    }
+
+    #[test]
+    fn recovers_up_to_a_cut() {
+        let (_, bytes, ends) = journal();
+        for cut in ends[0] + 1..ends[1] {
+            let mut recovered = InkDocument::new();
+            let recovery = recovered.recover_journal(&bytes[..cut]);
+            assert!(!recovery.complete());
+            assert_eq!((recovery.recovered(), recovery.valid_bytes()), (1, ends[0]), "cut at {cut}");
+            assert_eq!(recovered.stroke_count(), 1);
+        }
+        // Garbage after the last blob loses nothing before it.
+        let mut damaged = bytes.clone();
+        damaged.extend_from_slice(b"INKO");
+        let recovery = InkDocument::new().recover_journal(&damaged);
+        assert_eq!((recovery.recovered(), recovery.valid_bytes()), (3, bytes.len()));
+    }
+
+    #[test]
+    fn stops_at_a_damaged_op() {
+        let (_, mut bytes, ends) = journal();
+        bytes[ends[1] - 1] ^= 0x40;
+        let mut recovered = InkDocument::new();
+        let recovery = recovered.recover_journal(&bytes);
+        assert_eq!((recovery.recovered(), recovery.valid_bytes()), (1, ends[0]));
+    }
+}
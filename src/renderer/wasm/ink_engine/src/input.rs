@@ -0,0 +1,90 @@
+use wasm_bindgen::prelude::*;
+
+use crate::nib::tilt_azimuth;
+
+/// Values per packed pointer sample: `x, y, pressure, tiltX, tiltY, twist,
+/// timeStamp, coalesced`, straight from a `PointerEvent` and its
+/// `getCoalescedEvents()`. `coalesced` is 1 for entries of the coalesced
+/// list and 0 for the dispatched event itself.
+pub(crate) const POINTER_STRIDE: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PointerSample {
+    pub x: f32,
+    pub y: f32,
+    pub pressure: f32,
+    /// Pen azimuth in radians from the tilt, when the pen leans.
+    pub azimuth: Option<f32>,
+    pub time: f64,
+    pub coalesced: bool,
+}
+
+/// Unpacks a batch, dropping samples with a non-finite position and a
+/// dispatched event that repeats the coalesced sample before it.
+pub(crate) fn unpack(packed: &[f64]) -> Vec<PointerSample> {
+    let mut out: Vec<PointerSample> = Vec::with_capacity(packed.len() / POINTER_STRIDE);
+    for v in packed.chunks_exact(POINTER_STRIDE) {
+        let s = PointerSample {
+            x: v[0] as f32,
+            y: v[1] as f32,
+            pressure: if v[2].is_finite() { v[2] as f32 } else { 1.0 },
+            azimuth: tilt_azimuth(v[3] as f32, v[4] as f32),
+            time: v[6],
+            coalesced: v[7] != 0.0,
+        };
+        if !(s.x.is_finite() && s.y.is_finite()) {
+            continue;
+        }
+        if !s.coalesced && out.last().is_some_and(|p| p.time == s.time) {
+            continue;
+        }
+        out.push(s);
+    }
+    out
+}
+
+/// How raw pointer samples become stroke samples.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct InputOptions {
+    /// Stroke width at full pressure.
+    pub base_width: f32,
+    /// Pressure floor, so light touches and mice reporting 0 still draw.
+    pub min_pressure: f32,
+    /// Run positions and pressure through a 1€ filter with the parameters below.
+    pub filter: bool,
+    pub min_cutoff: f64,
+    pub beta: f64,
+    pub d_cutoff: f64,
+    /// How far ahead to predict the pen tip in milliseconds, typically
+    /// 10–30 to hide input latency; 0 disables prediction.
+    pub predict_ms: f32,
+}
+
+#[wasm_bindgen]
+impl InputOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> InputOptions {
+        InputOptions::default()
+    }
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        InputOptions {
+            base_width: 2.0,
+            min_pressure: 0.25,
+            filter: false,
+            min_cutoff: 1.0,
+            beta: 0.007,
+            d_cutoff: 1.0,
+            predict_ms: 0.0,
+        }
+    }
+}
+
+impl InputOptions {
+    pub(crate) fn width(&self, pressure: f32) -> f32 {
+        self.base_width * pressure.clamp(self.min_pressure, 1.0)
+    }
+}
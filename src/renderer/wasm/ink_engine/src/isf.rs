@@ -0,0 +1,632 @@
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::preset::BrushStyle;
+use crate::transform::{self, Affine};
+
+// Tags of the Ink Serialized Format, from [MS-ISF]. Tags from 50 on name
+// GUIDs: the known ones below, then the stream's own GUID table from 100.
+const INK_SPACE_RECT: u64 = 0;
+const DRAW_ATTRS_TABLE: u64 = 2;
+const DRAW_ATTRS_BLOCK: u64 = 3;
+const STROKE_DESC_TABLE: u64 = 4;
+const STROKE_DESC_BLOCK: u64 = 5;
+const BUTTONS: u64 = 6;
+const NO_X: u64 = 7;
+const NO_Y: u64 = 8;
+const DIDX: u64 = 9;
+const STROKE: u64 = 10;
+const STROKE_PROPERTY_LIST: u64 = 11;
+const SIDX: u64 = 13;
+const TRANSFORM_TABLE: u64 = 15;
+const TRANSFORM: u64 = 16;
+const TRANSFORM_ISOTROPIC_SCALE: u64 = 17;
+const TRANSFORM_ANISOTROPIC_SCALE: u64 = 18;
+const TRANSFORM_ROTATE: u64 = 19;
+const TRANSFORM_TRANSLATE: u64 = 20;
+const TRANSFORM_SCALE_AND_TRANSLATE: u64 = 21;
+const TRANSFORM_QUAD: u64 = 22;
+const TIDX: u64 = 23;
+const METRIC_TABLE: u64 = 24;
+const METRIC_BLOCK: u64 = 25;
+const MIDX: u64 = 26;
+const MANTISSA: u64 = 27;
+const CUSTOM_GUID_BASE: u64 = 100;
+
+const NORMAL_PRESSURE: u64 = 56;
+const COLOR_REF: u64 = 68;
+const PEN_WIDTH: u64 = 69;
+const PEN_HEIGHT: u64 = 70;
+const PEN_TIP: u64 = 71;
+const DRAWING_FLAGS: u64 = 72;
+const TRANSPARENCY: u64 = 80;
+const CURVE_FITTING_ERROR: u64 = 81;
+const RASTER_OPERATION: u64 = 87;
+
+/// `R2_MASKPEN`, the raster operation highlighters draw with.
+const MASK_PEN: u64 = 9;
+/// ISF coordinates are HIMETRIC, 0.01 mm; document units are 96 dpi pixels.
+const UNITS_PER_HIMETRIC: f32 = 96.0 / 2540.0;
+/// Pen width of a drawing attributes block that sets none.
+const DEFAULT_PEN_WIDTH: f32 = 53.0;
+/// Pressure range assumed when the stream has no metric for it.
+const DEFAULT_PRESSURE_MAX: f32 = 1023.0;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Adds the strokes of an Ink Serialized Format stream, as Windows Ink,
+    /// OneNote and Journal export, on top of the active layer and returns
+    /// their ids. Points are converted from HIMETRIC to 96 dpi units with
+    /// the stroke's transform applied, pressure scales the pen width about
+    /// its mid value, and highlighter strokes go to the first registered
+    /// `BrushStyle::Highlighter` brush; others use brush 0. Takes the raw
+    /// stream, not the GIF or base64 wrappers. Returns nothing, adding
+    /// nothing, when the stream or any stroke in it does not decode.
+    pub fn import_isf(&mut self, bytes: &[u8]) -> Vec<u32> {
+        let Some(strokes) = decode(bytes) else {
+            return Vec::new();
+        };
+        let highlighter = self.brushes.with_style(BrushStyle::Highlighter).unwrap_or(0);
+        strokes
+            .iter()
+            .map(|s| {
+                let brush = if s.attrs.highlighter { highlighter } else { 0 };
+                self.add_stroke(&s.points, &s.widths, &[], &s.attrs.color, brush).id
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone)]
+struct DrawAttrs {
+    color: [f32; 4],
+    /// HIMETRIC.
+    width: f32,
+    highlighter: bool,
+}
+
+impl Default for DrawAttrs {
+    fn default() -> Self {
+        DrawAttrs {
+            color: [0.0, 0.0, 0.0, 1.0],
+            width: DEFAULT_PEN_WIDTH,
+            highlighter: false,
+        }
+    }
+}
+
+/// The packet properties of each point: whether X and Y are stored, and
+/// the GUID tags of the rest in order.
+#[derive(Clone)]
+struct StrokeDesc {
+    x: bool,
+    y: bool,
+    properties: Vec<u64>,
+}
+
+impl Default for StrokeDesc {
+    fn default() -> Self {
+        StrokeDesc {
+            x: true,
+            y: true,
+            properties: Vec::new(),
+        }
+    }
+}
+
+struct IsfStroke {
+    /// Document units.
+    points: Vec<f32>,
+    widths: Vec<f32>,
+    attrs: DrawAttrs,
+}
+
+/// Byte reader with ISF's multi-byte integers, which are LEB128.
+struct Stream<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Stream<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Stream { buf, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let out = self.buf.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(out)
+    }
+
+    fn uint(&mut self) -> Option<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = *self.take(1)?.first()?;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Some(v);
+            }
+        }
+        None
+    }
+
+    /// Magnitude shifted left with the sign in the low bit; not zigzag.
+    fn int(&mut self) -> Option<i64> {
+        let v = self.uint()?;
+        let magnitude = (v >> 1) as i64;
+        Some(if v & 1 != 0 { -magnitude } else { magnitude })
+    }
+
+    fn float(&mut self) -> Option<f32> {
+        self.take(4).map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    /// A multi-byte integer used as a count of items that each take at
+    /// least `1 / per_byte` of a byte, bounded by the bytes left so a
+    /// corrupt count cannot trigger a huge allocation.
+    fn count(&mut self, per_byte: usize) -> Option<usize> {
+        let n = self.uint()?;
+        let left = (self.buf.len() - self.pos.min(self.buf.len())) as u64;
+        (n <= left.saturating_mul(per_byte as u64)).then_some(n as usize)
+    }
+
+    /// A size-prefixed block as a stream of its own.
+    fn block(&mut self) -> Option<Stream<'a>> {
+        let n = usize::try_from(self.uint()?).ok()?;
+        self.take(n).map(Stream::new)
+    }
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<IsfStroke>> {
+    let mut s = Stream::new(bytes);
+    if s.uint()? != 0 {
+        return None;
+    }
+    let mut s = s.block()?;
+    let (mut attrs, mut descs, mut transforms) = (Vec::new(), Vec::new(), Vec::new());
+    let mut pressure_max = None;
+    let (mut didx, mut sidx, mut tidx) = (0, 0, 0);
+    let mut strokes = Vec::new();
+    while !s.at_end() {
+        match s.uint()? {
+            INK_SPACE_RECT => {
+                for _ in 0..4 {
+                    s.int()?;
+                }
+            }
+            DRAW_ATTRS_TABLE => {
+                let mut table = s.block()?;
+                while !table.at_end() {
+                    attrs.push(read_draw_attrs(table.block()?));
+                }
+            }
+            DRAW_ATTRS_BLOCK => attrs.push(read_draw_attrs(s.block()?)),
+            STROKE_DESC_TABLE => {
+                let mut table = s.block()?;
+                while !table.at_end() {
+                    descs.push(read_stroke_desc(table.block()?)?);
+                }
+            }
+            STROKE_DESC_BLOCK => descs.push(read_stroke_desc(s.block()?)?),
+            DIDX => didx = s.uint()? as usize,
+            SIDX => sidx = s.uint()? as usize,
+            TIDX => tidx = s.uint()? as usize,
+            MIDX => {
+                s.uint()?;
+            }
+            TRANSFORM_TABLE => {
+                let mut table = s.block()?;
+                while !table.at_end() {
+                    let tag = table.uint()?;
+                    transforms.push(read_transform(&mut table, tag)?);
+                }
+            }
+            tag @ TRANSFORM..=TRANSFORM_QUAD => transforms.push(read_transform(&mut s, tag)?),
+            METRIC_TABLE => {
+                let mut table = s.block()?;
+                while !table.at_end() {
+                    pressure_max = read_metrics(table.block()?).or(pressure_max);
+                }
+            }
+            METRIC_BLOCK => pressure_max = read_metrics(s.block()?).or(pressure_max),
+            STROKE => {
+                let desc = descs.get(sidx).cloned().unwrap_or_default();
+                let a = attrs.get(didx).cloned().unwrap_or_default();
+                let m = transforms.get(tidx).copied().unwrap_or(transform::IDENTITY);
+                let stroke = read_stroke(s.block()?, &desc, a, &m, pressure_max.unwrap_or(DEFAULT_PRESSURE_MAX))?;
+                if !stroke.points.is_empty() {
+                    strokes.push(stroke);
+                }
+            }
+            tag if tag >= CUSTOM_GUID_BASE => {
+                // Custom properties count a type byte past their size.
+                let n = usize::try_from(s.uint()?).ok()?;
+                s.take(n.checked_add(1)?)?;
+            }
+            // Every other known tag is size-prefixed.
+            _ => {
+                s.block()?;
+            }
+        }
+    }
+    Some(strokes)
+}
+
+/// Reads what it knows up to the first property it does not, whose size it
+/// cannot tell; the block's own size bounds that.
+fn read_draw_attrs(mut s: Stream) -> DrawAttrs {
+    let mut a = DrawAttrs::default();
+    let mut transparency = 0;
+    while let Some(tag) = s.uint() {
+        let Some(v) = s.uint() else {
+            break;
+        };
+        match tag {
+            COLOR_REF => {
+                let c = |shift: u64| ((v >> shift) & 0xff) as f32 / 255.0;
+                a.color[..3].copy_from_slice(&[c(0), c(8), c(16)]);
+            }
+            PEN_WIDTH => a.width = v as f32,
+            TRANSPARENCY => transparency = v.min(255),
+            // The fraction of the width before it; too small to matter.
+            MANTISSA => {
+                if s.take(v as usize).is_none() {
+                    break;
+                }
+            }
+            PEN_HEIGHT | PEN_TIP | DRAWING_FLAGS | CURVE_FITTING_ERROR => {}
+            RASTER_OPERATION => a.highlighter = v == MASK_PEN,
+            _ => break,
+        }
+    }
+    a.color[3] = 1.0 - transparency as f32 / 255.0;
+    a
+}
+
+fn read_stroke_desc(mut s: Stream) -> Option<StrokeDesc> {
+    let mut desc = StrokeDesc::default();
+    while !s.at_end() {
+        match s.uint()? {
+            NO_X => desc.x = false,
+            NO_Y => desc.y = false,
+            BUTTONS => {
+                let n = s.count(1)?;
+                for _ in 0..n {
+                    s.uint()?;
+                }
+            }
+            // What follows describes the stroke, not its points.
+            STROKE_PROPERTY_LIST => break,
+            tag => desc.properties.push(tag),
+        }
+    }
+    Some(desc)
+}
+
+/// ISF's `[m11, m12, m21, m22, dx, dy]` is our affine layout already.
+fn read_transform(s: &mut Stream, tag: u64) -> Option<Affine> {
+    let mut m = transform::IDENTITY;
+    match tag {
+        TRANSFORM => {
+            for v in &mut m {
+                *v = s.float()?;
+            }
+        }
+        TRANSFORM_ISOTROPIC_SCALE => {
+            let k = s.float()?;
+            (m[0], m[3]) = (k, k);
+        }
+        TRANSFORM_ANISOTROPIC_SCALE => (m[0], m[3]) = (s.float()?, s.float()?),
+        TRANSFORM_ROTATE => {
+            // Hundredths of a degree.
+            let a = (s.uint()? as f32 / 100.0).to_radians();
+            m[..4].copy_from_slice(&[a.cos(), a.sin(), -a.sin(), a.cos()]);
+        }
+        TRANSFORM_TRANSLATE => (m[4], m[5]) = (s.float()?, s.float()?),
+        TRANSFORM_SCALE_AND_TRANSLATE => (m[0], m[3], m[4], m[5]) = (s.float()?, s.float()?, s.float()?, s.float()?),
+        // [MS-ISF] gives a quad no layout, so nothing after one can be
+        // found.
+        TRANSFORM_QUAD => return None,
+        _ => return None,
+    }
+    Some(m)
+}
+
+/// The pressure maximum of a metric block, if it has one: entries of a GUID
+/// tag and a sized body starting with the minimum and maximum.
+fn read_metrics(mut s: Stream) -> Option<f32> {
+    let mut max = None;
+    while !s.at_end() {
+        let tag = s.uint()?;
+        let mut entry = s.block()?;
+        if tag == NORMAL_PRESSURE {
+            entry.int()?;
+            max = Some(entry.int()? as f32).filter(|&m| m > 0.0);
+        }
+    }
+    max
+}
+
+fn read_stroke(mut s: Stream, desc: &StrokeDesc, attrs: DrawAttrs, m: &Affine, pressure_max: f32) -> Option<IsfStroke> {
+    // Every value of a channel takes at least a bit.
+    let n = s.count(8)?;
+    let mut channel = || decompress(&mut s, n);
+    let xs = if desc.x { channel()? } else { vec![0; n] };
+    let ys = if desc.y { channel()? } else { vec![0; n] };
+    let mut pressures = None;
+    for &tag in &desc.properties {
+        let values = channel()?;
+        if tag == NORMAL_PRESSURE {
+            pressures = Some(values);
+            break;
+        }
+    }
+    let m = transform::compose(&[UNITS_PER_HIMETRIC, 0.0, 0.0, UNITS_PER_HIMETRIC, 0.0, 0.0], m);
+    let points = xs
+        .iter()
+        .zip(&ys)
+        .flat_map(|(&x, &y)| {
+            let (x, y) = transform::apply(&m, (x as f32, y as f32));
+            [x, y]
+        })
+        .collect();
+    let width = attrs.width * transform::scale_factor(&m);
+    let widths = match pressures {
+        Some(p) => p.iter().map(|&p| width * (2.0 * p as f32 / pressure_max).clamp(0.1, 2.0)).collect(),
+        None => vec![width; n],
+    };
+    Some(IsfStroke { points, widths, attrs })
+}
+
+/// One channel of packet data: an algorithm byte, then Huffman codes or
+/// fixed-width "gorilla" values, either through the delta-delta transform.
+fn decompress(s: &mut Stream, n: usize) -> Option<Vec<i64>> {
+    let algo = *s.take(1)?.first()?;
+    let mut bits = Bits::new(&s.buf[s.pos..]);
+    let mut dd = DeltaDelta::default();
+    let mut out = Vec::with_capacity(n);
+    match algo & 0xc0 {
+        0x80 => {
+            let table = HUFFMAN_BITS.get((algo & 0x1f) as usize)?;
+            let mins = huffman_mins(table);
+            for _ in 0..n {
+                out.push(dd.inverse(huffman_value(&mut bits, table, &mins)?)?);
+            }
+        }
+        0x00 => {
+            let width = match algo & 0x1f {
+                0 => 32,
+                w => w as u32,
+            };
+            let delta = algo & 0x20 != 0;
+            if delta {
+                // The first two values are multi-byte integers.
+                for _ in 0..2.min(n) {
+                    out.push(dd.inverse(s.int()?)?);
+                }
+                bits = Bits::new(&s.buf[s.pos..]);
+            }
+            while out.len() < n {
+                let v = bits.signed(width)?;
+                out.push(if delta { dd.inverse(v)? } else { v });
+            }
+        }
+        _ => return None,
+    }
+    s.take(bits.bytes_used())?;
+    Some(out)
+}
+
+/// Bits per index of the eight default Huffman codecs; index `k` is coded
+/// as `k` ones and a zero, then that many bits of value and sign.
+const HUFFMAN_BITS: [&[u32]; 8] = [
+    &[0, 1, 2, 4, 6, 8, 12, 16, 24, 32],
+    &[0, 1, 1, 2, 4, 8, 12, 16, 24, 32],
+    &[0, 1, 1, 1, 2, 4, 8, 14, 22, 32],
+    &[0, 2, 2, 3, 5, 8, 12, 16, 24, 32],
+    &[0, 3, 4, 5, 8, 12, 16, 24, 32],
+    &[0, 4, 6, 8, 12, 16, 24, 32],
+    &[0, 6, 8, 12, 16, 24, 32],
+    &[0, 7, 8, 12, 16, 24, 32],
+];
+
+/// Smallest magnitude each index codes, the ranges running on from 1.
+fn huffman_mins(table: &[u32]) -> Vec<i64> {
+    let mut mins = vec![0; table.len()];
+    let mut lower = 1i64;
+    for k in 1..table.len() {
+        mins[k] = lower;
+        lower += 1 << (table[k] - 1);
+    }
+    mins
+}
+
+fn huffman_value(bits: &mut Bits, table: &[u32], mins: &[i64]) -> Option<i64> {
+    let mut k = 0;
+    while bits.bit()? {
+        k += 1;
+        // The escape to 64-bit values is never written by Windows Ink.
+        if k >= table.len() {
+            return None;
+        }
+    }
+    if k == 0 {
+        return Some(0);
+    }
+    let v = bits.unsigned(table[k])? as i64;
+    let magnitude = (v >> 1) + mins[k];
+    Some(if v & 1 != 0 { -magnitude } else { magnitude })
+}
+
+/// Undoes the second difference packet data is stored as.
+#[derive(Default)]
+struct DeltaDelta {
+    prev: i64,
+    prev2: i64,
+}
+
+impl DeltaDelta {
+    /// `None` when the value overflows, which no real pen produces.
+    fn inverse(&mut self, v: i64) -> Option<i64> {
+        let x = v.checked_add(self.prev.checked_mul(2)?)?.checked_sub(self.prev2)?;
+        (self.prev2, self.prev) = (self.prev, x);
+        Some(x)
+    }
+}
+
+/// Most significant bit first.
+struct Bits<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Bits { buf, pos: 0 }
+    }
+
+    fn bit(&mut self) -> Option<bool> {
+        let b = *self.buf.get(self.pos / 8)?;
+        let bit = b >> (7 - self.pos % 8) & 1 != 0;
+        self.pos += 1;
+        Some(bit)
+    }
+
+    fn unsigned(&mut self, n: u32) -> Option<u64> {
+        (0..n).try_fold(0u64, |v, _| Some(v << 1 | self.bit()? as u64))
+    }
+
+    fn signed(&mut self, n: u32) -> Option<i64> {
+        let v = self.unsigned(n)? as i64;
+        let sign = 1i64 << (n - 1);
+        Some((v ^ sign) - sign)
+    }
+
+    fn bytes_used(&self) -> usize {
+        self.pos.div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uint(out: &mut Vec<u8>, mut v: u64) {
+        while v >= 0x80 {
+            out.push(v as u8 | 0x80);
+            v >>= 7;
+        }
+        out.push(v as u8);
+    }
+
+    /// `tag` and a size-prefixed `body`.
+    fn block(out: &mut Vec<u8>, tag: u64, body: &[u8]) {
+        uint(out, tag);
+        uint(out, body.len() as u64);
+        out.extend_from_slice(body);
+    }
+
+    /// A stream of the given tagged items.
+    fn stream(items: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        block(&mut out, 0, items);
+        out
+    }
+
+    /// Two points at (10, 20) and (100, 50) HIMETRIC, as 8-bit values.
+    fn two_points() -> Vec<u8> {
+        let mut items = Vec::new();
+        block(&mut items, STROKE, &[2, 0x08, 10, 100, 0x08, 20, 50]);
+        stream(&items)
+    }
+
+    #[test]
+    fn decodes_a_stroke() {
+        let strokes = decode(&two_points()).unwrap();
+        assert_eq!(strokes.len(), 1);
+        let expected = [10.0, 20.0, 100.0, 50.0].map(|v| v * UNITS_PER_HIMETRIC);
+        assert!(strokes[0].points.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-4));
+        assert_eq!(strokes[0].widths.len(), 2);
+    }
+
+    #[test]
+    fn rejects_truncated_streams() {
+        let bytes = two_points();
+        for k in 0..bytes.len() {
+            assert!(decode(&bytes[..k]).is_none(), "{k} bytes");
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_counts() {
+        // A point count no stroke of this size could hold.
+        let mut body = Vec::new();
+        uint(&mut body, 1 << 61);
+        body.push(0x00);
+        let mut items = Vec::new();
+        block(&mut items, STROKE, &body);
+        assert!(decode(&stream(&items)).is_none());
+
+        let mut items = Vec::new();
+        uint(&mut items, STROKE);
+        uint(&mut items, u64::MAX);
+        assert!(decode(&stream(&items)).is_none());
+
+        let mut desc = vec![BUTTONS as u8];
+        uint(&mut desc, 1 << 40);
+        let mut items = Vec::new();
+        block(&mut items, STROKE_DESC_BLOCK, &desc);
+        assert!(decode(&stream(&items)).is_none());
+    }
+
+    #[test]
+    fn rejects_overflowing_deltas() {
+        let mut body = vec![2, 0x20 | 0x08];
+        uint(&mut body, (1 << 62) << 1);
+        uint(&mut body, (1 << 62) << 1);
+        body.extend([0x08, 0, 0]);
+        let mut items = Vec::new();
+        block(&mut items, STROKE, &body);
+        assert!(decode(&stream(&items)).is_none());
+    }
+
+    #[test]
+    fn a_bad_stroke_fails_the_import() {
+        let mut items = Vec::new();
+        block(&mut items, STROKE, &[2, 0x08, 10, 100, 0x08, 20, 50]);
+        // Two points of which only one is stored.
+        block(&mut items, STROKE, &[2, 0x08, 10]);
+        let mut doc = InkDocument::new();
+        assert!(doc.import_isf(&stream(&items)).is_empty());
+        assert!(doc.strokes.is_empty());
+        assert_eq!(doc.import_isf(&two_points()).len(), 1);
+    }
+
+    #[test]
+    fn applies_the_stroke_transform() {
+        let mut items = Vec::new();
+        uint(&mut items, TRANSFORM_SCALE_AND_TRANSLATE);
+        [2.0f32, 3.0, 100.0, 0.0].iter().for_each(|v| items.extend(v.to_le_bytes()));
+        block(&mut items, STROKE, &[1, 0x08, 10, 0x08, 20]);
+        let strokes = decode(&stream(&items)).unwrap();
+        let expected = [120.0, 60.0].map(|v| v * UNITS_PER_HIMETRIC);
+        assert!(strokes[0].points.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-4));
+    }
+
+    #[test]
+    fn rejects_quad_transforms() {
+        let mut quad = Vec::new();
+        uint(&mut quad, TRANSFORM_QUAD);
+        (0..8).for_each(|_| quad.extend(1.0f32.to_le_bytes()));
+        let mut items = quad.clone();
+        block(&mut items, STROKE, &[2, 0x08, 10, 100, 0x08, 20, 50]);
+        assert!(decode(&stream(&items)).is_none());
+        let mut items = Vec::new();
+        block(&mut items, TRANSFORM_TABLE, &quad);
+        assert!(decode(&stream(&items)).is_none());
+    }
+}
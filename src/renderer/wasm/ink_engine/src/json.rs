@@ -0,0 +1,341 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::document::{InkDocument, Layer, PointerType, Stroke};
+use crate::group::Group;
+use crate::snapshot::Snapshot;
+use crate::transform::{self, Affine};
+
+const FORMAT: &str = "ink-document";
+const VERSION: u32 = 1;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// The document as JSON, for tooling in the main process that indexes,
+    /// searches or rewrites documents without the engine. Floats are
+    /// written so they read back exactly; brushes go by name. Keys are
+    /// snake_case; non-finite numbers are written as `null`.
+    pub fn to_json(&self) -> String {
+        let doc = DocumentJson {
+            format: FORMAT.to_owned(),
+            version: VERSION,
+            next_id: self.next_id,
+            next_layer_id: self.next_layer_id,
+            next_group_id: self.next_group_id,
+            active_layer: self.active_layer,
+            layers: self.layers.iter().map(|l| LayerJson { id: l.id, name: l.name.clone(), visible: l.visible, opacity: Num(l.opacity) }).collect(),
+            groups: self.groups.iter().map(|g| GroupJson { id: g.id, parent: g.parent, transform: g.transform.map(Num) }).collect(),
+            strokes: self.strokes.iter().map(|s| StrokeJson::new(s, self.brushes.name(s.brush))).collect(),
+        };
+        serde_json::to_string(&doc).unwrap_or_default()
+    }
+
+    /// Replaces the document with one read from `to_json` output, which
+    /// may have been edited: unknown keys are ignored and missing ones take
+    /// their defaults, except layers and the active layer. Brushes are
+    /// matched by name, falling back to brush 0. The op log and saved
+    /// versions start over. Returns false, leaving the document untouched,
+    /// when the text is not such a document.
+    pub fn from_json(&mut self, text: &str) -> bool {
+        let Some(state) = serde_json::from_str::<DocumentJson>(text).ok().and_then(|doc| doc.snapshot(&|name| self.brushes.id(name).unwrap_or(0))) else {
+            return false;
+        };
+        self.replace_model(state);
+        self.ops.clear();
+        self.op_base = 0;
+        self.history_base = self.snapshot();
+        self.versions.clear();
+        self.next_version_id = 1;
+        true
+    }
+}
+
+/// The numbers of a JSON array, `None` when it is not an array of numbers.
+/// For the importers of other apps' JSON.
+pub(crate) fn numbers(v: &serde_json::Value) -> Option<Vec<f64>> {
+    v.as_array()?.iter().map(serde_json::Value::as_f64).collect()
+}
+
+/// A float that serde_json writes as `null` when it is not finite, and
+/// that reads `null` back as NaN.
+#[derive(Clone, Copy, Default, Serialize)]
+#[serde(transparent)]
+struct Num<T>(T);
+
+impl<'de, T: Deserialize<'de> + From<f32>> Deserialize<'de> for Num<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(Num(Option::<T>::deserialize(d)?.unwrap_or_else(|| T::from(f32::NAN))))
+    }
+}
+
+fn nums<T: Copy>(values: &[T]) -> Vec<Num<T>> {
+    values.iter().copied().map(Num).collect()
+}
+
+fn floats<T>(values: Vec<Num<T>>) -> Vec<T> {
+    values.into_iter().map(|n| n.0).collect()
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn opaque() -> Num<f32> {
+    Num(1.0)
+}
+
+fn identity() -> [Num<f32>; 6] {
+    transform::IDENTITY.map(Num)
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocumentJson {
+    format: String,
+    version: u32,
+    #[serde(default = "one")]
+    next_id: u32,
+    #[serde(default = "one")]
+    next_layer_id: u32,
+    #[serde(default = "one")]
+    next_group_id: u32,
+    active_layer: u32,
+    layers: Vec<LayerJson>,
+    #[serde(default)]
+    groups: Vec<GroupJson>,
+    #[serde(default)]
+    strokes: Vec<StrokeJson>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayerJson {
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default = "yes")]
+    visible: bool,
+    #[serde(default = "opaque")]
+    opacity: Num<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GroupJson {
+    id: u32,
+    #[serde(default)]
+    parent: Option<u32>,
+    #[serde(default = "identity")]
+    transform: [Num<f32>; 6],
+}
+
+#[derive(Serialize, Deserialize)]
+struct StrokeJson {
+    id: u32,
+    /// The active layer when left out.
+    #[serde(default)]
+    layer: Option<u32>,
+    #[serde(default)]
+    group: Option<u32>,
+    #[serde(default)]
+    brush: Option<String>,
+    /// Black when empty.
+    #[serde(default)]
+    color: Vec<Num<f32>>,
+    #[serde(default = "identity")]
+    transform: [Num<f32>; 6],
+    #[serde(default)]
+    points: Vec<Num<f32>>,
+    /// Padded with 1 to the point count.
+    #[serde(default)]
+    widths: Vec<Num<f32>>,
+    #[serde(default)]
+    timestamps: Vec<Num<f64>>,
+    #[serde(default)]
+    created: Num<f64>,
+    #[serde(default)]
+    pointer: PointerJson,
+    #[serde(default)]
+    author: String,
+    /// Rings of flat `x, y` pairs.
+    #[serde(default)]
+    fill: Option<Vec<Vec<Num<f32>>>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PointerJson {
+    Mouse,
+    Pen,
+    Touch,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl DocumentJson {
+    fn snapshot(self, brush_id: &dyn Fn(&str) -> u32) -> Option<Snapshot> {
+        if self.format != FORMAT || self.version > VERSION {
+            return None;
+        }
+        let active_layer = self.active_layer;
+        // A document always has a layer to draw on.
+        if !self.layers.iter().any(|l| l.id == active_layer) {
+            return None;
+        }
+        let layers = self.layers.into_iter().map(|l| Layer { id: l.id, name: l.name, visible: l.visible, opacity: l.opacity.0 }).collect();
+        let groups = self.groups.into_iter().map(|g| Group { id: g.id, parent: g.parent, transform: g.transform.map(|n| n.0) }).collect();
+        let strokes = self.strokes.into_iter().map(|s| s.stroke(active_layer, brush_id)).collect::<Option<Vec<_>>>()?;
+        // Counters, given or left out, move past every id in use.
+        Snapshot {
+            watermark: 0,
+            next_id: self.next_id,
+            next_layer_id: self.next_layer_id,
+            next_group_id: self.next_group_id,
+            active_layer,
+            layers,
+            groups,
+            strokes,
+        }
+        .checked()
+    }
+}
+
+impl StrokeJson {
+    fn new(s: &Stroke, brush: Option<String>) -> Self {
+        StrokeJson {
+            id: s.id,
+            layer: Some(s.layer),
+            group: s.group,
+            brush: Some(brush.unwrap_or_default()),
+            color: nums(&s.color),
+            transform: s.transform.map(Num),
+            points: nums(&s.points),
+            widths: nums(&s.widths),
+            timestamps: nums(&s.timestamps),
+            created: Num(s.created),
+            pointer: match s.pointer {
+                PointerType::Unknown => PointerJson::Unknown,
+                PointerType::Mouse => PointerJson::Mouse,
+                PointerType::Pen => PointerJson::Pen,
+                PointerType::Touch => PointerJson::Touch,
+            },
+            author: s.author.clone(),
+            fill: s.fill.as_ref().map(|rings| rings.iter().map(|ring| ring.iter().flat_map(|p| [Num(p.0), Num(p.1)]).collect()).collect()),
+        }
+    }
+
+    fn stroke(self, default_layer: u32, brush_id: &dyn Fn(&str) -> u32) -> Option<Stroke> {
+        let points = floats(self.points);
+        let mut widths = floats(self.widths);
+        widths.resize(points.len() / 2, 1.0);
+        let color = match floats(self.color)[..] {
+            [] => [0.0, 0.0, 0.0, 1.0],
+            [r, g, b, a] => [r, g, b, a],
+            _ => return None,
+        };
+        let transform: Affine = self.transform.map(|n| n.0);
+        Some(Stroke {
+            id: self.id,
+            points,
+            widths,
+            timestamps: floats(self.timestamps),
+            brush: self.brush.as_deref().map_or(0, brush_id),
+            color,
+            layer: self.layer.unwrap_or(default_layer),
+            group: self.group,
+            transform,
+            created: self.created.0,
+            pointer: match self.pointer {
+                PointerJson::Unknown => PointerType::Unknown,
+                PointerJson::Mouse => PointerType::Mouse,
+                PointerJson::Pen => PointerType::Pen,
+                PointerJson::Touch => PointerType::Touch,
+            },
+            author: self.author,
+            fill: self.fill.map(|rings| rings.into_iter().map(|ring| floats(ring).chunks_exact(2).map(|p| (p[0], p[1])).collect()).collect()),
+            meshes: Vec::new(),
+            indexed: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    fn layer_with(opacity: &str, name: &str) -> String {
+        format!(r#"{{"format":"ink-document","version":1,"active_layer":1,"layers":[{{"id":1,"name":"{name}","opacity":{opacity}}}]}}"#)
+    }
+
+    fn with_stroke(stroke: &str) -> String {
+        format!(r#"{{"format":"ink-document","version":1,"active_layer":1,"layers":[{{"id":1}}],"strokes":[{stroke}]}}"#)
+    }
+
+    #[test]
+    fn keeps_floats_and_nans() {
+        let mut doc = InkDocument::new();
+        let id = doc.add_stroke(&[0.1, 0.2, 1e-7, 3.4e38], &[0.3, 2.0], &[0.1, 16.7], &[0.1, 0.2, 0.3, 0.4], 0).id;
+        let layer = doc.add_layer("Notes \"quoted\"\n");
+        doc.set_stroke_layer(id, layer);
+        doc.group(&[id], &[]);
+        doc.strokes[0].created = f64::NAN;
+        doc.strokes[0].fill = Some(vec![vec![(0.1, 0.2), (1.0, 0.0), (0.0, 1.0)]]);
+        let text = doc.to_json();
+        let mut copy = InkDocument::new();
+        assert!(copy.from_json(&text));
+        assert_eq!(copy.to_json(), text);
+        assert!(copy.strokes[0].created.is_nan());
+        assert_eq!(copy.strokes[0].points, [0.1, 0.2, 1e-7, 3.4e38]);
+        assert_eq!(copy.strokes[0].timestamps, [0.1, 16.7]);
+    }
+
+    #[test]
+    fn fills_in_what_is_left_out() {
+        let mut doc = InkDocument::new();
+        assert!(doc.from_json(&with_stroke(r#"{"id":4,"points":[0,0,1,1],"widths":[3],"pointer":"stylus","extra":{"a":[1]}}"#)));
+        let s = &doc.strokes[0];
+        assert_eq!((s.layer, s.group, s.brush, s.pointer), (1, None, 0, PointerType::Unknown));
+        assert_eq!((s.widths.clone(), s.color, s.transform), (vec![3.0, 1.0], BLACK, transform::IDENTITY));
+        assert_eq!((doc.layers[0].name.as_str(), doc.layers[0].visible, doc.layers[0].opacity), ("", true, 1.0));
+        assert_eq!(doc.next_id, 5);
+    }
+
+    #[test]
+    fn rejects_json_that_is_not_strict() {
+        let mut doc = InkDocument::new();
+        assert!(doc.from_json(&layer_with("0.5", r"A")));
+        assert_eq!(doc.layers[0].name, "A");
+        for (opacity, name) in [("+1", ""), (".5", ""), ("01", ""), ("1.", ""), ("1", r"\u+041"), ("1", r"\u04")] {
+            assert!(!doc.from_json(&layer_with(opacity, name)), "{opacity} {name}");
+        }
+        assert!(!doc.from_json(&format!("{},", layer_with("1", ""))));
+        assert_eq!(doc.layers[0].name, "A");
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let deep = format!(r#"{{"format":"ink-document","version":1,"active_layer":1,"layers":[{{"id":1}}],"extra":{}}}"#, "[".repeat(100_000));
+        assert!(!InkDocument::new().from_json(&deep));
+    }
+
+    #[test]
+    fn rejects_broken_models() {
+        let mut doc = InkDocument::new();
+        let later = r#"{"format":"ink-document","version":2,"active_layer":1,"layers":[{"id":1}]}"#;
+        let other = r#"{"format":"svg","version":1,"active_layer":1,"layers":[{"id":1}]}"#;
+        let no_active = r#"{"format":"ink-document","version":1,"active_layer":2,"layers":[{"id":1}]}"#;
+        let no_layers = r#"{"format":"ink-document","version":1,"active_layer":1}"#;
+        let cycle = r#"{"format":"ink-document","version":1,"active_layer":1,"layers":[{"id":1}],"groups":[{"id":1,"parent":2},{"id":2,"parent":1}]}"#;
+        for text in [later, other, no_active, no_layers, cycle] {
+            assert!(!doc.from_json(text), "{text}");
+        }
+        for stroke in [r#"{"id":1,"layer":2}"#, r#"{"id":1,"group":1}"#, r#"{"id":1},{"id":1}"#, r#"{"id":4294967295}"#, r#"{"id":1.5}"#, r#"{"id":1,"color":[0,0,0]}"#, r#"{"id":1,"transform":[1,0,0,1,0]}"#] {
+            assert!(!doc.from_json(&with_stroke(stroke)), "{stroke}");
+        }
+        assert!(doc.strokes.is_empty() && doc.layers.len() == 1);
+    }
+}
@@ -1,190 +1,226 @@
 use wasm_bindgen::prelude::*;
 
+mod bezier;
+mod brush;
+mod buffer;
+mod builder;
+mod clipboard;
+mod codec;
+mod context;
+mod curve;
+mod dash;
+mod document;
+mod edit;
+mod erase;
+mod file;
+mod filter;
+mod geom;
+mod group;
+mod history;
+mod input;
+mod isf;
+mod json;
+mod mesh;
+mod msdf;
+mod noise;
+mod nib;
+mod outline;
+mod parallel;
+mod pdf;
+mod pencilkit;
+mod polygon;
+mod predict;
+mod preprocess;
+mod preset;
+#[cfg(feature = "protobuf")]
+mod proto;
+mod raster;
+mod replay;
+mod samples;
+mod schedule;
+mod sdf;
+mod select;
+mod simd;
+mod smooth;
+mod snapshot;
+mod spatial;
+mod stamp;
+mod svg;
+mod tessellate;
+mod thumbnail;
+mod tile;
+mod transform;
+mod version;
+mod whiteboard;
+
+pub use bezier::{build_mesh_beziers, fit_beziers, BezierPath};
+pub use brush::{
+    build_mesh_airbrush, build_mesh_dual_tone, build_mesh_eraser, build_mesh_fountain_pen,
+    build_mesh_highlighter, build_mesh_pencil, build_mesh_rainbow, build_mesh_sketch,
+    build_mesh_wet_ink, build_mesh_with_shadow, AirbrushOptions, BlendMode, BrushMesh,
+    FountainPenOptions, PencilOptions, RainbowOptions, ShadowOptions, ShadowedMesh, SketchOptions,
+    WetInkOptions,
+};
+pub use buffer::{alloc_f32_buffer, build_mesh_into, free_f32_buffer};
+pub use builder::{MeshDelta, StrokeBuilder};
+pub use context::MeshContext;
+pub use curve::{alphas_from_speed, Easing, PressureCurve};
+pub use dash::build_mesh_dashed;
+pub use document::{InkDocument, PointerType, StrokeChange, StrokeMetadata};
+pub use erase::{EraseFilter, EraseResult};
+pub use filter::{KalmanStabilizer, OneEuroFilter};
+pub use history::Recovery;
+pub use input::InputOptions;
+pub use mesh::{IndexFormat, IndexedMesh, MeshBatch, PlanarMesh, Topology, VertexLayout};
+pub use msdf::MultiDistanceField;
+pub use nib::{azimuth_from_tilt, build_mesh_nib, NibOptions};
+pub use outline::StrokeOutline;
+pub use parallel::parallel_enabled;
+#[cfg(all(feature = "parallel", target_arch = "wasm32"))]
+pub use parallel::init_thread_pool;
+pub use predict::{predict_tail, PredictedTail};
+pub use preprocess::{clean_points, reject_width_outliers, resample_by_arclength, simplify_rdp};
+pub use preset::{Brush, BrushRegistry, BrushStyle};
+pub use replay::{Playback, PlaybackFrame, TimeLapse};
+pub use samples::StrokeSamples;
+pub use schedule::TessellationScheduler;
+pub use sdf::DistanceField;
+pub use select::{selection_matrix, SelectMode, SelectionEdit};
+pub use simd::simd_enabled;
+pub use smooth::{detect_corners, smooth_catmull_rom, smooth_moving_average, smooth_preserving_corners};
+pub use snapshot::{apply_patch, diff_snapshots};
+pub use stamp::{build_stamps, StampOptions};
+pub use tessellate::{CapStyle, Decoration, JoinStyle, MeshOptions};
+pub use thumbnail::{PageTemplate, Thumbnail, ThumbnailOptions};
+
+use mesh::Mesh;
+
 #[wasm_bindgen]
 pub fn build_mesh(points: &[f32], widths: &[f32], color: &[f32]) -> Vec<f32> {
-    let n = points.len() / 2;
-    if n == 0 {
-        return Vec::new();
-    }
-    let r = *color.get(0).unwrap_or(&0.0);
-    let g = *color.get(1).unwrap_or(&0.0);
-    let b = *color.get(2).unwrap_or(&0.0);
-    let a = *color.get(3).unwrap_or(&1.0);
-
-    if n == 1 {
-        let radius = widths.get(0).unwrap_or(&1.0) * 0.5;
-        return circle_mesh(points[0], points[1], radius, r, g, b, a);
-    }
+    build_mesh_with_options(points, widths, color, &MeshOptions::default())
+}
 
-    let mut dirs: Vec<(f32, f32)> = Vec::with_capacity(n - 1);
-    let mut norms: Vec<(f32, f32)> = Vec::with_capacity(n - 1);
-    for i in 0..(n - 1) {
-        let x0 = points[i * 2];
-        let y0 = points[i * 2 + 1];
-        let x1 = points[(i + 1) * 2];
-        let y1 = points[(i + 1) * 2 + 1];
-        let dx = x1 - x0;
-        let dy = y1 - y0;
-        let len = (dx * dx + dy * dy).sqrt().max(1e-6);
-        let dir = (dx / len, dy / len);
-        dirs.push(dir);
-        norms.push((-dir.1, dir.0));
-    }
+#[wasm_bindgen]
+pub fn build_mesh_with_options(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions) -> Vec<f32> {
+    // Restart indices have no meaning once the strip is expanded into vertices.
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(points, widths, rgba(color), options, &mut mesh);
+    mesh.triangle_soup()
+}
 
-    let mut left_prev: Vec<(f32, f32)> = vec![(0.0, 0.0); n];
-    let mut right_prev: Vec<(f32, f32)> = vec![(0.0, 0.0); n];
-    let mut left_next: Vec<(f32, f32)> = vec![(0.0, 0.0); n];
-    let mut right_next: Vec<(f32, f32)> = vec![(0.0, 0.0); n];
-    let mut join: Vec<bool> = vec![true; n]; // true = miter, false = round
-
-    for i in 0..n {
-        let px = points[i * 2];
-        let py = points[i * 2 + 1];
-        let radius = widths.get(i).unwrap_or(&1.0) * 0.5;
-        if i == 0 {
-            let n0 = norms[0];
-            left_prev[i] = (px + n0.0 * radius, py + n0.1 * radius);
-            right_prev[i] = (px - n0.0 * radius, py - n0.1 * radius);
-            left_next[i] = left_prev[i];
-            right_next[i] = right_prev[i];
-            continue;
-        }
-        if i == n - 1 {
-            let n0 = norms[n - 2];
-            left_prev[i] = (px + n0.0 * radius, py + n0.1 * radius);
-            right_prev[i] = (px - n0.0 * radius, py - n0.1 * radius);
-            left_next[i] = left_prev[i];
-            right_next[i] = right_prev[i];
-            continue;
-        }
-
-        let n0 = norms[i - 1];
-        let n1 = norms[i];
-        let miter = (n0.0 + n1.0, n0.1 + n1.1);
-        let miter_len = (miter.0 * miter.0 + miter.1 * miter.1).sqrt();
-        if miter_len < 1e-4 {
-            left_prev[i] = (px + n0.0 * radius, py + n0.1 * radius);
-            right_prev[i] = (px - n0.0 * radius, py - n0.1 * radius);
-            left_next[i] = (px + n1.0 * radius, py + n1.1 * radius);
-            right_next[i] = (px - n1.0 * radius, py - n1.1 * radius);
-            join[i] = false;
-            continue;
-        }
-
-        let mdir = (miter.0 / miter_len, miter.1 / miter_len);
-        let dot = mdir.0 * n1.0 + mdir.1 * n1.1;
-        let miter_length = if dot.abs() > 1e-6 { radius / dot } else { radius };
-        let miter_limit = 4.0;
-
-        if miter_length.abs() <= miter_limit * radius {
-            left_prev[i] = (px + mdir.0 * miter_length, py + mdir.1 * miter_length);
-            right_prev[i] = (px - mdir.0 * miter_length, py - mdir.1 * miter_length);
-            left_next[i] = left_prev[i];
-            right_next[i] = right_prev[i];
-        } else {
-            left_prev[i] = (px + n0.0 * radius, py + n0.1 * radius);
-            right_prev[i] = (px - n0.0 * radius, py - n0.1 * radius);
-            left_next[i] = (px + n1.0 * radius, py + n1.1 * radius);
-            right_next[i] = (px - n1.0 * radius, py - n1.1 * radius);
-            join[i] = false;
-        }
-    }
+/// Like `build_mesh_with_options` with the vertex attributes chosen by
+/// `layout`; UVs and normals are only computed when the layout has them.
+#[wasm_bindgen]
+pub fn build_mesh_with_layout(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions, layout: VertexLayout) -> Vec<f32> {
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate_with_layout(points, widths, rgba(color), options, layout, &mut mesh);
+    mesh.triangle_soup_with(layout)
+}
 
-    let mut vertices: Vec<f32> = Vec::new();
+/// Like `build_mesh_with_layout` with each attribute in its own array.
+#[wasm_bindgen]
+pub fn build_mesh_planar(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions, layout: VertexLayout) -> PlanarMesh {
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate_with_layout(points, widths, rgba(color), options, layout, &mut mesh);
+    PlanarMesh::with_layout(&mesh, layout)
+}
 
-    for i in 0..(n - 1) {
-        let l0 = left_next[i];
-        let r0 = right_next[i];
-        let l1 = left_prev[i + 1];
-        let r1 = right_prev[i + 1];
-        push_tri(&mut vertices, l0, r0, r1, r, g, b, a);
-        push_tri(&mut vertices, l0, r1, l1, r, g, b, a);
+#[wasm_bindgen]
+pub fn build_mesh_indexed_with_layout(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions, layout: VertexLayout) -> IndexedMesh {
+    let mut mesh = Mesh::with_topology(options.topology, options.primitive_restart);
+    tessellate_with_layout(points, widths, rgba(color), options, layout, &mut mesh);
+    if options.weld {
+        mesh.weld(options.weld_tolerance);
     }
+    IndexedMesh::with_layout(mesh, layout)
+}
 
-    for i in 1..(n - 1) {
-        if join[i] {
-            continue;
-        }
-        let px = points[i * 2];
-        let py = points[i * 2 + 1];
-        let radius = widths.get(i).unwrap_or(&1.0) * 0.5;
-        let d0 = dirs[i - 1];
-        let d1 = dirs[i];
-        let n0 = (-d0.1, d0.0);
-        let n1 = (-d1.1, d1.0);
-        let cross = d0.0 * d1.1 - d0.1 * d1.0;
-        let outer0 = if cross >= 0.0 { n0 } else { (-n0.0, -n0.1) };
-        let outer1 = if cross >= 0.0 { n1 } else { (-n1.0, -n1.1) };
-        let mut a0 = outer0.1.atan2(outer0.0);
-        let mut a1 = outer1.1.atan2(outer1.0);
-        while a1 < a0 {
-            a1 += std::f32::consts::PI * 2.0;
-        }
-        let angle = a1 - a0;
-        let steps = ((angle / (std::f32::consts::PI / 8.0)).ceil() as usize).max(4);
-        for s in 0..steps {
-            let t0 = a0 + angle * (s as f32) / (steps as f32);
-            let t1 = a0 + angle * ((s + 1) as f32) / (steps as f32);
-            let p0 = (px + t0.cos() * radius, py + t0.sin() * radius);
-            let p1 = (px + t1.cos() * radius, py + t1.sin() * radius);
-            push_tri(&mut vertices, (px, py), p0, p1, r, g, b, a);
-        }
-    }
+fn tessellate_with_layout(points: &[f32], widths: &[f32], color: [f32; 4], options: &MeshOptions, layout: VertexLayout, mesh: &mut Mesh) {
+    tessellate::tessellate_shaded(points, widths, color, &tessellate::Shading::for_layout(layout), options, mesh);
+}
 
-    // Round caps
-    let (sx, sy) = (points[0], points[1]);
-    let (ex, ey) = (points[(n - 1) * 2], points[(n - 1) * 2 + 1]);
-    let n0 = norms[0];
-    let n1 = norms[n - 2];
-    cap_mesh(&mut vertices, (sx, sy), n0, widths.get(0).unwrap_or(&1.0) * 0.5, r, g, b, a);
-    cap_mesh(&mut vertices, (ex, ey), n1, widths.get(n - 1).unwrap_or(&1.0) * 0.5, r, g, b, a);
+/// Like `build_mesh_with_options` with an RGBA color per point in `colors`,
+/// blended smoothly along the stroke. Points past the end of `colors` reuse
+/// the last color given.
+#[wasm_bindgen]
+pub fn build_mesh_with_colors(points: &[f32], widths: &[f32], colors: &[f32], options: &MeshOptions) -> Vec<f32> {
+    let colors = rgba_per_point(colors, points.len() / 2);
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate_colored(points, widths, rgba(&[]), &colors, options, &mut mesh);
+    mesh.triangle_soup()
+}
 
-    vertices
+/// Like `build_mesh_with_options` with an opacity per point in `alphas`,
+/// multiplied into the color's alpha and blended smoothly along the stroke.
+/// Points past the end of `alphas` are opaque.
+#[wasm_bindgen]
+pub fn build_mesh_with_alpha(points: &[f32], widths: &[f32], alphas: &[f32], color: &[f32], options: &MeshOptions) -> Vec<f32> {
+    let color = rgba(color);
+    let colors: Vec<[f32; 4]> = (0..points.len() / 2)
+        .map(|i| [color[0], color[1], color[2], color[3] * alphas.get(i).copied().unwrap_or(1.0).clamp(0.0, 1.0)])
+        .collect();
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate_colored(points, widths, color, &colors, options, &mut mesh);
+    mesh.triangle_soup()
 }
 
+/// Like `build_mesh_with_options` with raw pen pressures in place of widths,
+/// mapped through `curve`.
+#[wasm_bindgen]
+pub fn build_mesh_with_pressure(points: &[f32], pressures: &[f32], color: &[f32], options: &MeshOptions, curve: &PressureCurve) -> Vec<f32> {
+    let n = points.len() / 2;
+    let widths: Vec<f32> = (0..n).map(|i| curve.width(pressures.get(i).copied().unwrap_or(1.0))).collect();
+    build_mesh_with_options(points, &widths, color, options)
+}
 
-fn circle_mesh(cx: f32, cy: f32, radius: f32, r: f32, g: f32, b: f32, a: f32) -> Vec<f32> {
-    let mut vertices = Vec::new();
-    let steps = 24;
-    for i in 0..steps {
-        let a0 = (i as f32) / (steps as f32) * std::f32::consts::PI * 2.0;
-        let a1 = ((i + 1) as f32) / (steps as f32) * std::f32::consts::PI * 2.0;
-        let p0 = (cx + a0.cos() * radius, cy + a0.sin() * radius);
-        let p1 = (cx + a1.cos() * radius, cy + a1.sin() * radius);
-        push_tri(&mut vertices, (cx, cy), p0, p1, r, g, b, a);
-    }
-    vertices
+#[wasm_bindgen]
+pub fn build_mesh_indexed(points: &[f32], widths: &[f32], color: &[f32]) -> IndexedMesh {
+    build_mesh_indexed_with_options(points, widths, color, &MeshOptions::default())
 }
 
-fn cap_mesh(vertices: &mut Vec<f32>, center: (f32, f32), normal: (f32, f32), radius: f32, r: f32, g: f32, b: f32, a: f32) {
-    let (cx, cy) = center;
-    let mut a0 = (-normal.1).atan2(-normal.0);
-    let mut a1 = normal.1.atan2(normal.0);
-    while a1 < a0 {
-        a1 += std::f32::consts::PI * 2.0;
+#[wasm_bindgen]
+pub fn build_mesh_indexed_with_options(points: &[f32], widths: &[f32], color: &[f32], options: &MeshOptions) -> IndexedMesh {
+    let mut mesh = Mesh::with_topology(options.topology, options.primitive_restart);
+    tessellate::tessellate(points, widths, rgba(color), options, &mut mesh);
+    if options.weld {
+        mesh.weld(options.weld_tolerance);
     }
-    let angle = a1 - a0;
-    let steps = ((angle / (std::f32::consts::PI / 10.0)).ceil() as usize).max(6);
-    for s in 0..steps {
-        let t0 = a0 + angle * (s as f32) / (steps as f32);
-        let t1 = a0 + angle * ((s + 1) as f32) / (steps as f32);
-        let p0 = (cx + t0.cos() * radius, cy + t0.sin() * radius);
-        let p1 = (cx + t1.cos() * radius, cy + t1.sin() * radius);
-        push_tri(vertices, (cx, cy), p0, p1, r, g, b, a);
+    mesh.into()
+}
+
+/// Tessellates many strokes in one call. `points` and `widths` hold every
+/// stroke back to back; stroke `k` starts at point `offsets[k]` and runs to
+/// the next offset (or the end), with its RGBA color at `colors[4k..4k + 4]`.
+/// Stroke ids in the result are indices into `offsets`.
+#[wasm_bindgen]
+pub fn build_meshes_batch(points: &[f32], widths: &[f32], offsets: &[u32], colors: &[f32], options: &MeshOptions) -> MeshBatch {
+    let n = points.len() / 2;
+    let mut batch = MeshBatch::default();
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    for (k, &start) in offsets.iter().enumerate() {
+        let start = (start as usize).min(n);
+        let end = offsets.get(k + 1).map_or(n, |&e| (e as usize).clamp(start, n));
+        let color = colors.get(k * 4..).unwrap_or(&[]);
+        mesh.clear();
+        tessellate::tessellate(&points[start * 2..end * 2], widths.get(start..end.min(widths.len())).unwrap_or(&[]), rgba(color), options, &mut mesh);
+        batch.push_mesh(k as u32, &mesh);
     }
+    batch
 }
 
-fn push_tri(out: &mut Vec<f32>, a: (f32, f32), b: (f32, f32), c: (f32, f32), r: f32, g: f32, bcol: f32, acol: f32) {
-    push_vertex(out, a.0, a.1, r, g, bcol, acol);
-    push_vertex(out, b.0, b.1, r, g, bcol, acol);
-    push_vertex(out, c.0, c.1, r, g, bcol, acol);
+pub(crate) fn rgba(color: &[f32]) -> [f32; 4] {
+    [
+        color.first().copied().unwrap_or(0.0),
+        color.get(1).copied().unwrap_or(0.0),
+        color.get(2).copied().unwrap_or(0.0),
+        color.get(3).copied().unwrap_or(1.0),
+    ]
 }
 
-fn push_vertex(out: &mut Vec<f32>, x: f32, y: f32, r: f32, g: f32, b: f32, a: f32) {
-    out.push(x);
-    out.push(y);
-    out.push(r);
-    out.push(g);
-    out.push(b);
-    out.push(a);
+pub(crate) fn rgba_per_point(colors: &[f32], n: usize) -> Vec<[f32; 4]> {
+    let given = colors.len() / 4;
+    if given == 0 {
+        return Vec::new();
+    }
+    (0..n).map(|i| rgba(&colors[i.min(given - 1) * 4..])).collect()
 }
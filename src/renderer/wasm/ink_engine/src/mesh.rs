@@ -0,0 +1,587 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{length, sub, Point};
+use crate::simd::F32x4;
+
+pub(crate) const FLOATS_PER_VERTEX: usize = 6;
+
+/// Index that ends one strip and starts the next when primitive restart is enabled.
+pub(crate) const STRIP_RESTART: u32 = u32::MAX;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Topology {
+    #[default]
+    TriangleList,
+    /// A single strip; separate pieces are stitched with degenerate triangles,
+    /// or with `STRIP_RESTART` when primitive restart is requested.
+    TriangleStrip,
+}
+
+/// Which attributes each output vertex carries, interleaved in this order.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum VertexLayout {
+    /// `[x, y, r, g, b, a]`, what `build_mesh` has always returned.
+    #[default]
+    PositionColor,
+    /// `[x, y, u, v, r, g, b, a]`, the same order as stamp vertices. `u` is
+    /// the arc length along the stroke in stroke units, negative over the
+    /// start cap; `v` runs from 0 on the left edge to 1 on the right.
+    PositionUvColor,
+    /// `[x, y, nx, ny, nz, side, r, g, b, a]`, for lighting or extruding
+    /// ink in a 3D scene. The normal is that of the stroke modelled as a half
+    /// tube as wide as the stroke: straight up on the centreline, turning out
+    /// to level at the outline and round the caps. `side` runs across the
+    /// ribbon from -1 on the left edge to 1 on the right.
+    PositionNormalColor,
+}
+
+impl VertexLayout {
+    pub(crate) fn floats(self) -> usize {
+        match self {
+            VertexLayout::PositionColor => FLOATS_PER_VERTEX,
+            VertexLayout::PositionUvColor => FLOATS_PER_VERTEX + 2,
+            VertexLayout::PositionNormalColor => FLOATS_PER_VERTEX + 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Vertex {
+    pub pos: Point,
+    /// Only filled in by tessellation that asks for UVs.
+    pub uv: Point,
+    pub color: [f32; 4],
+    /// Only filled in by tessellation that asks for normals; `UP` and 0
+    /// otherwise.
+    pub normal: [f32; 3],
+    pub side: f32,
+}
+
+/// Normal of flat ink, facing the viewer.
+pub(crate) const UP: [f32; 3] = [0.0, 0.0, 1.0];
+
+#[derive(Default)]
+pub(crate) struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub topology: Topology,
+    restart: bool,
+    /// Strip indices written since the last restart, for winding parity.
+    strip_len: usize,
+    /// Polygon zigzag being converted to a strip, kept for its capacity.
+    zigzag: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn with_topology(topology: Topology, restart: bool) -> Self {
+        Mesh {
+            topology,
+            restart,
+            ..Mesh::default()
+        }
+    }
+
+    /// Empties the mesh for reuse, keeping its allocations and settings.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.strip_len = 0;
+    }
+
+    /// `clear` with new settings, as `with_topology` would make them.
+    pub fn reset(&mut self, topology: Topology, restart: bool) {
+        self.clear();
+        self.topology = topology;
+        self.restart = restart;
+    }
+
+    pub fn push_vertex(&mut self, pos: Point, color: [f32; 4]) -> u32 {
+        self.push_vertex_uv(pos, (0.0, 0.0), color)
+    }
+
+    pub fn push_vertex_uv(&mut self, pos: Point, uv: Point, color: [f32; 4]) -> u32 {
+        self.push(Vertex {
+            pos,
+            uv,
+            color,
+            normal: UP,
+            side: 0.0,
+        })
+    }
+
+    pub fn push(&mut self, vertex: Vertex) -> u32 {
+        self.vertices.push(vertex);
+        (self.vertices.len() - 1) as u32
+    }
+
+    pub fn push_tri(&mut self, a: u32, b: u32, c: u32) {
+        match self.topology {
+            Topology::TriangleList => self.indices.extend_from_slice(&[a, b, c]),
+            Topology::TriangleStrip => self.push_strip(&[a, b, c]),
+        }
+    }
+
+    /// Appends strip-ordered vertices, converting to triangles in list mode.
+    pub fn push_strip(&mut self, strip: &[u32]) {
+        if strip.len() < 3 {
+            return;
+        }
+        if self.topology == Topology::TriangleList {
+            for k in 0..strip.len() - 2 {
+                if k.is_multiple_of(2) {
+                    self.push_tri(strip[k], strip[k + 1], strip[k + 2]);
+                } else {
+                    self.push_tri(strip[k + 1], strip[k], strip[k + 2]);
+                }
+            }
+            return;
+        }
+        if self.strip_len > 0 {
+            if self.restart {
+                self.indices.push(STRIP_RESTART);
+                self.strip_len = 0;
+            } else {
+                let last = self.indices[self.indices.len() - 1];
+                self.indices.push(last);
+                self.indices.push(strip[0]);
+                self.strip_len += 2;
+                // Keep the new piece starting on an even position so its winding is preserved.
+                if self.strip_len.is_multiple_of(2) {
+                    self.indices.push(strip[0]);
+                    self.strip_len += 1;
+                }
+            }
+        }
+        self.indices.extend_from_slice(strip);
+        self.strip_len += strip.len();
+    }
+
+    /// Convex polygon given as a hub followed by its rim, e.g. a join or cap
+    /// wedge. Lists fan out from the hub; strips zigzag across the polygon.
+    pub fn push_fan(&mut self, hub: u32, rim: &[u32]) {
+        if self.topology == Topology::TriangleList {
+            for k in 1..rim.len() {
+                self.push_tri(hub, rim[k - 1], rim[k]);
+            }
+            return;
+        }
+        self.push_zigzag(rim.len() + 1, |k| if k == 0 { hub } else { rim[k - 1] });
+    }
+
+    /// Convex polygon without a hub vertex.
+    pub fn push_polygon(&mut self, poly: &[u32]) {
+        self.push_zigzag(poly.len(), |k| poly[k]);
+    }
+
+    /// Strips the convex polygon of `len` vertices `at(0)..at(len - 1)` by
+    /// zigzagging from the first vertex between its two sides.
+    fn push_zigzag(&mut self, len: usize, at: impl Fn(usize) -> u32) {
+        if len < 3 {
+            return;
+        }
+        let (mut lo, mut hi) = (1, len - 1);
+        let mut strip = std::mem::take(&mut self.zigzag);
+        strip.clear();
+        strip.push(at(0));
+        while lo <= hi {
+            strip.push(at(lo));
+            lo += 1;
+            if lo <= hi {
+                strip.push(at(hi));
+                hi -= 1;
+            }
+        }
+        self.push_strip(&strip);
+        self.zigzag = strip;
+    }
+
+    /// Merges vertices with identical attributes whose positions are at most
+    /// `tolerance` apart into the first of them, and drops the list
+    /// triangles that collapse as a result; at 0 only exact duplicates
+    /// merge. See `MeshOptions::weld`.
+    pub fn weld(&mut self, tolerance: f32) {
+        let tolerance = tolerance.max(0.0);
+        // Exact duplicates share a cell keyed on their bits; otherwise a
+        // match may sit in any neighbouring cell.
+        let cell = |p: Point| {
+            if tolerance > 0.0 {
+                ((p.0 / tolerance).floor() as i32, (p.1 / tolerance).floor() as i32)
+            } else {
+                (p.0.to_bits() as i32, p.1.to_bits() as i32)
+            }
+        };
+        let reach = if tolerance > 0.0 { 1 } else { 0 };
+        let same = |a: &Vertex, b: &Vertex| a.uv == b.uv && a.color == b.color && a.normal == b.normal && a.side == b.side && length(sub(a.pos, b.pos)) <= tolerance;
+        let mut grid: HashMap<(i32, i32), Vec<u32>> = HashMap::new();
+        let mut kept: Vec<Vertex> = Vec::with_capacity(self.vertices.len());
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        for v in &self.vertices {
+            let (cx, cy) = cell(v.pos);
+            let found = (-reach..=reach)
+                .flat_map(|dx| (-reach..=reach).map(move |dy| (cx.wrapping_add(dx), cy.wrapping_add(dy))))
+                .filter_map(|c| grid.get(&c))
+                .flatten()
+                .copied()
+                .find(|&k| same(&kept[k as usize], v));
+            remap.push(found.unwrap_or_else(|| {
+                kept.push(*v);
+                let k = (kept.len() - 1) as u32;
+                grid.entry((cx, cy)).or_default().push(k);
+                k
+            }));
+        }
+        self.vertices = kept;
+        for i in self.indices.iter_mut().filter(|i| **i != STRIP_RESTART) {
+            *i = remap[*i as usize];
+        }
+        if self.topology == Topology::TriangleList {
+            let mut tris = Vec::with_capacity(self.indices.len());
+            for t in self.indices.chunks_exact(3) {
+                if t[0] != t[1] && t[1] != t[2] && t[0] != t[2] {
+                    tris.extend_from_slice(t);
+                }
+            }
+            self.indices = tris;
+        }
+    }
+
+    /// Interleaved vertex attributes, one entry per unique vertex.
+    pub fn vertex_data_with(&self, layout: VertexLayout) -> Vec<f32> {
+        let mut out = vec![0.0; self.vertices.len() * layout.floats()];
+        write_vertices(&mut out, self.vertices.iter(), layout);
+        out
+    }
+
+    /// Expands the index buffer into flat vertices: the triangle soup `build_mesh`
+    /// has always returned, or the strip sequence in strip mode.
+    pub fn triangle_soup(&self) -> Vec<f32> {
+        self.triangle_soup_with(VertexLayout::PositionColor)
+    }
+
+    pub fn triangle_soup_with(&self, layout: VertexLayout) -> Vec<f32> {
+        let mut out = vec![0.0; self.indices.len() * layout.floats()];
+        self.write_triangle_soup_with(&mut out, layout);
+        out
+    }
+
+    /// Floats `triangle_soup` produces.
+    pub fn soup_len(&self) -> usize {
+        self.indices.len() * FLOATS_PER_VERTEX
+    }
+
+    /// `triangle_soup` written into `out`, which must hold `soup_len` floats.
+    pub fn write_triangle_soup(&self, out: &mut [f32]) {
+        self.write_triangle_soup_with(out, VertexLayout::PositionColor);
+    }
+
+    /// `triangle_soup_with` written into `out`, which must hold one vertex
+    /// of `layout` per index.
+    pub fn write_triangle_soup_with(&self, out: &mut [f32], layout: VertexLayout) {
+        write_vertices(out, self.indices.iter().map(|&i| &self.vertices[i as usize]), layout);
+    }
+}
+
+/// Multiplies red, green and blue by alpha, see
+/// `MeshOptions::premultiplied_alpha`.
+pub(crate) fn premultiply(vertices: &mut [Vertex]) {
+    for v in vertices {
+        let a = v.color[3];
+        v.color[..3].iter_mut().for_each(|c| *c *= a);
+    }
+}
+
+/// `[min_x, min_y, max_x, max_y]` of flat vertex data, or `None` when empty.
+pub(crate) fn bounds(vertices: &[f32]) -> Option<[f32; 4]> {
+    strided_bounds(vertices, FLOATS_PER_VERTEX)
+}
+
+/// As `bounds` for vertices of `stride` floats that start with `x, y`.
+pub(crate) fn strided_bounds(vertices: &[f32], stride: usize) -> Option<[f32; 4]> {
+    vertices.chunks_exact(stride).fold(None, |acc, v| {
+        let [x0, y0, x1, y1] = acc.unwrap_or([v[0], v[1], v[0], v[1]]);
+        Some([x0.min(v[0]), y0.min(v[1]), x1.max(v[0]), y1.max(v[1])])
+    })
+}
+
+/// Smallest rectangle covering both, either of which may be missing.
+pub(crate) fn union_bounds(a: Option<[f32; 4]>, b: Option<[f32; 4]>) -> Option<[f32; 4]> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some([a[0].min(b[0]), a[1].min(b[1]), a[2].max(b[2]), a[3].max(b[3])]),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Writes `vertices` back to back into `out`, `layout.floats()` each, four
+/// attributes at a time.
+fn write_vertices<'a>(out: &mut [f32], vertices: impl Iterator<Item = &'a Vertex>, layout: VertexLayout) {
+    let chunks = out.chunks_exact_mut(layout.floats()).zip(vertices);
+    match layout {
+        VertexLayout::PositionColor => {
+            for (dst, v) in chunks {
+                dst[..2].copy_from_slice(&[v.pos.0, v.pos.1]);
+                F32x4::load(&v.color).store(&mut dst[2..]);
+            }
+        }
+        VertexLayout::PositionUvColor => {
+            for (dst, v) in chunks {
+                F32x4::pair(v.pos, v.uv).store(dst);
+                F32x4::load(&v.color).store(&mut dst[4..]);
+            }
+        }
+        VertexLayout::PositionNormalColor => {
+            for (dst, v) in chunks {
+                let [x, y, z] = v.normal;
+                dst[..2].copy_from_slice(&[v.pos.0, v.pos.1]);
+                F32x4::new(x, y, z, v.side).store(&mut dst[2..]);
+                F32x4::load(&v.color).store(&mut dst[6..]);
+            }
+        }
+    }
+}
+
+/// Index type of an `IndexedMesh`'s element buffer, WebGL's
+/// `UNSIGNED_SHORT` or `UNSIGNED_INT`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IndexFormat {
+    Uint16,
+    /// Past 65535 vertices; WebGL 1 needs `OES_element_index_uint` for it.
+    Uint32,
+}
+
+#[wasm_bindgen]
+pub struct IndexedMesh {
+    vertices: Vec<f32>,
+    indices: Vec<u32>,
+    layout: VertexLayout,
+}
+
+#[wasm_bindgen]
+impl IndexedMesh {
+    pub fn vertices(&self) -> Vec<f32> {
+        self.vertices.clone()
+    }
+
+    pub fn indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.layout.floats()
+    }
+
+    pub fn layout(&self) -> VertexLayout {
+        self.layout
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// The narrowest index type every index fits: 16 bits up to 65535
+    /// vertices, leaving `0xFFFF` free as the 16-bit primitive restart index.
+    pub fn index_format(&self) -> IndexFormat {
+        if self.vertex_count() <= u16::MAX as usize {
+            IndexFormat::Uint16
+        } else {
+            IndexFormat::Uint32
+        }
+    }
+
+    /// `indices` at half the size, restarts as `0xFFFF`, when `index_format`
+    /// is `Uint16`; empty otherwise, in which case use `indices`.
+    pub fn indices_u16(&self) -> Vec<u16> {
+        if self.index_format() == IndexFormat::Uint32 {
+            return Vec::new();
+        }
+        self.indices.iter().map(|&i| if i == STRIP_RESTART { u16::MAX } else { i as u16 }).collect()
+    }
+}
+
+impl From<Mesh> for IndexedMesh {
+    fn from(mesh: Mesh) -> Self {
+        IndexedMesh::with_layout(mesh, VertexLayout::PositionColor)
+    }
+}
+
+impl IndexedMesh {
+    pub(crate) fn with_layout(mesh: Mesh, layout: VertexLayout) -> Self {
+        IndexedMesh {
+            vertices: mesh.vertex_data_with(layout),
+            indices: mesh.indices,
+            layout,
+        }
+    }
+}
+
+/// Triangle soup with each attribute in its own array instead of
+/// interleaved, for renderers that bind them as separate buffers. One that
+/// draws a stroke's color from a uniform uploads `positions` and, when
+/// fringes or alpha vary, `alphas`, and never the colors. Attributes the
+/// layout lacks are empty.
+#[wasm_bindgen]
+pub struct PlanarMesh {
+    layout: VertexLayout,
+    positions: Vec<f32>,
+    uvs: Vec<f32>,
+    normals: Vec<f32>,
+    sides: Vec<f32>,
+    colors: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl PlanarMesh {
+    /// `[x, y]` per vertex.
+    pub fn positions(&self) -> Vec<f32> {
+        self.positions.clone()
+    }
+
+    /// `[u, v]` per vertex, as `VertexLayout::PositionUvColor`.
+    pub fn uvs(&self) -> Vec<f32> {
+        self.uvs.clone()
+    }
+
+    /// `[nx, ny, nz]` per vertex, as `VertexLayout::PositionNormalColor`.
+    pub fn normals(&self) -> Vec<f32> {
+        self.normals.clone()
+    }
+
+    /// `side` per vertex, as `VertexLayout::PositionNormalColor`.
+    pub fn sides(&self) -> Vec<f32> {
+        self.sides.clone()
+    }
+
+    /// `[r, g, b, a]` per vertex.
+    pub fn colors(&self) -> Vec<f32> {
+        self.colors.clone()
+    }
+
+    /// Just the alpha of `colors`, one float per vertex.
+    pub fn alphas(&self) -> Vec<f32> {
+        self.colors.iter().skip(3).step_by(4).copied().collect()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len() / 2
+    }
+
+    pub fn layout(&self) -> VertexLayout {
+        self.layout
+    }
+}
+
+impl PlanarMesh {
+    /// The mesh's triangle soup split into the attributes of `layout`.
+    pub(crate) fn with_layout(mesh: &Mesh, layout: VertexLayout) -> Self {
+        let n = mesh.indices.len();
+        let mut planar = PlanarMesh {
+            layout,
+            positions: Vec::with_capacity(n * 2),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            sides: Vec::new(),
+            colors: Vec::with_capacity(n * 4),
+        };
+        for v in mesh.indices.iter().map(|&i| &mesh.vertices[i as usize]) {
+            planar.positions.extend_from_slice(&[v.pos.0, v.pos.1]);
+            planar.colors.extend_from_slice(&v.color);
+            match layout {
+                VertexLayout::PositionColor => {}
+                VertexLayout::PositionUvColor => planar.uvs.extend_from_slice(&[v.uv.0, v.uv.1]),
+                VertexLayout::PositionNormalColor => {
+                    planar.normals.extend_from_slice(&v.normal);
+                    planar.sides.push(v.side);
+                }
+            }
+        }
+        planar
+    }
+}
+
+/// Several strokes' triangle soup in one vertex buffer. Stroke `k` is drawn
+/// from vertex `ranges[2k]` for `ranges[2k + 1]` vertices.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct MeshBatch {
+    ids: Vec<u32>,
+    ranges: Vec<u32>,
+    vertices: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl MeshBatch {
+    pub fn ids(&self) -> Vec<u32> {
+        self.ids.clone()
+    }
+
+    /// `[first_vertex, vertex_count]` per stroke.
+    pub fn ranges(&self) -> Vec<u32> {
+        self.ranges.clone()
+    }
+
+    pub fn vertices(&self) -> Vec<f32> {
+        self.vertices.clone()
+    }
+
+    /// Where `vertices` starts in linear memory, to view it as
+    /// `new Float32Array(memory.buffer, batch.vertices_ptr(),
+    /// batch.vertices_len())` instead of copying it. The view is valid until
+    /// the batch is freed or `memory.buffer` grows, which any later call
+    /// into the engine may do; upload it before making another call.
+    pub fn vertices_ptr(&self) -> *const f32 {
+        self.vertices.as_ptr()
+    }
+
+    pub fn vertices_len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    /// `vertices` as `[x, y, z, r, g, b, a]`, so the whole batch can be
+    /// drawn in one call with depth testing instead of one ordered call per
+    /// stroke. Stroke `k` of `n` sits at `z = 1 - (k + 1) / (n + 1)`: from
+    /// just under 1 for the first to just over 0 for the last, so a later
+    /// stroke passes a `LESS` test over anything earlier. Under `LESS` a
+    /// stroke also covers each pixel once, so translucent ink does not
+    /// darken where it crosses itself. A 16-bit depth buffer tells some
+    /// 65000 strokes apart; larger batches want 24 bits.
+    pub fn vertices_with_depth(&self) -> Vec<f32> {
+        let step = 1.0 / (self.ids.len() + 1) as f32;
+        let mut out = Vec::with_capacity(self.vertices.len() / FLOATS_PER_VERTEX * (FLOATS_PER_VERTEX + 1));
+        for (k, range) in self.ranges.chunks_exact(2).enumerate() {
+            let z = 1.0 - (k + 1) as f32 * step;
+            let (first, count) = (range[0] as usize, range[1] as usize);
+            for v in self.vertices[first * FLOATS_PER_VERTEX..(first + count) * FLOATS_PER_VERTEX].chunks_exact(FLOATS_PER_VERTEX) {
+                out.extend_from_slice(&[v[0], v[1], z]);
+                out.extend_from_slice(&v[2..]);
+            }
+        }
+        out
+    }
+
+    pub fn stroke_count(&self) -> usize {
+        self.ids.len()
+    }
+}
+
+impl MeshBatch {
+    pub(crate) fn push(&mut self, id: u32, soup: &[f32]) {
+        self.ids.push(id);
+        self.ranges.push((self.vertices.len() / FLOATS_PER_VERTEX) as u32);
+        self.ranges.push((soup.len() / FLOATS_PER_VERTEX) as u32);
+        self.vertices.extend_from_slice(soup);
+    }
+
+    /// Appends the mesh's triangle soup without an intermediate copy.
+    pub(crate) fn push_mesh(&mut self, id: u32, mesh: &Mesh) {
+        let start = self.vertices.len();
+        self.ids.push(id);
+        self.ranges.push((start / FLOATS_PER_VERTEX) as u32);
+        self.ranges.push((mesh.soup_len() / FLOATS_PER_VERTEX) as u32);
+        self.vertices.resize(start + mesh.soup_len(), 0.0);
+        mesh.write_triangle_soup(&mut self.vertices[start..]);
+    }
+}
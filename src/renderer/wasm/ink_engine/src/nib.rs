@@ -0,0 +1,94 @@
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{length, point_at, sub, Point};
+use crate::mesh::Mesh;
+use crate::tessellate::{self, MeshOptions};
+
+/// A broad, flat nib. The stroke is widest when it moves across the nib's
+/// edge and thinnest when it moves along it, like italic calligraphy.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct NibOptions {
+    /// Angle of the nib edge in radians, counter-clockwise from the x axis,
+    /// used when no azimuth is known. 45° is the classic italic hand.
+    pub angle: f32,
+    /// Width when moving along the edge, as a fraction of the full width.
+    pub thin_ratio: f32,
+    /// Orient the edge across the pen azimuth when one is reported, instead of
+    /// using the fixed `angle`.
+    pub follow_azimuth: bool,
+}
+
+#[wasm_bindgen]
+impl NibOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> NibOptions {
+        NibOptions::default()
+    }
+}
+
+impl Default for NibOptions {
+    fn default() -> Self {
+        NibOptions {
+            angle: std::f32::consts::FRAC_PI_4,
+            thin_ratio: 0.15,
+            follow_azimuth: true,
+        }
+    }
+}
+
+impl NibOptions {
+    /// Width of a `width`-wide nib moving along `dir`, with the pen leaning
+    /// towards `azimuth` if known.
+    pub(crate) fn width(&self, width: f32, dir: Point, azimuth: Option<f32>) -> f32 {
+        let len = length(dir);
+        if len < 1e-6 {
+            return width;
+        }
+        let edge = match azimuth {
+            Some(a) if self.follow_azimuth => a + std::f32::consts::FRAC_PI_2,
+            _ => self.angle,
+        };
+        let across = (edge.cos() * dir.1 - edge.sin() * dir.0).abs() / len;
+        width * across.max(self.thin_ratio.clamp(0.0, 1.0))
+    }
+}
+
+/// Pen azimuth in radians from `PointerEvent.tiltX`/`tiltY` in degrees, or
+/// NaN for a pen held upright (or a device without tilt).
+#[wasm_bindgen]
+pub fn azimuth_from_tilt(tilt_x: f32, tilt_y: f32) -> f32 {
+    tilt_azimuth(tilt_x, tilt_y).unwrap_or(f32::NAN)
+}
+
+pub(crate) fn tilt_azimuth(tilt_x: f32, tilt_y: f32) -> Option<f32> {
+    if !(tilt_x.is_finite() && tilt_y.is_finite()) || (tilt_x == 0.0 && tilt_y == 0.0) {
+        return None;
+    }
+    let (tx, ty) = (tilt_x.to_radians().tan(), tilt_y.to_radians().tan());
+    Some(ty.atan2(tx))
+}
+
+/// Tessellates with widths shaped by a broad nib. `azimuths` holds one pen
+/// azimuth in radians per point (NaN where unknown, see `azimuth_from_tilt`)
+/// and may be empty to use the fixed nib angle throughout.
+#[wasm_bindgen]
+pub fn build_mesh_nib(points: &[f32], widths: &[f32], azimuths: &[f32], color: &[f32], options: &MeshOptions, nib: &NibOptions) -> Vec<f32> {
+    let shaped = nib_widths(points, widths, azimuths, nib);
+    let mut mesh = Mesh::with_topology(options.topology, false);
+    tessellate::tessellate(points, &shaped, crate::rgba(color), options, &mut mesh);
+    mesh.triangle_soup()
+}
+
+pub(crate) fn nib_widths(points: &[f32], widths: &[f32], azimuths: &[f32], nib: &NibOptions) -> Vec<f32> {
+    let n = points.len() / 2;
+    (0..n)
+        .map(|i| {
+            // Central difference, one-sided at the ends.
+            let a = point_at(points, i.saturating_sub(1));
+            let b = point_at(points, (i + 1).min(n - 1));
+            let azimuth = azimuths.get(i).copied().filter(|a| a.is_finite());
+            nib.width(widths.get(i).copied().unwrap_or(1.0), sub(b, a), azimuth)
+        })
+        .collect()
+}
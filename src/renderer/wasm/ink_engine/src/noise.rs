@@ -0,0 +1,48 @@
+/// Small deterministic generator (SplitMix64) so seeded brushes produce the
+/// same texture every time a stroke is re-tessellated.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Rng(seed as u64 ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..1`.
+    pub fn unit(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `-1..1`.
+    pub fn signed(&mut self) -> f32 {
+        self.unit() * 2.0 - 1.0
+    }
+}
+
+/// Smooth value noise in `0..1` with one lattice cell per unit; each seed
+/// gives an unrelated field. Deterministic in its inputs, so vertices that
+/// share a position always get the same value.
+pub(crate) fn value_noise(x: f32, y: f32, seed: u32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sy) = (smooth(fx), smooth(fy));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let top = lerp(lattice(ix, iy, seed), lattice(ix + 1, iy, seed), sx);
+    let bottom = lerp(lattice(ix, iy + 1, seed), lattice(ix + 1, iy + 1, seed), sx);
+    lerp(top, bottom, sy)
+}
+
+fn lattice(ix: i32, iy: i32, seed: u32) -> f32 {
+    let key = ((ix as u32 as u64) << 32 | iy as u32 as u64) ^ (seed as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93);
+    let mut rng = Rng(key);
+    rng.unit()
+}
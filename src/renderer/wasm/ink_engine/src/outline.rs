@@ -0,0 +1,96 @@
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::erase::{placed_rings, triangle_pieces};
+use crate::geom::Point;
+use crate::polygon::{self, point_in_ring, signed_area};
+
+/// A stroke's boundary as closed polygons in document coordinates, grouped
+/// into connected regions: each an outer ring, counter-clockwise, followed
+/// by the holes inside it, clockwise. Rings do not repeat their first
+/// point.
+#[wasm_bindgen]
+pub struct StrokeOutline {
+    regions: Vec<Vec<Vec<Point>>>,
+}
+
+#[wasm_bindgen]
+impl StrokeOutline {
+    /// Every ring's `[x, y]` pairs back to back, region by region.
+    pub fn points(&self) -> Vec<f32> {
+        self.rings_in_order().flat_map(|r| r.iter().flat_map(|p| [p.0, p.1])).collect()
+    }
+
+    /// `[first_point, point_count]` per ring.
+    pub fn rings(&self) -> Vec<u32> {
+        let mut out = Vec::new();
+        let mut first = 0;
+        for ring in self.rings_in_order() {
+            out.extend([first, ring.len() as u32]);
+            first += ring.len() as u32;
+        }
+        out
+    }
+
+    /// `[first_ring, ring_count]` per region; its first ring is the outer
+    /// one and the rest are holes.
+    pub fn regions(&self) -> Vec<u32> {
+        let mut out = Vec::new();
+        let mut first = 0;
+        for region in &self.regions {
+            out.extend([first, region.len() as u32]);
+            first += region.len() as u32;
+        }
+        out
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Whether `x, y` is inside an outer ring and none of its holes.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.regions
+            .iter()
+            .any(|r| point_in_ring((x, y), &r[0]) && !r[1..].iter().any(|hole| point_in_ring((x, y), hole)))
+    }
+}
+
+impl StrokeOutline {
+    fn rings_in_order(&self) -> impl Iterator<Item = &Vec<Point>> {
+        self.regions.iter().flatten()
+    }
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// The outline of what the stroke draws, traced from its tessellation
+    /// at the current zoom with its transform applied, or the rings its
+    /// fill keeps after vector erasing. Overlaps and self-intersections are
+    /// merged, so the rings never cross and fill the same under the
+    /// nonzero and even-odd rules. `None` for an unknown id, a stamp
+    /// stroke, or a stroke that draws nothing.
+    pub fn stroke_outline(&mut self, id: u32) -> Option<StrokeOutline> {
+        let i = self.index(id)?;
+        let rings = self.outline_rings(i)?;
+        let regions = polygon::regions(&rings);
+        (!regions.is_empty()).then_some(StrokeOutline { regions })
+    }
+}
+
+impl InkDocument {
+    /// Stroke `i`'s outline rings as `polygon::union` orients them.
+    pub(crate) fn outline_rings(&mut self, i: usize) -> Option<Vec<Vec<Point>>> {
+        let s = &self.strokes[i];
+        if let Some(rings) = &s.fill {
+            let mut rings = placed_rings(&s.transform, rings);
+            // A mirroring transform turns every ring around.
+            if rings.first().is_some_and(|outer| signed_area(outer) < 0.0) {
+                rings.iter_mut().for_each(|r| r.reverse());
+            }
+            return Some(rings);
+        }
+        let mesh = self.strokes[i].placed_mesh(&self.brushes, self.zoom_bucket.max(0));
+        Some(polygon::union(&triangle_pieces(&mesh)?))
+    }
+}
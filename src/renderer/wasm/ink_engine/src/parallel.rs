@@ -0,0 +1,45 @@
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+
+/// Starts the thread pool page rebuilds are tessellated on, from JS as
+/// `await initThreadPool(navigator.hardwareConcurrency)` right after the
+/// module is initialized and before the first mesh is built. A rebuild
+/// before it runs on the calling thread, after which the pool can no longer
+/// be started. Only in builds with the `parallel` feature, which need shared
+/// memory and so a cross-origin isolated page; elsewhere load the
+/// single-threaded build.
+#[cfg(all(feature = "parallel", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
+/// Whether this build tessellates page rebuilds on a thread pool, i.e. was
+/// built with the `parallel` feature.
+#[wasm_bindgen]
+pub fn parallel_enabled() -> bool {
+    cfg!(feature = "parallel")
+}
+
+impl InkDocument {
+    /// Tessellates the strokes of `order` that have no mesh for `bucket`
+    /// across the thread pool, so a full-page rebuild, after a zoom change
+    /// or opening a document, finds them cached. Meshes are cached in
+    /// `order`'s order, as building them one by one would.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn build_missing(&mut self, order: &[usize], bucket: i32) {
+        let (strokes, brushes) = (&self.strokes, &self.brushes);
+        let built: Vec<_> = order
+            .par_iter()
+            .filter(|&&i| !strokes[i].has_mesh(bucket))
+            .map(|&i| (i, strokes[i].build(brushes, bucket)))
+            .collect();
+        for (i, mesh) in built {
+            self.strokes[i].cache(bucket, mesh);
+        }
+    }
+
+    /// Without the `parallel` feature each stroke is built on first use.
+    #[cfg(not(feature = "parallel"))]
+    pub(crate) fn build_missing(&mut self, _order: &[usize], _bucket: i32) {}
+}
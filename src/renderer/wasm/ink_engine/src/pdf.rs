@@ -0,0 +1,233 @@
+use std::fmt::Write;
+
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::geom::Point;
+use crate::svg::{num, VectorShape};
+
+/// PDF points per document unit, taking document units as CSS pixels.
+const POINTS_PER_UNIT: f32 = 0.75;
+/// US Letter in CSS pixels, the page of a blank document.
+const BLANK_PAGE: [f32; 4] = [0.0, 0.0, 816.0, 1056.0];
+/// Objects before the pages: the catalog, the page tree and the shared
+/// transparency states.
+const CATALOG: usize = 1;
+const PAGE_TREE: usize = 2;
+const STATES: usize = 3;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// The visible layers as a PDF of vector paths, one page per
+    /// `x, y, width, height` rectangle in `pages` (document units, taken
+    /// as CSS pixels), or a single page around everything drawn when
+    /// `pages` is empty. Strokes are the same paths `to_svg` writes: fills
+    /// traced from the tessellation, and lines for constant-width strokes
+    /// that render as round-capped, round-joined lines. Stroke alpha is a
+    /// constant alpha per path; a layer with less than full opacity is drawn as a transparency group, so its own
+    /// overlapping strokes don't show through one another.
+    pub fn to_pdf(&mut self, pages: &[f32]) -> Vec<u8> {
+        let mut rects: Vec<[f32; 4]> = pages.chunks_exact(4).map(|r| [r[0], r[1], r[2], r[3]]).collect();
+        if rects.is_empty() {
+            rects.push(match self.document_bounds(true)[..] {
+                [x0, y0, x1, y1] => [x0, y0, x1 - x0, y1 - y0],
+                _ => BLANK_PAGE,
+            });
+        }
+        let mut pdf = Pdf::new();
+        let mut kids = Vec::new();
+        for [x, y, w, h] in rects {
+            let view = [x, y, x + w, y + h];
+            // Document units in, points out, with y running up the page.
+            let mut content = format!("{} 0 0 {} {} {} cm\n", POINTS_PER_UNIT, -POINTS_PER_UNIT, num(-x * POINTS_PER_UNIT), num((y + h) * POINTS_PER_UNIT));
+            let mut forms = Vec::new();
+            for (opacity, order) in self.drawn_layers(&view) {
+                let paths = self.pdf_paths(&order, &mut pdf.alphas);
+                if paths.is_empty() {
+                    continue;
+                }
+                if opacity >= 1.0 {
+                    content.push_str(&paths);
+                    continue;
+                }
+                let form = pdf.object(stream(
+                    &format!(
+                        "/Type /XObject /Subtype /Form /BBox [{} {} {} {}] /Group << /S /Transparency >> /Resources << /ExtGState {STATES} 0 R >>",
+                        num(view[0]),
+                        num(view[1]),
+                        num(view[2]),
+                        num(view[3])
+                    ),
+                    &paths,
+                ));
+                let _ = writeln!(content, "q /{} gs /L{} Do Q", alpha_state(&mut pdf.alphas, opacity), forms.len());
+                forms.push(form);
+            }
+            let contents = pdf.object(stream("", &content));
+            let xobjects: String = forms.iter().enumerate().map(|(k, id)| format!(" /L{k} {id} 0 R")).collect();
+            kids.push(pdf.object(format!(
+                "<< /Type /Page /Parent {PAGE_TREE} 0 R /MediaBox [0 0 {} {}] /Group << /S /Transparency /CS /DeviceRGB >> /Resources << /ExtGState {STATES} 0 R /XObject <<{xobjects} >> >> /Contents {contents} 0 R >>",
+                num(w * POINTS_PER_UNIT),
+                num(h * POINTS_PER_UNIT)
+            )));
+        }
+        pdf.finish(&kids)
+    }
+}
+
+impl InkDocument {
+    /// Content stream operators painting the strokes at `order`,
+    /// registering the alphas they use.
+    fn pdf_paths(&mut self, order: &[usize], alphas: &mut Vec<String>) -> String {
+        let mut out = String::new();
+        let mut current = 1.0;
+        for &i in order {
+            let color = self.strokes[i].color;
+            let Some(shape) = self.vector_shape(i) else {
+                continue;
+            };
+            let alpha = color[3].clamp(0.0, 1.0);
+            if alpha != current {
+                let _ = writeln!(out, "/{} gs", alpha_state(alphas, alpha));
+                current = alpha;
+            }
+            let rgb = format!("{} {} {}", channel(color[0]), channel(color[1]), channel(color[2]));
+            match shape {
+                VectorShape::Line { points, width } => {
+                    let _ = writeln!(out, "{rgb} RG {} w 1 J 1 j", num(width));
+                    path(&mut out, &points, false);
+                    out.push_str("S\n");
+                }
+                VectorShape::Outline(rings) => {
+                    let _ = writeln!(out, "{rgb} rg");
+                    for ring in &rings {
+                        path(&mut out, ring, true);
+                    }
+                    out.push_str("f\n");
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Objects written so far, numbered from one, and the alphas the shared
+/// `/A<n>` graphics states stand for.
+struct Pdf {
+    objects: Vec<String>,
+    alphas: Vec<String>,
+}
+
+impl Pdf {
+    fn new() -> Self {
+        Pdf {
+            // Held for the catalog, page tree and states, written last.
+            objects: vec![String::new(); STATES],
+            alphas: Vec::new(),
+        }
+    }
+
+    fn object(&mut self, body: String) -> usize {
+        self.objects.push(body);
+        self.objects.len()
+    }
+
+    fn finish(mut self, kids: &[usize]) -> Vec<u8> {
+        self.objects[CATALOG - 1] = format!("<< /Type /Catalog /Pages {PAGE_TREE} 0 R >>");
+        let kids: Vec<String> = kids.iter().map(|id| format!("{id} 0 R")).collect();
+        self.objects[PAGE_TREE - 1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), kids.len());
+        let states: String = self.alphas.iter().enumerate().map(|(k, a)| format!(" /A{k} << /ca {a} /CA {a} >>")).collect();
+        self.objects[STATES - 1] = format!("<<{states} >>");
+
+        // The binary comment marks the file as binary to transfer tools.
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(self.objects.len());
+        for (k, body) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{body}\nendobj\n", k + 1).as_bytes());
+        }
+        let xref = out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{offset:010} 00000 n ");
+        }
+        let _ = write!(table, "trailer\n<< /Size {} /Root {CATALOG} 0 R >>\nstartxref\n{xref}\n%%EOF\n", self.objects.len() + 1);
+        out.extend_from_slice(table.as_bytes());
+        out
+    }
+}
+
+/// The name of the graphics state setting both alphas to `alpha`.
+fn alpha_state(alphas: &mut Vec<String>, alpha: f32) -> String {
+    let value = channel(alpha);
+    let k = alphas.iter().position(|a| *a == value).unwrap_or_else(|| {
+        alphas.push(value);
+        alphas.len() - 1
+    });
+    format!("A{k}")
+}
+
+fn stream(dict: &str, data: &str) -> String {
+    format!("<< {dict} /Length {} >>\nstream\n{data}endstream", data.len())
+}
+
+fn path(out: &mut String, points: &[Point], closed: bool) {
+    for (k, p) in points.iter().enumerate() {
+        let _ = writeln!(out, "{} {} {}", num(p.0), num(p.1), if k == 0 { "m" } else { "l" });
+    }
+    if closed {
+        out.push_str("h\n");
+    }
+}
+
+/// A color channel or alpha to three decimals, finer than 8-bit steps.
+fn channel(v: f32) -> String {
+    let s = format!("{:.3}", v.clamp(0.0, 1.0));
+    s.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::{Brush, BrushStyle};
+    use crate::sdf::extent;
+    use crate::tessellate::{CapStyle, JoinStyle, MeshOptions};
+
+    fn brush(style: BrushStyle, cap: CapStyle, join: JoinStyle, taper: f32) -> Brush {
+        let mut brush = Brush::new(style);
+        brush.set_mesh_options(&MeshOptions {
+            cap,
+            join,
+            end_taper: taper,
+            ..MeshOptions::default()
+        });
+        brush
+    }
+
+    #[test]
+    fn strokes_match_the_render() {
+        let cases = [
+            (brush(BrushStyle::Ink, CapStyle::Round, JoinStyle::Round, 0.0), true),
+            (brush(BrushStyle::Ink, CapStyle::Square, JoinStyle::Round, 0.0), false),
+            (brush(BrushStyle::Ink, CapStyle::Butt, JoinStyle::Miter, 0.0), false),
+            (brush(BrushStyle::Ink, CapStyle::Round, JoinStyle::Round, 30.0), false),
+            (brush(BrushStyle::Highlighter, CapStyle::Round, JoinStyle::Round, 0.0), false),
+            (brush(BrushStyle::Pencil, CapStyle::Round, JoinStyle::Round, 0.0), false),
+        ];
+        for (k, (b, line)) in cases.iter().enumerate() {
+            let mut doc = InkDocument::new();
+            let id = doc.register_brush(&format!("b{k}"), b);
+            let stroke = doc.add_stroke(&[0.0, 0.0, 40.0, 10.0, 80.0, 0.0], &[6.0; 3], &[], &[0.0, 0.0, 0.0, 1.0], id).id;
+            let ops = doc.pdf_paths(&[0], &mut Vec::new());
+            assert_eq!(ops.contains("S\n"), *line, "case {k}: {ops}");
+            assert_eq!(ops.contains("f\n"), !*line, "case {k}");
+            if let Some(VectorShape::Outline(rings)) = doc.vector_shape(0) {
+                let (lo, hi) = extent(&rings).unwrap();
+                let bounds = doc.stroke_bounds(stroke);
+                for (a, b) in [lo.0, lo.1, hi.0, hi.1].iter().zip(&bounds) {
+                    assert!((a - b).abs() < 1e-3, "case {k}: {lo:?} {hi:?} vs {bounds:?}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::json;
+use crate::preset::BrushStyle;
+use crate::transform::{self, Affine};
+
+/// `PKInk.InkType` raw values share this prefix.
+const INK_PREFIX: &str = "com.apple.ink.";
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Adds the strokes of a PencilKit drawing, transcribed to JSON as
+    /// `read_drawing` describes, on top of the active layer and returns
+    /// their ids. Canvas points are taken as document units with each stroke's
+    /// transform applied; widths are the tip width and the stroke's alpha
+    /// is its color alpha times the mean point opacity. Each ink goes to
+    /// the first registered brush of the closest style: marker to
+    /// highlighter, pencil and crayon to pencil, fountain pen to fountain
+    /// pen, watercolor to wet ink, and pen and monoline to ink, falling
+    /// back to brush 0. Returns nothing when the text is not such a
+    /// drawing.
+    pub fn import_pencilkit(&mut self, text: &str) -> Vec<u32> {
+        let Some(strokes) = serde_json::from_str::<Value>(text).ok().and_then(|v| read_drawing(&v)) else {
+            return Vec::new();
+        };
+        strokes
+            .iter()
+            .map(|s| {
+                let brush = self.brushes.with_style(s.style).unwrap_or(0);
+                self.add_stroke(&s.points, &s.widths, &s.timestamps, &s.color, brush).id
+            })
+            .collect()
+    }
+}
+
+struct PencilKitStroke {
+    style: BrushStyle,
+    color: [f32; 4],
+    /// Document units.
+    points: Vec<f32>,
+    widths: Vec<f32>,
+    /// Milliseconds.
+    timestamps: Vec<f64>,
+}
+
+/// A `PKDrawing` as JSON. Its `dataRepresentation()` is an undocumented
+/// archive, so the companion app writes out what the public API exposes:
+///
+/// ```json
+/// { "strokes": [ {
+///     "ink": "com.apple.ink.pen",
+///     "color": [0, 0, 0, 1],
+///     "transform": [1, 0, 0, 1, 0, 0],
+///     "creationDate": 1760400000000,
+///     "points": [ { "location": [10, 20], "timeOffset": 0, "size": [3, 3], "opacity": 1 } ]
+/// } ] }
+/// ```
+///
+/// `ink` is a `PKInk.InkType` raw value, or the part after `com.apple.ink.`;
+/// `color` is sRGB from 0 to 1; `transform` is the stroke's
+/// `CGAffineTransform` as `a, b, c, d, tx, ty`; `creationDate` is the path's
+/// creation date in milliseconds since the Unix epoch. Point `location` is
+/// in canvas points, `timeOffset` in seconds since `creationDate`, `size`
+/// the tip size. Every key but `points` and `location` is optional; unknown
+/// keys, such as a point's `force`, `azimuth` and `altitude`, are ignored.
+fn read_drawing(v: &Value) -> Option<Vec<PencilKitStroke>> {
+    v.get("strokes")?.as_array()?.iter().map(read_stroke).collect()
+}
+
+fn read_stroke(v: &Value) -> Option<PencilKitStroke> {
+    let ink = v.get("ink").map_or(Some("pen"), Value::as_str)?;
+    let style = ink_style(ink.strip_prefix(INK_PREFIX).unwrap_or(ink));
+    let color = match v.get("color") {
+        Some(c) => crate::rgba(&json::numbers(c)?.iter().map(|&v| v as f32).collect::<Vec<_>>()),
+        None => [0.0, 0.0, 0.0, 1.0],
+    };
+    let m: Affine = match v.get("transform") {
+        Some(m) => transform::from_slice(&json::numbers(m)?.iter().map(|&v| v as f32).collect::<Vec<_>>()),
+        None => transform::IDENTITY,
+    };
+    let scale = transform::scale_factor(&m);
+    let created = v.get("creationDate").map_or(Some(0.0), Value::as_f64)?;
+
+    let points = v.get("points")?.as_array()?;
+    let mut out = PencilKitStroke {
+        style,
+        color,
+        points: Vec::with_capacity(points.len() * 2),
+        widths: Vec::with_capacity(points.len()),
+        timestamps: Vec::with_capacity(points.len()),
+    };
+    let mut opacity = 0.0;
+    for p in points {
+        let location = json::numbers(p.get("location")?)?;
+        let [x, y] = location[..] else {
+            return None;
+        };
+        let (x, y) = transform::apply(&m, (x as f32, y as f32));
+        out.points.extend([x, y]);
+        let width = match p.get("size") {
+            Some(size) => *json::numbers(size)?.first()?,
+            None => 1.0,
+        };
+        out.widths.push(width as f32 * scale);
+        out.timestamps.push(created + p.get("timeOffset").map_or(Some(0.0), Value::as_f64)? * 1000.0);
+        opacity += p.get("opacity").map_or(Some(1.0), Value::as_f64)?;
+    }
+    if !points.is_empty() {
+        out.color[3] *= (opacity / points.len() as f64).clamp(0.0, 1.0) as f32;
+    }
+    Some(out)
+}
+
+fn ink_style(ink: &str) -> BrushStyle {
+    match ink.to_ascii_lowercase().as_str() {
+        "marker" => BrushStyle::Highlighter,
+        "pencil" | "crayon" => BrushStyle::Pencil,
+        "fountainpen" => BrushStyle::FountainPen,
+        "watercolor" => BrushStyle::WetInk,
+        _ => BrushStyle::Ink,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::Brush;
+
+    fn drawing(strokes: &[&str]) -> String {
+        format!(r#"{{"strokes":[{}]}}"#, strokes.join(","))
+    }
+
+    #[test]
+    fn inks_go_to_the_closest_brush() {
+        let mut doc = InkDocument::new();
+        for (name, style) in [("ink", BrushStyle::Ink), ("marker", BrushStyle::Highlighter), ("pencil", BrushStyle::Pencil)] {
+            doc.register_brush(name, &Brush::new(style));
+        }
+        let point = r#""points":[{"location":[0,0]},{"location":[4,4]}]"#;
+        let inks = ["com.apple.ink.marker", "pencil", "com.apple.ink.crayon", "com.apple.ink.monoline", "watercolor", "com.apple.ink.FountainPen"];
+        let strokes: Vec<String> = inks.iter().map(|ink| format!(r#"{{"ink":"{ink}",{point}}}"#)).collect();
+        let ids = doc.import_pencilkit(&drawing(&strokes.iter().map(String::as_str).collect::<Vec<_>>()));
+        assert_eq!(ids.len(), inks.len());
+        // Nothing is registered for watercolor or fountain pen.
+        assert_eq!(doc.strokes.iter().map(|s| s.brush).collect::<Vec<_>>(), [1, 2, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn applies_the_transform_and_times() {
+        let stroke = r#"{"transform":[2,0,0,2,10,0],"creationDate":1000,"color":[1,0,0,0.5],
+            "points":[{"location":[1,1],"size":[3,3],"timeOffset":0.5},{"location":[2,1],"timeOffset":1,"opacity":0.5,"force":2}]}"#;
+        let s = &read_drawing(&serde_json::from_str(&drawing(&[stroke])).unwrap()).unwrap()[0];
+        assert_eq!(s.points, [12.0, 2.0, 14.0, 2.0]);
+        assert_eq!(s.widths, [6.0, 2.0]);
+        assert_eq!(s.timestamps, [1500.0, 2000.0]);
+        // Color alpha times the mean point opacity.
+        assert_eq!(s.color, [1.0, 0.0, 0.0, 0.375]);
+    }
+
+    #[test]
+    fn needs_points_and_locations() {
+        let mut doc = InkDocument::new();
+        for stroke in [r#"{"ink":"pen"}"#, r#"{"points":[{"size":[3,3]}]}"#, r#"{"points":[{"location":[1,2,3]}]}"#, r#"{"points":[{"location":[1,2],"timeOffset":"0"}]}"#] {
+            assert!(doc.import_pencilkit(&drawing(&[r#"{"points":[{"location":[0,0]}]}"#, stroke])).is_empty(), "{stroke}");
+        }
+        assert!(doc.import_pencilkit(r#"{"drawing":[]}"#).is_empty());
+        assert!(doc.strokes.is_empty());
+    }
+}
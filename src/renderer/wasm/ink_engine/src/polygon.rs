@@ -0,0 +1,614 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::geom::Point;
+
+type P = (f64, f64);
+
+/// Parameter slack for treating an intersection as landing on a segment end.
+const PARAM_EPS: f64 = 1e-9;
+
+fn sub(a: P, b: P) -> P {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn cross(a: P, b: P) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+pub(crate) fn signed_area(ring: &[Point]) -> f32 {
+    let mut sum = 0.0f64;
+    for i in 0..ring.len() {
+        let a = ring[i];
+        let b = ring[(i + 1) % ring.len()];
+        sum += a.0 as f64 * b.1 as f64 - b.0 as f64 * a.1 as f64;
+    }
+    (sum * 0.5) as f32
+}
+
+pub(crate) fn point_in_ring(p: Point, ring: &[Point]) -> bool {
+    let mut inside = false;
+    let n = ring.len();
+    let mut j = n.wrapping_sub(1);
+    for i in 0..n {
+        let (a, b) = (ring[i], ring[j]);
+        if (a.1 > p.1) != (b.1 > p.1) && p.0 < (b.0 - a.0) * (p.1 - a.1) / (b.1 - a.1) + a.0 {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Uniform bucket grid over bounding boxes.
+struct Grid {
+    min: P,
+    cell: f64,
+    nx: usize,
+    ny: usize,
+    cells: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    /// `item_size` is the typical extent of an inserted box; cells smaller
+    /// than that only multiply the bookkeeping.
+    fn new(min: P, max: P, count: usize, item_size: f64) -> Grid {
+        let (w, h) = ((max.0 - min.0).max(1e-9), (max.1 - min.1).max(1e-9));
+        let cell = (w * h / count.max(1) as f64).sqrt().max(item_size).max(w.max(h) / 512.0);
+        let nx = (w / cell).ceil() as usize + 1;
+        let ny = (h / cell).ceil() as usize + 1;
+        Grid {
+            min,
+            cell,
+            nx,
+            ny,
+            cells: vec![Vec::new(); nx * ny],
+        }
+    }
+
+    fn coord(&self, p: P) -> (usize, usize) {
+        let cx = ((p.0 - self.min.0) / self.cell).floor().max(0.0) as usize;
+        let cy = ((p.1 - self.min.1) / self.cell).floor().max(0.0) as usize;
+        (cx.min(self.nx - 1), cy.min(self.ny - 1))
+    }
+
+    fn insert(&mut self, id: usize, min: P, max: P) {
+        let (x0, y0) = self.coord(min);
+        let (x1, y1) = self.coord(max);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.cells[y * self.nx + x].push(id);
+            }
+        }
+    }
+
+    fn at(&self, p: P) -> &[usize] {
+        let (x, y) = self.coord(p);
+        &self.cells[y * self.nx + x]
+    }
+}
+
+/// A split point along an edge as (parameter, position).
+type Cut = (f64, P);
+
+struct Edge {
+    a: P,
+    b: P,
+    min: P,
+    max: P,
+    cuts: Vec<Cut>,
+}
+
+fn bounds(points: impl Iterator<Item = P>) -> (P, P) {
+    let mut min = (f64::MAX, f64::MAX);
+    let mut max = (f64::MIN, f64::MIN);
+    for p in points {
+        min = (min.0.min(p.0), min.1.min(p.1));
+        max = (max.0.max(p.0), max.1.max(p.1));
+    }
+    (min, max)
+}
+
+/// Strict containment in a counter-clockwise convex polygon.
+fn inside_convex(p: P, poly: &[P]) -> bool {
+    for i in 0..poly.len() {
+        let a = poly[i];
+        let b = poly[(i + 1) % poly.len()];
+        if cross(sub(b, a), sub(p, a)) <= 0.0 {
+            return false;
+        }
+    }
+    true
+}
+
+/// Outline of the union of convex pieces (triangles, quads, wedges). Returns
+/// closed rings: outer boundaries counter-clockwise, holes clockwise, so the
+/// filled region is always on the left.
+pub(crate) fn union(pieces: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    outline(pieces, &[])
+}
+
+/// Outline of the area the convex `pieces` cover and the convex `cutters`
+/// do not, with rings oriented as by `union`.
+pub(crate) fn difference(pieces: &[Vec<Point>], cutters: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    outline(pieces, cutters)
+}
+
+/// Counter-clockwise copies of the pieces that enclose any area.
+fn normalize(pieces: &[Vec<Point>]) -> Vec<Vec<P>> {
+    let mut convex: Vec<Vec<P>> = Vec::with_capacity(pieces.len());
+    for piece in pieces {
+        if piece.len() < 3 {
+            continue;
+        }
+        let area = signed_area(piece);
+        if area.abs() < 1e-9 {
+            continue;
+        }
+        let mut poly: Vec<P> = piece.iter().map(|p| (p.0 as f64, p.1 as f64)).collect();
+        if area < 0.0 {
+            poly.reverse();
+        }
+        convex.push(poly);
+    }
+    convex
+}
+
+/// Every edge of both sets is split where it meets another, and a fragment
+/// is kept where the filled area, inside a piece and outside every cutter,
+/// lies on exactly one side of it, directed so that side is on the left.
+fn outline(pieces: &[Vec<Point>], cutters: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    let mut convex = normalize(pieces);
+    if convex.is_empty() {
+        return Vec::new();
+    }
+    let solid = convex.len();
+    convex.extend(normalize(cutters));
+
+    let (min, max) = bounds(convex.iter().flatten().copied());
+    let diag = (max.0 - min.0).hypot(max.1 - min.1).max(1e-6);
+    let probe = diag * 1e-7;
+
+    let mut edges: Vec<Edge> = Vec::new();
+    for poly in &convex {
+        for i in 0..poly.len() {
+            let a = poly[i];
+            let b = poly[(i + 1) % poly.len()];
+            if a == b {
+                continue;
+            }
+            let (lo, hi) = bounds([a, b].into_iter());
+            edges.push(Edge {
+                a,
+                b,
+                min: lo,
+                max: hi,
+                cuts: Vec::new(),
+            });
+        }
+    }
+
+    let mean_len = edges.iter().map(|e| (e.max.0 - e.min.0).max(e.max.1 - e.min.1)).sum::<f64>() / edges.len().max(1) as f64;
+    let mut edge_grid = Grid::new(min, max, edges.len(), mean_len);
+    for (i, e) in edges.iter().enumerate() {
+        edge_grid.insert(i, e.min, e.max);
+    }
+    for cell in 0..edge_grid.cells.len() {
+        let ids = &edge_grid.cells[cell];
+        let (cx, cy) = (cell % edge_grid.nx, cell / edge_grid.nx);
+        for x in 0..ids.len() {
+            for y in (x + 1)..ids.len() {
+                let (i, j) = (ids[x], ids[y]);
+                let (ei, ej) = (&edges[i], &edges[j]);
+                if ei.max.0 < ej.min.0 || ej.max.0 < ei.min.0 || ei.max.1 < ej.min.1 || ej.max.1 < ei.min.1 {
+                    continue;
+                }
+                // Only test each pair in the first cell both edges share.
+                let first = edge_grid.coord((ei.min.0.max(ej.min.0), ei.min.1.max(ej.min.1)));
+                if first != (cx, cy) {
+                    continue;
+                }
+                let (mut ci, mut cj) = (Vec::new(), Vec::new());
+                intersect(ei.a, ei.b, ej.a, ej.b, &mut ci, &mut cj);
+                edges[i].cuts.extend(ci);
+                edges[j].cuts.extend(cj);
+            }
+        }
+    }
+
+    let mut piece_grid = Grid::new(min, max, convex.len(), mean_len);
+    for (i, poly) in convex.iter().enumerate() {
+        let (lo, hi) = bounds(poly.iter().copied());
+        piece_grid.insert(i, lo, hi);
+    }
+    let filled = |x: P| {
+        let hits = || piece_grid.at(x).iter().copied().filter(|&pi| inside_convex(x, &convex[pi]));
+        !hits().any(|pi| pi >= solid) && hits().any(|pi| pi < solid)
+    };
+
+    let mut kept: Vec<(P, P)> = Vec::new();
+    let mut seen: HashSet<(Key, Key)> = HashSet::new();
+    for e in &mut edges {
+        e.cuts.sort_by(|x, y| x.0.total_cmp(&y.0));
+        let mut stops = Vec::with_capacity(e.cuts.len() + 2);
+        stops.push(e.a);
+        stops.extend(e.cuts.iter().map(|c| c.1));
+        stops.push(e.b);
+        for k in 0..stops.len() - 1 {
+            let (p, q) = (stops[k], stops[k + 1]);
+            let d = sub(q, p);
+            let len = d.0.hypot(d.1);
+            if len < probe {
+                continue;
+            }
+            let mid = ((p.0 + q.0) * 0.5, (p.1 + q.1) * 0.5);
+            let (nx, ny) = (d.1 / len * probe, -d.0 / len * probe);
+            let left = filled((mid.0 - nx, mid.1 - ny));
+            let right = filled((mid.0 + nx, mid.1 + ny));
+            let (p, q) = match (left, right) {
+                (true, false) => (p, q),
+                (false, true) => (q, p),
+                _ => continue,
+            };
+            if seen.insert((key(p), key(q))) {
+                kept.push((p, q));
+            }
+        }
+    }
+
+    link(&kept)
+}
+
+/// Split points where two segments cross or touch, snapped onto existing
+/// endpoints so shared vertices stay bit-identical.
+fn intersect(a: P, b: P, c: P, d: P, on_ab: &mut Vec<Cut>, on_cd: &mut Vec<Cut>) {
+    let r = sub(b, a);
+    let s = sub(d, c);
+    let denom = cross(r, s);
+    let len_r = r.0.hypot(r.1);
+    let len_s = s.0.hypot(s.1);
+    let interior = |t: f64| t > PARAM_EPS && t < 1.0 - PARAM_EPS;
+    if denom.abs() <= 1e-12 * len_r * len_s {
+        // Parallel: only collinear overlaps matter, splitting at the other's ends.
+        if cross(sub(c, a), r).abs() > 1e-9 * len_r * len_r.max(len_s) {
+            return;
+        }
+        let rr = r.0 * r.0 + r.1 * r.1;
+        let ss = s.0 * s.0 + s.1 * s.1;
+        for p in [c, d] {
+            let t = ((p.0 - a.0) * r.0 + (p.1 - a.1) * r.1) / rr;
+            if interior(t) {
+                on_ab.push((t, p));
+            }
+        }
+        for p in [a, b] {
+            let u = ((p.0 - c.0) * s.0 + (p.1 - c.1) * s.1) / ss;
+            if interior(u) {
+                on_cd.push((u, p));
+            }
+        }
+        return;
+    }
+    let t = cross(sub(c, a), s) / denom;
+    let u = cross(sub(c, a), r) / denom;
+    let lo = -PARAM_EPS;
+    let hi = 1.0 + PARAM_EPS;
+    if t < lo || t > hi || u < lo || u > hi {
+        return;
+    }
+    let mut x = (a.0 + r.0 * t, a.1 + r.1 * t);
+    if t <= PARAM_EPS {
+        x = a;
+    } else if t >= 1.0 - PARAM_EPS {
+        x = b;
+    } else if u <= PARAM_EPS {
+        x = c;
+    } else if u >= 1.0 - PARAM_EPS {
+        x = d;
+    }
+    if interior(t) {
+        on_ab.push((t, x));
+    }
+    if interior(u) {
+        on_cd.push((u, x));
+    }
+}
+
+type Key = (i64, i64);
+
+fn key(p: P) -> Key {
+    ((p.0 * 4096.0).round() as i64, (p.1 * 4096.0).round() as i64)
+}
+
+/// Chains directed boundary edges into closed rings. Where several edges
+/// leave one vertex, the sharpest right turn is taken so touching rings
+/// come apart instead of merging into figure eights.
+fn link(edges: &[(P, P)]) -> Vec<Vec<Point>> {
+    let mut outgoing: HashMap<Key, Vec<usize>> = HashMap::new();
+    for (i, e) in edges.iter().enumerate() {
+        outgoing.entry(key(e.0)).or_default().push(i);
+    }
+    let mut used = vec![false; edges.len()];
+    let mut rings = Vec::new();
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        let mut ring: Vec<Point> = Vec::new();
+        let mut cur = start;
+        loop {
+            used[cur] = true;
+            let (a, b) = edges[cur];
+            ring.push((a.0 as f32, a.1 as f32));
+            if key(b) == key(edges[start].0) {
+                break;
+            }
+            let incoming = sub(b, a);
+            let next = outgoing.get(&key(b)).and_then(|cands| {
+                cands
+                    .iter()
+                    .copied()
+                    .filter(|&c| !used[c])
+                    .min_by(|&x, &y| turn(incoming, edges[x]).total_cmp(&turn(incoming, edges[y])))
+            });
+            match next {
+                Some(n) => cur = n,
+                None => break,
+            }
+        }
+        if ring.len() >= 3 && signed_area(&ring).abs() > 1e-9 {
+            rings.push(ring);
+        }
+    }
+    rings
+}
+
+/// Signed turn angle from `incoming` onto `edge`; negative is a right turn.
+fn turn(incoming: P, edge: (P, P)) -> f64 {
+    let d = sub(edge.1, edge.0);
+    cross(incoming, d).atan2(incoming.0 * d.0 + incoming.1 * d.1)
+}
+
+/// Rings as returned by `union` grouped into connected regions, each its
+/// outer ring followed by the holes directly inside it.
+pub(crate) fn regions(rings: &[Vec<Point>]) -> Vec<Vec<Vec<Point>>> {
+    let (outers, holes): (Vec<&Vec<Point>>, Vec<&Vec<Point>>) = rings.iter().partition(|r| signed_area(r) > 0.0);
+    let mut out: Vec<Vec<Vec<Point>>> = outers.iter().map(|r| vec![r.to_vec()]).collect();
+    for hole in holes.into_iter().filter(|h| !h.is_empty()) {
+        let owner = outers
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| point_in_ring(hole[0], r))
+            .min_by(|a, b| signed_area(a.1).total_cmp(&signed_area(b.1)))
+            .map(|(i, _)| i);
+        if let Some(o) = owner {
+            out[o].push(hole.clone());
+        }
+    }
+    out
+}
+
+/// Triangulates rings as returned by `union`: counter-clockwise outers with
+/// clockwise holes. Holes are bridged into their enclosing outer ring and the
+/// result is ear-clipped. Returns the vertex positions and triangles.
+pub(crate) fn triangulate(rings: &[Vec<Point>]) -> (Vec<Point>, Vec<[u32; 3]>) {
+    let mut verts: Vec<Point> = Vec::new();
+    let mut outers: Vec<(Vec<u32>, f32)> = Vec::new();
+    let mut holes: Vec<Vec<u32>> = Vec::new();
+    for ring in rings {
+        let base = verts.len() as u32;
+        verts.extend_from_slice(ring);
+        let ids: Vec<u32> = (0..ring.len() as u32).map(|i| base + i).collect();
+        let area = signed_area(ring);
+        if area > 0.0 {
+            outers.push((ids, area));
+        } else if area < 0.0 {
+            holes.push(ids);
+        }
+    }
+
+    let mut owned: Vec<Vec<Vec<u32>>> = vec![Vec::new(); outers.len()];
+    for hole in holes {
+        let p = verts[hole[0] as usize];
+        let owner = outers
+            .iter()
+            .enumerate()
+            .filter(|(_, (ids, _))| {
+                let ring: Vec<Point> = ids.iter().map(|&i| verts[i as usize]).collect();
+                point_in_ring(p, &ring)
+            })
+            .min_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+            .map(|(i, _)| i);
+        if let Some(o) = owner {
+            owned[o].push(hole);
+        }
+    }
+
+    let mut tris = Vec::new();
+    for (o, (outer, _)) in outers.iter().enumerate() {
+        let mut poly = outer.clone();
+        let mut hs = std::mem::take(&mut owned[o]);
+        hs.sort_by(|a, b| max_x(&verts, b).total_cmp(&max_x(&verts, a)));
+        for hole in hs {
+            bridge(&verts, &mut poly, &hole);
+        }
+        ear_clip(&verts, &poly, &mut tris);
+    }
+    (verts, tris)
+}
+
+fn max_x(verts: &[Point], ids: &[u32]) -> f32 {
+    ids.iter().map(|&i| verts[i as usize].0).fold(f32::MIN, f32::max)
+}
+
+/// Splices `hole` into `poly` through a mutually visible vertex pair (Eberly).
+fn bridge(verts: &[Point], poly: &mut Vec<u32>, hole: &[u32]) {
+    let at = |i: u32| verts[i as usize];
+    let (hm, &m_id) = hole.iter().enumerate().max_by(|a, b| at(*a.1).0.total_cmp(&at(*b.1).0)).unwrap();
+    let m = at(m_id);
+
+    // Closest edge hit by a ray from M towards +x.
+    let mut best: Option<(f32, usize)> = None;
+    for i in 0..poly.len() {
+        let a = at(poly[i]);
+        let b = at(poly[(i + 1) % poly.len()]);
+        if (a.1 > m.1) == (b.1 > m.1) {
+            continue;
+        }
+        let x = a.0 + (m.1 - a.1) / (b.1 - a.1) * (b.0 - a.0);
+        if x >= m.0 && best.is_none_or(|(bx, _)| x < bx) {
+            best = Some((x, i));
+        }
+    }
+    let Some((ix, edge)) = best else {
+        return;
+    };
+    let (ea, eb) = (edge, (edge + 1) % poly.len());
+    let mut pick = if at(poly[ea]).0 > at(poly[eb]).0 { ea } else { eb };
+    let i_pt = (ix, m.1);
+    let p = at(poly[pick]);
+    // A reflex vertex inside triangle (M, I, P) would block the view of P;
+    // take the one closest in angle to the ray instead.
+    let mut best_angle = f32::MAX;
+    for (k, &v) in poly.iter().enumerate() {
+        let q = at(v);
+        if q == p || !in_triangle(q, m, i_pt, p) {
+            continue;
+        }
+        let angle = (q.1 - m.1).abs().atan2(q.0 - m.0);
+        if angle < best_angle {
+            best_angle = angle;
+            pick = k;
+        }
+    }
+
+    let mut merged = Vec::with_capacity(poly.len() + hole.len() + 2);
+    merged.extend_from_slice(&poly[..=pick]);
+    for k in 0..=hole.len() {
+        merged.push(hole[(hm + k) % hole.len()]);
+    }
+    merged.push(poly[pick]);
+    merged.extend_from_slice(&poly[pick + 1..]);
+    *poly = merged;
+}
+
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    let d1 = (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+    let d2 = (c.0 - b.0) * (p.1 - b.1) - (c.1 - b.1) * (p.0 - b.0);
+    let d3 = (a.0 - c.0) * (p.1 - c.1) - (a.1 - c.1) * (p.0 - c.0);
+    (d1 >= 0.0 && d2 >= 0.0 && d3 >= 0.0) || (d1 <= 0.0 && d2 <= 0.0 && d3 <= 0.0)
+}
+
+fn ear_clip(verts: &[Point], poly: &[u32], tris: &mut Vec<[u32; 3]>) {
+    let at = |i: u32| verts[i as usize];
+    let mut ring: Vec<u32> = poly.to_vec();
+    let mut i = 0;
+    let mut stalled = 0;
+    while ring.len() > 3 {
+        let n = ring.len();
+        let (ia, ib, ic) = (ring[(i + n - 1) % n], ring[i % n], ring[(i + 1) % n]);
+        let (a, b, c) = (at(ia), at(ib), at(ic));
+        let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+        let is_ear = area > 0.0
+            && !ring.iter().any(|&v| {
+                let q = at(v);
+                q != a && q != b && q != c && in_triangle(q, a, b, c)
+            });
+        // Collinear vertices are dropped without a triangle; after a full
+        // fruitless lap the polygon is degenerate and the vertex is clipped anyway.
+        if is_ear || area == 0.0 || stalled > n {
+            if area != 0.0 {
+                tris.push([ia, ib, ic]);
+            }
+            ring.remove(i % n);
+            stalled = 0;
+            i = i.saturating_sub(1);
+        } else {
+            i = (i + 1) % n;
+            stalled += 1;
+        }
+    }
+    if ring.len() == 3 {
+        let (a, b, c) = (at(ring[0]), at(ring[1]), at(ring[2]));
+        if (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0) != 0.0 {
+            tris.push([ring[0], ring[1], ring[2]]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, side: f32) -> Vec<Point> {
+        vec![(x, y), (x + side, y), (x + side, y + side), (x, y + side)]
+    }
+
+    fn area(rings: &[Vec<Point>]) -> f32 {
+        rings.iter().map(|r| signed_area(r)).sum()
+    }
+
+    #[test]
+    fn union_of_overlapping_squares() {
+        let rings = union(&[square(0.0, 0.0, 2.0), square(1.0, 1.0, 2.0)]);
+        assert_eq!(rings.len(), 1);
+        assert!((area(&rings) - 7.0).abs() < 1e-4);
+        // Clockwise input comes out the same.
+        let mut flipped = square(1.0, 1.0, 2.0);
+        flipped.reverse();
+        assert!((area(&union(&[square(0.0, 0.0, 2.0), flipped])) - 7.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn union_of_touching_squares() {
+        let rings = union(&[square(0.0, 0.0, 1.0), square(1.0, 0.0, 1.0)]);
+        assert_eq!(rings.len(), 1);
+        assert!((area(&rings) - 2.0).abs() < 1e-4);
+
+        let rings = union(&[square(0.0, 0.0, 1.0), square(1.0, 1.0, 1.0)]);
+        assert!((area(&rings) - 2.0).abs() < 1e-4);
+
+        // A square inside another adds nothing.
+        let rings = union(&[square(0.0, 0.0, 3.0), square(1.0, 1.0, 1.0)]);
+        assert_eq!(rings.len(), 1);
+        assert!((area(&rings) - 9.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn difference_leaves_a_hole() {
+        let rings = difference(&[square(0.0, 0.0, 3.0)], &[square(1.0, 1.0, 1.0)]);
+        assert_eq!(rings.len(), 2);
+        assert!((area(&rings) - 8.0).abs() < 1e-4);
+        assert_eq!(rings.iter().filter(|r| signed_area(r) < 0.0).count(), 1);
+    }
+
+    #[test]
+    fn difference_of_overlapping_squares() {
+        let rings = difference(&[square(0.0, 0.0, 2.0)], &[square(1.0, 1.0, 2.0)]);
+        assert_eq!(rings.len(), 1);
+        assert!((area(&rings) - 3.0).abs() < 1e-4);
+
+        let rings = difference(&[square(0.0, 0.0, 2.0)], &[square(-1.0, -1.0, 4.0)]);
+        assert!(rings.is_empty());
+
+        // A cutter along one edge takes nothing.
+        let rings = difference(&[square(0.0, 0.0, 1.0)], &[square(1.0, 0.0, 1.0)]);
+        assert!((area(&rings) - 1.0).abs() < 1e-4);
+
+        // Cutting a strip through the middle leaves two pieces.
+        let strip = vec![(1.0, -1.0), (2.0, -1.0), (2.0, 4.0), (1.0, 4.0)];
+        let rings = difference(&[square(0.0, 0.0, 3.0)], &[strip]);
+        assert_eq!(rings.len(), 2);
+        assert!((area(&rings) - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn regions_keep_holes_with_their_outer() {
+        let mut rings = difference(&[square(0.0, 0.0, 3.0)], &[square(1.0, 1.0, 1.0)]);
+        rings.extend(union(&[square(5.0, 0.0, 1.0)]));
+        let regions = regions(&rings);
+        assert_eq!(regions.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+        let (verts, tris) = triangulate(&rings);
+        let covered: f32 = tris.iter().map(|t| signed_area(&[verts[t[0] as usize], verts[t[1] as usize], verts[t[2] as usize]]).abs()).sum();
+        assert!((covered - 9.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,145 @@
+use wasm_bindgen::prelude::*;
+
+use crate::samples::{samples_from, split, Sample};
+
+/// Recent input used to estimate the tip's motion, in milliseconds.
+const FIT_WINDOW_MS: f64 = 60.0;
+/// Longer horizons overshoot badly on direction changes.
+const MAX_HORIZON_MS: f64 = 50.0;
+
+/// Extrapolated samples past the end of the real input. They are a guess
+/// and never part of the stroke: draw them separately and throw them away
+/// on the next update, which predicts a fresh tail. `anchor` is the index of
+/// the last real sample the tail continues from; the tail itself starts
+/// after it.
+#[wasm_bindgen]
+pub struct PredictedTail {
+    anchor: u32,
+    points: Vec<f32>,
+    widths: Vec<f32>,
+    timestamps: Vec<f64>,
+}
+
+#[wasm_bindgen]
+impl PredictedTail {
+    pub fn anchor(&self) -> u32 {
+        self.anchor
+    }
+
+    pub fn points(&self) -> Vec<f32> {
+        self.points.clone()
+    }
+
+    pub fn widths(&self) -> Vec<f32> {
+        self.widths.clone()
+    }
+
+    pub fn timestamps(&self) -> Vec<f64> {
+        self.timestamps.clone()
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.widths.len()
+    }
+}
+
+/// Predicts where the pen will be `horizon_ms` past the last sample (10–30 ms
+/// covers typical Electron input latency), fitting velocity and acceleration
+/// to the last few samples. Returns `steps` evenly timed samples, or an empty
+/// tail when the pen is at rest or the input has no usable timestamps.
+#[wasm_bindgen]
+pub fn predict_tail(points: &[f32], widths: &[f32], timestamps: &[f64], horizon_ms: f32, steps: u32) -> PredictedTail {
+    let samples = samples_from(points, widths, timestamps);
+    let tail = predict(&samples, horizon_ms as f64, steps.max(1) as usize);
+    let (points, widths) = split(&tail);
+    PredictedTail {
+        anchor: samples.len().saturating_sub(1) as u32,
+        points,
+        widths,
+        timestamps: tail.iter().map(|s| s.time).collect(),
+    }
+}
+
+pub(crate) fn predict(samples: &[Sample], horizon_ms: f64, steps: usize) -> Vec<Sample> {
+    let Some(last) = samples.last().copied() else {
+        return Vec::new();
+    };
+    if horizon_ms.is_nan() || horizon_ms <= 0.0 {
+        return Vec::new();
+    }
+    let horizon_ms = horizon_ms.min(MAX_HORIZON_MS);
+    let recent: Vec<&Sample> = samples.iter().rev().take_while(|s| last.time - s.time <= FIT_WINDOW_MS).collect();
+    if recent.len() < 2 || last.time - recent[recent.len() - 1].time <= 0.0 {
+        return Vec::new();
+    }
+    // Times in seconds relative to the tip, so the fit is evaluated at zero.
+    let t: Vec<f64> = recent.iter().map(|s| (s.time - last.time) / 1000.0).collect();
+    let (vx, ax) = motion(&t, &recent.iter().map(|s| s.x as f64).collect::<Vec<_>>());
+    let (vy, ay) = motion(&t, &recent.iter().map(|s| s.y as f64).collect::<Vec<_>>());
+    if vx.hypot(vy) < 1.0 {
+        return Vec::new();
+    }
+
+    let mut tail = Vec::with_capacity(steps);
+    for k in 1..=steps {
+        let dt = horizon_ms / 1000.0 * k as f64 / steps as f64;
+        // Stop before deceleration would turn the tip back on itself.
+        if (vx + ax * dt) * vx + (vy + ay * dt) * vy < 0.0 {
+            break;
+        }
+        tail.push(Sample {
+            x: last.x + (vx * dt + 0.5 * ax * dt * dt) as f32,
+            y: last.y + (vy * dt + 0.5 * ay * dt * dt) as f32,
+            width: last.width,
+            time: last.time + dt * 1000.0,
+        });
+    }
+    tail
+}
+
+/// Velocity and acceleration at `t = 0` from a least-squares fit of
+/// `x(t)`: quadratic when there are enough samples, linear otherwise.
+fn motion(t: &[f64], x: &[f64]) -> (f64, f64) {
+    // Power sums for the normal equations.
+    let mut s = [0.0f64; 5];
+    let mut r = [0.0f64; 3];
+    for (&ti, &xi) in t.iter().zip(x) {
+        let mut p = 1.0;
+        for (k, sk) in s.iter_mut().enumerate() {
+            *sk += p;
+            if k < 3 {
+                r[k] += p * xi;
+            }
+            p *= ti;
+        }
+    }
+    let linear = || {
+        let det = s[0] * s[2] - s[1] * s[1];
+        if det.abs() < 1e-18 {
+            (0.0, 0.0)
+        } else {
+            ((s[0] * r[1] - s[1] * r[0]) / det, 0.0)
+        }
+    };
+    if t.len() < 4 {
+        return linear();
+    }
+    let m = [[s[0], s[1], s[2]], [s[1], s[2], s[3]], [s[2], s[3], s[4]]];
+    let det3 = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let det = det3(&m);
+    if det.abs() < 1e-24 {
+        return linear();
+    }
+    // Cramer's rule for the linear and quadratic coefficients.
+    let with_column = |c: usize| {
+        let mut mc = m;
+        for (row, &v) in mc.iter_mut().zip(&r) {
+            row[c] = v;
+        }
+        det3(&mc) / det
+    };
+    (with_column(1), 2.0 * with_column(2))
+}
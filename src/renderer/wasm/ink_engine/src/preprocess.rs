@@ -0,0 +1,179 @@
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{dot, length, sub};
+use crate::samples::{samples_from, Sample, StrokeSamples};
+
+/// Drops non-finite samples, exact duplicates and segments shorter than
+/// `min_distance`, so the tessellator never sees sliver segments. The final
+/// sample always survives so the stroke still ends where the pen lifted.
+#[wasm_bindgen]
+pub fn clean_points(points: &[f32], widths: &[f32], timestamps: &[f64], min_distance: f32) -> StrokeSamples {
+    let samples = samples_from(points, widths, timestamps);
+    StrokeSamples::from_samples(&clean(&samples, min_distance), !timestamps.is_empty())
+}
+
+pub(crate) fn clean(samples: &[Sample], min_distance: f32) -> Vec<Sample> {
+    let min_sq = min_distance.max(0.0) * min_distance.max(0.0);
+    let mut out: Vec<Sample> = Vec::with_capacity(samples.len());
+    let mut collapsed = false;
+    for s in samples {
+        if !(s.x.is_finite() && s.y.is_finite()) {
+            continue;
+        }
+        let mut s = *s;
+        if !s.width.is_finite() {
+            s.width = out.last().map_or(1.0, |p| p.width);
+        }
+        if let Some(last) = out.last() {
+            let (dx, dy) = (s.x - last.x, s.y - last.y);
+            let d_sq = dx * dx + dy * dy;
+            if d_sq == 0.0 || d_sq < min_sq {
+                collapsed = true;
+                continue;
+            }
+        }
+        out.push(s);
+        collapsed = false;
+    }
+    // Keep the pen-up position: move the last kept sample onto it rather than
+    // adding another short segment.
+    if collapsed && out.len() > 1 {
+        if let Some(end) = samples.iter().rev().find(|s| s.x.is_finite() && s.y.is_finite()) {
+            let last = out.len() - 1;
+            out[last] = Sample {
+                width: if end.width.is_finite() { end.width } else { out[last].width },
+                ..*end
+            };
+        }
+    }
+    out
+}
+
+/// Resamples the stroke at uniform `spacing` along its arc length, with
+/// widths and timestamps interpolated. The first and last samples are kept.
+#[wasm_bindgen]
+pub fn resample_by_arclength(points: &[f32], widths: &[f32], timestamps: &[f64], spacing: f32) -> StrokeSamples {
+    let samples = samples_from(points, widths, timestamps);
+    StrokeSamples::from_samples(&resample(&samples, spacing), !timestamps.is_empty())
+}
+
+pub(crate) fn resample(samples: &[Sample], spacing: f32) -> Vec<Sample> {
+    if samples.len() < 2 || spacing.is_nan() || spacing <= 0.0 {
+        return samples.to_vec();
+    }
+    let mut out = vec![samples[0]];
+    // Distance still to travel before the next output sample.
+    let mut need = spacing;
+    for pair in samples.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let seg = length(sub(b.pos(), a.pos()));
+        let mut at = 0.0;
+        while seg - at >= need {
+            at += need;
+            out.push(a.lerp(b, at / seg));
+            need = spacing;
+        }
+        need -= seg - at;
+    }
+    let last = samples[samples.len() - 1];
+    // The final gap is shorter than `spacing`; when it is negligible the end
+    // replaces the last spaced sample instead of adding a sliver.
+    if need < spacing - spacing * 1e-3 {
+        out.push(last);
+    } else if let Some(end) = out.last_mut() {
+        *end = last;
+    }
+    out
+}
+
+/// Ramer–Douglas–Peucker simplification. A sample is dropped only when both
+/// its position and its width are within `tolerance` pixels of the line
+/// between the surviving neighbours, so pressure swells are not flattened.
+#[wasm_bindgen]
+pub fn simplify_rdp(points: &[f32], widths: &[f32], timestamps: &[f64], tolerance: f32) -> StrokeSamples {
+    let samples = samples_from(points, widths, timestamps);
+    StrokeSamples::from_samples(&simplify(&samples, tolerance), !timestamps.is_empty())
+}
+
+pub(crate) fn simplify(samples: &[Sample], tolerance: f32) -> Vec<Sample> {
+    let n = samples.len();
+    if n < 3 || tolerance.is_nan() || tolerance <= 0.0 {
+        return samples.to_vec();
+    }
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[n - 1] = true;
+    // Explicit stack: long strokes would otherwise recurse thousands deep.
+    let mut stack = vec![(0, n - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let (a, b) = (&samples[first], &samples[last]);
+        let chord = sub(b.pos(), a.pos());
+        let len_sq = dot(chord, chord);
+        let mut worst = (tolerance, None);
+        for (i, s) in samples.iter().enumerate().take(last).skip(first + 1) {
+            let rel = sub(s.pos(), a.pos());
+            let t = if len_sq > 0.0 { (dot(rel, chord) / len_sq).clamp(0.0, 1.0) } else { 0.0 };
+            let on_chord = a.lerp(b, t);
+            let err = length(sub(s.pos(), on_chord.pos())).max((s.width - on_chord.width).abs());
+            if err > worst.0 {
+                worst = (err, Some(i));
+            }
+        }
+        if let Some(i) = worst.1 {
+            keep[i] = true;
+            stack.push((first, i));
+            stack.push((i, last));
+        }
+    }
+    samples.iter().zip(keep).filter(|(_, k)| *k).map(|(s, _)| *s).collect()
+}
+
+/// Hampel filter for width or pressure glitches: a sample further than
+/// `threshold` scaled median absolute deviations from the median of its
+/// `radius` neighbours on each side is replaced by that median. Works the
+/// same on raw pressures or on widths derived from them.
+#[wasm_bindgen]
+pub fn reject_width_outliers(widths: &[f32], radius: u32, threshold: f32) -> Vec<f32> {
+    hampel(widths, radius as usize, threshold)
+}
+
+/// Scales the median absolute deviation to a standard deviation for Gaussian noise.
+const MAD_SCALE: f32 = 1.4826;
+/// Deviations below this fraction of the median are never treated as outliers,
+/// so a perfectly steady run does not flag every tiny change.
+const MIN_DEVIATION: f32 = 0.05;
+
+pub(crate) fn hampel(values: &[f32], radius: usize, threshold: f32) -> Vec<f32> {
+    let n = values.len();
+    if radius == 0 || n < 3 {
+        return values.to_vec();
+    }
+    let mut window = Vec::with_capacity(radius * 2 + 1);
+    (0..n)
+        .map(|i| {
+            let v = values[i];
+            window.clear();
+            window.extend(values[i.saturating_sub(radius)..(i + radius + 1).min(n)].iter().filter(|w| w.is_finite()));
+            if window.is_empty() {
+                return v;
+            }
+            let med = median(&mut window);
+            for w in window.iter_mut() {
+                *w = (*w - med).abs();
+            }
+            let sigma = MAD_SCALE * median(&mut window);
+            let limit = (threshold.max(0.0) * sigma).max(MIN_DEVIATION * med.abs());
+            if !v.is_finite() || (v - med).abs() > limit {
+                med
+            } else {
+                v
+            }
+        })
+        .collect()
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    let mid = values.len() / 2;
+    let (_, m, _) = values.select_nth_unstable_by(mid, f32::total_cmp);
+    *m
+}
@@ -0,0 +1,260 @@
+use wasm_bindgen::prelude::*;
+
+use crate::brush::{self, AirbrushOptions, BlendMode, BrushMesh, FountainPenOptions, PencilOptions, RainbowOptions, SketchOptions, WetInkOptions};
+use crate::curve::PressureCurve;
+use crate::mesh::{Mesh, FLOATS_PER_VERTEX};
+use crate::nib::{self, NibOptions};
+use crate::stamp::{self, StampOptions, STAMP_FLOATS_PER_VERTEX};
+use crate::tessellate::{self, CapStyle, Decoration, JoinStyle, MeshOptions};
+
+/// How a brush turns its widths into geometry.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BrushStyle {
+    /// Plain ribbon, as `build_mesh_with_options`.
+    #[default]
+    Ink,
+    /// Broad nib, as `build_mesh_nib`.
+    Nib,
+    /// As `build_mesh_highlighter`, at the curve's maximum width.
+    Highlighter,
+    /// As `build_mesh_pencil`.
+    Pencil,
+    /// As `build_mesh_airbrush`.
+    Airbrush,
+    /// Textured quads, as `build_stamps`.
+    Stamp,
+    /// Hue cycling, as `build_mesh_rainbow`; the stroke color is ignored.
+    Rainbow,
+    /// Darker edges and pools, as `build_mesh_wet_ink`.
+    WetInk,
+    /// Speed- and direction-dependent width, as `build_mesh_fountain_pen`.
+    FountainPen,
+    /// Jittered multi-pass roughs, as `build_mesh_sketch`.
+    Sketch,
+    /// Ink geometry flagged `BlendMode::DestinationOut`; the color is ignored.
+    Eraser,
+}
+
+/// A named brush configuration: style, pressure-to-width curve, mesh options
+/// and the settings of whichever style it uses.
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Brush {
+    style: BrushStyle,
+    curve: PressureCurve,
+    options: MeshOptions,
+    nib: NibOptions,
+    pencil: PencilOptions,
+    airbrush: AirbrushOptions,
+    stamp: StampOptions,
+    rainbow: RainbowOptions,
+    wet_ink: WetInkOptions,
+    fountain_pen: FountainPenOptions,
+    sketch: SketchOptions,
+}
+
+#[wasm_bindgen]
+impl Brush {
+    #[wasm_bindgen(constructor)]
+    pub fn new(style: BrushStyle) -> Brush {
+        Brush {
+            style,
+            curve: PressureCurve::new(0.5, 4.0),
+            options: MeshOptions::default(),
+            nib: NibOptions::default(),
+            pencil: PencilOptions::default(),
+            airbrush: AirbrushOptions::default(),
+            stamp: StampOptions::default(),
+            rainbow: RainbowOptions::default(),
+            wet_ink: WetInkOptions::default(),
+            fountain_pen: FountainPenOptions::default(),
+            sketch: SketchOptions::default(),
+        }
+    }
+
+    pub fn style(&self) -> BrushStyle {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: BrushStyle) {
+        self.style = style;
+    }
+
+    pub fn set_pressure_curve(&mut self, curve: &PressureCurve) {
+        self.curve = curve.clone();
+    }
+
+    /// Caps, joins, smoothing, tapers and the rest. Ignored by the
+    /// highlighter, airbrush and stamp styles, which shape their own geometry.
+    pub fn set_mesh_options(&mut self, options: &MeshOptions) {
+        self.options = *options;
+    }
+
+    pub fn set_nib(&mut self, nib: &NibOptions) {
+        self.nib = *nib;
+    }
+
+    pub fn set_pencil(&mut self, pencil: &PencilOptions) {
+        self.pencil = *pencil;
+    }
+
+    pub fn set_airbrush(&mut self, airbrush: &AirbrushOptions) {
+        self.airbrush = *airbrush;
+    }
+
+    pub fn set_stamp(&mut self, stamp: &StampOptions) {
+        self.stamp = *stamp;
+    }
+
+    pub fn set_rainbow(&mut self, rainbow: &RainbowOptions) {
+        self.rainbow = *rainbow;
+    }
+
+    pub fn set_wet_ink(&mut self, wet_ink: &WetInkOptions) {
+        self.wet_ink = *wet_ink;
+    }
+
+    pub fn set_fountain_pen(&mut self, fountain_pen: &FountainPenOptions) {
+        self.fountain_pen = *fountain_pen;
+    }
+
+    pub fn set_sketch(&mut self, sketch: &SketchOptions) {
+        self.sketch = *sketch;
+    }
+}
+
+impl Brush {
+    /// Geometry for one stroke. `pressures` and `timestamps` hold one entry
+    /// per point and may be empty for full pressure and untimed input.
+    pub(crate) fn build(&self, points: &[f32], pressures: &[f32], timestamps: &[f64], color: [f32; 4]) -> BrushMesh {
+        let n = points.len() / 2;
+        let widths: Vec<f32> = (0..n).map(|i| self.curve.width(pressures.get(i).copied().unwrap_or(1.0))).collect();
+        self.build_widths(points, &widths, timestamps, color, 1.0)
+    }
+
+    /// Like `build` with widths already mapped from pressure, for a zoom of
+    /// `zoom` times the mesh options' `scale`.
+    pub(crate) fn build_widths(&self, points: &[f32], widths: &[f32], timestamps: &[f64], color: [f32; 4], zoom: f32) -> BrushMesh {
+        // Built straight, as some styles adjust alpha after tessellating.
+        let options = &MeshOptions {
+            scale: self.options.scale * zoom,
+            premultiplied_alpha: false,
+            ..self.options
+        };
+        let soup = |vertices| BrushMesh::new(vertices, BlendMode::Normal, FLOATS_PER_VERTEX);
+        let mut mesh = match self.style {
+            BrushStyle::Ink => {
+                let mut mesh = Mesh::with_topology(options.topology, false);
+                tessellate::tessellate(points, widths, color, options, &mut mesh);
+                soup(mesh.triangle_soup())
+            }
+            BrushStyle::Nib => {
+                let widths = nib::nib_widths(points, widths, &[], &self.nib);
+                let mut mesh = Mesh::with_topology(options.topology, false);
+                tessellate::tessellate(points, &widths, color, options, &mut mesh);
+                soup(mesh.triangle_soup())
+            }
+            BrushStyle::Highlighter => brush::highlighter(points, self.curve.max_width, color),
+            BrushStyle::Pencil => soup(brush::pencil(points, widths, color, options, &self.pencil)),
+            BrushStyle::Airbrush => soup(brush::airbrush(points, widths, timestamps, color, &self.airbrush)),
+            BrushStyle::Stamp => BrushMesh::new(stamp::build_stamps(points, widths, &color, &self.stamp), BlendMode::Normal, STAMP_FLOATS_PER_VERTEX),
+            BrushStyle::Rainbow => soup(brush::rainbow_mesh(points, widths, timestamps, options, &self.rainbow)),
+            BrushStyle::WetInk => soup(brush::wet_ink(points, widths, timestamps, color, options, &self.wet_ink)),
+            BrushStyle::FountainPen => soup(brush::fountain_pen(points, widths, timestamps, color, options, &self.fountain_pen)),
+            BrushStyle::Sketch => soup(brush::sketch_mesh(points, widths, color, options, &self.sketch)),
+            BrushStyle::Eraser => brush::eraser(points, widths, options),
+        };
+        if self.premultiplied() {
+            mesh.premultiply();
+        }
+        mesh
+    }
+
+    /// Whether a constant-width stroke of this brush renders exactly as a
+    /// line with round caps and joins: plain ink without tapers, smoothing,
+    /// decorations or a closing join, which vector exporters can then keep
+    /// as an editable line.
+    pub(crate) fn draws_round_line(&self) -> bool {
+        let o = &self.options;
+        self.style == BrushStyle::Ink
+            && o.cap == CapStyle::Round
+            && o.join == JoinStyle::Round
+            && !o.closed
+            && o.start_taper == 0.0
+            && o.end_taper == 0.0
+            && o.smoothing == 0
+            && o.start_decoration == Decoration::None
+            && o.end_decoration == Decoration::None
+    }
+
+    /// Whether the brush's meshes come premultiplied, see
+    /// `MeshOptions::premultiplied_alpha`.
+    pub(crate) fn premultiplied(&self) -> bool {
+        self.options.premultiplied_alpha
+    }
+}
+
+/// Brushes registered once by name and then referred to by a small id, so a
+/// stroke only carries the id across the boundary instead of every setting.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct BrushRegistry {
+    brushes: Vec<(String, Brush)>,
+}
+
+#[wasm_bindgen]
+impl BrushRegistry {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> BrushRegistry {
+        BrushRegistry::default()
+    }
+
+    /// Stores a copy of `brush` under `name` and returns its id. Registering
+    /// an existing name replaces that brush and keeps its id.
+    pub fn register(&mut self, name: &str, brush: &Brush) -> u32 {
+        if let Some(id) = self.id(name) {
+            self.brushes[id as usize].1 = brush.clone();
+            return id;
+        }
+        self.brushes.push((name.to_owned(), brush.clone()));
+        (self.brushes.len() - 1) as u32
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.brushes.iter().position(|(n, _)| n == name).map(|i| i as u32)
+    }
+
+    pub fn name(&self, id: u32) -> Option<String> {
+        self.brushes.get(id as usize).map(|(n, _)| n.clone())
+    }
+
+    pub fn brush(&self, id: u32) -> Option<Brush> {
+        self.get(id).cloned()
+    }
+
+    pub fn brush_count(&self) -> usize {
+        self.brushes.len()
+    }
+
+    /// Tessellates a stroke with brush `id`; an unknown id gives an empty mesh.
+    /// `pressures` and `timestamps` may be empty.
+    pub fn build(&self, id: u32, points: &[f32], pressures: &[f32], timestamps: &[f64], color: &[f32]) -> BrushMesh {
+        match self.get(id) {
+            Some(brush) => brush.build(points, pressures, timestamps, crate::rgba(color)),
+            None => BrushMesh::new(Vec::new(), BlendMode::Normal, FLOATS_PER_VERTEX),
+        }
+    }
+}
+
+impl BrushRegistry {
+    pub(crate) fn get(&self, id: u32) -> Option<&Brush> {
+        self.brushes.get(id as usize).map(|(_, b)| b)
+    }
+
+    /// The first registered brush of `style`, for importers mapping
+    /// another app's tools onto ours.
+    pub(crate) fn with_style(&self, style: BrushStyle) -> Option<u32> {
+        self.brushes.iter().position(|(_, b)| b.style == style).map(|i| i as u32)
+    }
+}
@@ -0,0 +1,822 @@
+use wasm_bindgen::prelude::*;
+
+use crate::codec::{Reader, Writer};
+use crate::document::{InkDocument, Layer, PointerType, Stroke};
+use crate::geom::Point;
+use crate::group::Group;
+use crate::history::Op;
+use crate::snapshot::{read_snapshot, write_snapshot, Snapshot};
+use crate::transform::{self, Affine};
+
+// Protobuf encoding of the messages in proto/ink.proto. Protobuf varints,
+// fixed32/64 and length-delimited fields are what `Writer` and `Reader`
+// already speak, so only the field keys are added here.
+
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LEN: u64 = 2;
+const FIXED32: u64 = 5;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// `export_ops` as an `ink.OpLog` protobuf message, for sync peers that
+    /// decode it with generated code in another language.
+    pub fn export_ops_proto(&self, from: usize) -> Vec<u8> {
+        let mut w = Writer::default();
+        uint(&mut w, 1, from.max(self.op_base) as u64);
+        for op in self.ops_from(from) {
+            message(&mut w, 2, encode_op(op));
+        }
+        w.buf
+    }
+
+    /// `apply_ops` for an `ink.OpLog` message, with the same checks: an op
+    /// handing out an id with no room for a next one, or loading a broken
+    /// model, fails the message, and strokes placed on a layer or group
+    /// that does not exist when their op is applied are skipped. Returns
+    /// false, applying nothing, when it does not decode.
+    pub fn apply_ops_proto(&mut self, bytes: &[u8]) -> bool {
+        let Some(ops) = decode_op_log(bytes) else {
+            return false;
+        };
+        for op in ops {
+            self.apply(op);
+        }
+        true
+    }
+
+    /// `snapshot` as an `ink.Snapshot` protobuf message.
+    pub fn snapshot_proto(&self) -> Vec<u8> {
+        let counters = [self.next_id, self.next_layer_id, self.next_group_id, self.active_layer];
+        encode_snapshot(self.op_count(), counters, &self.layers, &self.groups, &self.strokes).buf
+    }
+
+    /// `restore` from an `ink.Snapshot` message.
+    pub fn restore_proto(&mut self, bytes: &[u8]) -> bool {
+        match decode_snapshot(bytes) {
+            Some(state) => self.restore(&snapshot_bytes(&state)),
+            None => false,
+        }
+    }
+}
+
+fn key(w: &mut Writer, field: u32, wire: u64) {
+    w.varint((field as u64) << 3 | wire);
+}
+
+// Scalars at their default are left out, as proto3 does; fields with
+// presence go through `message` or the `_always` writers.
+
+fn uint(w: &mut Writer, field: u32, v: u64) {
+    if v != 0 {
+        uint_always(w, field, v);
+    }
+}
+
+fn uint_always(w: &mut Writer, field: u32, v: u64) {
+    key(w, field, VARINT);
+    w.varint(v);
+}
+
+fn float(w: &mut Writer, field: u32, v: f32) {
+    if v.to_bits() != 0 {
+        key(w, field, FIXED32);
+        w.f32(v);
+    }
+}
+
+fn double(w: &mut Writer, field: u32, v: f64) {
+    if v.to_bits() != 0 {
+        key(w, field, FIXED64);
+        w.f64(v);
+    }
+}
+
+fn string(w: &mut Writer, field: u32, v: &str) {
+    if !v.is_empty() {
+        key(w, field, LEN);
+        w.str(v);
+    }
+}
+
+fn message(w: &mut Writer, field: u32, body: Writer) {
+    key(w, field, LEN);
+    w.bytes(&body.buf);
+}
+
+fn floats(w: &mut Writer, field: u32, vs: &[f32]) {
+    if !vs.is_empty() {
+        let mut body = Writer::default();
+        vs.iter().for_each(|&v| body.f32(v));
+        message(w, field, body);
+    }
+}
+
+fn doubles(w: &mut Writer, field: u32, vs: &[f64]) {
+    if !vs.is_empty() {
+        let mut body = Writer::default();
+        vs.iter().for_each(|&v| body.f64(v));
+        message(w, field, body);
+    }
+}
+
+fn uints(w: &mut Writer, field: u32, vs: &[u32]) {
+    if !vs.is_empty() {
+        let mut body = Writer::default();
+        vs.iter().for_each(|&v| body.varint(v as u64));
+        message(w, field, body);
+    }
+}
+
+fn encode_stroke(s: &Stroke) -> Writer {
+    let mut w = Writer::default();
+    uint(&mut w, 1, s.id as u64);
+    floats(&mut w, 2, &s.points);
+    floats(&mut w, 3, &s.widths);
+    doubles(&mut w, 4, &s.timestamps);
+    uint(&mut w, 5, s.brush as u64);
+    floats(&mut w, 6, &s.color);
+    uint(&mut w, 7, s.layer as u64);
+    if let Some(g) = s.group {
+        uint_always(&mut w, 8, g as u64);
+    }
+    floats(&mut w, 9, &s.transform);
+    double(&mut w, 10, s.created);
+    uint(&mut w, 11, s.pointer as u64);
+    string(&mut w, 12, &s.author);
+    if let Some(rings) = &s.fill {
+        let mut fill = Writer::default();
+        for ring in rings {
+            let mut r = Writer::default();
+            floats(&mut r, 1, &ring.iter().flat_map(|p| [p.0, p.1]).collect::<Vec<_>>());
+            message(&mut fill, 1, r);
+        }
+        message(&mut w, 13, fill);
+    }
+    w
+}
+
+fn encode_snapshot(watermark: usize, counters: [u32; 4], layers: &[Layer], groups: &[Group], strokes: &[Stroke]) -> Writer {
+    let mut w = Writer::default();
+    uint(&mut w, 1, watermark as u64);
+    for (k, &v) in counters.iter().enumerate() {
+        uint(&mut w, 2 + k as u32, v as u64);
+    }
+    for l in layers {
+        let mut m = Writer::default();
+        uint(&mut m, 1, l.id as u64);
+        string(&mut m, 2, &l.name);
+        uint(&mut m, 3, l.visible as u64);
+        float(&mut m, 4, l.opacity);
+        message(&mut w, 6, m);
+    }
+    for g in groups {
+        let mut m = Writer::default();
+        uint(&mut m, 1, g.id as u64);
+        if let Some(p) = g.parent {
+            uint_always(&mut m, 2, p as u64);
+        }
+        floats(&mut m, 3, &g.transform);
+        message(&mut w, 7, m);
+    }
+    for s in strokes {
+        message(&mut w, 8, encode_stroke(s));
+    }
+    w
+}
+
+fn id_pair(a: u32, b: u64) -> Writer {
+    let mut w = Writer::default();
+    uint(&mut w, 1, a as u64);
+    uint(&mut w, 2, b);
+    w
+}
+
+fn id_name(id: u32, name: &str) -> Writer {
+    let mut w = Writer::default();
+    uint(&mut w, 1, id as u64);
+    string(&mut w, 2, name);
+    w
+}
+
+fn id_transform(id: u32, m: &Affine) -> Writer {
+    let mut w = Writer::default();
+    uint(&mut w, 1, id as u64);
+    floats(&mut w, 2, m);
+    w
+}
+
+fn encode_op(op: &Op) -> Writer {
+    let mut w = Writer::default();
+    match op {
+        Op::InsertStroke { index, stroke } => {
+            let mut m = Writer::default();
+            uint(&mut m, 1, *index as u64);
+            message(&mut m, 2, encode_stroke(stroke));
+            message(&mut w, 1, m);
+        }
+        Op::RemoveStroke(id) => uint_always(&mut w, 2, *id as u64),
+        Op::UpdateStroke(stroke) => message(&mut w, 3, encode_stroke(stroke)),
+        Op::ReplaceStroke { id, pieces } => {
+            let mut m = Writer::default();
+            uint(&mut m, 1, *id as u64);
+            pieces.iter().for_each(|p| message(&mut m, 2, encode_stroke(p)));
+            message(&mut w, 4, m);
+        }
+        Op::SetTransform { id, transform } => message(&mut w, 5, id_transform(*id, transform)),
+        Op::SetStrokeLayer { id, layer } => message(&mut w, 6, id_pair(*id, *layer as u64)),
+        Op::Clear => message(&mut w, 7, Writer::default()),
+        Op::AddLayer { id, name } => message(&mut w, 8, id_name(*id, name)),
+        Op::RemoveLayer(id) => uint_always(&mut w, 9, *id as u64),
+        Op::MoveLayer { id, index } => message(&mut w, 10, id_pair(*id, *index as u64)),
+        Op::SetLayerName { id, name } => message(&mut w, 11, id_name(*id, name)),
+        Op::SetLayerVisible { id, visible } => message(&mut w, 12, id_pair(*id, *visible as u64)),
+        Op::SetLayerOpacity { id, opacity } => {
+            let mut m = Writer::default();
+            uint(&mut m, 1, *id as u64);
+            float(&mut m, 2, *opacity);
+            message(&mut w, 13, m);
+        }
+        Op::SetActiveLayer(id) => uint_always(&mut w, 14, *id as u64),
+        Op::Group { id, strokes, groups } => {
+            let mut m = Writer::default();
+            uint(&mut m, 1, *id as u64);
+            uints(&mut m, 2, strokes);
+            uints(&mut m, 3, groups);
+            message(&mut w, 15, m);
+        }
+        Op::Ungroup(id) => uint_always(&mut w, 16, *id as u64),
+        Op::SetGroupTransform { id, transform } => message(&mut w, 17, id_transform(*id, transform)),
+        Op::Load(snapshot) => {
+            // Ops only hold snapshots that decoded when they were made.
+            if let Some(s) = read_snapshot(snapshot) {
+                let counters = [s.next_id, s.next_layer_id, s.next_group_id, s.active_layer];
+                message(&mut w, 18, encode_snapshot(s.watermark, counters, &s.layers, &s.groups, &s.strokes));
+            }
+        }
+    }
+    w
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Fixed64([u8; 8]),
+    Bytes(&'a [u8]),
+    Fixed32([u8; 4]),
+}
+
+impl<'a> Value<'a> {
+    fn uint(&self) -> Option<u64> {
+        match self {
+            Value::Varint(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn id(&self) -> Option<u32> {
+        self.uint().and_then(|v| u32::try_from(v).ok())
+    }
+
+    fn float(&self) -> Option<f32> {
+        match self {
+            Value::Fixed32(b) => Some(f32::from_le_bytes(*b)),
+            _ => None,
+        }
+    }
+
+    fn double(&self) -> Option<f64> {
+        match self {
+            Value::Fixed64(b) => Some(f64::from_le_bytes(*b)),
+            _ => None,
+        }
+    }
+
+    fn bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn string(&self) -> Option<String> {
+        self.bytes().and_then(|b| String::from_utf8(b.to_vec()).ok())
+    }
+
+    // Repeated scalars arrive packed or one field each; both are accepted.
+
+    fn push_floats(&self, out: &mut Vec<f32>) -> Option<()> {
+        match self {
+            Value::Bytes(b) if b.len() % 4 == 0 => out.extend(b.chunks_exact(4).map(|c| f32::from_le_bytes(c.try_into().unwrap()))),
+            _ => out.push(self.float()?),
+        }
+        Some(())
+    }
+
+    fn push_doubles(&self, out: &mut Vec<f64>) -> Option<()> {
+        match self {
+            Value::Bytes(b) if b.len() % 8 == 0 => out.extend(b.chunks_exact(8).map(|c| f64::from_le_bytes(c.try_into().unwrap()))),
+            _ => out.push(self.double()?),
+        }
+        Some(())
+    }
+
+    fn push_ids(&self, out: &mut Vec<u32>) -> Option<()> {
+        match self {
+            Value::Bytes(b) => {
+                let mut r = Reader::new(b);
+                while r.pos() < b.len() {
+                    out.push(u32::try_from(r.varint()?).ok()?);
+                }
+            }
+            _ => out.push(self.id()?),
+        }
+        Some(())
+    }
+}
+
+/// Every field of a message in order. Unknown fields come back like known
+/// ones and callers skip them, which is how newer peers stay readable.
+fn fields(bytes: &[u8]) -> Option<Vec<(u32, Value<'_>)>> {
+    let mut r = Reader::new(bytes);
+    let mut out = Vec::new();
+    while r.pos() < bytes.len() {
+        let k = r.varint()?;
+        let field = u32::try_from(k >> 3).ok()?;
+        let value = match k & 7 {
+            VARINT => Value::Varint(r.varint()?),
+            FIXED64 => Value::Fixed64(r.raw(8)?.try_into().ok()?),
+            LEN => Value::Bytes(r.bytes()?),
+            FIXED32 => Value::Fixed32(r.raw(4)?.try_into().ok()?),
+            _ => return None,
+        };
+        out.push((field, value));
+    }
+    Some(out)
+}
+
+fn affine(values: Vec<f32>) -> Option<Affine> {
+    match values.len() {
+        0 => Some(transform::IDENTITY),
+        _ => values.try_into().ok(),
+    }
+}
+
+fn decode_stroke(bytes: &[u8]) -> Option<Stroke> {
+    let (mut id, mut brush, mut layer, mut group, mut created, mut pointer) = (0, 0, 0, None, 0.0, PointerType::Unknown);
+    let (mut points, mut widths, mut timestamps, mut color, mut matrix) = (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let (mut author, mut fill) = (String::new(), None);
+    for (field, v) in fields(bytes)? {
+        match field {
+            1 => id = v.id()?,
+            2 => v.push_floats(&mut points)?,
+            3 => v.push_floats(&mut widths)?,
+            4 => v.push_doubles(&mut timestamps)?,
+            5 => brush = v.id()?,
+            6 => v.push_floats(&mut color)?,
+            7 => layer = v.id()?,
+            8 => group = Some(v.id()?),
+            9 => v.push_floats(&mut matrix)?,
+            10 => created = v.double()?,
+            11 => {
+                pointer = match v.uint()? {
+                    1 => PointerType::Mouse,
+                    2 => PointerType::Pen,
+                    3 => PointerType::Touch,
+                    _ => PointerType::Unknown,
+                }
+            }
+            12 => author = v.string()?,
+            13 => fill = Some(decode_fill(v.bytes()?)?),
+            _ => {}
+        }
+    }
+    Some(Stroke {
+        id,
+        points,
+        widths,
+        timestamps,
+        brush,
+        color: color.try_into().ok()?,
+        layer,
+        group,
+        transform: affine(matrix)?,
+        created,
+        pointer,
+        author,
+        fill,
+        meshes: Vec::new(),
+        indexed: false,
+    })
+}
+
+fn decode_fill(bytes: &[u8]) -> Option<Vec<Vec<Point>>> {
+    let mut rings = Vec::new();
+    for (field, v) in fields(bytes)? {
+        if field == 1 {
+            let mut flat = Vec::new();
+            for (field, p) in fields(v.bytes()?)? {
+                if field == 1 {
+                    p.push_floats(&mut flat)?;
+                }
+            }
+            rings.push(flat.chunks_exact(2).map(|p| (p[0], p[1])).collect());
+        }
+    }
+    Some(rings)
+}
+
+fn decode_snapshot(bytes: &[u8]) -> Option<Snapshot> {
+    let mut state = Snapshot {
+        watermark: 0,
+        next_id: 0,
+        next_layer_id: 0,
+        next_group_id: 0,
+        active_layer: 0,
+        layers: Vec::new(),
+        groups: Vec::new(),
+        strokes: Vec::new(),
+    };
+    for (field, v) in fields(bytes)? {
+        match field {
+            1 => state.watermark = usize::try_from(v.uint()?).ok()?,
+            2 => state.next_id = v.id()?,
+            3 => state.next_layer_id = v.id()?,
+            4 => state.next_group_id = v.id()?,
+            5 => state.active_layer = v.id()?,
+            6 => {
+                let mut l = Layer {
+                    id: 0,
+                    name: String::new(),
+                    visible: false,
+                    opacity: 0.0,
+                };
+                for (field, v) in fields(v.bytes()?)? {
+                    match field {
+                        1 => l.id = v.id()?,
+                        2 => l.name = v.string()?,
+                        3 => l.visible = v.uint()? != 0,
+                        4 => l.opacity = v.float()?,
+                        _ => {}
+                    }
+                }
+                state.layers.push(l);
+            }
+            7 => {
+                let (mut id, mut parent, mut matrix) = (0, None, Vec::new());
+                for (field, v) in fields(v.bytes()?)? {
+                    match field {
+                        1 => id = v.id()?,
+                        2 => parent = Some(v.id()?),
+                        3 => v.push_floats(&mut matrix)?,
+                        _ => {}
+                    }
+                }
+                state.groups.push(Group {
+                    id,
+                    parent,
+                    transform: affine(matrix)?,
+                });
+            }
+            8 => state.strokes.push(decode_stroke(v.bytes()?)?),
+            _ => {}
+        }
+    }
+    // A document always has a layer to draw on.
+    if !state.layers.iter().any(|l| l.id == state.active_layer) {
+        return None;
+    }
+    state.checked()
+}
+
+fn snapshot_bytes(s: &Snapshot) -> Vec<u8> {
+    let counters = [s.next_id, s.next_layer_id, s.next_group_id, s.active_layer];
+    write_snapshot(s.watermark, counters, &s.layers, &s.groups, &s.strokes)
+}
+
+/// The id and second scalar of a two-field op message such as
+/// `SetStrokeLayer`, the second as its raw field value.
+fn decode_id_and<'a>(bytes: &'a [u8]) -> Option<(u32, Option<Value<'a>>)> {
+    let (mut id, mut second) = (0, None);
+    for (field, v) in fields(bytes)? {
+        match field {
+            1 => id = v.id()?,
+            2 => second = Some(v),
+            _ => {}
+        }
+    }
+    Some((id, second))
+}
+
+/// `Some(None)` for an op of a kind added after this version.
+fn decode_op(bytes: &[u8]) -> Option<Option<Op>> {
+    // The oneof's last field wins, as in generated code.
+    let mut op = None;
+    for (field, v) in fields(bytes)? {
+        op = Some(match field {
+            1 => {
+                let (index, stroke) = decode_id_and(v.bytes()?)?;
+                Op::InsertStroke {
+                    index,
+                    stroke: decode_stroke(stroke?.bytes()?)?,
+                }
+            }
+            2 => Op::RemoveStroke(v.id()?),
+            3 => Op::UpdateStroke(decode_stroke(v.bytes()?)?),
+            4 => {
+                let (mut id, mut pieces) = (0, Vec::new());
+                for (field, v) in fields(v.bytes()?)? {
+                    match field {
+                        1 => id = v.id()?,
+                        2 => pieces.push(decode_stroke(v.bytes()?)?),
+                        _ => {}
+                    }
+                }
+                Op::ReplaceStroke { id, pieces }
+            }
+            5 | 17 => {
+                let (id, m) = decode_id_and(v.bytes()?)?;
+                let mut matrix = Vec::new();
+                if let Some(m) = m {
+                    m.push_floats(&mut matrix)?;
+                }
+                let transform = affine(matrix)?;
+                match field {
+                    5 => Op::SetTransform { id, transform },
+                    _ => Op::SetGroupTransform { id, transform },
+                }
+            }
+            6 => {
+                let (id, layer) = decode_id_and(v.bytes()?)?;
+                Op::SetStrokeLayer {
+                    id,
+                    layer: layer.map_or(Some(0), |l| l.id())?,
+                }
+            }
+            7 => Op::Clear,
+            8 | 11 => {
+                let (id, name) = decode_id_and(v.bytes()?)?;
+                let name = name.map_or(Some(String::new()), |n| n.string())?;
+                match field {
+                    8 => Op::AddLayer { id, name },
+                    _ => Op::SetLayerName { id, name },
+                }
+            }
+            9 => Op::RemoveLayer(v.id()?),
+            10 => {
+                let (id, index) = decode_id_and(v.bytes()?)?;
+                Op::MoveLayer {
+                    id,
+                    index: index.map_or(Some(0), |i| i.id())?,
+                }
+            }
+            12 => {
+                let (id, visible) = decode_id_and(v.bytes()?)?;
+                Op::SetLayerVisible {
+                    id,
+                    visible: visible.map_or(Some(0), |b| b.uint())? != 0,
+                }
+            }
+            13 => {
+                let (id, opacity) = decode_id_and(v.bytes()?)?;
+                Op::SetLayerOpacity {
+                    id,
+                    opacity: opacity.map_or(Some(0.0), |o| o.float())?,
+                }
+            }
+            14 => Op::SetActiveLayer(v.id()?),
+            15 => {
+                let (mut id, mut strokes, mut groups) = (0, Vec::new(), Vec::new());
+                for (field, v) in fields(v.bytes()?)? {
+                    match field {
+                        1 => id = v.id()?,
+                        2 => v.push_ids(&mut strokes)?,
+                        3 => v.push_ids(&mut groups)?,
+                        _ => {}
+                    }
+                }
+                Op::Group { id, strokes, groups }
+            }
+            16 => Op::Ungroup(v.id()?),
+            18 => Op::Load(snapshot_bytes(&decode_snapshot(v.bytes()?)?)),
+            _ => continue,
+        });
+    }
+    Some(op)
+}
+
+fn decode_op_log(bytes: &[u8]) -> Option<Vec<Op>> {
+    let mut ops = Vec::new();
+    for (field, v) in fields(bytes)? {
+        if field == 2 {
+            if let Some(op) = decode_op(v.bytes()?)? {
+                ops.push(op.checked()?);
+            }
+        }
+    }
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    /// Field numbers and wire types of `message` as proto/ink.proto
+    /// declares them, repeated scalars packed.
+    fn schema(message: &str) -> Vec<(u32, u64)> {
+        let proto = include_str!("../proto/ink.proto");
+        let body = proto.split(&format!("message {message} {{")).nth(1).unwrap().split("\n}").next().unwrap();
+        body.lines()
+            .filter_map(|line| {
+                let (decl, number) = line.trim().strip_suffix(';')?.split_once(" = ")?;
+                let words: Vec<&str> = decl.split_whitespace().collect();
+                let wire = match (words[0], words[words.len() - 2]) {
+                    ("repeated", "float" | "double" | "uint32") => LEN,
+                    (_, "float") => FIXED32,
+                    (_, "double") => FIXED64,
+                    (_, "uint32" | "uint64" | "bool" | "PointerType") => VARINT,
+                    _ => LEN,
+                };
+                Some((number.parse().unwrap(), wire))
+            })
+            .collect()
+    }
+
+    fn keys(bytes: &[u8]) -> Vec<(u32, u64)> {
+        let wire = |v: &Value| match v {
+            Value::Varint(_) => VARINT,
+            Value::Fixed64(_) => FIXED64,
+            Value::Bytes(_) => LEN,
+            Value::Fixed32(_) => FIXED32,
+        };
+        fields(bytes).unwrap().iter().map(|(field, v)| (*field, wire(v))).collect()
+    }
+
+    fn body(bytes: &[u8], field: u32) -> Vec<u8> {
+        fields(bytes).unwrap().into_iter().find(|(f, _)| *f == field).unwrap().1.bytes().unwrap().to_vec()
+    }
+
+    /// A stroke with every field away from its default.
+    fn full_stroke() -> Stroke {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 10.0, 5.0], &[2.0; 2], &[0.0, 16.0], &BLACK, 0);
+        let mut s = doc.strokes[0].record();
+        (s.brush, s.layer, s.group, s.created, s.pointer, s.author) = (1, 2, Some(3), 1.5, PointerType::Pen, "ana".into());
+        s.fill = Some(vec![vec![(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]]);
+        s
+    }
+
+    fn layer(id: u32) -> Layer {
+        Layer { id, name: "Notes".into(), visible: true, opacity: 0.5 }
+    }
+
+    fn group(id: u32, parent: Option<u32>) -> Group {
+        Group { id, parent, transform: [2.0, 0.0, 0.0, 2.0, 1.0, 1.0] }
+    }
+
+    #[test]
+    fn fields_match_the_schema() {
+        let s = full_stroke();
+        assert_eq!(keys(&encode_stroke(&s).buf), schema("Stroke"));
+        let snapshot = encode_snapshot(7, [4, 3, 2, 1], &[layer(1)], &[group(1, Some(2))], std::slice::from_ref(&s)).buf;
+        assert_eq!(keys(&snapshot), schema("Snapshot"));
+        assert_eq!(keys(&body(&snapshot, 6)), schema("Layer"));
+        assert_eq!(keys(&body(&snapshot, 7)), schema("Group"));
+        let fill = body(&encode_stroke(&s).buf, 13);
+        assert_eq!(keys(&fill), schema("Fill"));
+        assert_eq!(keys(&body(&fill, 1)), schema("Ring"));
+
+        let m = [1.0, 0.0, 0.0, 1.0, 4.0, 0.0];
+        let ops = [
+            Op::InsertStroke { index: 1, stroke: s.clone() },
+            Op::RemoveStroke(1),
+            Op::UpdateStroke(s.clone()),
+            Op::ReplaceStroke { id: 1, pieces: vec![s] },
+            Op::SetTransform { id: 1, transform: m },
+            Op::SetStrokeLayer { id: 1, layer: 2 },
+            Op::Clear,
+            Op::AddLayer { id: 2, name: "Notes".into() },
+            Op::RemoveLayer(2),
+            Op::MoveLayer { id: 2, index: 1 },
+            Op::SetLayerName { id: 2, name: "Ink".into() },
+            Op::SetLayerVisible { id: 2, visible: true },
+            Op::SetLayerOpacity { id: 2, opacity: 0.5 },
+            Op::SetActiveLayer(2),
+            Op::Group { id: 1, strokes: vec![1], groups: vec![2] },
+            Op::Ungroup(1),
+            Op::SetGroupTransform { id: 1, transform: m },
+            Op::Load(InkDocument::new().snapshot()),
+        ];
+        assert_eq!(ops.iter().flat_map(|op| keys(&encode_op(op).buf)).collect::<Vec<_>>(), schema("Op"));
+        let messages = [(1, "InsertStroke"), (4, "ReplaceStroke"), (5, "SetTransform"), (6, "SetStrokeLayer"), (8, "AddLayer"), (10, "MoveLayer"), (11, "SetLayerName"), (12, "SetLayerVisible"), (13, "SetLayerOpacity"), (15, "GroupStrokes"), (17, "SetTransform")];
+        for (field, name) in messages {
+            assert_eq!(keys(&body(&encode_op(&ops[field as usize - 1]).buf, field)), schema(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn writes_what_generated_code_reads() {
+        assert_eq!(encode_op(&Op::RemoveStroke(5)).buf, [0x10, 0x05]);
+        assert_eq!(encode_op(&Op::SetLayerOpacity { id: 1, opacity: 0.5 }).buf, [0x6a, 0x07, 0x08, 0x01, 0x15, 0x00, 0x00, 0x00, 0x3f]);
+        // Defaults are left out, but an optional group of 0 is not.
+        let mut s = full_stroke();
+        (s.id, s.brush, s.layer, s.group, s.created, s.pointer, s.author, s.fill) = (0, 0, 0, Some(0), 0.0, PointerType::Unknown, String::new(), None);
+        s.timestamps.clear();
+        assert_eq!(keys(&encode_stroke(&s).buf), [(2, LEN), (3, LEN), (6, LEN), (8, VARINT), (9, LEN)]);
+    }
+
+    #[test]
+    fn reads_unpacked_and_unknown_fields() {
+        let mut w = Writer::default();
+        uint(&mut w, 1, 4);
+        for v in [1.0, 2.0] {
+            key(&mut w, 2, FIXED32);
+            w.f32(v);
+        }
+        floats(&mut w, 2, &[3.0, 4.0]);
+        floats(&mut w, 6, &BLACK);
+        uint(&mut w, 7, 1);
+        uint(&mut w, 99, 12);
+        string(&mut w, 100, "later");
+        let s = decode_stroke(&w.buf).unwrap();
+        assert_eq!((s.id, s.points, s.transform), (4, vec![1.0, 2.0, 3.0, 4.0], transform::IDENTITY));
+    }
+
+    #[test]
+    fn round_trips_ops_and_snapshots() {
+        let mut doc = InkDocument::new();
+        let a = doc.add_stroke(&[0.0, 0.0, 10.0, 5.0], &[2.0; 2], &[0.0, 16.0], &BLACK, 0).id;
+        let b = doc.add_stroke(&[5.0, 5.0, 0.1, 0.2], &[1.0; 2], &[], &[0.1, 0.2, 0.3, 0.4], 0).id;
+        let layer = doc.add_layer("Notes");
+        doc.set_stroke_layer(b, layer);
+        doc.set_layer_opacity(layer, 0.25);
+        doc.transform_stroke(a, &[1.0, 0.0, 0.0, 1.0, 4.0, 0.0]);
+        doc.group(&[a, b], &[]);
+        let mut peer = InkDocument::new();
+        assert!(peer.apply_ops_proto(&doc.export_ops_proto(0)));
+        assert_eq!(peer.snapshot(), doc.snapshot());
+        let mut restored = InkDocument::new();
+        assert!(restored.restore_proto(&doc.snapshot_proto()));
+        assert_eq!(restored.snapshot(), doc.snapshot());
+        assert_eq!(restored.snapshot_proto(), doc.snapshot_proto());
+    }
+
+    #[test]
+    fn rejects_truncated_messages() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 10.0, 5.0], &[2.0; 2], &[0.0, 16.0], &BLACK, 0);
+        let ops = doc.export_ops_proto(0);
+        let mut peer = InkDocument::new();
+        for cut in 1..ops.len() {
+            assert!(!peer.apply_ops_proto(&ops[..cut]), "{cut}");
+        }
+        assert!(peer.strokes.is_empty());
+        // Strokes come last; a cut anywhere in one fails.
+        let snapshot = doc.snapshot_proto();
+        let stroke = encode_stroke(&doc.strokes[0].record()).buf.len() + 2;
+        for cut in snapshot.len() - stroke + 1..snapshot.len() {
+            assert!(!peer.restore_proto(&snapshot[..cut]), "{cut}");
+        }
+        assert!(peer.strokes.is_empty());
+    }
+
+    #[test]
+    fn rejects_broken_models() {
+        let log = |ops: &[Op]| {
+            let mut w = Writer::default();
+            ops.iter().for_each(|op| message(&mut w, 2, encode_op(op)));
+            w.buf
+        };
+        let (mut s, mut peer) = (full_stroke(), InkDocument::new());
+        (s.layer, s.group, s.fill) = (1, None, None);
+        let last = Stroke { id: u32::MAX, ..s.record() };
+        for op in [Op::InsertStroke { index: 0, stroke: last.record() }, Op::UpdateStroke(last), Op::AddLayer { id: u32::MAX, name: String::new() }, Op::Group { id: u32::MAX, strokes: vec![], groups: vec![] }] {
+            assert!(!peer.apply_ops_proto(&log(&[op])));
+        }
+        // Strokes on what does not exist are skipped, as `apply_ops` skips them.
+        let (mut lost, mut orphan) = (s.record(), s.record());
+        (lost.layer, orphan.group) = (9, Some(9));
+        assert!(peer.apply_ops_proto(&log(&[Op::InsertStroke { index: 0, stroke: lost }, Op::InsertStroke { index: 0, stroke: orphan }])));
+        assert!(peer.strokes.is_empty());
+
+        let broken = [
+            encode_snapshot(0, [2, 2, 1, 2], &[layer(1)], &[], &[]),
+            encode_snapshot(0, [2, 2, 1, 1], &[layer(1), layer(1)], &[], &[]),
+            encode_snapshot(0, [2, 2, 3, 1], &[layer(1)], &[group(1, Some(2)), group(2, Some(1))], &[]),
+            encode_snapshot(0, [2, 2, 2, 1], &[layer(1)], &[group(1, Some(1))], &[]),
+            encode_snapshot(0, [2, 2, 1, 1], &[layer(1)], &[], &[Stroke { layer: 9, ..s.record() }]),
+            encode_snapshot(0, [2, 2, 1, 1], &[layer(1)], &[], &[Stroke { group: Some(9), ..s.record() }]),
+            encode_snapshot(0, [2, 2, 1, 1], &[layer(1)], &[], &[s.record(), s.record()]),
+        ];
+        for (k, snapshot) in broken.iter().enumerate() {
+            assert!(!peer.restore_proto(&snapshot.buf), "{k}");
+            let mut load = Writer::default();
+            message(&mut load, 18, Writer { buf: snapshot.buf.clone() });
+            let mut w = Writer::default();
+            message(&mut w, 2, load);
+            assert!(!peer.apply_ops_proto(&w.buf), "{k}");
+        }
+        assert_eq!(peer.snapshot(), InkDocument::new().snapshot());
+    }
+}
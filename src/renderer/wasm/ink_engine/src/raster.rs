@@ -0,0 +1,346 @@
+use wasm_bindgen::prelude::*;
+
+use crate::brush::BlendMode;
+use crate::document::{zoom_bucket, InkDocument};
+use crate::mesh::FLOATS_PER_VERTEX;
+
+/// Longest side a raster may have, as browsers limit canvases.
+pub(crate) const MAX_SIDE: usize = 16384;
+/// Most pixels a raster may have, the canvas area Safari allows. Rendering
+/// keeps a float canvas per layer besides the output, so this also bounds
+/// memory.
+pub(crate) const MAX_PIXELS: usize = 1 << 24;
+/// Most samples per pixel each way `rasterize_antialiased` takes.
+const MAX_SAMPLES: u32 = 8;
+/// Samples rendered at once when supersampling; larger rasters are done in
+/// bands of rows so memory stays bounded.
+const BAND_SAMPLES: usize = 1 << 20;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Renders the visible layers in the view `x, y, width, height`
+    /// (document units) at `scale` pixels per unit to straight-alpha RGBA8,
+    /// rows top down, as `ImageData` takes them: `ceil(width * scale)` by
+    /// `ceil(height * scale)` pixels on a transparent background. Meshes
+    /// are tessellated for `scale`'s zoom bucket and blended per triangle
+    /// as the GPU renderer does, each layer on its own before it is
+    /// composited with its opacity. Pixels are sampled at their centres.
+    /// Stamp strokes need their textures and are left out. Empty when the
+    /// raster would be empty, wider or taller than 16384 pixels, or over
+    /// 16777216 pixels in all.
+    pub fn rasterize(&mut self, x: f32, y: f32, width: f32, height: f32, scale: f32) -> Vec<u8> {
+        match raster_size(width, height, scale) {
+            Some((w, h)) => {
+                let scale = scale as f64;
+                self.render([x as f64 * scale, y as f64 * scale], scale, w, h).to_rgba8()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// As `rasterize`, with `samples` by `samples` samples per pixel (1 to
+    /// 8) averaged, so edges are antialiased and strokes thinner than a
+    /// pixel fade instead of breaking up, as exports need. 4 is usually
+    /// indistinguishable from more.
+    pub fn rasterize_antialiased(&mut self, x: f32, y: f32, width: f32, height: f32, scale: f32, samples: u32) -> Vec<u8> {
+        match raster_size(width, height, scale) {
+            Some((w, h)) => {
+                let scale = scale as f64;
+                let samples = samples.clamp(1, MAX_SAMPLES) as usize;
+                self.render_antialiased([x as f64 * scale, y as f64 * scale], scale, w, h, samples).to_rgba8()
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+impl InkDocument {
+    /// The visible layers at `scale` pixels per unit on a `width` by
+    /// `height` canvas whose top left pixel corner is `origin` pixels from
+    /// the document origin.
+    pub(crate) fn render(&mut self, origin: [f64; 2], scale: f64, width: usize, height: usize) -> Canvas {
+        let bucket = zoom_bucket(scale as f32);
+        // A pixel more each side, so rounding can't drop an edge stroke.
+        let at = |p: f64| (p / scale) as f32;
+        let view = [at(origin[0] - 1.0), at(origin[1] - 1.0), at(origin[0] + width as f64 + 1.0), at(origin[1] + height as f64 + 1.0)];
+        let mut out = Canvas::new(width, height, origin, scale);
+        for (opacity, order) in self.drawn_layers(&view) {
+            let mut target = Canvas::new(width, height, origin, scale);
+            for i in order {
+                let mesh = self.strokes[i].placed_mesh(&self.brushes, bucket);
+                if mesh.floats_per_vertex() == FLOATS_PER_VERTEX {
+                    target.draw(mesh.soup(), mesh.blend(), mesh.premultiplied());
+                }
+            }
+            out.composite(&target, opacity);
+        }
+        out
+    }
+
+    /// As `render`, drawn `samples` times finer each way and box-filtered
+    /// down, a band of rows at a time.
+    pub(crate) fn render_antialiased(&mut self, origin: [f64; 2], scale: f64, width: usize, height: usize, samples: usize) -> Canvas {
+        if samples <= 1 {
+            return self.render(origin, scale, width, height);
+        }
+        let mut out = Canvas::new(width, height, origin, scale);
+        let fine = scale * samples as f64;
+        let rows = (BAND_SAMPLES / (width * samples * samples)).max(1);
+        for top in (0..height).step_by(rows) {
+            let n = rows.min(height - top);
+            let band_origin = [origin[0] * samples as f64, (origin[1] + top as f64) * samples as f64];
+            let band = self.render(band_origin, fine, width * samples, n * samples).downsample(samples);
+            out.pixels[top * width * 4..(top + n) * width * 4].copy_from_slice(&band.pixels);
+        }
+        out
+    }
+
+    /// The layers that show in `view` with their opacity, bottom first,
+    /// each with its strokes whose extent meets the view in drawing order.
+    pub(crate) fn drawn_layers(&mut self, view: &[f32; 4]) -> Vec<(f32, Vec<usize>)> {
+        self.refresh_index();
+        let hits = |b: [f32; 4]| b[0] <= view[2] && b[2] >= view[0] && b[1] <= view[3] && b[3] >= view[1];
+        self.layers
+            .iter()
+            .filter(|l| l.visible && l.opacity > 0.0)
+            .map(|l| {
+                let order = (0..self.strokes.len()).filter(|&i| self.strokes[i].layer == l.id && self.index.bounds(self.strokes[i].id).is_some_and(hits)).collect();
+                (l.opacity.min(1.0), order)
+            })
+            .filter(|(_, order): &(f32, Vec<usize>)| !order.is_empty())
+            .collect()
+    }
+}
+
+/// Pixel size of `width` by `height` units at `scale`, `None` when empty,
+/// over `MAX_SIDE` or over `MAX_PIXELS`.
+pub(crate) fn raster_size(width: f32, height: f32, scale: f32) -> Option<(usize, usize)> {
+    let side = |v: f32| {
+        let n = (v * scale).ceil();
+        (n >= 1.0 && n <= MAX_SIDE as f32).then_some(n as usize)
+    };
+    let (w, h) = (side(width)?, side(height)?);
+    (w * h <= MAX_PIXELS).then_some((w, h))
+}
+
+/// Premultiplied RGBA in 0–1 floats, so repeated blending doesn't band,
+/// placed on the document's pixel grid at a scale. Coverage is decided in
+/// that shared grid, so canvases that tile a region draw exactly what one
+/// canvas covering it would.
+pub(crate) struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<f32>,
+    /// Grid position of the top left pixel corner.
+    pub origin: [f64; 2],
+    /// Pixels per document unit.
+    pub scale: f64,
+}
+
+impl Canvas {
+    pub(crate) fn new(width: usize, height: usize, origin: [f64; 2], scale: f64) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![0.0; width * height * 4],
+            origin,
+            scale,
+        }
+    }
+
+    /// Blends an `[x, y, r, g, b, a]` triangle soup in document units,
+    /// its colors straight or `premultiplied`.
+    pub(crate) fn draw(&mut self, soup: &[f32], blend: BlendMode, premultiplied: bool) {
+        for tri in soup.chunks_exact(FLOATS_PER_VERTEX * 3) {
+            let vertex = |k: usize| {
+                let v = &tri[k * FLOATS_PER_VERTEX..(k + 1) * FLOATS_PER_VERTEX];
+                ([v[0] as f64 * self.scale, v[1] as f64 * self.scale], [v[2], v[3], v[4], v[5]])
+            };
+            self.triangle([vertex(0), vertex(1), vertex(2)], blend, premultiplied);
+        }
+    }
+
+    /// Fills the pixels whose centres the triangle covers, interpolating
+    /// its vertex colors as they are, straight or `premultiplied`, as a GPU
+    /// would. An edge shared by two triangles belongs to exactly one of
+    /// them, so soups blend without seams or doubled alpha along their
+    /// diagonals.
+    fn triangle(&mut self, mut v: [([f64; 2], [f32; 4]); 3], blend: BlendMode, premultiplied: bool) {
+        let mut area = edge(v[0].0, v[1].0, v[2].0);
+        if area < 0.0 {
+            v.swap(1, 2);
+            area = -area;
+        }
+        if area <= 0.0 || !area.is_finite() {
+            return;
+        }
+        let (p0, p1, p2) = (v[0].0, v[1].0, v[2].0);
+        // Pixel ranges a pixel generous each way; the edge tests decide.
+        let clip = |lo: f64, hi: f64, origin: f64, n: usize| ((lo - origin - 1.0).floor().max(0.0) as usize, (hi - origin + 1.0).ceil().clamp(0.0, n as f64) as usize);
+        let (x0, x1) = clip(p0[0].min(p1[0]).min(p2[0]), p0[0].max(p1[0]).max(p2[0]), self.origin[0], self.width);
+        let (y0, y1) = clip(p0[1].min(p1[1]).min(p2[1]), p0[1].max(p1[1]).max(p2[1]), self.origin[1], self.height);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+        let edges = [(p1, p2), (p2, p0), (p0, p1)];
+        let owns = edges.map(|(a, b)| owns_edge(a, b));
+        for py in y0..y1 {
+            // Narrow the row to where every edge function can be positive,
+            // a pixel wider each side for rounding; long thin triangles
+            // would otherwise scan their whole box.
+            let cy = self.origin[1] + py as f64 + 0.5;
+            let (mut lo, mut hi) = (x0 as f64, x1 as f64);
+            for &(a, b) in &edges {
+                let dy = b[1] - a[1];
+                if dy != 0.0 {
+                    let x = (b[0] - a[0]) * (cy - a[1]) / dy + a[0] - self.origin[0] - 0.5;
+                    if dy > 0.0 { hi = hi.min(x + 2.0) } else { lo = lo.max(x - 1.0) }
+                }
+            }
+            let (lo, hi) = (lo.max(x0 as f64) as usize, hi.clamp(x0 as f64, x1 as f64) as usize);
+            for px in lo..hi {
+                let p = [self.origin[0] + px as f64 + 0.5, cy];
+                let mut weights = [0.0; 3];
+                let mut inside = true;
+                for (k, &(a, b)) in edges.iter().enumerate() {
+                    let w = edge(a, b, p);
+                    inside &= w > 0.0 || (w == 0.0 && owns[k]);
+                    weights[k] = (w / area) as f32;
+                }
+                if !inside {
+                    continue;
+                }
+                let mut color = [0.0; 4];
+                for (k, (_, c)) in v.iter().enumerate() {
+                    for (o, &c) in color.iter_mut().zip(c) {
+                        *o += weights[k] * c;
+                    }
+                }
+                if premultiplied && color[3] > 0.0 {
+                    let a = color[3];
+                    color[..3].iter_mut().for_each(|c| *c /= a);
+                }
+                let at = (py * self.width + px) * 4;
+                blend_pixel(&mut self.pixels[at..at + 4], color, blend);
+            }
+        }
+    }
+
+    /// Box-filtered down by `factor` each way; the size must divide.
+    pub(crate) fn downsample(&self, factor: usize) -> Canvas {
+        let (width, height) = (self.width / factor, self.height / factor);
+        let mut out = Canvas::new(width, height, [self.origin[0] / factor as f64, self.origin[1] / factor as f64], self.scale / factor as f64);
+        let norm = 1.0 / (factor * factor) as f32;
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 4];
+                for sy in y * factor..(y + 1) * factor {
+                    let row = &self.pixels[(sy * self.width + x * factor) * 4..(sy * self.width + (x + 1) * factor) * 4];
+                    for p in row.chunks_exact(4) {
+                        sum.iter_mut().zip(p).for_each(|(s, v)| *s += v);
+                    }
+                }
+                let at = (y * width + x) * 4;
+                out.pixels[at..at + 4].iter_mut().zip(sum).for_each(|(o, s)| *o = s * norm);
+            }
+        }
+        out
+    }
+
+    /// Source-over of `layer` at `opacity`.
+    pub(crate) fn composite(&mut self, layer: &Canvas, opacity: f32) {
+        for (d, s) in self.pixels.chunks_exact_mut(4).zip(layer.pixels.chunks_exact(4)) {
+            let keep = 1.0 - s[3] * opacity;
+            for k in 0..4 {
+                d[k] = s[k] * opacity + d[k] * keep;
+            }
+        }
+    }
+
+    /// Straight-alpha RGBA8.
+    pub(crate) fn to_rgba8(&self) -> Vec<u8> {
+        let byte = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let mut out = Vec::with_capacity(self.pixels.len());
+        for p in self.pixels.chunks_exact(4) {
+            let a = p[3];
+            if a <= 0.0 {
+                out.extend([0; 4]);
+                continue;
+            }
+            out.extend([byte(p[0] / a), byte(p[1] / a), byte(p[2] / a), byte(a)]);
+        }
+        out
+    }
+}
+
+/// Twice the signed area of `a, b, p`, positive with `p` left of `a → b`
+/// in y-down pixels.
+fn edge(a: [f64; 2], b: [f64; 2], p: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Whether centres exactly on the edge `a → b` are filled. With every
+/// triangle wound the same way, neighbours run a shared edge in opposite
+/// directions, so exactly one of them owns it.
+fn owns_edge(a: [f64; 2], b: [f64; 2]) -> bool {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    dy > 0.0 || (dy == 0.0 && dx < 0.0)
+}
+
+/// Blends a straight-alpha `color` onto a premultiplied pixel, as canvas
+/// composite operations do.
+fn blend_pixel(d: &mut [f32], color: [f32; 4], blend: BlendMode) {
+    let a = color[3].clamp(0.0, 1.0);
+    let s = [color[0].clamp(0.0, 1.0) * a, color[1].clamp(0.0, 1.0) * a, color[2].clamp(0.0, 1.0) * a];
+    match blend {
+        BlendMode::Normal => {
+            for k in 0..3 {
+                d[k] = s[k] + d[k] * (1.0 - a);
+            }
+            d[3] = a + d[3] * (1.0 - a);
+        }
+        BlendMode::Multiply => {
+            let da = d[3];
+            for k in 0..3 {
+                d[k] = s[k] * d[k] + s[k] * (1.0 - da) + d[k] * (1.0 - a);
+            }
+            d[3] = a + da * (1.0 - a);
+        }
+        BlendMode::DestinationOut => d.iter_mut().for_each(|v| *v *= 1.0 - a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raster_size_bounds_sides_and_area() {
+        assert_eq!(raster_size(10.5, 2.0, 2.0), Some((21, 4)));
+        assert_eq!(raster_size(0.0, 10.0, 1.0), None);
+        assert_eq!(raster_size(MAX_SIDE as f32 + 1.0, 1.0, 1.0), None);
+        assert_eq!(raster_size(MAX_SIDE as f32, 1024.0, 1.0), Some((MAX_SIDE, 1024)));
+        assert_eq!(raster_size(MAX_SIDE as f32, 1025.0, 1.0), None);
+        assert_eq!(raster_size(MAX_SIDE as f32, MAX_SIDE as f32, 1.0), None);
+    }
+
+    #[test]
+    fn oversized_rasters_are_empty() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 100.0, 100.0], &[4.0; 2], &[], &[0.0, 0.0, 0.0, 1.0], 0);
+        assert!(doc.rasterize(0.0, 0.0, 10000.0, 10000.0, 1.0).is_empty());
+        assert!(doc.rasterize_antialiased(0.0, 0.0, 100.0, 100.0, 100.0, 4).is_empty());
+        assert_eq!(doc.rasterize(0.0, 0.0, 100.0, 50.0, 1.0).len(), 100 * 50 * 4);
+    }
+
+    #[test]
+    fn bands_match_one_render() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 300.0, 200.0, 80.0, 300.0], &[9.0; 3], &[], &[0.2, 0.4, 0.8, 0.7], 0);
+        let origin = [3.0, 5.0];
+        let banded = doc.render_antialiased(origin, 1.0, 320, 320, 4);
+        let whole = doc.render([origin[0] * 4.0, origin[1] * 4.0], 4.0, 1280, 1280).downsample(4);
+        assert!(banded.pixels.iter().zip(&whole.pixels).all(|(a, b)| (a - b).abs() < 1e-6));
+        assert!(banded.pixels.iter().any(|&v| v > 0.0));
+    }
+}
@@ -0,0 +1,250 @@
+use wasm_bindgen::prelude::*;
+
+use crate::brush::BrushMesh;
+use crate::document::{InkDocument, Stroke};
+use crate::mesh::{MeshBatch, FLOATS_PER_VERTEX};
+use crate::samples::Sample;
+use crate::transform;
+
+/// A stretch of recorded drawing to play back: which strokes appear when,
+/// at what speed. Frames come from `InkDocument::playback_frame`.
+#[wasm_bindgen]
+pub struct Playback {
+    from: f64,
+    to: f64,
+    speed: f64,
+    /// Visible strokes in drawing order with the recorded times they start
+    /// and finish at.
+    spans: Vec<(u32, f64, f64)>,
+}
+
+#[wasm_bindgen]
+impl Playback {
+    /// Wall-clock milliseconds the playback lasts.
+    pub fn duration(&self) -> f64 {
+        (self.to - self.from) / self.speed
+    }
+
+    /// Recorded time shown `elapsed` wall-clock milliseconds in.
+    pub fn time_at(&self, elapsed: f64) -> f64 {
+        (self.from + elapsed.max(0.0) * self.speed).min(self.to)
+    }
+
+    pub fn stroke_count(&self) -> usize {
+        self.spans.len()
+    }
+}
+
+/// One animation frame: everything drawn by its time, in drawing order and
+/// document coordinates, with strokes still being drawn cut off mid-way.
+#[wasm_bindgen]
+pub struct PlaybackFrame {
+    time: f64,
+    meshes: MeshBatch,
+    drawing: Vec<u32>,
+    finished: bool,
+}
+
+#[wasm_bindgen]
+impl PlaybackFrame {
+    /// Recorded time the frame shows.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn meshes(&self) -> MeshBatch {
+        self.meshes.clone()
+    }
+
+    /// Ids of the strokes in `meshes` that are only partly drawn; the rest
+    /// are whole and match their cached meshes.
+    pub fn drawing(&self) -> Vec<u32> {
+        self.drawing.clone()
+    }
+
+    /// Whether this is the last frame of the playback.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+}
+
+/// Frames of a time-lapse: the document at evenly spaced moments of its
+/// drawing, first stroke to last.
+#[wasm_bindgen]
+pub struct TimeLapse {
+    times: Vec<f64>,
+    frames: Vec<MeshBatch>,
+}
+
+#[wasm_bindgen]
+impl TimeLapse {
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Recorded time of each frame.
+    pub fn times(&self) -> Vec<f64> {
+        self.times.clone()
+    }
+
+    /// Frame `k` as `PlaybackFrame::meshes`.
+    pub fn frame(&self, k: usize) -> Option<MeshBatch> {
+        self.frames.get(k).cloned()
+    }
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Milliseconds between the stroke's first and last timestamps; 0 for
+    /// an untimed stroke or an unknown id.
+    pub fn stroke_duration(&self, id: u32) -> f64 {
+        self.stroke(id).map_or(0.0, |s| {
+            let (a, b) = span(s);
+            b - a
+        })
+    }
+
+    /// The stroke as it looked `t` milliseconds after its first sample, for
+    /// replaying handwriting: the samples drawn by then, the last one
+    /// interpolated to exactly `t`, tessellated with the stroke's brush so
+    /// the temporary end gets a proper cap. In the stroke's own
+    /// coordinates, like `get_mesh`, and never cached. Before the first
+    /// sample the mesh is empty; untimed strokes and fills appear whole.
+    pub fn mesh_at_time(&self, id: u32, t: f64) -> Option<BrushMesh> {
+        let s = self.stroke(id)?;
+        if s.timestamps.is_empty() {
+            return Some(s.build(&self.brushes, self.zoom_bucket));
+        }
+        let drawn = drawn_by(&s.samples(), t);
+        Some(s.with_samples(s.id, &drawn).build(&self.brushes, self.zoom_bucket))
+    }
+
+    /// `[first, last]` recorded time across every stroke: timestamps, or
+    /// the creation time of untimed strokes. Empty for an empty document.
+    pub fn recorded_time_range(&self) -> Vec<f64> {
+        let mut range: Option<(f64, f64)> = None;
+        for (a, b) in self.strokes.iter().map(span) {
+            range = Some(range.map_or((a, b), |r| (r.0.min(a), r.1.max(b))));
+        }
+        range.map_or_else(Vec::new, |r| vec![r.0, r.1])
+    }
+
+    /// Plays back the drawing recorded between `from` and `to`, in the
+    /// host's timestamp clock, `speed` times faster than it was drawn, e.g.
+    /// for a lecture-recording mode. Strokes on visible layers are shown as
+    /// their timestamps say: finished before `from` they are already there,
+    /// started after `to` they never appear. Untimed strokes and fills pop
+    /// in whole at their creation time. A `speed` that is not positive
+    /// plays in real time.
+    pub fn replay_range(&self, from: f64, to: f64, speed: f64) -> Playback {
+        let spans = self
+            .ordered()
+            .map(|i| &self.strokes[i])
+            .filter(|s| self.layer_visible(s.layer))
+            .map(|s| {
+                let (a, b) = span(s);
+                (s.id, a, b)
+            })
+            .filter(|&(_, a, _)| a <= to)
+            .collect();
+        Playback {
+            from,
+            to: to.max(from),
+            speed: if speed > 0.0 { speed } else { 1.0 },
+            spans,
+        }
+    }
+
+    /// Samples the recorded drawing at `frames` evenly spaced times, the
+    /// first when the first stroke starts and the last when the last one
+    /// ends, for rendering time-lapse videos. One frame shows the finished
+    /// document. Every frame holds a full copy of what is drawn by then,
+    /// so keep `frames` to what the encoder needs.
+    pub fn time_lapse(&mut self, frames: usize) -> TimeLapse {
+        let [from, to] = self.recorded_time_range()[..] else {
+            return TimeLapse {
+                times: Vec::new(),
+                frames: Vec::new(),
+            };
+        };
+        let playback = self.replay_range(from, to, 1.0);
+        let step = if frames > 1 { (to - from) / (frames - 1) as f64 } else { 0.0 };
+        let (mut times, mut out) = (Vec::with_capacity(frames), Vec::with_capacity(frames));
+        for k in 0..frames {
+            let elapsed = if frames > 1 { step * k as f64 } else { to - from };
+            let frame = self.playback_frame(&playback, elapsed);
+            times.push(frame.time);
+            out.push(frame.meshes);
+        }
+        TimeLapse { times, frames: out }
+    }
+
+    /// The frame of `playback` shown `elapsed` wall-clock milliseconds after
+    /// it started. Whole strokes come from the mesh cache; only the strokes
+    /// being drawn at that moment are tessellated. Stamp strokes are left
+    /// out, as by `meshes`.
+    pub fn playback_frame(&mut self, playback: &Playback, elapsed: f64) -> PlaybackFrame {
+        let time = playback.time_at(elapsed);
+        let mut meshes = MeshBatch::default();
+        let mut drawing = Vec::new();
+        for &(id, start, end) in &playback.spans {
+            let Some(i) = self.index(id) else {
+                continue;
+            };
+            if start > time {
+                continue;
+            }
+            let s = &mut self.strokes[i];
+            let whole = end <= time;
+            let mesh = if whole {
+                s.placed_mesh(&self.brushes, self.zoom_bucket)
+            } else {
+                let partial = s.with_samples(id, &drawn_by(&s.samples(), time - start));
+                let mesh = partial.build(&self.brushes, self.zoom_bucket);
+                mesh.with_vertices(transform::apply_vertices(&s.transform, mesh.soup(), mesh.floats_per_vertex()))
+            };
+            if mesh.floats_per_vertex() != FLOATS_PER_VERTEX {
+                continue;
+            }
+            meshes.push(id, mesh.soup());
+            if !whole {
+                drawing.push(id);
+            }
+        }
+        PlaybackFrame {
+            time,
+            meshes,
+            drawing,
+            finished: time >= playback.to,
+        }
+    }
+}
+
+/// Recorded times a stroke starts and finishes at.
+fn span(s: &Stroke) -> (f64, f64) {
+    match (s.timestamps.first(), s.timestamps.last()) {
+        (Some(&a), Some(&b)) => (a, b.max(a)),
+        _ => (s.created, s.created),
+    }
+}
+
+/// The samples drawn by `t` milliseconds after the first one, ending with
+/// a sample interpolated at `t`.
+pub(crate) fn drawn_by(samples: &[Sample], t: f64) -> Vec<Sample> {
+    let Some(start) = samples.first().map(|s| s.time) else {
+        return Vec::new();
+    };
+    if t.is_nan() || t < 0.0 {
+        return Vec::new();
+    }
+    let at = start + t;
+    let k = samples.iter().position(|s| s.time > at).unwrap_or(samples.len());
+    let mut drawn = samples[..k].to_vec();
+    if let (Some(a), Some(b)) = (drawn.last().copied(), samples.get(k)) {
+        let f = ((at - a.time) / (b.time - a.time)) as f32;
+        if f > 0.0 {
+            drawn.push(a.lerp(b, f));
+        }
+    }
+    drawn
+}
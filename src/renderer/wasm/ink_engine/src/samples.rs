@@ -0,0 +1,85 @@
+use wasm_bindgen::prelude::*;
+
+/// One input sample. Missing widths default to 1.0 and missing timestamps to 0.0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Sample {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub time: f64,
+}
+
+impl Sample {
+    pub fn pos(&self) -> (f32, f32) {
+        (self.x, self.y)
+    }
+
+    pub fn lerp(&self, other: &Sample, t: f32) -> Sample {
+        Sample {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+            width: self.width + (other.width - self.width) * t,
+            time: self.time + (other.time - self.time) * t as f64,
+        }
+    }
+}
+
+pub(crate) fn samples_from(points: &[f32], widths: &[f32], timestamps: &[f64]) -> Vec<Sample> {
+    (0..points.len() / 2)
+        .map(|i| Sample {
+            x: points[i * 2],
+            y: points[i * 2 + 1],
+            width: widths.get(i).copied().unwrap_or(1.0),
+            time: timestamps.get(i).copied().unwrap_or(0.0),
+        })
+        .collect()
+}
+
+/// Filtered or resampled stroke data handed back to the host as parallel arrays.
+/// `timestamps` is empty when the input carried none.
+#[wasm_bindgen]
+pub struct StrokeSamples {
+    points: Vec<f32>,
+    widths: Vec<f32>,
+    timestamps: Vec<f64>,
+}
+
+/// Splits samples back into flat point and width arrays.
+pub(crate) fn split(samples: &[Sample]) -> (Vec<f32>, Vec<f32>) {
+    let mut points = Vec::with_capacity(samples.len() * 2);
+    for s in samples {
+        points.push(s.x);
+        points.push(s.y);
+    }
+    (points, samples.iter().map(|s| s.width).collect())
+}
+
+impl StrokeSamples {
+    pub(crate) fn from_samples(samples: &[Sample], with_times: bool) -> Self {
+        let (points, widths) = split(samples);
+        StrokeSamples {
+            points,
+            widths,
+            timestamps: if with_times { samples.iter().map(|s| s.time).collect() } else { Vec::new() },
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl StrokeSamples {
+    pub fn points(&self) -> Vec<f32> {
+        self.points.clone()
+    }
+
+    pub fn widths(&self) -> Vec<f32> {
+        self.widths.clone()
+    }
+
+    pub fn timestamps(&self) -> Vec<f64> {
+        self.timestamps.clone()
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.widths.len()
+    }
+}
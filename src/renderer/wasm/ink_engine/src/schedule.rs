@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+
+use wasm_bindgen::prelude::*;
+
+use crate::mesh::{Mesh, MeshBatch};
+use crate::tessellate::{self, MeshOptions};
+
+/// Pieces emitted between clock checks.
+const PIECES_PER_CHUNK: usize = 32;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    performance_now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+struct Job {
+    id: u32,
+    color: [f32; 4],
+    points: Vec<f32>,
+    widths: Vec<f32>,
+    prepared: bool,
+    /// Next piece to emit once prepared.
+    next: usize,
+    soup: Vec<f32>,
+}
+
+impl Job {
+    /// Does one slice of work; returns true when the stroke is complete.
+    fn step(&mut self, options: &MeshOptions) -> bool {
+        if !self.prepared {
+            let (points, widths) = tessellate::prepare(&self.points, &self.widths, options);
+            (self.points, self.widths) = (points.into_owned(), widths.into_owned());
+            self.prepared = true;
+            if !tessellate::emits_in_pieces(options) {
+                let mut mesh = Mesh::with_topology(options.topology, false);
+                // Already prepared, so skip the smoothing and taper passes.
+                let plain = MeshOptions {
+                    smoothing: 0,
+                    start_taper: 0.0,
+                    end_taper: 0.0,
+                    ..*options
+                };
+                tessellate::tessellate(&self.points, &self.widths, self.color, &plain, &mut mesh);
+                self.soup = mesh.triangle_soup();
+                return true;
+            }
+            return false;
+        }
+        let n = self.widths.len();
+        if n == 0 {
+            return true;
+        }
+        let last = (self.next + PIECES_PER_CHUNK - 1).min(n - 1);
+        let soup = tessellate::emit_pieces(&self.points, &self.widths, self.color, options, self.next, last, last == n - 1);
+        self.soup.extend_from_slice(&soup);
+        self.next = last + 1;
+        self.next >= n
+    }
+
+    fn pending_segments(&self) -> usize {
+        self.widths.len().saturating_sub(self.next)
+    }
+}
+
+/// Spreads the tessellation of many strokes, e.g. a page rebuilt after a
+/// zoom change, over several frames. Each `run` works until its budget is
+/// spent, a chunk of segments at a time, so even one very long stroke does
+/// not blow the frame; unfinished work resumes on the next call.
+#[wasm_bindgen]
+pub struct TessellationScheduler {
+    options: MeshOptions,
+    queue: VecDeque<Job>,
+    completed: MeshBatch,
+}
+
+#[wasm_bindgen]
+impl TessellationScheduler {
+    #[wasm_bindgen(constructor)]
+    pub fn new(options: &MeshOptions) -> TessellationScheduler {
+        TessellationScheduler {
+            options: *options,
+            queue: VecDeque::new(),
+            completed: MeshBatch::default(),
+        }
+    }
+
+    /// Queues a stroke; `id` comes back with its mesh.
+    pub fn enqueue(&mut self, id: u32, points: &[f32], widths: &[f32], color: &[f32]) {
+        let n = points.len() / 2;
+        self.queue.push_back(Job {
+            id,
+            color: crate::rgba(color),
+            points: points[..n * 2].to_vec(),
+            widths: (0..n).map(|i| widths.get(i).copied().unwrap_or(1.0)).collect(),
+            prepared: false,
+            next: 0,
+            soup: Vec::new(),
+        });
+    }
+
+    /// Drops queued work for `id`, e.g. when the stroke was erased meanwhile.
+    pub fn cancel(&mut self, id: u32) {
+        self.queue.retain(|job| job.id != id);
+    }
+
+    /// Tessellates for up to `budget_ms` milliseconds and returns the number
+    /// of strokes finished. At least one chunk of work is done per call so
+    /// the queue always drains.
+    pub fn run(&mut self, budget_ms: f64) -> u32 {
+        let start = now_ms();
+        let mut finished = 0;
+        while let Some(job) = self.queue.front_mut() {
+            if job.step(&self.options) {
+                if let Some(job) = self.queue.pop_front() {
+                    self.completed.push(job.id, &job.soup);
+                }
+                finished += 1;
+            }
+            if now_ms() - start >= budget_ms {
+                break;
+            }
+        }
+        finished
+    }
+
+    /// Meshes finished since the last call, in completion order.
+    pub fn take_completed(&mut self) -> MeshBatch {
+        std::mem::take(&mut self.completed)
+    }
+
+    pub fn pending_strokes(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Segments still to emit, counted on the raw points for strokes not
+    /// started yet, which smoothing will multiply.
+    pub fn pending_segments(&self) -> usize {
+        self.queue.iter().map(Job::pending_segments).sum()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
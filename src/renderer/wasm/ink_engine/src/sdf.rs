@@ -0,0 +1,233 @@
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::geom::{segment_distance, Point};
+use crate::raster::raster_size;
+
+/// Signed distances from texel centres to the nearest ink edge, in
+/// document units: negative inside, positive outside, clamped to
+/// `±spread`. Texel `i, j` is centred at `x + (i + 0.5) / scale, y + (j +
+/// 0.5) / scale`, rows top down.
+#[wasm_bindgen]
+pub struct DistanceField {
+    x: f32,
+    y: f32,
+    scale: f32,
+    spread: f32,
+    width: u32,
+    height: u32,
+    distances: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl DistanceField {
+    pub fn x(&self) -> f32 {
+        self.x
+    }
+
+    pub fn y(&self) -> f32 {
+        self.y
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn spread(&self) -> f32 {
+        self.spread
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn distances(&self) -> Vec<f32> {
+        self.distances.clone()
+    }
+
+    /// One byte per texel for an `R8` texture: the edge at 128, rising to
+    /// 255 `spread` inside and falling to 0 `spread` outside, so a shader
+    /// reads ink where the sample is over 0.5.
+    pub fn texture(&self) -> Vec<u8> {
+        self.distances.iter().map(|d| (128.0 - d / self.spread * 128.0).round().clamp(0.0, 255.0) as u8).collect()
+    }
+
+    /// The distance at document point `x, y`, interpolated between texel
+    /// centres; `spread` outside the field.
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        self.sample(x, y, |k| self.distances[k]).unwrap_or(self.spread)
+    }
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// The stroke's distance field at `scale` texels per document unit,
+    /// over its outline's extent grown by `spread` each way so a glow or
+    /// outline that far out still fits. Distances are exact to the outline
+    /// `stroke_outline` traces, so the field stays sharp when magnified on
+    /// the GPU. `None` for an unknown id, a stroke without an outline, a
+    /// spread or scale that is not positive, or a field over 16384 texels
+    /// a side or 16777216 in all.
+    pub fn stroke_distance_field(&mut self, id: u32, scale: f32, spread: f32) -> Option<DistanceField> {
+        if !(scale > 0.0 && spread > 0.0) {
+            return None;
+        }
+        let i = self.index(id)?;
+        let rings = self.outline_rings(i)?;
+        let mut field = DistanceField::around(&rings, scale, spread)?;
+        field.add(&rings);
+        Some(field)
+    }
+
+    /// The distance field of the rect `x, y, width, height` (document
+    /// units), a tile say, at `scale` texels per unit: the nearest edge of
+    /// any stroke on a visible layer, whatever its opacity, with
+    /// overlapping strokes merged. `None` as for `stroke_distance_field`.
+    pub fn distance_field(&mut self, x: f32, y: f32, width: f32, height: f32, scale: f32, spread: f32) -> Option<DistanceField> {
+        if !(scale > 0.0 && spread > 0.0) {
+            return None;
+        }
+        let mut field = DistanceField::new(x, y, width, height, scale, spread)?;
+        let view = [x - spread, y - spread, x + width + spread, y + height + spread];
+        for (_, order) in self.drawn_layers(&view) {
+            for i in order {
+                if let Some(rings) = self.outline_rings(i) {
+                    field.add(&rings);
+                }
+            }
+        }
+        Some(field)
+    }
+}
+
+impl DistanceField {
+    /// A field with nothing in it, `None` when too small or too large.
+    pub(crate) fn new(x: f32, y: f32, width: f32, height: f32, scale: f32, spread: f32) -> Option<DistanceField> {
+        let (w, h) = raster_size(width, height, scale)?;
+        Some(DistanceField {
+            x,
+            y,
+            scale,
+            spread,
+            width: w as u32,
+            height: h as u32,
+            distances: vec![spread; w * h],
+        })
+    }
+
+    /// A field over the extent of `rings` grown by `spread` each way.
+    pub(crate) fn around(rings: &[Vec<Point>], scale: f32, spread: f32) -> Option<DistanceField> {
+        let (lo, hi) = extent(rings)?;
+        DistanceField::new(lo.0 - spread, lo.1 - spread, hi.0 - lo.0 + 2.0 * spread, hi.1 - lo.1 + 2.0 * spread, scale, spread)
+    }
+
+    /// Distance at texel index `k`, rows top down.
+    pub(crate) fn texel(&self, k: usize) -> f32 {
+        self.distances[k]
+    }
+
+    /// Centre of texel `i, j` in document units.
+    pub(crate) fn centre(&self, i: usize, j: usize) -> Point {
+        (self.x + (i as f32 + 0.5) / self.scale, self.y + (j as f32 + 0.5) / self.scale)
+    }
+
+    /// Columns and rows of the texels whose centres are within `spread`,
+    /// each way, of the box from `lo` to `hi`.
+    pub(crate) fn texels_near(&self, lo: Point, hi: Point) -> (Range<usize>, Range<usize>) {
+        let pad = self.spread * self.scale;
+        let span = |lo: f32, hi: f32, origin: f32, n: u32| {
+            let start = ((lo - origin) * self.scale - 0.5 - pad).ceil().max(0.0) as usize;
+            let end = (((hi - origin) * self.scale - 0.5 + pad).floor() + 1.0).clamp(0.0, n as f32) as usize;
+            start..end.max(start)
+        };
+        (span(lo.0, hi.0, self.x, self.width), span(lo.1, hi.1, self.y, self.height))
+    }
+
+    /// `value` of texel index `k` interpolated bilinearly at document point
+    /// `x, y`; `None` outside the field.
+    pub(crate) fn sample(&self, x: f32, y: f32, value: impl Fn(usize) -> f32) -> Option<f32> {
+        let (u, v) = ((x - self.x) * self.scale - 0.5, (y - self.y) * self.scale - 0.5);
+        let (w, h) = (self.width as f32, self.height as f32);
+        if !(u > -1.0 && v > -1.0 && u < w && v < h) {
+            return None;
+        }
+        let (u, v) = (u.clamp(0.0, w - 1.0), v.clamp(0.0, h - 1.0));
+        let (i, j) = (u.floor() as usize, v.floor() as usize);
+        let (fu, fv) = (u - i as f32, v - j as f32);
+        let (i1, j1) = ((i + 1).min(self.width as usize - 1), (j + 1).min(self.height as usize - 1));
+        let at = |i: usize, j: usize| value(j * self.width as usize + i);
+        let top = at(i, j) + (at(i1, j) - at(i, j)) * fu;
+        let bottom = at(i, j1) + (at(i1, j1) - at(i, j1)) * fu;
+        Some(top + (bottom - top) * fv)
+    }
+
+    /// Merges in the shape bounded by `rings`, as `polygon::union` orients
+    /// them, by taking the smaller distance: exact outside the shapes and
+    /// conservative where they overlap inside.
+    pub(crate) fn add(&mut self, rings: &[Vec<Point>]) {
+        let Some((lo, hi)) = extent(rings) else {
+            return;
+        };
+        let (cols, rows) = self.texels_near(lo, hi);
+        if cols.is_empty() || rows.is_empty() {
+            return;
+        }
+        let cw = cols.len();
+        let mut near = vec![self.spread; cw * rows.len()];
+        for ring in rings {
+            for (k, &a) in ring.iter().enumerate() {
+                let b = ring[(k + 1) % ring.len()];
+                let (edge_cols, edge_rows) = self.texels_near((a.0.min(b.0), a.1.min(b.1)), (a.0.max(b.0), a.1.max(b.1)));
+                for j in edge_rows {
+                    for i in edge_cols.clone() {
+                        let d = segment_distance(self.centre(i, j), a, b);
+                        let cell = &mut near[(j - rows.start) * cw + i - cols.start];
+                        *cell = cell.min(d);
+                    }
+                }
+            }
+        }
+        let w = self.width as usize;
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for j in rows.clone() {
+            let cy = self.centre(0, j).1;
+            crossings.clear();
+            for ring in rings {
+                for (k, &a) in ring.iter().enumerate() {
+                    let b = ring[(k + 1) % ring.len()];
+                    if (a.1 > cy) != (b.1 > cy) {
+                        let x = a.0 + (cy - a.1) / (b.1 - a.1) * (b.0 - a.0);
+                        crossings.push((x, if b.1 > a.1 { 1 } else { -1 }));
+                    }
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let (mut winding, mut next) = (0, 0);
+            for i in cols.clone() {
+                let cx = self.centre(i, j).0;
+                while next < crossings.len() && crossings[next].0 <= cx {
+                    winding += crossings[next].1;
+                    next += 1;
+                }
+                let d = near[(j - rows.start) * cw + i - cols.start];
+                let d = if winding != 0 { -d } else { d };
+                let cell = &mut self.distances[j * w + i];
+                *cell = cell.min(d);
+            }
+        }
+    }
+}
+
+/// Corners of the box around every ring point, `None` when there are none.
+pub(crate) fn extent(rings: &[Vec<Point>]) -> Option<(Point, Point)> {
+    let b = rings.iter().flatten().fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |b, p| {
+        [b[0].min(p.0), b[1].min(p.1), b[2].max(p.0), b[3].max(p.1)]
+    });
+    (b[0] <= b[2]).then_some(((b[0], b[1]), (b[2], b[3])))
+}
@@ -0,0 +1,140 @@
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::geom::{point_at, segments_intersect, Point};
+use crate::mesh::{self, MeshBatch};
+use crate::polygon::point_in_ring;
+use crate::transform;
+
+/// Which strokes a selection shape picks.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SelectMode {
+    /// Strokes lying entirely inside the shape.
+    #[default]
+    Contained,
+    /// Strokes with any part inside the shape or crossing its edge.
+    Intersecting,
+}
+
+/// Outcome of `transform_selection`: the moved strokes' new meshes and the
+/// area to repaint.
+#[wasm_bindgen]
+pub struct SelectionEdit {
+    meshes: MeshBatch,
+    dirty: Option<[f32; 4]>,
+}
+
+#[wasm_bindgen]
+impl SelectionEdit {
+    /// The transformed strokes in document coordinates, in drawing order,
+    /// as from `InkDocument::meshes`.
+    pub fn meshes(&self) -> MeshBatch {
+        self.meshes.clone()
+    }
+
+    /// `[min_x, min_y, max_x, max_y]` covering the strokes before and after
+    /// the edit; empty when nothing moved.
+    pub fn dirty_rect(&self) -> Vec<f32> {
+        self.dirty.map_or_else(Vec::new, |r| r.to_vec())
+    }
+}
+
+/// The `[a, b, c, d, e, f]` matrix that scales by `scale_x, scale_y` and
+/// rotates by `rotation` radians about the pivot, then translates by
+/// `dx, dy`: what a move, resize or rotate handle drags out.
+#[wasm_bindgen]
+pub fn selection_matrix(dx: f32, dy: f32, scale_x: f32, scale_y: f32, rotation: f32, pivot_x: f32, pivot_y: f32) -> Vec<f32> {
+    let (s, c) = rotation.sin_cos();
+    let about = [c * scale_x, s * scale_x, -s * scale_y, c * scale_y, 0.0, 0.0];
+    let (px, py) = transform::apply(&about, (pivot_x, pivot_y));
+    vec![about[0], about[1], about[2], about[3], pivot_x - px + dx, pivot_y - py + dy]
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Applies `matrix` (`[a, b, c, d, e, f]`, e.g. from `selection_matrix`)
+    /// to every stroke in `ids`. With `bake` false only the transforms
+    /// change and the cached meshes are reused, which suits a live drag;
+    /// with `bake` true each stroke's whole transform is folded into its
+    /// points and it is retessellated, e.g. when the handle is released.
+    pub fn transform_selection(&mut self, ids: &[u32], matrix: &[f32], bake: bool) -> SelectionEdit {
+        let mut dirty = None;
+        for &id in ids {
+            let Some(change) = self.transform_stroke(id, matrix) else {
+                continue;
+            };
+            dirty = mesh::union_bounds(dirty, change.dirty);
+            if bake {
+                dirty = mesh::union_bounds(dirty, self.bake_stroke_transform(id).and_then(|c| c.dirty));
+            }
+        }
+        let order: Vec<usize> = self.ordered().filter(|&i| ids.contains(&self.strokes[i].id)).collect();
+        SelectionEdit {
+            meshes: self.batch(&order, self.zoom_bucket),
+            dirty,
+        }
+    }
+
+    /// Ids of the strokes on visible layers picked by the freeform lasso
+    /// `polygon` (document units, implicitly closed), in drawing order.
+    /// Self-intersecting lassos use the even-odd rule. Strokes are judged by
+    /// their centreline.
+    pub fn select_lasso(&mut self, polygon: &[f32], mode: SelectMode) -> Vec<u32> {
+        let ring: Vec<Point> = (0..polygon.len() / 2).map(|i| point_at(polygon, i)).collect();
+        if ring.len() < 3 {
+            return Vec::new();
+        }
+        let bounds = ring.iter().fold([f32::MAX, f32::MAX, f32::MIN, f32::MIN], |b, p| {
+            [b[0].min(p.0), b[1].min(p.1), b[2].max(p.0), b[3].max(p.1)]
+        });
+        let edges: Vec<(Point, Point)> = (0..ring.len()).map(|i| (ring[i], ring[(i + 1) % ring.len()])).collect();
+        let crosses = |a: Point, b: Point| edges.iter().any(|&(c, d)| segments_intersect(a, b, c, d));
+        self.select_where(bounds, |line| {
+            let any_inside = line.iter().any(|&p| point_in_ring(p, &ring));
+            let any_crossing = line.windows(2).any(|s| crosses(s[0], s[1]));
+            match mode {
+                SelectMode::Contained => any_inside && !any_crossing && line.iter().all(|&p| point_in_ring(p, &ring)),
+                SelectMode::Intersecting => any_inside || any_crossing,
+            }
+        })
+    }
+
+    /// Marquee selection: ids of the strokes on visible layers picked by the
+    /// rectangle `x, y, width, height` (document units; negative sizes
+    /// drag the other way), in drawing order. `Contained` is the strict
+    /// mode, `Intersecting` the loose one that also takes strokes the
+    /// rectangle only partly covers or merely crosses.
+    pub fn select_rect(&mut self, x: f32, y: f32, width: f32, height: f32, mode: SelectMode) -> Vec<u32> {
+        let r = [x.min(x + width), y.min(y + height), x.max(x + width), y.max(y + height)];
+        let inside = |p: &Point| p.0 >= r[0] && p.0 <= r[2] && p.1 >= r[1] && p.1 <= r[3];
+        let corners = [(r[0], r[1]), (r[2], r[1]), (r[2], r[3]), (r[0], r[3])];
+        let crosses = |a: Point, b: Point| (0..4).any(|k| segments_intersect(a, b, corners[k], corners[(k + 1) % 4]));
+        self.select_where(r, |line| match mode {
+            SelectMode::Contained => line.iter().all(inside),
+            SelectMode::Intersecting => line.iter().any(inside) || line.windows(2).any(|s| crosses(s[0], s[1])),
+        })
+    }
+}
+
+impl InkDocument {
+    /// Strokes on visible layers with bounds meeting `bounds` whose
+    /// document-space centreline, or outline vertices for a fill, passes
+    /// `test`, in drawing order.
+    pub(crate) fn select_where(&mut self, bounds: [f32; 4], test: impl Fn(&[Point]) -> bool) -> Vec<u32> {
+        self.refresh_index();
+        let hits = self.index.query(&bounds);
+        self.ordered()
+            .map(|i| &self.strokes[i])
+            .filter(|s| hits.binary_search(&s.id).is_ok() && self.layer_visible(s.layer))
+            .filter(|s| {
+                let line: Vec<Point> = match &s.fill {
+                    Some(rings) => rings.iter().flatten().map(|&p| transform::apply(&s.transform, p)).collect(),
+                    None => s.points.chunks_exact(2).map(|p| transform::apply(&s.transform, (p[0], p[1]))).collect(),
+                };
+                !line.is_empty() && test(&line)
+            })
+            .map(|s| s.id)
+            .collect()
+    }
+}
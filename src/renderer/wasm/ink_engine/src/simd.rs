@@ -0,0 +1,226 @@
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+use std::arch::wasm32::*;
+
+use wasm_bindgen::prelude::*;
+
+use crate::geom::Point;
+
+/// Whether this build vectorizes tessellation with WebAssembly `simd128`.
+/// The engine is compiled with it by default; a build without it, for
+/// runtimes that reject SIMD modules, runs the same loops one lane at a
+/// time and gives identical meshes.
+#[wasm_bindgen]
+pub fn simd_enabled() -> bool {
+    cfg!(all(target_arch = "wasm32", target_feature = "simd128"))
+}
+
+/// Four floats operated on together: a `v128` with `simd128`, otherwise an
+/// array the same operations loop over. Only exactly rounded operations
+/// are offered, so both agree with the scalar code bit for bit.
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+#[derive(Clone, Copy)]
+pub(crate) struct F32x4(v128);
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+#[derive(Clone, Copy)]
+pub(crate) struct F32x4([f32; 4]);
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+impl F32x4 {
+    pub fn new(a: f32, b: f32, c: f32, d: f32) -> F32x4 {
+        F32x4(f32x4(a, b, c, d))
+    }
+
+    /// The first four floats of `src`.
+    pub fn load(src: &[f32]) -> F32x4 {
+        let src = &src[..4];
+        // SAFETY: `src` holds four floats and `v128_load` allows any alignment.
+        F32x4(unsafe { v128_load(src.as_ptr() as *const v128) })
+    }
+
+    /// Writes the lanes to the first four floats of `dst`.
+    pub fn store(self, dst: &mut [f32]) {
+        let dst = &mut dst[..4];
+        // SAFETY: as for `load`.
+        unsafe { v128_store(dst.as_mut_ptr() as *mut v128, self.0) }
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [
+            f32x4_extract_lane::<0>(self.0),
+            f32x4_extract_lane::<1>(self.0),
+            f32x4_extract_lane::<2>(self.0),
+            f32x4_extract_lane::<3>(self.0),
+        ]
+    }
+
+    pub fn add(self, o: F32x4) -> F32x4 {
+        F32x4(f32x4_add(self.0, o.0))
+    }
+
+    pub fn sub(self, o: F32x4) -> F32x4 {
+        F32x4(f32x4_sub(self.0, o.0))
+    }
+
+    pub fn mul(self, o: F32x4) -> F32x4 {
+        F32x4(f32x4_mul(self.0, o.0))
+    }
+
+    pub fn div(self, o: F32x4) -> F32x4 {
+        F32x4(f32x4_div(self.0, o.0))
+    }
+
+    pub fn sqrt(self) -> F32x4 {
+        F32x4(f32x4_sqrt(self.0))
+    }
+
+    /// Lane-wise `f32::max(self, floor)` for a `floor` that is not NaN.
+    pub fn at_least(self, floor: F32x4) -> F32x4 {
+        F32x4(f32x4_pmax(floor.0, self.0))
+    }
+
+    /// `[b, a, d, c]` from `[a, b, c, d]`: each point's coordinates swapped.
+    pub fn swap_pairs(self) -> F32x4 {
+        F32x4(i32x4_shuffle::<1, 0, 3, 2>(self.0, self.0))
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+impl F32x4 {
+    pub fn new(a: f32, b: f32, c: f32, d: f32) -> F32x4 {
+        F32x4([a, b, c, d])
+    }
+
+    pub fn load(src: &[f32]) -> F32x4 {
+        F32x4([src[0], src[1], src[2], src[3]])
+    }
+
+    pub fn store(self, dst: &mut [f32]) {
+        dst[..4].copy_from_slice(&self.0);
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        self.0
+    }
+
+    fn zip(self, o: F32x4, f: impl Fn(f32, f32) -> f32) -> F32x4 {
+        F32x4([f(self.0[0], o.0[0]), f(self.0[1], o.0[1]), f(self.0[2], o.0[2]), f(self.0[3], o.0[3])])
+    }
+
+    pub fn add(self, o: F32x4) -> F32x4 {
+        self.zip(o, |a, b| a + b)
+    }
+
+    pub fn sub(self, o: F32x4) -> F32x4 {
+        self.zip(o, |a, b| a - b)
+    }
+
+    pub fn mul(self, o: F32x4) -> F32x4 {
+        self.zip(o, |a, b| a * b)
+    }
+
+    pub fn div(self, o: F32x4) -> F32x4 {
+        self.zip(o, |a, b| a / b)
+    }
+
+    pub fn sqrt(self) -> F32x4 {
+        F32x4(self.0.map(f32::sqrt))
+    }
+
+    pub fn at_least(self, floor: F32x4) -> F32x4 {
+        self.zip(floor, f32::max)
+    }
+
+    pub fn swap_pairs(self) -> F32x4 {
+        let [a, b, c, d] = self.0;
+        F32x4([b, a, d, c])
+    }
+}
+
+impl F32x4 {
+    pub fn splat(v: f32) -> F32x4 {
+        F32x4::new(v, v, v, v)
+    }
+
+    pub fn pair(a: Point, b: Point) -> F32x4 {
+        F32x4::new(a.0, a.1, b.0, b.1)
+    }
+}
+
+/// Fills `dirs` and `norms` with the unit direction and left normal of each
+/// of `count` segments of the polyline `points`, as flat `[x, y]` pairs,
+/// segment `i` running from point `i` to point `(i + 1) % n`, two segments
+/// at a time. Degenerate segments keep a length of 1e-6 so they never
+/// divide by zero.
+pub(crate) fn segment_frames(points: &[f32], n: usize, count: usize, dirs: &mut Vec<f32>, norms: &mut Vec<f32>) {
+    for v in [&mut *dirs, &mut *norms] {
+        v.clear();
+        v.resize(count * 2, 0.0);
+    }
+    let floor = F32x4::splat(1e-6);
+    let flip = F32x4::new(-1.0, 1.0, -1.0, 1.0);
+    let frame = |d: F32x4| {
+        let sq = d.mul(d);
+        let dir = d.div(sq.add(sq.swap_pairs()).sqrt().at_least(floor));
+        (dir, dir.swap_pairs().mul(flip))
+    };
+    let mut i = 0;
+    // Two segments from three consecutive points, while neither wraps.
+    while i + 1 < count && i + 2 < n {
+        let (dir, nrm) = frame(F32x4::load(&points[i * 2 + 2..]).sub(F32x4::load(&points[i * 2..])));
+        dir.store(&mut dirs[i * 2..]);
+        nrm.store(&mut norms[i * 2..]);
+        i += 2;
+    }
+    for i in i..count {
+        let j = (i + 1) % n;
+        let d = (points[j * 2] - points[i * 2], points[j * 2 + 1] - points[i * 2 + 1]);
+        let (dir, nrm) = frame(F32x4::pair(d, d));
+        let ([x, y, _, _], [nx, ny, _, _]) = (dir.to_array(), nrm.to_array());
+        dirs[i * 2..i * 2 + 2].copy_from_slice(&[x, y]);
+        norms[i * 2..i * 2 + 2].copy_from_slice(&[nx, ny]);
+    }
+}
+
+/// Fills `out` with, per point of a polyline of `n` points with segment
+/// normals `norms`, flat `[x, y]` pairs: the unit bisector of the normals either side, the
+/// length of their sum, and the bisector's dot product with the outgoing
+/// normal, which scales the radius to the miter length. The first point
+/// takes the last segment as its incoming one, as a closed stroke does;
+/// past the last segment the outgoing normal is the first.
+pub(crate) fn join_miters(norms: &[f32], n: usize, out: &mut Vec<[f32; 4]>) {
+    let count = norms.len() / 2;
+    out.clear();
+    let at = |k: usize| (norms[(k % count) * 2], norms[(k % count) * 2 + 1]);
+    let push = |n0: F32x4, n1: F32x4| {
+        let miter = n0.add(n1);
+        let sq = miter.mul(miter);
+        let len = sq.add(sq.swap_pairs()).sqrt();
+        let dir = miter.div(len);
+        let d = dir.mul(n1);
+        let d = d.add(d.swap_pairs());
+        let ([mx0, my0, mx1, my1], [l0, _, l1, _], [d0, _, d1, _]) = (dir.to_array(), len.to_array(), d.to_array());
+        [[mx0, my0, l0, d0], [mx1, my1, l1, d1]]
+    };
+    if n > 0 {
+        let [first, _] = push(F32x4::pair(at(count - 1), at(count - 1)), F32x4::pair(at(0), at(0)));
+        out.push(first);
+    }
+    let mut i = 1;
+    // Points `i` and `i + 1` from three consecutive normals.
+    while i + 1 < n && i + 1 < count {
+        out.extend(push(F32x4::load(&norms[(i - 1) * 2..]), F32x4::load(&norms[i * 2..])));
+        i += 2;
+    }
+    for i in i..n {
+        let [m, _] = push(F32x4::pair(at(i - 1), at(i - 1)), F32x4::pair(at(i), at(i)));
+        out.push(m);
+    }
+}
+
+/// `p` moved `dist` along `dir` and `dist` against it, the left and right
+/// edge points of a ribbon, in one operation.
+pub(crate) fn offset_pair(p: Point, dir: Point, dist: f32) -> (Point, Point) {
+    let [lx, ly, rx, ry] = F32x4::pair(p, p).add(F32x4::pair(dir, dir).mul(F32x4::new(dist, dist, -dist, -dist))).to_array();
+    ((lx, ly), (rx, ry))
+}
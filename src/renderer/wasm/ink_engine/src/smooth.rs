@@ -0,0 +1,179 @@
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{cross, dot, length, sub};
+use crate::samples::{samples_from, Sample, StrokeSamples};
+
+/// Fits a centripetal Catmull-Rom spline through the samples and inserts
+/// `subdivisions` interpolated samples per segment. Widths and timestamps are
+/// interpolated linearly along each segment.
+#[wasm_bindgen]
+pub fn smooth_catmull_rom(points: &[f32], widths: &[f32], timestamps: &[f64], subdivisions: u32) -> StrokeSamples {
+    let samples = samples_from(points, widths, timestamps);
+    StrokeSamples::from_samples(&catmull_rom(&samples, subdivisions as usize), !timestamps.is_empty())
+}
+
+pub(crate) fn catmull_rom(samples: &[Sample], subdivisions: usize) -> Vec<Sample> {
+    let n = samples.len();
+    if n < 3 || subdivisions == 0 {
+        return samples.to_vec();
+    }
+    // Phantom end points mirror the first and last segments.
+    let pos = |i: isize| -> (f32, f32) {
+        if i < 0 {
+            let (a, b) = (samples[0].pos(), samples[1].pos());
+            (2.0 * a.0 - b.0, 2.0 * a.1 - b.1)
+        } else if i as usize >= n {
+            let (a, b) = (samples[n - 1].pos(), samples[n - 2].pos());
+            (2.0 * a.0 - b.0, 2.0 * a.1 - b.1)
+        } else {
+            samples[i as usize].pos()
+        }
+    };
+
+    let mut out = Vec::with_capacity((n - 1) * (subdivisions + 1) + 1);
+    for i in 0..n - 1 {
+        let k = i as isize;
+        let (p0, p1, p2, p3) = (pos(k - 1), pos(k), pos(k + 1), pos(k + 2));
+        out.push(samples[i]);
+        for s in 1..=subdivisions {
+            let u = s as f32 / (subdivisions + 1) as f32;
+            let mut sample = samples[i].lerp(&samples[i + 1], u);
+            (sample.x, sample.y) = centripetal(p0, p1, p2, p3, u);
+            out.push(sample);
+        }
+    }
+    out.push(samples[n - 1]);
+    out
+}
+
+/// Centred moving average over `window` samples (rounded up to odd) applied
+/// to positions and widths. `weights[k]` weights the samples `k` away from the
+/// centre; missing entries are 1. Near the ends the window shrinks
+/// symmetrically, so the first and last samples stay put and the start of the
+/// stroke does not jump once a full window is available.
+#[wasm_bindgen]
+pub fn smooth_moving_average(points: &[f32], widths: &[f32], timestamps: &[f64], window: u32, weights: &[f32]) -> StrokeSamples {
+    let samples = samples_from(points, widths, timestamps);
+    StrokeSamples::from_samples(&moving_average(&samples, window as usize / 2, weights), !timestamps.is_empty())
+}
+
+pub(crate) fn moving_average(samples: &[Sample], radius: usize, weights: &[f32]) -> Vec<Sample> {
+    let n = samples.len();
+    let weight = |k: usize| weights.get(k).copied().unwrap_or(1.0).max(0.0);
+    (0..n)
+        .map(|i| {
+            let r = radius.min(i).min(n - 1 - i);
+            let mut sum = (0.0, 0.0, 0.0);
+            let mut total = 0.0;
+            for (j, s) in samples.iter().enumerate().take(i + r + 1).skip(i - r) {
+                let w = weight(i.abs_diff(j));
+                sum.0 += s.x * w;
+                sum.1 += s.y * w;
+                sum.2 += s.width * w;
+                total += w;
+            }
+            if total <= 0.0 {
+                return samples[i];
+            }
+            Sample {
+                x: sum.0 / total,
+                y: sum.1 / total,
+                width: sum.2 / total,
+                ..samples[i]
+            }
+        })
+        .collect()
+}
+
+/// Samples looked at on either side of a point when measuring its turn, so a
+/// corner spread over a few dense samples still registers as one.
+const CORNER_REACH: usize = 3;
+
+/// Indices of samples where the stroke turns by more than `corner_angle`
+/// degrees. The first and last samples are always included.
+#[wasm_bindgen]
+pub fn detect_corners(points: &[f32], corner_angle: f32) -> Vec<u32> {
+    let samples = samples_from(points, &[], &[]);
+    corners(&samples, corner_angle.to_radians()).into_iter().map(|i| i as u32).collect()
+}
+
+/// Like `smooth_catmull_rom`, but the spline is broken at corners sharper
+/// than `corner_angle` degrees so an "L" or "7" keeps its point.
+#[wasm_bindgen]
+pub fn smooth_preserving_corners(points: &[f32], widths: &[f32], timestamps: &[f64], subdivisions: u32, corner_angle: f32) -> StrokeSamples {
+    let samples = samples_from(points, widths, timestamps);
+    let anchors = corners(&samples, corner_angle.to_radians());
+    StrokeSamples::from_samples(&catmull_rom_anchored(&samples, subdivisions as usize, &anchors), !timestamps.is_empty())
+}
+
+pub(crate) fn corners(samples: &[Sample], threshold: f32) -> Vec<usize> {
+    let n = samples.len();
+    let mut anchors = vec![0];
+    if n < 3 {
+        anchors.extend(1..n);
+        return anchors;
+    }
+    let turn: Vec<f32> = (0..n)
+        .map(|i| {
+            if i == 0 || i == n - 1 {
+                return 0.0;
+            }
+            let p = samples[i].pos();
+            let back = sub(p, samples[i.saturating_sub(CORNER_REACH)].pos());
+            let ahead = sub(samples[(i + CORNER_REACH).min(n - 1)].pos(), p);
+            if dot(back, back) == 0.0 || dot(ahead, ahead) == 0.0 {
+                return 0.0;
+            }
+            cross(back, ahead).atan2(dot(back, ahead)).abs()
+        })
+        .collect();
+    for i in 1..n - 1 {
+        if turn[i] <= threshold {
+            continue;
+        }
+        // Only the sharpest sample of a neighbourhood becomes the anchor.
+        let lo = i.saturating_sub(CORNER_REACH).max(1);
+        let hi = (i + CORNER_REACH).min(n - 2);
+        let peak = (lo..=hi).all(|j| turn[j] < turn[i] || (turn[j] == turn[i] && j >= i));
+        if peak {
+            anchors.push(i);
+        }
+    }
+    anchors.push(n - 1);
+    anchors
+}
+
+/// Catmull-Rom smoothing run separately on each span between anchors, so no
+/// tangent is carried across a corner.
+pub(crate) fn catmull_rom_anchored(samples: &[Sample], subdivisions: usize, anchors: &[usize]) -> Vec<Sample> {
+    if anchors.len() <= 2 {
+        return catmull_rom(samples, subdivisions);
+    }
+    let mut out = Vec::new();
+    for span in anchors.windows(2) {
+        let piece = catmull_rom(&samples[span[0]..=span[1]], subdivisions);
+        let skip = usize::from(!out.is_empty());
+        out.extend_from_slice(&piece[skip..]);
+    }
+    out
+}
+
+/// Point at fraction `u` of the p1..p2 span (Barry-Goldman pyramid, alpha 0.5).
+fn centripetal(p0: (f32, f32), p1: (f32, f32), p2: (f32, f32), p3: (f32, f32), u: f32) -> (f32, f32) {
+    let knot = |a: (f32, f32), b: (f32, f32)| length(sub(b, a)).sqrt().max(1e-4);
+    let t0 = 0.0;
+    let t1 = t0 + knot(p0, p1);
+    let t2 = t1 + knot(p1, p2);
+    let t3 = t2 + knot(p2, p3);
+    let t = t1 + (t2 - t1) * u;
+    let mix = |a: (f32, f32), b: (f32, f32), ta: f32, tb: f32| {
+        let w = (t - ta) / (tb - ta);
+        (a.0 + (b.0 - a.0) * w, a.1 + (b.1 - a.1) * w)
+    };
+    let a1 = mix(p0, p1, t0, t1);
+    let a2 = mix(p1, p2, t1, t2);
+    let a3 = mix(p2, p3, t2, t3);
+    let b1 = mix(a1, a2, t0, t2);
+    let b2 = mix(a2, a3, t1, t3);
+    mix(b1, b2, t1, t2)
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::codec::{checksum, Reader, Writer};
+use crate::document::{InkDocument, Layer, Stroke};
+use crate::group::Group;
+use crate::history::{read_stroke, write_stroke, Op};
+use crate::transform;
+
+const MAGIC: &[u8; 4] = b"INKS";
+const VERSION: u8 = 1;
+const PATCH_MAGIC: &[u8; 4] = b"INKD";
+const PATCH_VERSION: u8 = 1;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Serializes the whole document model: strokes exactly as held, layers,
+    /// groups, the id counters and the op count as a history watermark.
+    /// Brushes, the zoom and cached meshes are left out; the host
+    /// registers brushes itself and meshes are rebuilt on demand.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let counters = [self.next_id, self.next_layer_id, self.next_group_id, self.active_layer];
+        write_snapshot(self.op_count(), counters, &self.layers, &self.groups, &self.strokes)
+    }
+
+    /// Replaces the document model with a `snapshot`, e.g. for crash
+    /// recovery or switching workspaces. The op log restarts empty at the
+    /// snapshot's watermark, so `op_count` carries on from where it was and
+    /// a journal of later ops can be applied on top. Saved versions belong
+    /// to the document replaced and start over. Returns false, leaving
+    /// the document untouched, when the blob is not a snapshot or is cut
+    /// short.
+    pub fn restore(&mut self, bytes: &[u8]) -> bool {
+        let Some(state) = read_snapshot(bytes) else {
+            return false;
+        };
+        self.op_base = state.watermark;
+        self.ops.clear();
+        self.history_base = bytes.to_vec();
+        self.replace_model(state);
+        self.versions.clear();
+        self.next_version_id = 1;
+        true
+    }
+}
+
+impl InkDocument {
+    /// Brings the model to a snapshot's state as one more change in the op
+    /// log, e.g. going back to a saved version. Id counters never move
+    /// back, so ids used since the snapshot are not handed out again.
+    pub(crate) fn load_snapshot(&mut self, bytes: &[u8]) -> bool {
+        let Some(mut state) = read_snapshot(bytes) else {
+            return false;
+        };
+        state.next_id = state.next_id.max(self.next_id);
+        state.next_layer_id = state.next_layer_id.max(self.next_layer_id);
+        state.next_group_id = state.next_group_id.max(self.next_group_id);
+        self.replace_model(state);
+        self.record(Op::Load(bytes.to_vec()));
+        true
+    }
+
+    /// Puts the model of `state` in place, leaving the op log alone.
+    pub(crate) fn replace_model(&mut self, state: Snapshot) {
+        self.next_id = state.next_id;
+        self.next_layer_id = state.next_layer_id;
+        self.next_group_id = state.next_group_id;
+        self.active_layer = state.active_layer;
+        self.layers = state.layers;
+        self.groups = state.groups;
+        self.strokes = state.strokes;
+        self.index.clear();
+    }
+}
+
+/// The changes that turn snapshot `a` into snapshot `b`, for an autosave
+/// that writes only what changed since the last save. Layers, groups and
+/// counters are always included; strokes that `a` holds byte for byte are
+/// referred to by position, so an edit costs about the strokes it touched.
+/// Empty when either blob is not a snapshot.
+#[wasm_bindgen]
+pub fn diff_snapshots(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let (Some((_, old)), Some((header, new))) = (sections(a), sections(b)) else {
+        return Vec::new();
+    };
+    let kept: HashMap<&[u8], usize> = old.iter().enumerate().map(|(k, &s)| (s, k)).collect();
+    let mut w = Writer::default();
+    w.buf.extend_from_slice(PATCH_MAGIC);
+    w.u8(PATCH_VERSION);
+    w.varint(a.len() as u64);
+    w.varint(checksum(a));
+    w.bytes(header);
+    w.varint(new.len() as u64);
+    for stroke in new {
+        match kept.get(stroke) {
+            Some(&k) => {
+                w.u8(0);
+                w.varint(k as u64);
+            }
+            None => {
+                w.u8(1);
+                w.bytes(stroke);
+            }
+        }
+    }
+    w.buf
+}
+
+/// Rebuilds snapshot `b` from `a` and `diff_snapshots(a, b)`. `None` when
+/// the patch is malformed or was made against a different base.
+#[wasm_bindgen]
+pub fn apply_patch(base: &[u8], patch: &[u8]) -> Option<Vec<u8>> {
+    let (_, old) = sections(base)?;
+    let mut r = Reader::new(patch);
+    if r.raw(PATCH_MAGIC.len())? != PATCH_MAGIC || r.u8()? != PATCH_VERSION {
+        return None;
+    }
+    if r.varint()? != base.len() as u64 || r.varint()? != checksum(base) {
+        return None;
+    }
+    let mut w = Writer::default();
+    w.buf.extend_from_slice(r.bytes()?);
+    let n = r.count()?;
+    w.varint(n as u64);
+    for _ in 0..n {
+        let stroke = match r.u8()? {
+            0 => *old.get(usize::try_from(r.varint()?).ok()?)?,
+            _ => r.bytes()?,
+        };
+        w.buf.extend_from_slice(stroke);
+    }
+    // Only hand back what `restore` will accept.
+    read_snapshot(&w.buf)?;
+    Some(w.buf)
+}
+
+/// A snapshot's bytes up to its strokes, and each stroke's bytes.
+fn sections(bytes: &[u8]) -> Option<(&[u8], Vec<&[u8]>)> {
+    let mut r = Reader::new(bytes);
+    read_header(&mut r)?;
+    let header = &bytes[..r.pos()];
+    let n = r.count()?;
+    let mut strokes = Vec::with_capacity(n);
+    for _ in 0..n {
+        let start = r.pos();
+        read_stroke(&mut r)?;
+        strokes.push(&bytes[start..r.pos()]);
+    }
+    Some((header, strokes))
+}
+
+pub(crate) struct Snapshot {
+    pub watermark: usize,
+    pub next_id: u32,
+    pub next_layer_id: u32,
+    pub next_group_id: u32,
+    pub active_layer: u32,
+    pub layers: Vec<Layer>,
+    pub groups: Vec<Group>,
+    pub strokes: Vec<Stroke>,
+}
+
+impl Snapshot {
+    /// The model when it holds together, for one read from bytes that may
+    /// be corrupt or crafted: ids unique, every stroke on an existing layer
+    /// and in an existing group, and group parents existing without cycles,
+    /// which would leave `top_group` walking forever. Counters move past
+    /// every id in use so none is handed out again.
+    pub(crate) fn checked(mut self) -> Option<Snapshot> {
+        let sorted = |ids: &mut dyn Iterator<Item = u32>| {
+            let mut ids: Vec<u32> = ids.collect();
+            ids.sort_unstable();
+            ids.windows(2).all(|w| w[0] != w[1]).then_some(ids)
+        };
+        let layers = sorted(&mut self.layers.iter().map(|l| l.id))?;
+        let groups = sorted(&mut self.groups.iter().map(|g| g.id))?;
+        let strokes = sorted(&mut self.strokes.iter().map(|s| s.id))?;
+        let has_group = |g: &u32| groups.binary_search(g).is_ok();
+        if self.groups.iter().any(|g| g.parent.is_some_and(|p| !has_group(&p))) {
+            return None;
+        }
+        if self.strokes.iter().any(|s| layers.binary_search(&s.layer).is_err() || s.group.is_some_and(|g| !has_group(&g))) {
+            return None;
+        }
+        let parents: HashMap<u32, Option<u32>> = self.groups.iter().map(|g| (g.id, g.parent)).collect();
+        for g in &self.groups {
+            // A chain longer than there are groups has come round again.
+            let mut at = g.parent;
+            for _ in 0..=self.groups.len() {
+                match at {
+                    Some(p) => at = parents[&p],
+                    None => break,
+                }
+            }
+            if at.is_some() {
+                return None;
+            }
+        }
+        let past = |ids: &[u32]| ids.last().map_or(Some(1), |&id| id.checked_add(1));
+        self.next_id = self.next_id.max(past(&strokes)?);
+        self.next_layer_id = self.next_layer_id.max(past(&layers)?);
+        self.next_group_id = self.next_group_id.max(past(&groups)?);
+        Some(self)
+    }
+}
+
+fn read_id(r: &mut Reader) -> Option<u32> {
+    r.varint().and_then(|v| u32::try_from(v).ok())
+}
+
+/// `snapshot` of a model given in parts; `counters` are the next stroke,
+/// layer and group ids and the active layer.
+pub(crate) fn write_snapshot(watermark: usize, counters: [u32; 4], layers: &[Layer], groups: &[Group], strokes: &[Stroke]) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.buf.extend_from_slice(MAGIC);
+    w.u8(VERSION);
+    w.varint(watermark as u64);
+    counters.iter().for_each(|&v| w.varint(v as u64));
+    w.varint(layers.len() as u64);
+    for l in layers {
+        w.varint(l.id as u64);
+        w.str(&l.name);
+        w.u8(l.visible as u8);
+        w.f32(l.opacity);
+    }
+    w.varint(groups.len() as u64);
+    for g in groups {
+        w.varint(g.id as u64);
+        match g.parent {
+            Some(p) => {
+                w.u8(1);
+                w.varint(p as u64);
+            }
+            None => w.u8(0),
+        }
+        g.transform.iter().for_each(|&v| w.f32(v));
+    }
+    w.varint(strokes.len() as u64);
+    for s in strokes {
+        write_stroke(&mut w, s);
+    }
+    w.buf
+}
+
+pub(crate) fn read_snapshot(bytes: &[u8]) -> Option<Snapshot> {
+    let mut r = Reader::new(bytes);
+    let mut state = read_header(&mut r)?;
+    let n = r.count()?;
+    state.strokes = (0..n).map(|_| read_stroke(&mut r)).collect::<Option<Vec<_>>>()?;
+    state.checked()
+}
+
+/// Everything before the strokes, which are left empty.
+fn read_header(r: &mut Reader) -> Option<Snapshot> {
+    if r.raw(MAGIC.len())? != MAGIC || r.u8()? != VERSION {
+        return None;
+    }
+    let watermark = usize::try_from(r.varint()?).ok()?;
+    let (next_id, next_layer_id, next_group_id, active_layer) = (read_id(r)?, read_id(r)?, read_id(r)?, read_id(r)?);
+    let n = r.count()?;
+    let mut layers = Vec::with_capacity(n);
+    for _ in 0..n {
+        layers.push(Layer {
+            id: read_id(r)?,
+            name: r.str()?,
+            visible: r.u8()? != 0,
+            opacity: r.f32()?,
+        });
+    }
+    // A document always has a layer to draw on.
+    if !layers.iter().any(|l| l.id == active_layer) {
+        return None;
+    }
+    let n = r.count()?;
+    let mut groups = Vec::with_capacity(n);
+    for _ in 0..n {
+        let id = read_id(r)?;
+        let parent = match r.u8()? {
+            0 => None,
+            _ => Some(read_id(r)?),
+        };
+        let mut m = transform::IDENTITY;
+        for v in &mut m {
+            *v = r.f32()?;
+        }
+        groups.push(Group { id, parent, transform: m });
+    }
+    Some(Snapshot {
+        watermark,
+        next_id,
+        next_layer_id,
+        next_group_id,
+        active_layer,
+        layers,
+        groups,
+        strokes: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+    #[test]
+    fn keeps_floats_exactly() {
+        let mut doc = InkDocument::new();
+        let id = doc.add_stroke(&[0.1, 1.0 / 3.0, 1e-7, 12345.678], &[0.3, f32::MIN_POSITIVE], &[0.1, 1e12], &[0.1, 0.2, 0.3, 0.4], 0).id;
+        doc.set_stroke_transform(id, &[0.7, 0.1, -0.2, 1.3, 0.01, 99.9]);
+        let mut restored = InkDocument::new();
+        assert!(restored.restore(&doc.snapshot()));
+        assert_eq!(restored.snapshot(), doc.snapshot());
+        assert_eq!(restored.stroke_points(id), doc.stroke_points(id));
+        assert_eq!(restored.strokes[0].timestamps, [0.1, 1e12]);
+    }
+
+    #[test]
+    fn the_op_count_carries_on() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0);
+        doc.add_layer("Notes");
+        let snapshot = doc.snapshot();
+        doc.add_layer("Later");
+        let journal = doc.export_ops(2);
+
+        let mut restored = InkDocument::new();
+        assert!(restored.restore(&snapshot));
+        assert_eq!(restored.op_count(), 2);
+        assert_eq!(restored.history_base(), snapshot);
+        assert!(restored.apply_ops(&journal));
+        assert_eq!(restored.snapshot(), doc.snapshot());
+    }
+
+    #[test]
+    fn a_bad_blob_leaves_the_document_alone() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0);
+        let before = doc.snapshot();
+        let mut cut = before.clone();
+        cut.pop();
+        let mut other = before.clone();
+        other[MAGIC.len()] = VERSION + 1;
+        for bytes in [&cut[..], &other[..], b"INKS"] {
+            assert!(!doc.restore(bytes));
+            assert_eq!(doc.snapshot(), before);
+        }
+    }
+
+    #[test]
+    fn rejects_broken_models() {
+        let layers = [Layer {
+            id: 0,
+            name: String::new(),
+            visible: true,
+            opacity: 1.0,
+        }];
+        let group = |id, parent| Group { id, parent, transform: transform::IDENTITY };
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0);
+        let stroke = doc.strokes[0].record();
+        let in_group = |g| [Stroke { group: Some(g), ..stroke.record() }];
+        let broken = [
+            write_snapshot(0, [2, 1, 3, 0], &layers, &[group(1, Some(2)), group(2, Some(1))], &[]),
+            write_snapshot(0, [2, 1, 2, 0], &layers, &[group(1, Some(1))], &[]),
+            write_snapshot(0, [2, 1, 2, 0], &layers, &[group(1, None), group(1, None)], &[]),
+            write_snapshot(0, [2, 1, 2, 0], &layers, &[], &in_group(5)),
+            write_snapshot(0, [2, 1, 1, 0], &layers, &[], &[stroke.record(), stroke.record()]),
+            write_snapshot(0, [2, 1, 1, 0], &layers, &[], &[Stroke { layer: 4, ..stroke.record() }]),
+            write_snapshot(0, [2, 1, 1, 0], &layers, &[], &[Stroke { id: u32::MAX, ..stroke.record() }]),
+            write_snapshot(0, [2, 1, 1, 3], &layers, &[], &[]),
+        ];
+        for (k, bytes) in broken.iter().enumerate() {
+            assert!(!InkDocument::new().restore(bytes), "case {k}");
+            // Brought in as a change, it is refused all the same.
+            assert!(!doc.load_snapshot(bytes), "case {k}");
+        }
+        assert!(InkDocument::new().restore(&write_snapshot(0, [2, 1, 2, 0], &layers, &[group(1, None)], &in_group(1))));
+    }
+
+    #[test]
+    fn counters_never_fall_behind_ids() {
+        let mut doc = InkDocument::new();
+        for _ in 0..3 {
+            doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0);
+        }
+        let bytes = write_snapshot(0, [1, 1, 1, 0], &doc.layers, &[], &doc.strokes);
+        let mut restored = InkDocument::new();
+        assert!(restored.restore(&bytes));
+        assert_eq!(restored.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0).id, 4);
+
+        // Going back to an older state hands out no id used since.
+        let old = doc.snapshot();
+        let newest = doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0).id;
+        assert!(doc.load_snapshot(&old));
+        assert!(doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0).id > newest);
+    }
+
+    #[test]
+    fn switching_workspaces_drops_versions() {
+        let mut other = InkDocument::new();
+        other.add_layer("Other");
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &BLACK, 0);
+        let old = doc.save_version("draft");
+        assert!(doc.restore(&other.snapshot()));
+        assert!(doc.version_ids().is_empty());
+        assert!(!doc.restore_version(old));
+        assert_eq!(doc.save_version("first"), 1);
+    }
+}
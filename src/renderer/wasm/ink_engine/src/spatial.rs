@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+/// Edge length of a grid cell in document units.
+const CELL: f32 = 256.0;
+/// Strokes covering more cells than this go on a list checked by every
+/// query instead, so one page-sized scribble does not fill the grid.
+const MAX_CELLS: i64 = 256;
+
+/// Uniform grid over stroke bounding boxes. Cells are created on demand,
+/// so the document can grow in any direction.
+#[derive(Default)]
+pub(crate) struct SpatialIndex {
+    cells: HashMap<(i32, i32), Vec<u32>>,
+    large: Vec<u32>,
+    bounds: HashMap<u32, [f32; 4]>,
+}
+
+fn cell_range(b: &[f32; 4]) -> (i32, i32, i32, i32) {
+    let c = |v: f32| (v / CELL).floor() as i32;
+    (c(b[0]), c(b[1]), c(b[2]), c(b[3]))
+}
+
+fn is_large(range: (i32, i32, i32, i32)) -> bool {
+    let (x0, y0, x1, y1) = range;
+    (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1) > MAX_CELLS
+}
+
+pub(crate) fn overlaps(a: &[f32; 4], b: &[f32; 4]) -> bool {
+    a[0] <= b[2] && b[0] <= a[2] && a[1] <= b[3] && b[1] <= a[3]
+}
+
+impl SpatialIndex {
+    /// Adds or moves `id`; `None` (a stroke that draws nothing) just removes it.
+    pub fn insert(&mut self, id: u32, bounds: Option<[f32; 4]>) {
+        self.remove(id);
+        let Some(b) = bounds else {
+            return;
+        };
+        self.bounds.insert(id, b);
+        let range = cell_range(&b);
+        if is_large(range) {
+            self.large.push(id);
+            return;
+        }
+        let (x0, y0, x1, y1) = range;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        let Some(b) = self.bounds.remove(&id) else {
+            return;
+        };
+        let range = cell_range(&b);
+        if is_large(range) {
+            self.large.retain(|&i| i != id);
+            return;
+        }
+        let (x0, y0, x1, y1) = range;
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if let Some(cell) = self.cells.get_mut(&(x, y)) {
+                    cell.retain(|&i| i != id);
+                    if cell.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = SpatialIndex::default();
+    }
+
+    pub fn bounds(&self, id: u32) -> Option<[f32; 4]> {
+        self.bounds.get(&id).copied()
+    }
+
+    /// Ids whose bounding box overlaps `rect`, each once, ascending.
+    pub fn query(&self, rect: &[f32; 4]) -> Vec<u32> {
+        let mut out: Vec<u32> = self.large.iter().copied().filter(|id| overlaps(&self.bounds[id], rect)).collect();
+        let range = cell_range(rect);
+        if is_large(range) {
+            // Cheaper to test every box than to visit that many cells.
+            out.extend(self.bounds.iter().filter(|(_, b)| overlaps(b, rect)).map(|(&id, _)| id));
+        } else {
+            let (x0, y0, x1, y1) = range;
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    for &id in self.cells.get(&(x, y)).into_iter().flatten() {
+                        if overlaps(&self.bounds[&id], rect) {
+                            out.push(id);
+                        }
+                    }
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+}
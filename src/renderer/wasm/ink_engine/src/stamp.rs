@@ -0,0 +1,128 @@
+use wasm_bindgen::prelude::*;
+
+use crate::geom::{length, point_at, sub, Point};
+use crate::noise::Rng;
+
+/// Floats per stamp vertex: `[x, y, u, v, r, g, b, a]`.
+pub(crate) const STAMP_FLOATS_PER_VERTEX: usize = 8;
+
+/// Stamps closer than this (in stroke units) are never emitted, whatever the width.
+const MIN_SPACING: f32 = 0.05;
+
+/// Textured-quad brush: copies of a brush texture placed along the path,
+/// spaced in proportion to the local width. Jitter is seeded, so a stroke
+/// looks the same every time it is rebuilt.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct StampOptions {
+    /// Distance between stamp centres as a fraction of the local width.
+    pub spacing: f32,
+    /// Stamp edge length as a multiple of the local width.
+    pub size: f32,
+    /// Base rotation in radians.
+    pub rotation: f32,
+    /// Add the stroke direction to the rotation, for textures with a grain.
+    pub follow_direction: bool,
+    /// Maximum random rotation either way, in radians.
+    pub rotation_jitter: f32,
+    /// Maximum random size change as a fraction of the size.
+    pub size_jitter: f32,
+    /// Maximum sideways offset from the path as a fraction of the width.
+    pub scatter: f32,
+    /// Maximum random alpha reduction as a fraction of the color's alpha.
+    pub opacity_jitter: f32,
+    pub seed: u32,
+}
+
+#[wasm_bindgen]
+impl StampOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StampOptions {
+        StampOptions::default()
+    }
+}
+
+impl Default for StampOptions {
+    fn default() -> Self {
+        StampOptions {
+            spacing: 0.25,
+            size: 1.0,
+            rotation: 0.0,
+            follow_direction: false,
+            rotation_jitter: 0.0,
+            size_jitter: 0.0,
+            scatter: 0.0,
+            opacity_jitter: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+/// Stamps quads along the stroke as a triangle list of
+/// `[x, y, u, v, r, g, b, a]` vertices, six per stamp, with UVs covering
+/// the texture once per quad. Bind the brush texture and sample it by `uv`.
+#[wasm_bindgen]
+pub fn build_stamps(points: &[f32], widths: &[f32], color: &[f32], options: &StampOptions) -> Vec<f32> {
+    let color = crate::rgba(color);
+    let mut rng = Rng::new(options.seed);
+    let mut out = Vec::new();
+    let n = points.len() / 2;
+    if n == 0 {
+        return out;
+    }
+    let width = |i: usize| widths.get(i).copied().unwrap_or(1.0);
+    let spacing = |w: f32| (options.spacing * w).max(MIN_SPACING);
+
+    let mut stamp = |center: Point, dir: Point, w: f32| {
+        let size = options.size * w * (1.0 + options.size_jitter * rng.signed()).max(0.0);
+        let side = rng.signed() * options.scatter * w;
+        let mut angle = options.rotation + options.rotation_jitter * rng.signed();
+        if options.follow_direction {
+            angle += dir.1.atan2(dir.0);
+        }
+        let alpha = color[3] * (1.0 - options.opacity_jitter.clamp(0.0, 1.0) * rng.unit());
+        let c = (center.0 - dir.1 * side, center.1 + dir.0 * side);
+        push_quad(&mut out, c, size * 0.5, angle, [color[0], color[1], color[2], alpha]);
+    };
+
+    if n == 1 {
+        stamp(point_at(points, 0), (1.0, 0.0), width(0));
+        return out;
+    }
+    // Walk the polyline, placing the next stamp one local spacing further on.
+    let mut need = 0.0;
+    for i in 0..n - 1 {
+        let (a, b) = (point_at(points, i), point_at(points, i + 1));
+        let seg = length(sub(b, a));
+        if seg < 1e-6 {
+            continue;
+        }
+        let dir = ((b.0 - a.0) / seg, (b.1 - a.1) / seg);
+        let mut at = need;
+        while at <= seg {
+            let t = at / seg;
+            let w = width(i) + (width(i + 1) - width(i)) * t;
+            stamp((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t), dir, w);
+            at += spacing(w);
+        }
+        need = at - seg;
+    }
+    out
+}
+
+fn push_quad(out: &mut Vec<f32>, c: Point, half: f32, angle: f32, color: [f32; 4]) {
+    let (s, co) = angle.sin_cos();
+    let corner = |x: f32, y: f32| (c.0 + (x * co - y * s) * half, c.1 + (x * s + y * co) * half);
+    let quad = [
+        (corner(-1.0, -1.0), (0.0, 0.0)),
+        (corner(1.0, -1.0), (1.0, 0.0)),
+        (corner(1.0, 1.0), (1.0, 1.0)),
+        (corner(-1.0, 1.0), (0.0, 1.0)),
+    ];
+    out.reserve(6 * STAMP_FLOATS_PER_VERTEX);
+    for k in [0, 1, 2, 0, 2, 3] {
+        let ((x, y), (u, v)) = quad[k];
+        out.extend_from_slice(&[x, y, u, v]);
+        out.extend_from_slice(&color);
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt::Write;
+
+use wasm_bindgen::prelude::*;
+
+use crate::brush::BlendMode;
+use crate::document::{InkDocument, Stroke};
+use crate::erase::{outline_pieces, placed_rings, triangle_pieces};
+use crate::geom::Point;
+use crate::polygon;
+use crate::preset::Brush;
+use crate::transform;
+
+/// Widths closer than this count as one, so the stroke exports as a plain
+/// SVG stroke.
+const WIDTH_TOLERANCE: f32 = 1e-3;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// The visible layers as an SVG document in document units, one
+    /// Inkscape layer group each. Constant-width strokes of plain ink brushes
+    /// with round caps and joins become stroked paths, which stay editable;
+    /// others become filled paths traced from their tessellation, as do
+    /// erased fills, so every brush style and mesh option exports as drawn.
+    /// Stamp strokes have no outline and are left out.
+    pub fn to_svg(&mut self) -> String {
+        let view = self.document_bounds(true);
+        let [x, y, w, h] = match view[..] {
+            [x0, y0, x1, y1] => [x0, y0, x1 - x0, y1 - y0],
+            _ => [0.0; 4],
+        };
+        let mut out = String::new();
+        let _ = writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            num(w),
+            num(h),
+            num(x),
+            num(y),
+            num(w),
+            num(h)
+        );
+        let layers: Vec<(u32, String, f32)> = self.layers.iter().filter(|l| l.visible).map(|l| (l.id, l.name.clone(), l.opacity)).collect();
+        for (layer, name, opacity) in layers {
+            let _ = write!(out, r#"  <g inkscape:groupmode="layer" inkscape:label="{}""#, escape(&name));
+            if opacity < 1.0 {
+                let _ = write!(out, r#" opacity="{}""#, num(opacity));
+            }
+            out.push_str(">\n");
+            let order: Vec<usize> = (0..self.strokes.len()).filter(|&i| self.strokes[i].layer == layer).collect();
+            for i in order {
+                if let Some(path) = self.svg_path(i) {
+                    let _ = writeln!(out, "    {path}");
+                }
+            }
+            out.push_str("  </g>\n");
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+/// A stroke as vector paths in document coordinates, shared by the SVG
+/// and PDF exporters.
+pub(crate) enum VectorShape {
+    /// A centreline drawn with round caps and joins.
+    Line { points: Vec<Point>, width: f32 },
+    /// Closed rings, outer ones counter-clockwise, filled nonzero.
+    Outline(Vec<Vec<Point>>),
+}
+
+impl InkDocument {
+    /// Constant-width strokes that render as round lines stay lines, which
+    /// editors can still adjust; the rest are traced from their
+    /// tessellation. `None` for stamp strokes and strokes that draw nothing.
+    pub(crate) fn vector_shape(&mut self, i: usize) -> Option<VectorShape> {
+        let s = &self.strokes[i];
+        let rings = match &s.fill {
+            Some(rings) => placed_rings(&s.transform, rings),
+            None if constant_width(s) && self.brushes.get(s.brush).is_some_and(Brush::draws_round_line) => return Some(line(s)),
+            None => {
+                let mesh = self.strokes[i].placed_mesh(&self.brushes, self.zoom_bucket.max(0));
+                // Highlighters multiply rather than cover, but still have a shape.
+                let pieces = match mesh.blend() {
+                    BlendMode::Multiply => triangle_pieces(&mesh)?,
+                    _ => outline_pieces(&mesh)?,
+                };
+                polygon::union(&pieces)
+            }
+        };
+        (!rings.is_empty()).then_some(VectorShape::Outline(rings))
+    }
+
+    fn svg_path(&mut self, i: usize) -> Option<String> {
+        let color = self.strokes[i].color;
+        let mut d = String::new();
+        match self.vector_shape(i)? {
+            VectorShape::Line { points, width } => {
+                path_data(&mut d, &points, false);
+                Some(format!(
+                    r#"<path d="{}" fill="none" {} stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+                    d.trim_end(),
+                    paint("stroke", color),
+                    num(width)
+                ))
+            }
+            VectorShape::Outline(rings) => {
+                for ring in &rings {
+                    path_data(&mut d, ring, true);
+                }
+                Some(format!(r#"<path d="{}" {}/>"#, d.trim_end(), paint("fill", color)))
+            }
+        }
+    }
+}
+
+fn constant_width(s: &Stroke) -> bool {
+    s.widths.first().is_some_and(|&w0| s.widths.iter().all(|w| (w - w0).abs() <= WIDTH_TOLERANCE))
+}
+
+fn line(s: &Stroke) -> VectorShape {
+    let mut points: Vec<Point> = s.points.chunks_exact(2).map(|p| transform::apply(&s.transform, (p[0], p[1]))).collect();
+    // A dot still needs a segment for its round caps to draw.
+    if points.len() == 1 {
+        points.push(points[0]);
+    }
+    VectorShape::Line {
+        points,
+        width: s.widths[0] * transform::scale_factor(&s.transform),
+    }
+}
+
+fn path_data(d: &mut String, points: &[Point], closed: bool) {
+    for (k, p) in points.iter().enumerate() {
+        let _ = write!(d, "{}{} {} ", if k == 0 { "M" } else { "L" }, num(p.0), num(p.1));
+    }
+    if closed {
+        d.push_str("Z ");
+    }
+}
+
+/// `fill` or `stroke` in the color, with its alpha as the opacity.
+fn paint(attr: &str, color: [f32; 4]) -> String {
+    let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut out = format!("{attr}=\"#{:02x}{:02x}{:02x}\"", c(color[0]), c(color[1]), c(color[2]));
+    if color[3] < 1.0 {
+        let _ = write!(out, r#" {attr}-opacity="{}""#, num(color[3].max(0.0)));
+    }
+    out
+}
+
+/// Two decimals, which is a hundredth of a document unit, without
+/// trailing zeros.
+pub(crate) fn num(v: f32) -> String {
+    let s = format!("{v:.2}");
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".to_owned() } else { s.to_owned() }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,943 @@
+use std::borrow::Cow;
+use std::f32::consts::PI;
+
+use wasm_bindgen::prelude::*;
+
+use crate::curve::Easing;
+use crate::geom::{cross, dot, length, offset, point_at, sub, Point};
+use crate::mesh::{self, Mesh, Topology, Vertex, VertexLayout, UP};
+use crate::polygon;
+use crate::samples::{samples_from, split};
+use crate::simd;
+use crate::smooth::{catmull_rom, catmull_rom_anchored, corners};
+
+/// How the outside of a turn between two segments is filled, following
+/// SVG/Canvas `lineJoin`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JoinStyle {
+    /// Sharp corner, falling back to bevel past the miter limit.
+    Miter,
+    Bevel,
+    Round,
+    /// Sharp corner, falling back to round past the miter limit. This is
+    /// what `build_mesh` has always drawn.
+    MiterRound,
+}
+
+/// Stroke end shape, following SVG/Canvas `lineCap`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CapStyle {
+    /// Half-disc; what `build_mesh` has always drawn.
+    #[default]
+    Round,
+    /// Flat, ending exactly at the end point.
+    Butt,
+    /// Flat, extended past the end point by half the width.
+    Square,
+}
+
+/// Shape drawn at an open stroke's end, oriented along the end tangent.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Decoration {
+    #[default]
+    None,
+    /// Filled triangle pointing away from the stroke, covering the cap.
+    Arrow,
+    /// Filled disc centred on the end point.
+    Circle,
+    /// Flat bar across the end point, as thick as the stroke.
+    Bar,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct MeshOptions {
+    pub join: JoinStyle,
+    pub cap: CapStyle,
+    /// Maximum ratio of miter length to stroke width, as in SVG `stroke-miterlimit`.
+    pub miter_limit: f32,
+    /// Connect the last point back to the first with a join and skip the caps.
+    pub closed: bool,
+    /// Width of the zero-alpha anti-aliasing fringe around the outline; 0 disables it.
+    pub fringe: f32,
+    pub topology: Topology,
+    /// Separate strip pieces with the restart index `0xFFFFFFFF` instead of
+    /// degenerate triangles. Only meaningful for indexed strip output.
+    pub primitive_restart: bool,
+    /// Screen pixels per stroke unit, i.e. the current zoom times the device pixel ratio.
+    pub scale: f32,
+    /// Maximum on-screen deviation in pixels between a round join or cap and
+    /// its polygonal approximation. 0 keeps the fixed angular steps.
+    pub tolerance: f32,
+    /// Merge ribbon, joins and caps into one outline and triangulate it, so no
+    /// pixel is covered twice and translucent ink blends uniformly. Slower.
+    pub overlap_free: bool,
+    /// Arc length over which the width grows from zero at the first point; 0 disables it.
+    pub start_taper: f32,
+    pub start_taper_easing: Easing,
+    /// Arc length over which the width shrinks to zero at the last point; 0 disables it.
+    pub end_taper: f32,
+    pub end_taper_easing: Easing,
+    /// Catmull-Rom subdivisions inserted per segment before tessellation; 0 disables smoothing.
+    pub smoothing: u32,
+    /// Turns sharper than this many degrees are kept as corners when
+    /// smoothing; 0 smooths through every turn.
+    pub corner_angle: f32,
+    /// Decorations at the first and last point; ignored for closed strokes.
+    /// A decorated end is never tapered.
+    pub start_decoration: Decoration,
+    pub end_decoration: Decoration,
+    /// Decoration length and breadth as a multiple of the width at that end.
+    pub decoration_size: f32,
+    /// Write vertex colors with red, green and blue multiplied by alpha, for
+    /// compositors that blend premultiplied (`ONE, ONE_MINUS_SRC_ALPHA`).
+    pub premultiplied_alpha: bool,
+    /// Merge duplicate vertices of indexed output and drop the triangles
+    /// that collapse. Tessellation already shares the ribbon's vertices with
+    /// its joins and caps; welding catches the rest, such as taper pieces
+    /// meeting and round fans ending a rounding error off the edge. Indexed
+    /// output with 16-bit indices is some 40% of the triangle soup for the
+    /// same stroke. Triangle soup is unaffected.
+    pub weld: bool,
+    /// Vertices with identical attributes at most this far apart, in stroke
+    /// units, merge when welding; 0 merges exact duplicates only.
+    pub weld_tolerance: f32,
+}
+
+#[wasm_bindgen]
+impl MeshOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> MeshOptions {
+        MeshOptions::default()
+    }
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        MeshOptions {
+            join: JoinStyle::MiterRound,
+            cap: CapStyle::Round,
+            miter_limit: 4.0,
+            closed: false,
+            fringe: 0.0,
+            topology: Topology::TriangleList,
+            primitive_restart: false,
+            scale: 1.0,
+            tolerance: 0.0,
+            overlap_free: false,
+            start_taper: 0.0,
+            start_taper_easing: Easing::Linear,
+            end_taper: 0.0,
+            end_taper_easing: Easing::Linear,
+            smoothing: 0,
+            corner_angle: 0.0,
+            start_decoration: Decoration::None,
+            end_decoration: Decoration::None,
+            decoration_size: 4.0,
+            premultiplied_alpha: false,
+            weld: false,
+            weld_tolerance: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum JoinFill {
+    Miter,
+    Bevel,
+    Round,
+}
+
+pub(crate) fn tessellate(points: &[f32], widths: &[f32], color: [f32; 4], options: &MeshOptions, mesh: &mut Mesh) {
+    tessellate_shaded(points, widths, color, &Shading::default(), options, mesh);
+}
+
+/// Like `tessellate` with one color per input point, used in place of `color`
+/// when not empty. Colors follow their points through smoothing and are
+/// interpolated across the ribbon between them.
+pub(crate) fn tessellate_colored(points: &[f32], widths: &[f32], color: [f32; 4], colors: &[[f32; 4]], options: &MeshOptions, mesh: &mut Mesh) {
+    let shading = Shading {
+        colors,
+        ..Shading::default()
+    };
+    tessellate_shaded(points, widths, color, &shading, options, mesh);
+}
+
+/// Per-vertex extras on top of the stroke geometry.
+#[derive(Clone, Copy)]
+pub(crate) struct Shading<'a> {
+    /// One color per input point, or empty for the flat color.
+    pub colors: &'a [[f32; 4]],
+    /// Alpha scale on the centreline, fading to full at the outline. Below 1
+    /// the ribbon gets a spine of centreline vertices so the fade shows;
+    /// overlap-free output is unshaded.
+    pub center_alpha: f32,
+    /// Fill in arc-length UVs, see `VertexLayout::PositionUvColor`.
+    pub uv: bool,
+    /// Fill in tube normals and sides, see `VertexLayout::PositionNormalColor`.
+    pub normals: bool,
+}
+
+impl Default for Shading<'_> {
+    fn default() -> Self {
+        Shading {
+            colors: &[],
+            center_alpha: 1.0,
+            uv: false,
+            normals: false,
+        }
+    }
+}
+
+impl Shading<'_> {
+    /// Just the extras `layout` has room for.
+    pub fn for_layout(layout: VertexLayout) -> Self {
+        Shading {
+            uv: layout == VertexLayout::PositionUvColor,
+            normals: layout == VertexLayout::PositionNormalColor,
+            ..Shading::default()
+        }
+    }
+}
+
+/// Working buffers for tessellation. A `MeshContext` keeps one between
+/// strokes, so once they have grown to its longest stroke the ribbon, its
+/// joins, caps and fringe are built without allocating.
+#[derive(Default)]
+pub(crate) struct Scratch {
+    frames: Frames,
+    /// Arc length to each point when UVs are wanted, otherwise empty.
+    arc: Vec<f32>,
+    /// Boundary edges of the solid geometry, oriented with the solid on the left.
+    outline: Vec<(u32, u32)>,
+    /// Rim of the fan or disc being built.
+    rim: Vec<u32>,
+    /// Summed outward normal and edge count per vertex, for the fringe.
+    fringe_normals: Vec<(Point, u32)>,
+    /// Each vertex's fringe copy, if it is on the outline.
+    fringe_outer: Vec<Option<u32>>,
+}
+
+/// Per-segment and per-point state of one `Tessellator::run`.
+#[derive(Default)]
+struct Frames {
+    /// Unit direction and left normal per segment, flat `[x, y]` pairs.
+    dirs: Vec<f32>,
+    norms: Vec<f32>,
+    /// `simd::join_miters` per point.
+    miters: Vec<[f32; 4]>,
+    /// Ribbon edge vertices per point: `[left_prev, right_prev, left_next, right_next]`.
+    /// Miter joins share one pair between the incoming and outgoing segment.
+    edges: Vec<[u32; 4]>,
+    fills: Vec<JoinFill>,
+    strip: Vec<u32>,
+}
+
+pub(crate) fn tessellate_shaded(points: &[f32], widths: &[f32], color: [f32; 4], shading: &Shading, options: &MeshOptions, mesh: &mut Mesh) {
+    tessellate_with(points, widths, color, shading, options, mesh, &mut Scratch::default());
+}
+
+/// `tessellate_shaded` working in `scratch`.
+pub(crate) fn tessellate_with(points: &[f32], widths: &[f32], color: [f32; 4], shading: &Shading, options: &MeshOptions, mesh: &mut Mesh, scratch: &mut Scratch) {
+    let first = mesh.vertices.len();
+    if options.overlap_free {
+        tessellate_overlap_free(points, widths, color, shading, options, mesh, scratch);
+    } else {
+        tessellate_ribbon(points, widths, color, shading, options, mesh, scratch);
+    }
+    if options.premultiplied_alpha {
+        mesh::premultiply(&mut mesh.vertices[first..]);
+    }
+}
+
+fn tessellate_ribbon(points: &[f32], widths: &[f32], color: [f32; 4], shading: &Shading, options: &MeshOptions, mesh: &mut Mesh, scratch: &mut Scratch) {
+    let input_count = points.len() / 2;
+    let (points, widths) = prepare(points, widths, options);
+    let colors = follow_points(shading.colors, input_count, points.len() / 2);
+    scratch.arc.clear();
+    if shading.uv {
+        arc_lengths(&points, &mut scratch.arc);
+    }
+    scratch.outline.clear();
+    let mut t = Tessellator {
+        points: &points,
+        widths: &widths,
+        color,
+        colors: &colors,
+        center_alpha: shading.center_alpha,
+        normals: shading.normals,
+        options,
+        mesh,
+        span: Span::ALL,
+        scratch,
+    };
+    t.run();
+    if options.fringe > 0.0 {
+        let outline = std::mem::take(&mut t.scratch.outline);
+        fringe(t.mesh, &outline, options.fringe, t.scratch);
+        t.scratch.outline = outline;
+    }
+}
+
+/// Distance along the polyline to each point, appended to `out`.
+fn arc_lengths(points: &[f32], out: &mut Vec<f32>) {
+    let mut total = 0.0;
+    out.extend((0..points.len() / 2).map(|i| {
+        if i > 0 {
+            total += length(sub(point_at(points, i), point_at(points, i - 1)));
+        }
+        total
+    }));
+}
+
+/// Applies the whole-stroke passes, smoothing then tapering, ahead of tessellation.
+pub(crate) fn prepare<'a>(points: &'a [f32], widths: &'a [f32], options: &MeshOptions) -> (Cow<'a, [f32]>, Cow<'a, [f32]>) {
+    let (points, widths): (Cow<[f32]>, Cow<[f32]>) = if options.smoothing > 0 {
+        let samples = samples_from(points, widths, &[]);
+        let subdivisions = options.smoothing as usize;
+        let (p, w) = split(&if options.corner_angle > 0.0 {
+            catmull_rom_anchored(&samples, subdivisions, &corners(&samples, options.corner_angle.to_radians()))
+        } else {
+            catmull_rom(&samples, subdivisions)
+        });
+        (p.into(), w.into())
+    } else {
+        (points.into(), widths.into())
+    };
+    if (options.start_taper > 0.0 || options.end_taper > 0.0) && !options.closed {
+        let tapered = taper(&points, &widths, options);
+        (points, tapered.into())
+    } else {
+        (points, widths)
+    }
+}
+
+/// Per-point values for `to` prepared points from those of `from` input
+/// points. Smoothing inserts the same number of points into every segment, so
+/// prepared point `j` sits at fractional input index `j * (from - 1) / (to - 1)`.
+pub(crate) fn follow_points(values: &[[f32; 4]], from: usize, to: usize) -> Cow<'_, [[f32; 4]]> {
+    if values.is_empty() || from == to || from < 2 || to < 2 {
+        return values.into();
+    }
+    let at = |i: usize| values[i.min(values.len() - 1)];
+    (0..to)
+        .map(|j| {
+            let f = j as f32 * (from - 1) as f32 / (to - 1) as f32;
+            let (i, t) = (f.floor() as usize, f.fract());
+            let (a, b) = (at(i), at((i + 1).min(from - 1)));
+            std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t)
+        })
+        .collect()
+}
+
+fn tessellate_overlap_free(points: &[f32], widths: &[f32], color: [f32; 4], shading: &Shading, options: &MeshOptions, mesh: &mut Mesh, scratch: &mut Scratch) {
+    let solid_options = MeshOptions {
+        overlap_free: false,
+        fringe: 0.0,
+        topology: Topology::TriangleList,
+        premultiplied_alpha: false,
+        ..*options
+    };
+    let mut solid = Mesh::default();
+    let solid_shading = Shading {
+        center_alpha: 1.0,
+        ..*shading
+    };
+    tessellate_with(points, widths, color, &solid_shading, &solid_options, &mut solid, scratch);
+    let pieces: Vec<Vec<Point>> = solid
+        .indices
+        .chunks(3)
+        .map(|t| t.iter().map(|&i| solid.vertices[i as usize].pos).collect())
+        .collect();
+    let rings = polygon::union(&pieces);
+    let (verts, tris) = polygon::triangulate(&rings);
+
+    let base = mesh.vertices.len() as u32;
+    for v in &verts {
+        // The union loses track of which point a vertex came from; the nearest
+        // solid vertex is either its origin or right next to a crossing.
+        if shading.colors.is_empty() && !shading.uv && !shading.normals {
+            mesh.push_vertex(*v, color);
+            continue;
+        }
+        let d = |p: Point| length(sub(p, *v));
+        match solid.vertices.iter().min_by(|a, b| d(a.pos).total_cmp(&d(b.pos))) {
+            Some(s) => mesh.push(Vertex { pos: *v, ..*s }),
+            None => mesh.push_vertex(*v, color),
+        };
+    }
+    for t in &tris {
+        mesh.push_tri(base + t[0], base + t[1], base + t[2]);
+    }
+    if options.fringe > 0.0 {
+        // `triangulate` keeps the ring vertices in ring order.
+        let mut outline = Vec::new();
+        let mut start = base;
+        for ring in &rings {
+            let len = ring.len() as u32;
+            for k in 0..len {
+                outline.push((start + k, start + (k + 1) % len));
+            }
+            start += len;
+        }
+        fringe(mesh, &outline, options.fringe, scratch);
+    }
+}
+
+/// Scales widths near the ends by the eased distance from each end, measured
+/// along the stroke, independent of whatever profile the host passed in.
+fn taper(points: &[f32], widths: &[f32], options: &MeshOptions) -> Vec<f32> {
+    let n = points.len() / 2;
+    let mut dist = Vec::with_capacity(n);
+    let mut total = 0.0;
+    for i in 0..n {
+        if i > 0 {
+            total += length(sub(point_at(points, i), point_at(points, i - 1)));
+        }
+        dist.push(total);
+    }
+    (0..n)
+        .map(|i| {
+            let mut w = widths.get(i).copied().unwrap_or(1.0);
+            if options.start_taper > 0.0 && options.start_decoration == Decoration::None {
+                w *= options.start_taper_easing.apply(dist[i] / options.start_taper);
+            }
+            if options.end_taper > 0.0 && options.end_decoration == Decoration::None {
+                w *= options.end_taper_easing.apply((total - dist[i]) / options.end_taper);
+            }
+            w
+        })
+        .collect()
+}
+
+/// A run of pieces to emit. Piece 0 is the start cap and piece `k` the ribbon
+/// of segment `k - 1..k` plus the join at point `k`; the end cap is separate.
+/// Each piece depends only on its own points and their neighbours, so an
+/// incremental build can emit the ones that no later point can change.
+#[derive(Clone, Copy)]
+pub(crate) struct Span {
+    pub from: usize,
+    pub to: usize,
+    pub end_cap: bool,
+}
+
+impl Span {
+    pub const ALL: Span = Span {
+        from: 0,
+        to: usize::MAX,
+        end_cap: true,
+    };
+
+    fn contains(&self, piece: usize) -> bool {
+        (self.from..=self.to).contains(&piece)
+    }
+}
+
+/// Whether prepared points can be emitted a few pieces at a time with the
+/// same result as one full pass. Outlines, strips and loops need the whole stroke.
+pub(crate) fn emits_in_pieces(options: &MeshOptions) -> bool {
+    !options.overlap_free && !options.closed && options.fringe <= 0.0 && options.topology == Topology::TriangleList
+}
+
+/// Soup for pieces `first..=last`, tessellating only the points they depend on.
+pub(crate) fn emit_pieces(points: &[f32], widths: &[f32], color: [f32; 4], options: &MeshOptions, first: usize, last: usize, end_cap: bool) -> Vec<f32> {
+    let n = widths.len();
+    let lo = first.saturating_sub(2);
+    let hi = (last + 1).min(n - 1);
+    let span = Span {
+        from: first - lo,
+        to: last - lo,
+        end_cap: end_cap && hi == n - 1,
+    };
+    let mut mesh = Mesh::default();
+    tessellate_span(&points[lo * 2..(hi + 1) * 2], &widths[lo..=hi], color, options, span, &mut mesh);
+    mesh.triangle_soup()
+}
+
+/// Emits only `span` of an open stroke into a triangle-list mesh. Edge
+/// vertices are still computed for every point so joins match a full build.
+pub(crate) fn tessellate_span(points: &[f32], widths: &[f32], color: [f32; 4], options: &MeshOptions, span: Span, mesh: &mut Mesh) {
+    debug_assert!(!options.closed && mesh.topology == Topology::TriangleList);
+    let first = mesh.vertices.len();
+    let mut t = Tessellator {
+        points,
+        widths,
+        color,
+        colors: &[],
+        center_alpha: 1.0,
+        normals: false,
+        options,
+        mesh,
+        span,
+        scratch: &mut Scratch::default(),
+    };
+    t.run();
+    if options.premultiplied_alpha {
+        mesh::premultiply(&mut mesh.vertices[first..]);
+    }
+}
+
+pub(crate) fn radius_at(widths: &[f32], i: usize) -> f32 {
+    widths.get(i).copied().unwrap_or(1.0) * 0.5
+}
+
+struct Tessellator<'a> {
+    points: &'a [f32],
+    widths: &'a [f32],
+    color: [f32; 4],
+    /// One color per point, or empty for `color` throughout.
+    colors: &'a [[f32; 4]],
+    /// Alpha scale on the centreline; 1 leaves the ribbon unshaded.
+    center_alpha: f32,
+    normals: bool,
+    options: &'a MeshOptions,
+    mesh: &'a mut Mesh,
+    /// Strip output only supports `Span::ALL`.
+    span: Span,
+    scratch: &'a mut Scratch,
+}
+
+impl Tessellator<'_> {
+    fn run(&mut self) {
+        let mut frames = std::mem::take(&mut self.scratch.frames);
+        self.stroke(&mut frames);
+        self.scratch.frames = frames;
+    }
+
+    fn stroke(&mut self, f: &mut Frames) {
+        let points = self.points;
+        let options = self.options;
+        let mut n = points.len() / 2;
+        if n == 0 {
+            return;
+        }
+        if n == 1 {
+            if self.span.contains(0) {
+                match options.cap {
+                    CapStyle::Round => self.circle(0),
+                    CapStyle::Square => self.square(0),
+                    // A zero-length butt-capped stroke covers nothing, as in SVG.
+                    CapStyle::Butt => {}
+                }
+            }
+            return;
+        }
+
+        // A closed loop wraps its last segment back to the first point; a repeated
+        // closing point would only add a zero-length segment.
+        let mut closed = options.closed;
+        if closed && n > 3 && length(sub(point_at(points, n - 1), point_at(points, 0))) < 1e-6 {
+            n -= 1;
+        }
+        if n < 3 {
+            closed = false;
+        }
+
+        let seg_count = if closed { n } else { n - 1 };
+        simd::segment_frames(points, n, seg_count, &mut f.dirs, &mut f.norms);
+        simd::join_miters(&f.norms, n, &mut f.miters);
+        let (dirs, norms, miters) = (&f.dirs, &f.norms, &f.miters);
+        // Room for a ribbon of miter joins between round caps, so long
+        // strokes do not copy the mesh as it grows.
+        let cap = self.arc_steps(PI, radius_at(self.widths, 0).max(radius_at(self.widths, n - 1)), PI / 10.0, 6) + 1;
+        self.mesh.vertices.reserve(n * 2 + cap * 2);
+        self.mesh.indices.reserve((seg_count + cap) * 6);
+        let is_join = |i: usize| closed || (i > 0 && i < n - 1);
+        let prev_seg = |i: usize| if i == 0 { seg_count - 1 } else { i - 1 };
+
+        let (edges, fills) = (&mut f.edges, &mut f.fills);
+        edges.clear();
+        fills.clear();
+        fills.resize(n, JoinFill::Miter);
+
+        for i in 0..n {
+            let p = point_at(points, i);
+            let radius = radius_at(self.widths, i);
+            if !is_join(i) {
+                let nrm = point_at(norms, if i == 0 { 0 } else { n - 2 });
+                let (l, r) = simd::offset_pair(p, nrm, radius);
+                let (l, r) = (self.vertex(i, l), self.vertex(i, r));
+                edges.push([l, r, l, r]);
+                continue;
+            }
+
+            let (n0, n1) = (point_at(norms, prev_seg(i)), point_at(norms, i));
+            if matches!(options.join, JoinStyle::Miter | JoinStyle::MiterRound) {
+                let [mx, my, miter_len, d] = miters[i];
+                if miter_len >= 1e-4 {
+                    let mdir = (mx, my);
+                    let miter_length = if d.abs() > 1e-6 { radius / d } else { radius };
+                    if miter_length.abs() <= options.miter_limit * radius {
+                        let (l, r) = simd::offset_pair(p, mdir, miter_length);
+                        let (l, r) = (self.vertex(i, l), self.vertex(i, r));
+                        edges.push([l, r, l, r]);
+                        continue;
+                    }
+                }
+            }
+
+            let ((lp, rp), (ln, rn)) = (simd::offset_pair(p, n0, radius), simd::offset_pair(p, n1, radius));
+            let (lp, rp, ln, rn) = (self.vertex(i, lp), self.vertex(i, rp), self.vertex(i, ln), self.vertex(i, rn));
+            edges.push([lp, rp, ln, rn]);
+            fills[i] = match options.join {
+                JoinStyle::Miter | JoinStyle::Bevel => JoinFill::Bevel,
+                JoinStyle::Round | JoinStyle::MiterRound => JoinFill::Round,
+            };
+        }
+
+        for i in 0..seg_count {
+            if !self.span.contains(i + 1) {
+                continue;
+            }
+            let [_, _, l0, r0] = edges[i];
+            let [l1, r1, _, _] = edges[(i + 1) % n];
+            if self.mesh.topology == Topology::TriangleList {
+                if self.center_alpha < 1.0 {
+                    let c0 = self.vertex(i, point_at(points, i));
+                    let c1 = self.vertex((i + 1) % n, point_at(points, (i + 1) % n));
+                    self.mesh.push_tri(l0, c0, c1);
+                    self.mesh.push_tri(l0, c1, l1);
+                    self.mesh.push_tri(c0, r0, r1);
+                    self.mesh.push_tri(c0, r1, c1);
+                } else {
+                    self.mesh.push_tri(l0, r0, r1);
+                    self.mesh.push_tri(l0, r1, l1);
+                }
+            }
+            let p = point_at(points, i);
+            self.edge(l0, l1, p);
+            self.edge(r1, r0, p);
+        }
+        if self.mesh.topology == Topology::TriangleStrip {
+            // One strip down the whole ribbon; split joins bridge their two
+            // edge pairs with an extra pair of triangles.
+            let strip = &mut f.strip;
+            strip.clear();
+            for [lp, rp, ln, rn] in edges.iter() {
+                strip.extend_from_slice(&[*lp, *rp]);
+                if lp != ln {
+                    strip.extend_from_slice(&[*ln, *rn]);
+                }
+            }
+            if closed {
+                strip.extend_from_slice(&[edges[0][0], edges[0][1]]);
+            }
+            self.mesh.push_strip(strip);
+        }
+
+        for i in 0..n {
+            if !is_join(i) || fills[i] == JoinFill::Miter || !self.span.contains(i) {
+                continue;
+            }
+            let (d0, d1) = (point_at(dirs, prev_seg(i)), point_at(dirs, i));
+            let turn = cross(d0, d1);
+            // The gap to fill is on the outside of the turn: the right edge for a
+            // counter-clockwise turn, the left edge for a clockwise one.
+            let [lp, rp, ln, rn] = edges[i];
+            let n0 = point_at(norms, prev_seg(i));
+            let (outer0, first, last) = if turn >= 0.0 { ((-n0.0, -n0.1), rp, rn) } else { (n0, lp, ln) };
+            if fills[i] == JoinFill::Bevel {
+                let c = self.vertex(i, point_at(points, i));
+                self.mesh.push_tri(c, first, last);
+                self.edge(first, last, point_at(points, i));
+                continue;
+            }
+            let sweep = turn.atan2(dot(d0, d1));
+            let steps = self.arc_steps(sweep, radius_at(self.widths, i), PI / 8.0, 4);
+            self.fan(i, outer0.1.atan2(outer0.0), sweep, steps, first, last);
+        }
+
+        if closed {
+            return;
+        }
+
+        // Caps run from the left edge around the back of the start point and
+        // from the right edge around the front of the end point.
+        let [l0, r0, _, _] = edges[0];
+        let [_, _, le, re] = edges[n - 1];
+        let (n0, ne) = (point_at(norms, 0), point_at(norms, n - 2));
+        if self.span.contains(0) {
+            self.cap(0, n0, l0, r0);
+            self.decoration(0, options.start_decoration);
+        }
+        if self.span.end_cap {
+            self.cap(n - 1, (-ne.0, -ne.1), re, le);
+            self.decoration(n - 1, options.end_decoration);
+        }
+    }
+
+    /// Decoration at end point `i`, the first or last.
+    fn decoration(&mut self, i: usize, kind: Decoration) {
+        if kind == Decoration::None {
+            return;
+        }
+        let p = point_at(self.points, i);
+        let Some(dir) = self.end_tangent(i) else {
+            return;
+        };
+        let radius = radius_at(self.widths, i);
+        let size = self.options.decoration_size.max(0.0) * radius * 2.0;
+        let side = (-dir.1, dir.0);
+        let outline: Vec<Point> = match kind {
+            Decoration::None => return,
+            Decoration::Circle => {
+                self.disc(i, p, size * 0.5);
+                return;
+            }
+            Decoration::Arrow => {
+                // Push the tip out far enough that the cap, which reaches at
+                // most `reach` from the end point, stays inside both sides.
+                let reach = if self.options.cap == CapStyle::Square { radius * std::f32::consts::SQRT_2 } else { radius };
+                let half = size * 0.5;
+                let sin = half / half.hypot(size);
+                let tip = offset(p, dir, if sin > 0.0 { reach / sin } else { 0.0 });
+                let base = offset(tip, dir, -size);
+                vec![tip, offset(base, side, half), offset(base, side, -half)]
+            }
+            Decoration::Bar => {
+                let (half_len, half_thick) = (size * 0.5, radius);
+                vec![
+                    offset(offset(p, side, half_len), dir, half_thick),
+                    offset(offset(p, side, half_len), dir, -half_thick),
+                    offset(offset(p, side, -half_len), dir, -half_thick),
+                    offset(offset(p, side, -half_len), dir, half_thick),
+                ]
+            }
+        };
+        let ids: Vec<u32> = outline.iter().map(|&q| self.vertex(i, q)).collect();
+        for k in 0..ids.len() {
+            self.edge(ids[k], ids[(k + 1) % ids.len()], p);
+        }
+        self.mesh.push_polygon(&ids);
+    }
+
+    /// Unit direction pointing out of the stroke at end point `i`, measured
+    /// from a point at least half a width back so a hook in the last few
+    /// samples does not swing it.
+    fn end_tangent(&self, i: usize) -> Option<Point> {
+        let n = self.points.len() / 2;
+        let p = point_at(self.points, i);
+        let reach = radius_at(self.widths, i);
+        let mut best = None;
+        for k in 1..n {
+            let j = if i == 0 { k } else { i - k };
+            let d = sub(p, point_at(self.points, j));
+            let len = length(d);
+            if len > 1e-6 {
+                best = Some((d.0 / len, d.1 / len));
+            }
+            if len >= reach {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Cap at point `i` from edge vertex `first`, which lies along `nrm`, to `last`.
+    fn cap(&mut self, i: usize, nrm: Point, first: u32, last: u32) {
+        let p = point_at(self.points, i);
+        let radius = radius_at(self.widths, i);
+        // `nrm` turned a quarter counter-clockwise points away from the stroke.
+        let out = (-nrm.1, nrm.0);
+        match self.options.cap {
+            CapStyle::Round => {
+                let steps = self.arc_steps(PI, radius, PI / 10.0, 6);
+                self.fan(i, nrm.1.atan2(nrm.0), PI, steps, first, last);
+            }
+            CapStyle::Butt => self.edge(first, last, offset(p, out, -radius)),
+            CapStyle::Square => {
+                let a = self.mesh.vertices[first as usize].pos;
+                let b = self.mesh.vertices[last as usize].pos;
+                let a = self.vertex(i, offset(a, out, radius));
+                let b = self.vertex(i, offset(b, out, radius));
+                self.mesh.push_polygon(&[first, a, b, last]);
+                self.edge(first, a, p);
+                self.edge(a, b, p);
+                self.edge(b, last, p);
+            }
+        }
+    }
+
+    /// Number of fan segments for an arc. With a tolerance set, the step angle
+    /// keeps the chord's sagitta under `tolerance` screen pixels at the current
+    /// scale; otherwise the fixed `step` and `min_steps` are used.
+    fn arc_steps(&self, sweep: f32, radius: f32, step: f32, min_steps: usize) -> usize {
+        let sweep = sweep.abs();
+        let tolerance = self.options.tolerance;
+        if tolerance <= 0.0 {
+            return ((sweep / step).ceil() as usize).max(min_steps);
+        }
+        let radius_px = radius * self.options.scale;
+        if radius_px <= tolerance {
+            return 1;
+        }
+        let adaptive = 2.0 * (1.0 - tolerance / radius_px).acos();
+        ((sweep / adaptive).ceil() as usize).clamp(1, 256)
+    }
+
+    fn edge(&mut self, a: u32, b: u32, inner: Point) {
+        // Only the fringe reads the outline.
+        if self.options.fringe > 0.0 {
+            self.outline_edge(a, b, inner);
+        }
+    }
+
+    fn outline_edge(&mut self, a: u32, b: u32, inner: Point) {
+        let pa = self.mesh.vertices[a as usize].pos;
+        let pb = self.mesh.vertices[b as usize].pos;
+        if cross(sub(pb, pa), sub(inner, pa)) >= 0.0 {
+            self.scratch.outline.push((a, b));
+        } else {
+            self.scratch.outline.push((b, a));
+        }
+    }
+
+    fn vertex(&mut self, i: usize, pos: Point) -> u32 {
+        let mut color = self.colors.get(i).copied().unwrap_or(self.color);
+        if self.center_alpha < 1.0 {
+            let radius = radius_at(self.widths, i).max(1e-6);
+            let edge = (length(sub(pos, point_at(self.points, i))) / radius).min(1.0);
+            color[3] *= self.center_alpha + (1.0 - self.center_alpha) * edge;
+        }
+        if self.scratch.arc.is_empty() && !self.normals {
+            return self.mesh.push_vertex(pos, color);
+        }
+        // Project onto the frame at point `i`, along the chord through its neighbours.
+        let n = self.points.len() / 2;
+        let p = point_at(self.points, i);
+        let chord = sub(point_at(self.points, (i + 1).min(n - 1)), point_at(self.points, i.saturating_sub(1)));
+        let len = length(chord);
+        let dir = if len > 1e-6 { (chord.0 / len, chord.1 / len) } else { (1.0, 0.0) };
+        let d = sub(pos, p);
+        let radius = radius_at(self.widths, i).max(1e-6);
+        let uv = match self.scratch.arc.get(i) {
+            Some(&arc) => (arc + dot(d, dir), (0.5 - cross(dir, d) / (2.0 * radius)).clamp(0.0, 1.0)),
+            None => (0.0, 0.0),
+        };
+        let (normal, side) = if self.normals {
+            // Miter corners reach past the radius; they lie on the tube's rim.
+            let reach = (length(d) / radius).max(1.0) * radius;
+            let e = (d.0 / reach, d.1 / reach);
+            ([e.0, e.1, (1.0 - dot(e, e)).max(0.0).sqrt()], (-cross(dir, d) / radius).clamp(-1.0, 1.0))
+        } else {
+            (UP, 0.0)
+        };
+        self.mesh.push(Vertex { pos, uv, color, normal, side })
+    }
+
+    fn square(&mut self, i: usize) {
+        let c = point_at(self.points, i);
+        let r = radius_at(self.widths, i);
+        let quad = [(-r, -r), (r, -r), (r, r), (-r, r)].map(|(dx, dy)| self.vertex(i, (c.0 + dx, c.1 + dy)));
+        for k in 0..4 {
+            self.edge(quad[k], quad[(k + 1) % 4], c);
+        }
+        self.mesh.push_polygon(&quad);
+    }
+
+    fn circle(&mut self, i: usize) {
+        self.disc(i, point_at(self.points, i), radius_at(self.widths, i));
+    }
+
+    fn disc(&mut self, i: usize, center: Point, radius: f32) {
+        let steps = self.arc_steps(PI * 2.0, radius, PI / 12.0, 24).max(3);
+        let mut ring = std::mem::take(&mut self.scratch.rim);
+        ring.clear();
+        for s in 0..steps {
+            let t = (s as f32) / (steps as f32) * PI * 2.0;
+            ring.push(self.vertex(i, (center.0 + t.cos() * radius, center.1 + t.sin() * radius)));
+        }
+        for s in 0..steps {
+            self.edge(ring[s], ring[(s + 1) % steps], center);
+        }
+        if self.mesh.topology == Topology::TriangleStrip {
+            self.mesh.push_polygon(&ring);
+        } else {
+            let c = self.vertex(i, center);
+            ring.push(ring[0]);
+            self.mesh.push_fan(c, &ring);
+        }
+        self.scratch.rim = ring;
+    }
+
+    /// Triangle fan around point `i` from the existing vertex `first` to `last`,
+    /// sweeping `sweep` radians (signed) from angle `a0`.
+    fn fan(&mut self, i: usize, a0: f32, sweep: f32, steps: usize, first: u32, last: u32) {
+        let center = point_at(self.points, i);
+        let radius = radius_at(self.widths, i);
+        let c = self.vertex(i, center);
+        let mut rim = std::mem::take(&mut self.scratch.rim);
+        rim.clear();
+        rim.push(first);
+        for s in 1..steps {
+            let t = a0 + sweep * (s as f32) / (steps as f32);
+            rim.push(self.vertex(i, (center.0 + t.cos() * radius, center.1 + t.sin() * radius)));
+        }
+        rim.push(last);
+        for k in 1..rim.len() {
+            self.edge(rim[k - 1], rim[k], center);
+        }
+        self.mesh.push_fan(c, &rim);
+        self.scratch.rim = rim;
+    }
+}
+
+/// Extrudes every outline edge outwards by `width` into a quad whose outer
+/// vertices have zero alpha, giving the renderer cheap geometric anti-aliasing.
+/// Edges are oriented with the solid on their left.
+fn fringe(mesh: &mut Mesh, outline: &[(u32, u32)], width: f32, scratch: &mut Scratch) {
+    let normals = &mut scratch.fringe_normals;
+    normals.clear();
+    normals.resize(mesh.vertices.len(), ((0.0, 0.0), 0));
+    for &(a, b) in outline {
+        let pa = mesh.vertices[a as usize].pos;
+        let pb = mesh.vertices[b as usize].pos;
+        let d = sub(pb, pa);
+        let len = length(d);
+        if len < 1e-6 {
+            continue;
+        }
+        let nrm = (d.1 / len, -d.0 / len);
+        for v in [a, b] {
+            let acc = &mut normals[v as usize];
+            acc.0 = (acc.0 .0 + nrm.0, acc.0 .1 + nrm.1);
+            acc.1 += 1;
+        }
+    }
+
+    let outer = &mut scratch.fringe_outer;
+    outer.clear();
+    outer.resize(normals.len(), None);
+    for (v, &(acc, count)) in normals.iter().enumerate() {
+        let len = length(acc);
+        if count == 0 || len < 1e-6 {
+            continue;
+        }
+        // Averaged normals shrink at corners; push out further so the fringe
+        // keeps its width, up to twice the nominal distance.
+        let dist = width * (count as f32 / len).min(2.0);
+        let base = mesh.vertices[v];
+        let mut color = base.color;
+        color[3] = 0.0;
+        outer[v] = Some(mesh.push(Vertex {
+            pos: offset(base.pos, (acc.0 / len, acc.1 / len), dist),
+            color,
+            ..base
+        }));
+    }
+
+    for &(a, b) in outline {
+        if let (Some(oa), Some(ob)) = (outer[a as usize], outer[b as usize]) {
+            match mesh.topology {
+                Topology::TriangleList => {
+                    mesh.push_tri(a, b, ob);
+                    mesh.push_tri(a, ob, oa);
+                }
+                Topology::TriangleStrip => mesh.push_strip(&[a, oa, b, ob]),
+            }
+        }
+    }
+}
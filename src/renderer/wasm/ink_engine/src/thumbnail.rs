@@ -0,0 +1,173 @@
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::raster::{raster_size, Canvas};
+
+/// Samples per thumbnail pixel each way; ink is drawn this much larger and
+/// box-filtered down, so hairlines thin out instead of breaking up.
+const OVERSAMPLE: usize = 4;
+
+/// The paper behind the ink.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PageTemplate {
+    #[default]
+    Blank,
+    /// Horizontal rules.
+    Ruled,
+    /// Squares.
+    Grid,
+    /// A dot at each grid crossing.
+    Dots,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct ThumbnailOptions {
+    /// Largest size in pixels; the rect is fitted inside, keeping its
+    /// aspect ratio.
+    pub max_width: u32,
+    pub max_height: u32,
+    pub template: PageTemplate,
+    /// Distance between template lines in document units, measured from
+    /// the document origin so they line up with the page on screen.
+    pub spacing: f32,
+    /// Template line width, or dot size, in thumbnail pixels.
+    pub line_width: f32,
+    background: [f32; 4],
+    line_color: [f32; 4],
+}
+
+#[wasm_bindgen]
+impl ThumbnailOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ThumbnailOptions {
+        ThumbnailOptions::default()
+    }
+
+    /// Paper color, white by default; a transparent one leaves the ink on
+    /// a transparent background.
+    pub fn set_background(&mut self, color: &[f32]) {
+        self.background = crate::rgba(color);
+    }
+
+    /// Template line color, a pale blue by default.
+    pub fn set_line_color(&mut self, color: &[f32]) {
+        self.line_color = crate::rgba(color);
+    }
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        ThumbnailOptions {
+            max_width: 256,
+            max_height: 256,
+            template: PageTemplate::Blank,
+            spacing: 32.0,
+            line_width: 1.0,
+            background: [1.0, 1.0, 1.0, 1.0],
+            line_color: [0.7, 0.78, 0.9, 1.0],
+        }
+    }
+}
+
+/// Straight-alpha RGBA8 pixels, rows top down.
+#[wasm_bindgen]
+pub struct Thumbnail {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Thumbnail {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// A small picture of the page or other rect `x, y, width, height`
+    /// (document units) for page lists and recent-document previews: the
+    /// visible layers over the options' paper and template, fitted inside
+    /// the maximum size. Ink is drawn oversampled and filtered down, so it
+    /// stays smooth however far the rect is shrunk. `None` for an empty
+    /// rect or maximum size.
+    pub fn thumbnail(&mut self, x: f32, y: f32, width: f32, height: f32, options: &ThumbnailOptions) -> Option<Thumbnail> {
+        let scale = (options.max_width as f32 / width).min(options.max_height as f32 / height);
+        let (w, h) = raster_size(width, height, scale)?;
+        let (w, h) = (w.min(options.max_width as usize), h.min(options.max_height as usize));
+        let scale = scale as f64;
+        let ink = self.render_antialiased([x as f64 * scale, y as f64 * scale], scale, w, h, OVERSAMPLE);
+        let mut page = Canvas::new(w, h, ink.origin, scale);
+        paper(&mut page, options);
+        page.composite(&ink, 1.0);
+        Some(Thumbnail {
+            width: w as u32,
+            height: h as u32,
+            pixels: page.to_rgba8(),
+        })
+    }
+}
+
+/// Fills the canvas with the background and draws the template over it.
+fn paper(page: &mut Canvas, options: &ThumbnailOptions) {
+    let step = options.spacing as f64 * page.scale;
+    let width = options.line_width.max(0.0) as f64;
+    let lines = |n: usize, origin: f64, width: f64| coverage(n, origin, step, width);
+    let (rows, cols) = match options.template {
+        PageTemplate::Blank => (Vec::new(), Vec::new()),
+        PageTemplate::Ruled => (lines(page.height, page.origin[1], width), Vec::new()),
+        PageTemplate::Grid => (lines(page.height, page.origin[1], width), lines(page.width, page.origin[0], width)),
+        PageTemplate::Dots => (lines(page.height, page.origin[1], width * 2.0), lines(page.width, page.origin[0], width * 2.0)),
+    };
+    let premultiply = |c: [f32; 4]| [c[0] * c[3], c[1] * c[3], c[2] * c[3], c[3]];
+    let (paper, ink) = (premultiply(options.background), premultiply(options.line_color));
+    for y in 0..page.height {
+        for x in 0..page.width {
+            let (row, col) = (rows.get(y).copied().unwrap_or(0.0), cols.get(x).copied().unwrap_or(0.0));
+            let cover = match options.template {
+                PageTemplate::Blank => 0.0,
+                PageTemplate::Ruled => row,
+                PageTemplate::Grid => 1.0 - (1.0 - row) * (1.0 - col),
+                PageTemplate::Dots => row * col,
+            };
+            let a = ink[3] * cover;
+            let at = (y * page.width + x) * 4;
+            for k in 0..4 {
+                page.pixels[at + k] = ink[k] * cover + paper[k] * (1.0 - a);
+            }
+        }
+    }
+}
+
+/// How much of each of `n` pixels, starting `origin` pixels along the
+/// grid, is covered by lines `width` pixels wide centred every `step`
+/// pixels from 0. Empty when the lines would be too dense to tell apart.
+fn coverage(n: usize, origin: f64, step: f64, width: f64) -> Vec<f32> {
+    if width <= 0.0 || step.is_nan() || step < 2.0 * width.max(1.0) {
+        return Vec::new();
+    }
+    let mut out = vec![0.0f32; n];
+    let first = ((origin - width) / step).floor() as i64;
+    let last = ((origin + n as f64 + width) / step).ceil() as i64;
+    for k in first..=last {
+        let centre = k as f64 * step - origin;
+        let (lo, hi) = (centre - width / 2.0, centre + width / 2.0);
+        let (start, end) = (lo.floor().max(0.0) as usize, hi.ceil().max(0.0) as usize);
+        for (p, v) in out.iter_mut().enumerate().take(end).skip(start) {
+            let overlap = hi.min(p as f64 + 1.0) - lo.max(p as f64);
+            *v = (*v + overlap.max(0.0) as f32).min(1.0);
+        }
+    }
+    out
+}
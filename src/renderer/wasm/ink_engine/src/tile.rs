@@ -0,0 +1,142 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use wasm_bindgen::prelude::*;
+
+use crate::document::{zoom_bucket, InkDocument};
+
+/// Side of a tile in pixels.
+pub(crate) const TILE_SIZE: usize = 256;
+/// Tiles kept by default, 16 MiB of pixels.
+const DEFAULT_CACHE_LIMIT: usize = 64;
+
+/// Rendered tiles by content hash, most recently used last.
+pub(crate) struct TileCache {
+    tiles: Vec<(u64, Vec<u8>)>,
+    limit: usize,
+}
+
+impl Default for TileCache {
+    fn default() -> Self {
+        TileCache {
+            tiles: Vec::new(),
+            limit: DEFAULT_CACHE_LIMIT,
+        }
+    }
+}
+
+impl TileCache {
+    fn get(&mut self, key: u64) -> Option<Vec<u8>> {
+        let k = self.tiles.iter().position(|(h, _)| *h == key)?;
+        let tile = self.tiles.remove(k);
+        let pixels = tile.1.clone();
+        self.tiles.push(tile);
+        Some(pixels)
+    }
+
+    fn insert(&mut self, key: u64, pixels: Vec<u8>) {
+        if self.limit == 0 {
+            return;
+        }
+        self.tiles.retain(|(h, _)| *h != key);
+        self.tiles.push((key, pixels));
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        let over = self.tiles.len().saturating_sub(self.limit);
+        self.tiles.drain(..over);
+    }
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// The tile at `col, row` of the grid of 256-pixel square tiles at
+    /// `scale` pixels per document unit, tile 0, 0 starting at the origin:
+    /// `rasterize` of its rect, so tiles line up edge to edge. Tiles are
+    /// cached by a hash of everything drawn in them, so unchanged tiles come
+    /// back without rendering however the document changed elsewhere, and
+    /// an edit undone finds its old tiles again. Empty for a scale that is
+    /// not positive.
+    pub fn rasterize_tile(&mut self, col: i32, row: i32, scale: f32) -> Vec<u8> {
+        if scale <= 0.0 || !scale.is_finite() {
+            return Vec::new();
+        }
+        let key = self.tile_key(col, row, scale);
+        if let Some(pixels) = self.tiles.get(key) {
+            return pixels;
+        }
+        let origin = [col as f64 * TILE_SIZE as f64, row as f64 * TILE_SIZE as f64];
+        let pixels = self.render(origin, scale as f64, TILE_SIZE, TILE_SIZE).to_rgba8();
+        self.tiles.insert(key, pixels.clone());
+        pixels
+    }
+
+    /// `col, row` pairs of the tiles at `scale` that meet the view `x, y,
+    /// width, height` (document units) and have something drawn in them,
+    /// row by row, so a minimap or export can skip empty canvas.
+    pub fn content_tiles(&mut self, x: f32, y: f32, width: f32, height: f32, scale: f32) -> Vec<i32> {
+        if scale <= 0.0 || !scale.is_finite() {
+            return Vec::new();
+        }
+        let unit = TILE_SIZE as f32 / scale;
+        let cell = |v: f32| (v / unit).floor() as i32;
+        let (c0, r0, c1, r1) = (cell(x), cell(y), cell(x + width), cell(y + height));
+        let mut hit = HashSet::new();
+        for (_, order) in self.drawn_layers(&[x, y, x + width, y + height]) {
+            for i in order {
+                let Some(b) = self.index.bounds(self.strokes[i].id) else {
+                    continue;
+                };
+                for row in cell(b[1]).max(r0)..=cell(b[3]).min(r1) {
+                    for col in cell(b[0]).max(c0)..=cell(b[2]).min(c1) {
+                        hit.insert((row, col));
+                    }
+                }
+            }
+        }
+        let mut tiles: Vec<(i32, i32)> = hit.into_iter().collect();
+        tiles.sort_unstable();
+        tiles.into_iter().flat_map(|(row, col)| [col, row]).collect()
+    }
+
+    /// How many rendered tiles to keep; 0 turns the cache off.
+    pub fn set_tile_cache_limit(&mut self, tiles: usize) {
+        self.tiles.limit = tiles;
+        self.tiles.trim();
+    }
+
+    pub fn clear_tile_cache(&mut self) {
+        self.tiles.tiles.clear();
+    }
+}
+
+impl InkDocument {
+    /// Hash of what the tile shows: its place, and each layer's opacity and
+    /// the meshes, blends and transforms of the strokes meeting it.
+    /// Meshes are tessellated here, which rendering needs anyway.
+    fn tile_key(&mut self, col: i32, row: i32, scale: f32) -> u64 {
+        let bucket = zoom_bucket(scale);
+        // As `render` culls, a pixel beyond the tile each way.
+        let unit = TILE_SIZE as f64 / scale as f64;
+        let at = |k: i32, end: f64| ((k as f64 + end) * unit) as f32;
+        let pad = 1.0 / TILE_SIZE as f64;
+        let view = [at(col, -pad), at(row, -pad), at(col, 1.0 + pad), at(row, 1.0 + pad)];
+        let mut h = DefaultHasher::new();
+        (col, row, scale.to_bits()).hash(&mut h);
+        for (opacity, order) in self.drawn_layers(&view) {
+            opacity.to_bits().hash(&mut h);
+            order.len().hash(&mut h);
+            for i in order {
+                let s = &mut self.strokes[i];
+                s.transform.map(f32::to_bits).hash(&mut h);
+                let mesh = s.mesh(&self.brushes, bucket);
+                (mesh.blend() as u8, mesh.floats_per_vertex()).hash(&mut h);
+                mesh.soup().len().hash(&mut h);
+                mesh.soup().iter().for_each(|v| v.to_bits().hash(&mut h));
+            }
+        }
+        h.finish()
+    }
+}
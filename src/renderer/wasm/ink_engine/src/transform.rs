@@ -0,0 +1,53 @@
+use crate::geom::Point;
+
+/// 2D affine transform `[a, b, c, d, e, f]` in canvas `setTransform`
+/// order: `x' = a·x + c·y + e`, `y' = b·x + d·y + f`.
+pub(crate) type Affine = [f32; 6];
+
+pub(crate) const IDENTITY: Affine = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// Reads six floats, falling back to the identity for anything shorter.
+pub(crate) fn from_slice(m: &[f32]) -> Affine {
+    m.get(..6).and_then(|m| m.try_into().ok()).unwrap_or(IDENTITY)
+}
+
+pub(crate) fn apply(m: &Affine, p: Point) -> Point {
+    (m[0] * p.0 + m[2] * p.1 + m[4], m[1] * p.0 + m[3] * p.1 + m[5])
+}
+
+/// `outer` applied after `inner`.
+pub(crate) fn compose(outer: &Affine, inner: &Affine) -> Affine {
+    [
+        outer[0] * inner[0] + outer[2] * inner[1],
+        outer[1] * inner[0] + outer[3] * inner[1],
+        outer[0] * inner[2] + outer[2] * inner[3],
+        outer[1] * inner[2] + outer[3] * inner[3],
+        outer[0] * inner[4] + outer[2] * inner[5] + outer[4],
+        outer[1] * inner[4] + outer[3] * inner[5] + outer[5],
+    ]
+}
+
+/// The inverse, or `None` for a transform that collapses the plane.
+pub(crate) fn invert(m: &Affine) -> Option<Affine> {
+    let det = m[0] * m[3] - m[1] * m[2];
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    let (a, b, c, d) = (m[3] / det, -m[1] / det, -m[2] / det, m[0] / det);
+    Some([a, b, c, d, -(a * m[4] + c * m[5]), -(b * m[4] + d * m[5])])
+}
+
+/// How much the transform scales lengths on average, for widths.
+pub(crate) fn scale_factor(m: &Affine) -> f32 {
+    (m[0] * m[3] - m[1] * m[2]).abs().sqrt()
+}
+
+/// Copy of vertex data of `stride` floats starting with `x, y`, with the
+/// positions transformed and everything else untouched.
+pub(crate) fn apply_vertices(m: &Affine, vertices: &[f32], stride: usize) -> Vec<f32> {
+    let mut out = vertices.to_vec();
+    for v in out.chunks_exact_mut(stride) {
+        (v[0], v[1]) = apply(m, (v[0], v[1]));
+    }
+    out
+}
@@ -0,0 +1,114 @@
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::mesh::MeshBatch;
+use crate::snapshot::read_snapshot;
+
+/// A named checkpoint of the whole document, kept alongside the op log.
+pub(crate) struct Version {
+    pub id: u32,
+    pub name: String,
+    /// `op_count` when the version was saved.
+    pub op_count: usize,
+    pub snapshot: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Saves the current state as a named version and returns its id.
+    /// Versions outlive history compaction and are not part of `snapshot`;
+    /// persist them through `version_snapshot`.
+    pub fn save_version(&mut self, name: &str) -> u32 {
+        let snapshot = self.snapshot();
+        self.push_version(name, snapshot)
+    }
+
+    /// Adds a version from a `snapshot` blob, e.g. one persisted with
+    /// `version_snapshot`. `None` when the blob is not a snapshot.
+    pub fn import_version(&mut self, name: &str, snapshot: &[u8]) -> Option<u32> {
+        read_snapshot(snapshot)?;
+        Some(self.push_version(name, snapshot.to_vec()))
+    }
+
+    /// Version ids, oldest first.
+    pub fn version_ids(&self) -> Vec<u32> {
+        self.versions.iter().map(|v| v.id).collect()
+    }
+
+    pub fn version_name(&self, id: u32) -> Option<String> {
+        self.version(id).map(|v| v.name.clone())
+    }
+
+    pub fn set_version_name(&mut self, id: u32, name: &str) {
+        if let Some(v) = self.versions.iter_mut().find(|v| v.id == id) {
+            v.name = name.to_owned();
+        }
+    }
+
+    /// `op_count` when the version was saved, to tell how far the document
+    /// has moved on since.
+    pub fn version_op_count(&self, id: u32) -> Option<usize> {
+        self.version(id).map(|v| v.op_count)
+    }
+
+    /// The version as a `snapshot` blob, e.g. to persist it or to open the
+    /// branch in a document of its own.
+    pub fn version_snapshot(&self, id: u32) -> Vec<u8> {
+        self.version(id).map_or_else(Vec::new, |v| v.snapshot.clone())
+    }
+
+    pub fn remove_version(&mut self, id: u32) -> bool {
+        let before = self.versions.len();
+        self.versions.retain(|v| v.id != id);
+        self.versions.len() < before
+    }
+
+    /// Box around the version's visible strokes, as `document_bounds`.
+    /// Empty when the id is unknown or the version was blank.
+    pub fn version_bounds(&self, id: u32) -> Vec<f32> {
+        self.version_document(id).map_or_else(Vec::new, |mut d| d.document_bounds(true))
+    }
+
+    /// The version's visible strokes as `meshes` would draw them, for a
+    /// preview next to the current page. Built with the current brushes.
+    pub fn preview_version(&self, id: u32) -> Option<MeshBatch> {
+        self.version_document(id).map(|mut d| d.meshes())
+    }
+
+    /// Brings the document back to a version. The going back is one more
+    /// change in the op log, so journals and peers follow it, and the
+    /// state it leaves stays reachable by saving a version first. Returns
+    /// false when the id is unknown.
+    pub fn restore_version(&mut self, id: u32) -> bool {
+        let Some(snapshot) = self.version(id).map(|v| v.snapshot.clone()) else {
+            return false;
+        };
+        self.load_snapshot(&snapshot)
+    }
+}
+
+impl InkDocument {
+    fn version(&self, id: u32) -> Option<&Version> {
+        self.versions.iter().find(|v| v.id == id)
+    }
+
+    fn push_version(&mut self, name: &str, snapshot: Vec<u8>) -> u32 {
+        let id = self.next_version_id;
+        self.next_version_id += 1;
+        self.versions.push(Version {
+            id,
+            name: name.to_owned(),
+            op_count: self.op_count(),
+            snapshot,
+        });
+        id
+    }
+
+    /// A scratch document holding the version, drawn with this one's brushes.
+    fn version_document(&self, id: u32) -> Option<InkDocument> {
+        let mut d = InkDocument::new();
+        d.brushes = self.brushes.clone();
+        d.zoom_bucket = self.zoom_bucket;
+        d.restore(&self.version(id)?.snapshot).then_some(d)
+    }
+}
@@ -0,0 +1,449 @@
+use std::f64::consts::FRAC_PI_2;
+
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::document::InkDocument;
+use crate::json;
+use crate::preset::BrushStyle;
+use crate::transform;
+
+/// How Excalidraw draws freedraw elements with perfect-freehand: the size
+/// per unit of `strokeWidth`, and the thinning.
+const EXCALIDRAW_SIZE: f64 = 4.25;
+const EXCALIDRAW_THINNING: f64 = 0.6;
+/// tldraw's stroke widths for its size styles, and the font sizes its
+/// highlighter widths follow.
+const TLDRAW_STROKE_SIZES: [(&str, f64); 4] = [("s", 2.0), ("m", 3.5), ("l", 5.0), ("xl", 10.0)];
+const TLDRAW_FONT_SIZES: [(&str, f64); 4] = [("s", 18.0), ("m", 24.0), ("l", 36.0), ("xl", 44.0)];
+/// tldraw's named colors, as its light theme paints them.
+const TLDRAW_COLORS: [(&str, u32); 13] = [
+    ("black", 0x1d1d1d),
+    ("grey", 0x9fa8b2),
+    ("light-violet", 0xe085f4),
+    ("violet", 0xae3ec9),
+    ("blue", 0x4465e9),
+    ("light-blue", 0x4ba1f1),
+    ("yellow", 0xf1ac4b),
+    ("orange", 0xe16919),
+    ("green", 0x099268),
+    ("light-green", 0x4cb05e),
+    ("light-red", 0xf87777),
+    ("red", 0xe03131),
+    ("white", 0xffffff),
+];
+/// How fast perfect-freehand's simulated pressure follows pointer speed.
+const PRESSURE_RATE: f64 = 0.275;
+
+#[wasm_bindgen]
+impl InkDocument {
+    /// Adds the freedraw elements of an Excalidraw scene or clipboard
+    /// payload on top of the active layer and returns their ids. Widths
+    /// follow Excalidraw's rendering: recorded pressures, or pressure
+    /// simulated from pointer speed when the element says so, thin the
+    /// stroke about its `strokeWidth`. Rotation is kept; other element
+    /// types and deleted elements are skipped. Returns nothing when the
+    /// text is not an Excalidraw document.
+    pub fn import_excalidraw(&mut self, text: &str) -> Vec<u32> {
+        let Some(strokes) = serde_json::from_str::<Value>(text).ok().and_then(|v| read_excalidraw(&v)) else {
+            return Vec::new();
+        };
+        self.add_whiteboard_strokes(&strokes)
+    }
+
+    /// Adds the draw and highlight shapes of a tldraw file or clipboard
+    /// payload on top of the active layer and returns their ids. Pen input
+    /// keeps its recorded pressure and other input is thinned by simulated
+    /// pressure, as tldraw draws them; highlight shapes go to the first
+    /// registered `BrushStyle::Highlighter` brush. Shapes inside groups or
+    /// frames are placed as if their parent were at the origin, and shapes
+    /// that store their points in the compressed path encoding are skipped.
+    /// Returns nothing when the text is not a tldraw document.
+    pub fn import_tldraw(&mut self, text: &str) -> Vec<u32> {
+        let Some(strokes) = serde_json::from_str::<Value>(text).ok().and_then(|v| read_tldraw(&v)) else {
+            return Vec::new();
+        };
+        self.add_whiteboard_strokes(&strokes)
+    }
+
+    /// The strokes of the visible layers as an Excalidraw scene of
+    /// freedraw elements, which tldraw can paste as well. Each element's
+    /// `strokeWidth` is set so its pressures reproduce our widths through
+    /// Excalidraw's thinning; layer opacity is folded into each element's.
+    /// Erased fills have no centreline and are left out.
+    pub fn to_excalidraw(&self) -> String {
+        let visible: Vec<(u32, f32)> = self.layers.iter().filter(|l| l.visible).map(|l| (l.id, l.opacity)).collect();
+        let mut elements = Vec::new();
+        for (layer, opacity) in visible {
+            for s in self.strokes.iter().filter(|s| s.layer == layer && s.fill.is_none() && s.points.len() >= 2) {
+                let scale = transform::scale_factor(&s.transform);
+                let points: Vec<(f32, f32)> = s.points.chunks_exact(2).map(|p| transform::apply(&s.transform, (p[0], p[1]))).collect();
+                let (x, y) = points[0];
+                let (mut x1, mut y1, mut x2, mut y2) = (0.0f32, 0.0f32, 0.0f32, 0.0f32);
+                for p in &points {
+                    (x1, y1, x2, y2) = (x1.min(p.0 - x), y1.min(p.1 - y), x2.max(p.0 - x), y2.max(p.1 - y));
+                }
+                let widths: Vec<f64> = s.widths.iter().map(|&w| (w * scale) as f64).collect();
+                let size = widths.iter().sum::<f64>() / widths.len() as f64 / freehand_width(1.0, EXCALIDRAW_THINNING, 0.5);
+                elements.push(FreedrawElement {
+                    kind: "freedraw",
+                    id: format!("ink-{}", s.id),
+                    x,
+                    y,
+                    width: x2 - x1,
+                    height: y2 - y1,
+                    angle: 0,
+                    stroke_color: hex(s.color),
+                    background_color: "transparent",
+                    stroke_width: size / EXCALIDRAW_SIZE,
+                    opacity: (s.color[3] * opacity * 100.0).round().clamp(0.0, 100.0) as u8,
+                    stroke_style: "solid",
+                    fill_style: "solid",
+                    roughness: 0,
+                    seed: s.id,
+                    version: 1,
+                    version_nonce: s.id,
+                    is_deleted: false,
+                    group_ids: [],
+                    frame_id: (),
+                    roundness: (),
+                    bound_elements: (),
+                    link: (),
+                    locked: false,
+                    updated: s.created.round(),
+                    points: points.iter().map(|p| [p.0 - x, p.1 - y]).collect(),
+                    pressures: widths.iter().map(|&w| freehand_pressure(w, size, EXCALIDRAW_THINNING) as f32).collect(),
+                    simulate_pressure: false,
+                    last_committed_point: (),
+                });
+            }
+        }
+        let scene = ExcalidrawScene {
+            kind: "excalidraw",
+            version: 2,
+            source: "ink-engine",
+            elements,
+            app_state: AppState { view_background_color: "#ffffff" },
+            files: Files {},
+        };
+        serde_json::to_string(&scene).unwrap_or_default()
+    }
+}
+
+impl InkDocument {
+    fn add_whiteboard_strokes(&mut self, strokes: &[WhiteboardStroke]) -> Vec<u32> {
+        let highlighter = self.brushes.with_style(BrushStyle::Highlighter).unwrap_or(0);
+        strokes
+            .iter()
+            .map(|s| {
+                let brush = if s.highlighter { highlighter } else { 0 };
+                self.add_stroke(&s.points, &s.widths, &[], &s.color, brush).id
+            })
+            .collect()
+    }
+}
+
+struct WhiteboardStroke {
+    color: [f32; 4],
+    /// Document units.
+    points: Vec<f32>,
+    widths: Vec<f32>,
+    highlighter: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExcalidrawScene {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    version: u32,
+    source: &'static str,
+    elements: Vec<FreedrawElement>,
+    app_state: AppState,
+    files: Files,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppState {
+    view_background_color: &'static str,
+}
+
+#[derive(Serialize)]
+struct Files {}
+
+/// An Excalidraw freedraw element; the `()` fields are ones Excalidraw
+/// expects and we leave `null`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FreedrawElement {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    angle: u8,
+    stroke_color: String,
+    background_color: &'static str,
+    stroke_width: f64,
+    opacity: u8,
+    stroke_style: &'static str,
+    fill_style: &'static str,
+    roughness: u8,
+    seed: u32,
+    version: u32,
+    version_nonce: u32,
+    is_deleted: bool,
+    group_ids: [u32; 0],
+    frame_id: (),
+    roundness: (),
+    bound_elements: (),
+    link: (),
+    locked: bool,
+    updated: f64,
+    points: Vec<[f32; 2]>,
+    pressures: Vec<f32>,
+    simulate_pressure: bool,
+    last_committed_point: (),
+}
+
+fn read_excalidraw(v: &Value) -> Option<Vec<WhiteboardStroke>> {
+    let mut out = Vec::new();
+    for e in v.get("elements")?.as_array()? {
+        if e.get("type").and_then(Value::as_str) != Some("freedraw") || e.get("isDeleted").and_then(Value::as_bool) == Some(true) {
+            continue;
+        }
+        let field = |key: &str, default: f64| e.get(key).map_or(Some(default), Value::as_f64);
+        let (x, y, angle) = (field("x", 0.0)?, field("y", 0.0)?, field("angle", 0.0)?);
+        let local = e.get("points")?.as_array()?.iter().map(|p| json::numbers(p).filter(|p| p.len() == 2).map(|p| (p[0], p[1]))).collect::<Option<Vec<_>>>()?;
+        if local.is_empty() {
+            continue;
+        }
+        let size = field("strokeWidth", 1.0)? * EXCALIDRAW_SIZE;
+        let pressures = match e.get("simulatePressure").and_then(Value::as_bool) {
+            Some(true) => simulated_pressures(&local, size),
+            _ => {
+                let given = e.get("pressures").map_or(Some(Vec::new()), json::numbers)?;
+                (0..local.len()).map(|k| given.get(k).copied().unwrap_or(0.5)).collect()
+            }
+        };
+        // Excalidraw turns elements about the middle of their points.
+        let (x1, y1, x2, y2) = local.iter().fold((f64::MAX, f64::MAX, f64::MIN, f64::MIN), |(x1, y1, x2, y2), p| (x1.min(p.0), y1.min(p.1), x2.max(p.0), y2.max(p.1)));
+        let centre = (x + (x1 + x2) / 2.0, y + (y1 + y2) / 2.0);
+        let mut color = e.get("strokeColor").and_then(Value::as_str).and_then(parse_hex).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        color[3] *= (field("opacity", 100.0)? / 100.0).clamp(0.0, 1.0) as f32;
+        out.push(WhiteboardStroke {
+            color,
+            points: local.iter().flat_map(|p| rotate((x + p.0, y + p.1), centre, angle)).collect(),
+            widths: pressures.iter().map(|&p| freehand_width(size, EXCALIDRAW_THINNING, p) as f32).collect(),
+            highlighter: false,
+        });
+    }
+    Some(out)
+}
+
+fn read_tldraw(v: &Value) -> Option<Vec<WhiteboardStroke>> {
+    let records = v.get("records").or_else(|| v.get("shapes"))?.as_array()?;
+    let mut out = Vec::new();
+    for r in records {
+        let kind = r.get("type").and_then(Value::as_str);
+        if r.get("typeName").and_then(Value::as_str).is_some_and(|t| t != "shape") || !matches!(kind, Some("draw" | "highlight")) {
+            continue;
+        }
+        let highlighter = kind == Some("highlight");
+        let field = |key: &str, default: f64| r.get(key).map_or(Some(default), Value::as_f64);
+        let (x, y, rotation) = (field("x", 0.0)?, field("y", 0.0)?, field("rotation", 0.0)?);
+        let props = r.get("props")?;
+        let mut local = Vec::new();
+        let mut recorded = Vec::new();
+        for segment in props.get("segments")?.as_array()? {
+            let Some(points) = segment.get("points").and_then(Value::as_array) else {
+                continue;
+            };
+            for p in points {
+                let coord = |key: &str| p.get(key).and_then(Value::as_f64);
+                local.push((coord("x")?, coord("y")?));
+                recorded.push(coord("z").unwrap_or(0.5));
+            }
+        }
+        if local.is_empty() {
+            continue;
+        }
+        let size_style = props.get("size").and_then(Value::as_str).unwrap_or("m");
+        let scale = props.get("scale").and_then(Value::as_f64).unwrap_or(1.0);
+        let pen = props.get("isPen").and_then(Value::as_bool) == Some(true);
+        let widths: Vec<f32> = if highlighter {
+            let width = named(&TLDRAW_FONT_SIZES, size_style).unwrap_or(24.0) * 1.12 * scale;
+            vec![width as f32; local.len()]
+        } else {
+            let stroke = named(&TLDRAW_STROKE_SIZES, size_style).unwrap_or(3.5) * scale;
+            let (size, thinning, pressures) = if pen {
+                (1.0 + stroke * 1.2, 0.62, recorded)
+            } else {
+                (1.0 + stroke * 1.5, 0.5, simulated_pressures(&local, 1.0 + stroke * 1.5))
+            };
+            pressures.iter().map(|&p| freehand_width(size, thinning, p) as f32).collect()
+        };
+        let (red, green, blue) = named(&TLDRAW_COLORS, props.get("color").and_then(Value::as_str).unwrap_or("black")).map_or((0x1d, 0x1d, 0x1d), |c| (c >> 16, (c >> 8) & 0xff, c & 0xff));
+        let channel = |c: u32| c as f32 / 255.0;
+        let opacity = field("opacity", 1.0)?.clamp(0.0, 1.0) as f32;
+        out.push(WhiteboardStroke {
+            color: [channel(red), channel(green), channel(blue), opacity],
+            // tldraw turns shapes about their origin.
+            points: local.iter().flat_map(|p| rotate((x + p.0, y + p.1), (x, y), rotation)).collect(),
+            widths,
+            highlighter,
+        });
+    }
+    Some(out)
+}
+
+fn named<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|(n, _)| *n == name).map(|&(_, v)| v)
+}
+
+fn rotate(p: (f64, f64), centre: (f64, f64), angle: f64) -> [f32; 2] {
+    let (sin, cos) = angle.sin_cos();
+    let (dx, dy) = (p.0 - centre.0, p.1 - centre.1);
+    [(centre.0 + dx * cos - dy * sin) as f32, (centre.1 + dx * sin + dy * cos) as f32]
+}
+
+/// perfect-freehand's stroke diameter at `pressure`, with the ease-out
+/// sine easing both apps use.
+fn freehand_width(size: f64, thinning: f64, pressure: f64) -> f64 {
+    2.0 * size * ((0.5 - thinning * (0.5 - pressure)) * FRAC_PI_2).sin()
+}
+
+/// The pressure at which `freehand_width` gives `width`.
+fn freehand_pressure(width: f64, size: f64, thinning: f64) -> f64 {
+    if size <= 0.0 {
+        return 0.5;
+    }
+    let eased = (width / (2.0 * size)).clamp(0.0, 1.0).asin() / FRAC_PI_2;
+    (0.5 - (0.5 - eased) / thinning).clamp(0.0, 1.0)
+}
+
+/// perfect-freehand's pressure for pointers that report none: it falls as
+/// the pointer speeds up, measured in steps per stroke size.
+fn simulated_pressures(points: &[(f64, f64)], size: f64) -> Vec<f64> {
+    let mut pressure = 0.5;
+    let mut prev = points[0];
+    points
+        .iter()
+        .map(|&p| {
+            let distance = ((p.0 - prev.0).powi(2) + (p.1 - prev.1).powi(2)).sqrt();
+            prev = p;
+            let speed = (distance / size.max(f64::EPSILON)).min(1.0);
+            pressure = (pressure + (1.0 - speed - pressure) * speed * PRESSURE_RATE).min(1.0);
+            pressure
+        })
+        .collect()
+}
+
+/// `#rgb` or `#rrggbb`; `None` for `transparent` and named colors.
+fn parse_hex(text: &str) -> Option<[f32; 4]> {
+    let digits = text.strip_prefix('#')?;
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let (r, g, b) = match digits.len() {
+        3 => (((value >> 8) & 0xf) * 17, ((value >> 4) & 0xf) * 17, (value & 0xf) * 17),
+        6 => (value >> 16, (value >> 8) & 0xff, value & 0xff),
+        _ => return None,
+    };
+    Some([r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0])
+}
+
+fn hex(color: [f32; 4]) -> String {
+    let c = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", c(color[0]), c(color[1]), c(color[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::preset::Brush;
+
+    fn close(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-3)
+    }
+
+    fn scene(elements: &str) -> String {
+        format!(r#"{{"type":"excalidraw","elements":[{elements}]}}"#)
+    }
+
+    #[test]
+    fn reads_excalidraw_freedraw() {
+        let elements = [
+            r##"{"type":"freedraw","x":10,"y":0,"angle":0,"points":[[0,0],[10,0]],"pressures":[0.5,1],"strokeColor":"#f00","opacity":50}"##,
+            r#"{"type":"freedraw","x":10,"y":0,"angle":0,"points":[[0,0],[10,0]],"isDeleted":true}"#,
+            r#"{"type":"rectangle","x":0,"y":0,"width":5,"height":5}"#,
+        ];
+        let strokes = read_excalidraw(&serde_json::from_str(&scene(&elements.join(","))).unwrap()).unwrap();
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(strokes[0].color, [1.0, 0.0, 0.0, 0.5]);
+        assert!(close(&strokes[0].points, &[10.0, 0.0, 20.0, 0.0]));
+        let size = EXCALIDRAW_SIZE;
+        assert!(close(&strokes[0].widths, &[freehand_width(size, EXCALIDRAW_THINNING, 0.5) as f32, freehand_width(size, EXCALIDRAW_THINNING, 1.0) as f32]));
+    }
+
+    #[test]
+    fn turns_elements_about_their_middle() {
+        let element = format!(r#"{{"type":"freedraw","x":10,"y":0,"angle":{PI},"points":[[0,0],[10,0]],"simulatePressure":true}}"#);
+        let strokes = read_excalidraw(&serde_json::from_str(&scene(&element)).unwrap()).unwrap();
+        assert!(close(&strokes[0].points, &[20.0, 0.0, 10.0, 0.0]));
+        // Simulated pressure thins the stroke as the pointer speeds up.
+        assert!(strokes[0].widths[1] < strokes[0].widths[0]);
+    }
+
+    #[test]
+    fn excalidraw_reads_back_what_it_writes() {
+        let mut doc = InkDocument::new();
+        doc.add_stroke(&[0.0, 0.0, 10.0, 5.0, 20.0, 0.0], &[2.0, 3.0, 2.0], &[], &[1.0, 0.0, 0.0, 0.5], 0);
+        let hidden = doc.add_layer("Hidden");
+        doc.set_layer_visible(hidden, false);
+        doc.set_active_layer(hidden);
+        doc.add_stroke(&[0.0, 0.0, 1.0, 1.0], &[1.0; 2], &[], &[0.0, 0.0, 0.0, 1.0], 0);
+        let text = doc.to_excalidraw();
+        let v: Value = serde_json::from_str(&text).unwrap();
+        let elements = v["elements"].as_array().unwrap();
+        assert_eq!(elements.len(), 1);
+        assert_eq!((elements[0]["strokeColor"].as_str(), elements[0]["opacity"].as_u64(), elements[0]["frameId"].is_null()), (Some("#ff0000"), Some(50), true));
+
+        let mut copy = InkDocument::new();
+        assert_eq!(copy.import_excalidraw(&text).len(), 1);
+        assert!(close(&copy.strokes[0].points, &doc.strokes[0].points));
+        assert!(close(&copy.strokes[0].widths, &doc.strokes[0].widths));
+        assert_eq!(copy.strokes[0].color, [1.0, 0.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn reads_tldraw_draw_and_highlight_shapes() {
+        let mut doc = InkDocument::new();
+        doc.register_brush("ink", &Brush::new(BrushStyle::Ink));
+        doc.register_brush("marker", &Brush::new(BrushStyle::Highlighter));
+        let records = [
+            r#"{"typeName":"shape","type":"draw","x":5,"y":5,"props":{"color":"red","size":"s","isPen":true,"segments":[{"points":[{"x":0,"y":0,"z":0.5},{"x":10,"y":0,"z":1}]}]}}"#,
+            r#"{"typeName":"shape","type":"highlight","x":0,"y":0,"opacity":0.5,"props":{"size":"m","segments":[{"points":[{"x":0,"y":0},{"x":0,"y":10}]}]}}"#,
+            r#"{"typeName":"shape","type":"draw","x":0,"y":0,"props":{"segments":[{"type":"free","path":"AAAA"}]}}"#,
+            r#"{"typeName":"page","type":"draw","props":{}}"#,
+        ];
+        let ids = doc.import_tldraw(&format!(r#"{{"records":[{}]}}"#, records.join(",")));
+        assert_eq!(ids.len(), 2);
+        let (draw, highlight) = (&doc.strokes[0], &doc.strokes[1]);
+        assert_eq!((draw.brush, highlight.brush), (0, 1));
+        assert!(close(&draw.points, &[5.0, 5.0, 15.0, 5.0]));
+        assert_eq!(draw.color, [0xe0 as f32 / 255.0, 0x31 as f32 / 255.0, 0x31 as f32 / 255.0, 1.0]);
+        let stroke = 1.0 + 2.0 * 1.2;
+        assert!(close(&draw.widths, &[freehand_width(stroke, 0.62, 0.5) as f32, freehand_width(stroke, 0.62, 1.0) as f32]));
+        assert!(close(&highlight.widths, &[24.0 * 1.12; 2]));
+        assert_eq!(highlight.color[3], 0.5);
+
+        // Clipboard payloads hold their shapes under `shapes`.
+        assert_eq!(doc.import_tldraw(&format!(r#"{{"shapes":[{}]}}"#, records[0])).len(), 1);
+        assert!(doc.import_tldraw(r#"{"records":{}}"#).is_empty());
+    }
+}